@@ -0,0 +1,71 @@
+// Native benchmarks for the texture decoders, comparing against the previous serial implementations.
+// Run with `cargo bench [--features parallel]`.
+
+use std::io::Write;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use encoders::texdec;
+use texture2ddecoder::{decode_bc1_block, decode_bc7_block};
+
+const SIZE: usize = 1024;
+
+fn legacy_blocky(data: &[u8], width: usize, height: usize, func: impl Fn(&[u8], &mut [u32]), stride: usize) -> Box<[u8]> {
+    let mut i = 0;
+    let mut out: Vec<u32> = vec![0; width * height];
+    for by in 0..((height + 3) / 4) {
+        for bx in 0..((width + 3) / 4) {
+            let mut outblk = [0u32; 16];
+            func(&data[i..i + stride], &mut outblk);
+            texdec::copy_block_buffer(bx, by, width, height, 4, 4, &outblk, &mut out);
+            i += stride;
+        }
+    }
+    let mut outdata = Vec::new();
+    for v in out {
+        outdata.write_all(&v.to_le_bytes()).unwrap();
+    }
+    outdata.into()
+}
+
+fn legacy_rgb565(data: &[u8], width: usize, height: usize) -> Box<[u8]> {
+    let mut out = Vec::new();
+    for i in 0..(width * height) {
+        let d: u32 = ((data[i * 2 + 1] as u32) << 8) | (data[i * 2] as u32);
+        out.write_all(&[
+            ((d >> 8 & 0xf8) | (d >> 13)) as u8,
+            ((d >> 3 & 0xfc) | (d >> 9 & 3)) as u8,
+            ((d << 3) | (d >> 2 & 7)) as u8,
+            0xff
+        ]).unwrap();
+    }
+    out.into()
+}
+
+fn pseudo_random(len: usize) -> Vec<u8> {
+    let mut state = 0x2545f491u32;
+    (0..len).map(|_| {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state as u8
+    }).collect()
+}
+
+fn bench_blocky(c: &mut Criterion) {
+    let mut dxt1 = pseudo_random(SIZE * SIZE / 2);
+    let mut bc7 = pseudo_random(SIZE * SIZE);
+
+    c.bench_function("dxt1 legacy", |b| b.iter(|| legacy_blocky(black_box(&dxt1), SIZE, SIZE, decode_bc1_block, 8)));
+    c.bench_function("dxt1", |b| b.iter(|| texdec::decode_dxt1(black_box(&mut dxt1), SIZE, SIZE)));
+    c.bench_function("bc7 legacy", |b| b.iter(|| legacy_blocky(black_box(&bc7), SIZE, SIZE, decode_bc7_block, 16)));
+    c.bench_function("bc7", |b| b.iter(|| texdec::decode_bc7(black_box(&mut bc7), SIZE, SIZE)));
+}
+
+fn bench_uncompressed(c: &mut Criterion) {
+    let mut rgb565 = pseudo_random(SIZE * SIZE * 2);
+
+    c.bench_function("rgb565 legacy", |b| b.iter(|| legacy_rgb565(black_box(&rgb565), SIZE, SIZE)));
+    c.bench_function("rgb565", |b| b.iter(|| texdec::decode_rgb565(black_box(&mut rgb565), SIZE, SIZE)));
+}
+
+criterion_group!(benches, bench_blocky, bench_uncompressed);
+criterion_main!(benches);
@@ -0,0 +1,35 @@
+#!/bin/sh
+# Builds the encoders package into ../js/encoders twice: with SIMD (the default from
+# .cargo/config.toml) and without, for browsers that don't support wasm SIMD. The generated
+# glue doesn't depend on target features, so both builds share encoders_bg.js and
+# simd-loader.js picks the right .wasm at load time.
+#
+# It then builds ../js/encoders-threads with the `parallel` feature for js/decodeWorker.js, which
+# cross-origin isolated pages use to decode textures on a thread pool. Wasm threads need an atomics
+# build with a std rebuilt for it (nightly with the rust-src component), and wasm-bindgen-rayon
+# needs the `web` target.
+set -e
+cd "$(dirname "$0")"
+
+OUT=../js/encoders
+RUSTFLAGS="-C target-feature=-simd128" wasm-pack build --out-dir target/nosimd-pkg "$@"
+wasm-pack build --out-dir "$OUT" "$@"
+
+cmp target/nosimd-pkg/encoders_bg.js "$OUT/encoders_bg.js"
+cp target/nosimd-pkg/encoders_bg.wasm "$OUT/encoders_bg_nosimd.wasm"
+cp simd-loader.js "$OUT/encoders.js"
+node -e '
+  const pkg = require(process.argv[1]);
+  pkg.files.push("encoders_bg_nosimd.wasm");
+  require("fs").writeFileSync(process.argv[1], JSON.stringify(pkg, null, 2) + "\n");
+' "$(pwd)/$OUT/package.json"
+
+THREADS_OUT=../js/encoders-threads
+RUSTFLAGS="-C target-feature=+atomics,+bulk-memory,+mutable-globals,+simd128" \
+  rustup run nightly wasm-pack build --target web --out-dir target/threads-pkg "$@" -- \
+  --features parallel -Z build-std=panic_abort,std
+rm -rf "$THREADS_OUT"
+mkdir -p "$THREADS_OUT"
+# everything but the .gitignore wasm-pack writes, including the worker snippets from wasm-bindgen-rayon
+cp -r target/threads-pkg/. "$THREADS_OUT"
+rm -f "$THREADS_OUT/.gitignore"
@@ -0,0 +1,14 @@
+// Replaces the encoders.js generated by wasm-pack (see build.sh): loads the SIMD build when the
+// browser can validate a module using v128 instructions, and the -simd128 build otherwise.
+import { __wbg_set_wasm } from "./encoders_bg.js";
+
+// (func (result v128) i32.const 0 i8x16.splat i8x16.popcnt)
+const SIMD_PROBE = new Uint8Array([
+  0, 97, 115, 109, 1, 0, 0, 0, 1, 5, 1, 96, 0, 1, 123, 3, 2, 1, 0, 10, 10, 1, 8, 0, 65, 0, 253, 15, 253, 98, 11
+]);
+
+export const simdSupported = WebAssembly.validate(SIMD_PROBE);
+
+const wasm = simdSupported ? await import("./encoders_bg.wasm") : await import("./encoders_bg_nosimd.wasm");
+__wbg_set_wasm(wasm);
+export * from "./encoders_bg.js";
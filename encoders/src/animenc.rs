@@ -0,0 +1,472 @@
+use std::collections::HashMap;
+use std::panic;
+use wasm_bindgen::prelude::*;
+use crate::pngenc::flip_v;
+use crate::webpenc::{encode_vp8l, riff_container, write_chunk, BitWriter};
+
+#[wasm_bindgen]
+/// Collects equally sized RGBA frames (bottom row first, like decoded textures) and writes them as an
+/// animated PNG, WebP or GIF.
+pub struct AnimationEncoder {
+    width: u32,
+    height: u32,
+    /// How many times to play the animation, or 0 to loop forever
+    pub loop_count: u32,
+    /// Use Floyd-Steinberg dithering when a GIF frame has more than 256 colours
+    pub dither: bool,
+    frames: Vec<(Box<[u8]>, u32)>
+}
+
+#[wasm_bindgen]
+impl AnimationEncoder {
+    #[wasm_bindgen(constructor)]
+    pub fn new(width: u32, height: u32) -> AnimationEncoder {
+        AnimationEncoder { width, height, loop_count: 0, dither: true, frames: Vec::new() }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Adds a frame covering the whole canvas, shown for `delay_ms` milliseconds.
+    pub fn add_frame(&mut self, data: &[u8], delay_ms: u32) {
+        let mut frame = vec![0u8; (self.width * self.height * 4) as usize];
+        let len = frame.len().min(data.len());
+        frame[..len].copy_from_slice(&data[..len]);
+        self.frames.push((frame.into(), delay_ms));
+    }
+
+    /// Adds a smaller image (e.g. a trimmed sprite) placed at `x`, `y` from the bottom-left of an otherwise
+    /// transparent frame.
+    pub fn add_frame_at(&mut self, data: &[u8], width: u32, height: u32, x: i32, y: i32, delay_ms: u32) {
+        let (cw, ch) = (self.width as i32, self.height as i32);
+        let mut frame = vec![0u8; (self.width * self.height * 4) as usize];
+        for row in 0..height as i32 {
+            let dy = y + row;
+            if dy < 0 || dy >= ch {
+                continue;
+            }
+            for col in 0..width as i32 {
+                let dx = x + col;
+                let s = ((row * width as i32 + col) * 4) as usize;
+                if dx < 0 || dx >= cw || s + 4 > data.len() {
+                    continue;
+                }
+                let d = ((dy * cw + dx) * 4) as usize;
+                frame[d..d + 4].copy_from_slice(&data[s..s + 4]);
+            }
+        }
+        self.frames.push((frame.into(), delay_ms));
+    }
+
+    /// Splits a flipbook sheet (e.g. for a particle system's texture sheet animation) into frames.
+    /// Tiles are read left to right, top to bottom, and each one must be the size of the canvas.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The decoded sheet, bottom row first
+    /// * `sheet_width`, `sheet_height` - The size of the sheet
+    /// * `columns`, `rows` - The tile grid
+    /// * `count` - How many tiles to use, or 0 for all of them
+    /// * `delay_ms` - How long to show each frame
+    pub fn add_sheet(&mut self, data: &[u8], sheet_width: u32, sheet_height: u32, columns: u32, rows: u32,
+                     count: u32, delay_ms: u32) {
+        if columns == 0 || rows == 0 {
+            return;
+        }
+        let (tw, th) = (sheet_width / columns, sheet_height / rows);
+        let count = if count == 0 { columns * rows } else { count.min(columns * rows) };
+        for i in 0..count {
+            let x = (i % columns) * tw;
+            let y = sheet_height - (i / columns + 1) * th;
+            let mut tile = vec![0u8; (tw * th * 4) as usize];
+            for row in 0..th {
+                let s = (((y + row) * sheet_width + x) * 4) as usize;
+                let d = (row * tw * 4) as usize;
+                if s + (tw * 4) as usize <= data.len() {
+                    tile[d..d + (tw * 4) as usize].copy_from_slice(&data[s..s + (tw * 4) as usize]);
+                }
+            }
+            self.add_frame_at(&tile, tw, th, 0, 0, delay_ms);
+        }
+    }
+
+    /// Encodes the frames as "apng", "webp" or "gif".
+    pub fn encode(&self, format: &str, flip: bool) -> Box<[u8]> {
+        match format {
+            "webp" => self.encode_webp(flip),
+            "gif" => self.encode_gif(flip),
+            _ => self.encode_apng(flip)
+        }
+    }
+
+    pub fn encode_apng(&self, flip: bool) -> Box<[u8]> {
+        panic::set_hook(Box::new(console_error_panic_hook::hook));
+        if self.width == 0 || self.height == 0 || self.frames.is_empty() {
+            return [].into();
+        }
+        let mut w = Vec::new();
+        let mut encoder = png::Encoder::new(&mut w, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_compression(png::Compression::Fast);
+        encoder.set_animated(self.frames.len() as u32, self.loop_count).unwrap();
+        encoder.set_blend_op(png::BlendOp::Source).unwrap();
+        encoder.set_dispose_op(png::DisposeOp::None).unwrap();
+        let mut writer = encoder.write_header().unwrap();
+        for (frame, delay) in &self.frames {
+            writer.set_frame_delay((*delay).min(0xffff) as u16, 1000).unwrap();
+            writer.write_image_data(&self.frame_rgba(frame, flip)).unwrap();
+        }
+        writer.finish().unwrap();
+        w.into()
+    }
+
+    /// Encodes the frames as an animated lossless WebP.
+    pub fn encode_webp(&self, flip: bool) -> Box<[u8]> {
+        panic::set_hook(Box::new(console_error_panic_hook::hook));
+        if self.width == 0 || self.height == 0 || self.width > 16384 || self.height > 16384 || self.frames.is_empty() {
+            return [].into();
+        }
+        let (w, h) = (self.width as usize, self.height as usize);
+        let frames: Vec<Box<[u8]>> = self.frames.iter().map(|(f, _)| self.frame_rgba(f, flip)).collect();
+        let has_alpha = frames.iter().any(|f| f.chunks_exact(4).any(|px| px[3] != 0xff));
+
+        let mut chunks = Vec::new();
+        let mut vp8x = [0u8; 10];
+        vp8x[0] = 0x02 | if has_alpha { 0x10 } else { 0 };
+        vp8x[4..7].copy_from_slice(&(self.width - 1).to_le_bytes()[..3]);
+        vp8x[7..10].copy_from_slice(&(self.height - 1).to_le_bytes()[..3]);
+        write_chunk(&mut chunks, b"VP8X", &vp8x);
+
+        let mut anim = [0u8; 6];
+        anim[4..6].copy_from_slice(&(self.loop_count.min(0xffff) as u16).to_le_bytes());
+        write_chunk(&mut chunks, b"ANIM", &anim);
+
+        for (rgba, (_, delay)) in frames.iter().zip(&self.frames) {
+            let mut anmf = vec![0u8; 16];
+            anmf[6..9].copy_from_slice(&(self.width - 1).to_le_bytes()[..3]);
+            anmf[9..12].copy_from_slice(&(self.height - 1).to_le_bytes()[..3]);
+            anmf[12..15].copy_from_slice(&(*delay).min(0xffffff).to_le_bytes()[..3]);
+            // don't blend with the previous frame, don't dispose
+            anmf[15] = 0x02;
+            write_chunk(&mut anmf, b"VP8L", &encode_vp8l(w, h, rgba));
+            write_chunk(&mut chunks, b"ANMF", &anmf);
+        }
+        riff_container(&chunks).into()
+    }
+
+    /// Encodes the frames as an animated GIF with a local palette for each frame.
+    /// Pixels with less than 50% alpha become transparent.
+    pub fn encode_gif(&self, flip: bool) -> Box<[u8]> {
+        panic::set_hook(Box::new(console_error_panic_hook::hook));
+        if self.width == 0 || self.height == 0 || self.width > 0xffff || self.height > 0xffff || self.frames.is_empty() {
+            return [].into();
+        }
+        let (w, h) = (self.width as usize, self.height as usize);
+        let mut out = Vec::new();
+        out.extend_from_slice(b"GIF89a");
+        out.extend_from_slice(&(w as u16).to_le_bytes());
+        out.extend_from_slice(&(h as u16).to_le_bytes());
+        out.extend_from_slice(&[0, 0, 0]);
+
+        // NETSCAPE2.0 looping extension
+        out.extend_from_slice(&[0x21, 0xff, 11]);
+        out.extend_from_slice(b"NETSCAPE2.0");
+        out.extend_from_slice(&[3, 1]);
+        out.extend_from_slice(&(self.loop_count.min(0xffff) as u16).to_le_bytes());
+        out.push(0);
+
+        for (frame, delay) in &self.frames {
+            let rgba = self.frame_rgba(frame, flip);
+            let (palette, indices, transparent) = quantize(&rgba, w, h, self.dither);
+            let bits = palette_bits(palette.len() / 3);
+
+            // graphic control extension: restore to background so transparent areas are cleared
+            let centis = ((*delay + 5) / 10).min(0xffff) as u16;
+            out.extend_from_slice(&[0x21, 0xf9, 4, 2 << 2 | transparent.is_some() as u8]);
+            out.extend_from_slice(&centis.to_le_bytes());
+            out.extend_from_slice(&[transparent.unwrap_or(0), 0]);
+
+            out.push(0x2c);
+            out.extend_from_slice(&[0, 0, 0, 0]);
+            out.extend_from_slice(&(w as u16).to_le_bytes());
+            out.extend_from_slice(&(h as u16).to_le_bytes());
+            out.push(0x80 | (bits - 1) as u8);
+            out.extend_from_slice(&palette);
+            out.extend(std::iter::repeat(0).take((3 << bits) - palette.len()));
+
+            let min_code_size = bits.max(2);
+            out.push(min_code_size as u8);
+            for block in lzw_encode(&indices, min_code_size).chunks(255) {
+                out.push(block.len() as u8);
+                out.extend_from_slice(block);
+            }
+            out.push(0);
+        }
+        out.push(0x3b);
+        out.into()
+    }
+}
+
+impl AnimationEncoder {
+    /// A frame in the row order that will be written.
+    fn frame_rgba(&self, frame: &[u8], flip: bool) -> Box<[u8]> {
+        if flip {flip_v(self.width as usize, self.height as usize, frame)} else {frame.into()}
+    }
+}
+
+/// The number of bits needed to index `colors` palette entries (at least 1).
+fn palette_bits(colors: usize) -> usize {
+    let mut bits = 1;
+    while (1 << bits) < colors {
+        bits += 1;
+    }
+    bits
+}
+
+fn lzw_encode(indices: &[u8], min_code_size: usize) -> Vec<u8> {
+    let clear = 1u32 << min_code_size;
+    let eoi = clear + 1;
+    let mut w = BitWriter::new();
+    let mut dict: HashMap<(u32, u8), u32> = HashMap::new();
+    let mut next = clear + 2;
+    let mut width = min_code_size + 1;
+    w.put(clear, width);
+    let mut cur = match indices.first() {
+        Some(&i) => i as u32,
+        None => {
+            w.put(eoi, width);
+            return w.finish();
+        }
+    };
+    for &c in &indices[1..] {
+        if let Some(&code) = dict.get(&(cur, c)) {
+            cur = code;
+            continue;
+        }
+        w.put(cur, width);
+        if next < 4096 {
+            dict.insert((cur, c), next);
+            next += 1;
+            if next > (1 << width) && width < 12 {
+                width += 1;
+            }
+        } else {
+            w.put(clear, width);
+            dict.clear();
+            next = clear + 2;
+            width = min_code_size + 1;
+        }
+        cur = c as u32;
+    }
+    w.put(cur, width);
+    // the decoder adds one more entry after the last code, which may widen the end code
+    if next == (1 << width) && width < 12 {
+        width += 1;
+    }
+    w.put(eoi, width);
+    w.finish()
+}
+
+struct ColorBox {
+    bins: Vec<usize>,
+}
+
+fn bin_channel(bin: usize, c: usize) -> usize {
+    bin >> (10 - c * 5) & 0x1f
+}
+
+/// Median cut over a 15-bit colour histogram. Returns the palette entries as RGB.
+fn median_cut(counts: &[u32], sums: &[[u64; 3]], max_colors: usize) -> Vec<[u8; 3]> {
+    let mut boxes = vec![ColorBox { bins: (0..counts.len()).filter(|&b| counts[b] > 0).collect() }];
+    while boxes.len() < max_colors {
+        // split the box with the most pixels that still has more than one colour
+        let mut best = None;
+        let mut best_count = 0u64;
+        for (i, b) in boxes.iter().enumerate() {
+            if b.bins.len() < 2 {
+                continue;
+            }
+            let count: u64 = b.bins.iter().map(|&bin| counts[bin] as u64).sum();
+            if count > best_count {
+                best_count = count;
+                best = Some(i);
+            }
+        }
+        let i = match best {
+            Some(i) => i,
+            None => break
+        };
+        let mut b = boxes.swap_remove(i);
+        let mut axis = 0;
+        let mut axis_range = 0;
+        for c in 0..3 {
+            let min = b.bins.iter().map(|&bin| bin_channel(bin, c)).min().unwrap();
+            let max = b.bins.iter().map(|&bin| bin_channel(bin, c)).max().unwrap();
+            if max - min >= axis_range {
+                axis_range = max - min;
+                axis = c;
+            }
+        }
+        b.bins.sort_by_key(|&bin| bin_channel(bin, axis));
+        let mut acc = 0u64;
+        let mut split = 1;
+        for (k, &bin) in b.bins.iter().enumerate() {
+            acc += counts[bin] as u64;
+            if acc * 2 >= best_count {
+                split = (k + 1).clamp(1, b.bins.len() - 1);
+                break;
+            }
+        }
+        let upper = b.bins.split_off(split);
+        boxes.push(b);
+        boxes.push(ColorBox { bins: upper });
+    }
+    boxes.iter().map(|b| {
+        let mut total = 0u64;
+        let mut sum = [0u64; 3];
+        for &bin in &b.bins {
+            total += counts[bin] as u64;
+            for c in 0..3 {
+                sum[c] += sums[bin][c];
+            }
+        }
+        let total = total.max(1);
+        [(sum[0] / total) as u8, (sum[1] / total) as u8, (sum[2] / total) as u8]
+    }).collect()
+}
+
+fn nearest(palette: &[[u8; 3]], r: i32, g: i32, b: i32) -> u8 {
+    let mut best = 0;
+    let mut best_dist = i32::MAX;
+    for (i, p) in palette.iter().enumerate() {
+        let dr = p[0] as i32 - r;
+        let dg = p[1] as i32 - g;
+        let db = p[2] as i32 - b;
+        let dist = dr * dr * 2 + dg * dg * 4 + db * db * 3;
+        if dist < best_dist {
+            best_dist = dist;
+            best = i;
+        }
+    }
+    best as u8
+}
+
+/// Reduces a frame to at most 256 colours. Returns the RGB palette, the index of each pixel and the
+/// transparent index, if any pixel is transparent.
+fn quantize(rgba: &[u8], width: usize, height: usize, dither: bool) -> (Vec<u8>, Vec<u8>, Option<u8>) {
+    let pixels = width * height;
+    let has_transparency = (0..pixels).any(|i| rgba[i * 4 + 3] < 0x80);
+    let max_colors = if has_transparency { 255 } else { 256 };
+
+    // keep the exact colours when there are few enough of them
+    let mut exact: HashMap<[u8; 3], u8> = HashMap::new();
+    for i in 0..pixels {
+        let o = i * 4;
+        if rgba[o + 3] < 0x80 {
+            continue;
+        }
+        let key = [rgba[o], rgba[o + 1], rgba[o + 2]];
+        if !exact.contains_key(&key) {
+            if exact.len() == max_colors {
+                exact.clear();
+                break;
+            }
+            exact.insert(key, exact.len() as u8);
+        }
+    }
+
+    let mut palette: Vec<[u8; 3]>;
+    let mut indices = vec![0u8; pixels];
+    if !exact.is_empty() || !(0..pixels).any(|i| rgba[i * 4 + 3] >= 0x80) {
+        palette = vec![[0; 3]; exact.len()];
+        for (c, &i) in &exact {
+            palette[i as usize] = *c;
+        }
+        for i in 0..pixels {
+            let o = i * 4;
+            if rgba[o + 3] >= 0x80 {
+                indices[i] = exact[&[rgba[o], rgba[o + 1], rgba[o + 2]]];
+            }
+        }
+    } else {
+        let mut counts = vec![0u32; 1 << 15];
+        let mut sums = vec![[0u64; 3]; 1 << 15];
+        for i in 0..pixels {
+            let o = i * 4;
+            if rgba[o + 3] < 0x80 {
+                continue;
+            }
+            let bin = (rgba[o] as usize >> 3) << 10 | (rgba[o + 1] as usize >> 3) << 5 | rgba[o + 2] as usize >> 3;
+            counts[bin] += 1;
+            for c in 0..3 {
+                sums[bin][c] += rgba[o + c] as u64;
+            }
+        }
+        palette = median_cut(&counts, &sums, max_colors);
+
+        let mut cache = vec![u16::MAX; 1 << 15];
+        let mut lookup = |r: i32, g: i32, b: i32| {
+            let bin = (r as usize >> 3) << 10 | (g as usize >> 3) << 5 | b as usize >> 3;
+            if cache[bin] == u16::MAX {
+                cache[bin] = nearest(&palette, r | 4, g | 4, b | 4) as u16;
+            }
+            cache[bin] as u8
+        };
+        // error diffused onto the current and next rows, with a pixel of padding on each side
+        let mut err = vec![[0i32; 3]; (width + 2) * 2];
+        for y in 0..height {
+            let (cur, next) = if y % 2 == 0 { (0, width + 2) } else { (width + 2, 0) };
+            for e in err[next..next + width + 2].iter_mut() {
+                *e = [0; 3];
+            }
+            for x in 0..width {
+                let o = (y * width + x) * 4;
+                if rgba[o + 3] < 0x80 {
+                    continue;
+                }
+                let e = if dither { err[cur + x + 1] } else { [0; 3] };
+                let r = (rgba[o] as i32 + e[0] / 16).clamp(0, 255);
+                let g = (rgba[o + 1] as i32 + e[1] / 16).clamp(0, 255);
+                let b = (rgba[o + 2] as i32 + e[2] / 16).clamp(0, 255);
+                let idx = lookup(r, g, b);
+                indices[y * width + x] = idx;
+                if dither {
+                    let p = palette[idx as usize];
+                    let d = [r - p[0] as i32, g - p[1] as i32, b - p[2] as i32];
+                    for c in 0..3 {
+                        err[cur + x + 2][c] += d[c] * 7;
+                        err[next + x][c] += d[c] * 3;
+                        err[next + x + 1][c] += d[c] * 5;
+                        err[next + x + 2][c] += d[c];
+                    }
+                }
+            }
+        }
+    }
+
+    let transparent = if has_transparency { Some(palette.len() as u8) } else { None };
+    if has_transparency {
+        for i in 0..pixels {
+            if rgba[i * 4 + 3] < 0x80 {
+                indices[i] = palette.len() as u8;
+            }
+        }
+        palette.push([0; 3]);
+    }
+    if palette.is_empty() {
+        palette.push([0; 3]);
+    }
+    (palette.concat(), indices, transparent)
+}
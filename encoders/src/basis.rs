@@ -0,0 +1,736 @@
+use wasm_bindgen::prelude::*;
+use crate::blockenc::{encode_astc_4x4_block, encode_bc7_block, encode_blocks};
+use crate::compress::{decompress_with, Codec};
+use crate::error::ImageError;
+use crate::uastc::decode_uastc_block;
+
+// Basis Universal transcoding for `.basis` files and Basis-compressed KTX2 files.
+//
+// ETC1S textures are stored as slices of 4x4 blocks, each referencing an endpoint (a 5-bit colour and an
+// ETC1 intensity table) and a 2-bit selector pattern from codebooks shared by the whole file.
+// Blocks can be transcoded to RGBA, losslessly to ETC1, or re-encoded to BC7 or ASTC.
+//
+// UASTC textures store 16-byte blocks directly (see uastc.rs), in KTX2 files optionally with each mip level
+// compressed with Zstandard. They can be transcoded to RGBA, BC7 or ASTC.
+
+const BASIS_HEADER_SIZE: usize = 77;
+const BASIS_SLICE_DESC_SIZE: usize = 23;
+const BASIS_FLAG_HAS_ALPHA_SLICES: u32 = 4;
+const BASIS_TEX_TYPE_VIDEO_FRAMES: u32 = 3;
+const KTX2_IDENTIFIER: [u8; 12] = [0xab, 0x4b, 0x54, 0x58, 0x20, 0x32, 0x30, 0xbb, 0x0d, 0x0a, 0x1a, 0x0a];
+const KTX2_SUPERCOMPRESSION_NONE: u32 = 0;
+const KTX2_SUPERCOMPRESSION_BASISLZ: u32 = 1;
+const KTX2_SUPERCOMPRESSION_ZSTD: u32 = 2;
+const KTX2_DF_MODEL_UASTC: usize = 166;
+const UASTC_BLOCK_SIZE: usize = 16;
+
+const HUFFMAN_MAX_CODE_SIZE: usize = 16;
+const HUFFMAN_TOTAL_CODELENGTH_CODES: usize = 21;
+const HUFFMAN_SORTED_CODELENGTH_CODES: [usize; HUFFMAN_TOTAL_CODELENGTH_CODES] =
+    [17, 18, 19, 20, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15, 16];
+
+const ENDPOINT_PRED_REPEAT_LAST_SYMBOL: u32 = 256;
+const ENDPOINT_PRED_MIN_REPEAT_COUNT: u32 = 3;
+const ENDPOINT_PRED_COUNT_VLC_BITS: u32 = 4;
+const SELECTOR_HISTORY_BUF_RLE_COUNT_THRESH: u32 = 3;
+const SELECTOR_HISTORY_BUF_RLE_COUNT_TOTAL: u32 = 64;
+const COLOR5_PAL0_PREV_HI: u8 = 9;
+const COLOR5_PAL1_PREV_HI: u8 = 21;
+
+const ETC1_INTEN_TABLES: [[i32; 4]; 8] = [
+    [-8, -2, 2, 8], [-17, -5, 5, 17], [-29, -9, 9, 29], [-42, -13, 13, 42],
+    [-60, -18, 18, 60], [-80, -24, 24, 80], [-106, -33, 33, 106], [-183, -47, 47, 183],
+];
+/// ETC1 pixel index for each selector, which Basis orders from darkest to brightest.
+const SELECTOR_TO_ETC1: [u32; 4] = [3, 2, 0, 1];
+
+fn invalid() -> ImageError {
+    ImageError::invalid_data("Basis")
+}
+
+fn read_uint(data: &[u8], offset: usize, size: usize) -> Result<u64, ImageError> {
+    let bytes = data.get(offset..offset + size).ok_or_else(invalid)?;
+    Ok(bytes.iter().rev().fold(0u64, |v, &b| v << 8 | b as u64))
+}
+
+/// Reads bits least significant first, returning zeros past the end like the reference decoder.
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buf: u64,
+    buf_bits: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> BitReader<'a> {
+        BitReader { data, pos: 0, buf: 0, buf_bits: 0 }
+    }
+
+    fn get_bits(&mut self, bits: u32) -> u32 {
+        while self.buf_bits < bits {
+            let byte = self.data.get(self.pos).copied().unwrap_or(0);
+            self.pos += 1;
+            self.buf |= (byte as u64) << self.buf_bits;
+            self.buf_bits += 8;
+        }
+        let v = (self.buf & ((1u64 << bits) - 1)) as u32;
+        self.buf >>= bits;
+        self.buf_bits -= bits;
+        v
+    }
+
+    /// A variable length number in chunks of `chunk_bits`, each followed by a continuation bit.
+    fn decode_vlc(&mut self, chunk_bits: u32) -> u32 {
+        let mut v = 0u32;
+        let mut shift = 0;
+        loop {
+            let s = self.get_bits(chunk_bits + 1);
+            v |= (s & ((1 << chunk_bits) - 1)) << shift;
+            shift += chunk_bits;
+            if s & (1 << chunk_bits) == 0 || shift >= 32 {
+                return v;
+            }
+        }
+    }
+
+    /// Canonical Huffman codes are sent most significant bit first.
+    fn decode_huffman(&mut self, table: &HuffmanTable) -> Result<u32, ImageError> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..=HUFFMAN_MAX_CODE_SIZE {
+            code |= self.get_bits(1) as i32;
+            let count = table.counts[len] as i32;
+            if code - first < count {
+                return Ok(table.symbols[(index + code - first) as usize] as u32);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid())
+    }
+
+    fn read_huffman_table(&mut self) -> Result<HuffmanTable, ImageError> {
+        let total_syms = self.get_bits(14) as usize;
+        if total_syms == 0 {
+            return Ok(HuffmanTable::default());
+        }
+        let num_codelength_codes = self.get_bits(5) as usize;
+        if num_codelength_codes == 0 || num_codelength_codes > HUFFMAN_TOTAL_CODELENGTH_CODES {
+            return Err(invalid());
+        }
+        let mut codelength_sizes = [0u8; HUFFMAN_TOTAL_CODELENGTH_CODES];
+        for &code in HUFFMAN_SORTED_CODELENGTH_CODES.iter().take(num_codelength_codes) {
+            codelength_sizes[code] = self.get_bits(3) as u8;
+        }
+        let codelength_table = HuffmanTable::new(&codelength_sizes)?;
+
+        let mut sizes = vec![0u8; total_syms];
+        let mut cur = 0;
+        while cur < total_syms {
+            let c = self.decode_huffman(&codelength_table)?;
+            let (run, size) = match c {
+                0..=16 => (1, c as u8),
+                17 => (self.get_bits(3) as usize + 3, 0),
+                18 => (self.get_bits(7) as usize + 11, 0),
+                19 | 20 => {
+                    let prev = if cur > 0 { sizes[cur - 1] } else { 0 };
+                    if prev == 0 {
+                        return Err(invalid());
+                    }
+                    (if c == 19 { self.get_bits(2) as usize + 3 } else { self.get_bits(7) as usize + 7 }, prev)
+                },
+                _ => return Err(invalid())
+            };
+            if cur + run > total_syms {
+                return Err(invalid());
+            }
+            sizes[cur..cur + run].iter_mut().for_each(|s| *s = size);
+            cur += run;
+        }
+        HuffmanTable::new(&sizes)
+    }
+}
+
+#[derive(Default)]
+struct HuffmanTable {
+    counts: [u16; HUFFMAN_MAX_CODE_SIZE + 1],
+    /// Symbols ordered by code length, then by value
+    symbols: Vec<u16>,
+}
+
+impl HuffmanTable {
+    fn new(code_sizes: &[u8]) -> Result<HuffmanTable, ImageError> {
+        let mut table = HuffmanTable::default();
+        for &size in code_sizes.iter() {
+            if size as usize > HUFFMAN_MAX_CODE_SIZE {
+                return Err(invalid());
+            }
+            table.counts[size as usize] += 1;
+        }
+        table.counts[0] = 0;
+        let mut offsets = [0usize; HUFFMAN_MAX_CODE_SIZE + 2];
+        for len in 1..=HUFFMAN_MAX_CODE_SIZE {
+            offsets[len + 1] = offsets[len] + table.counts[len] as usize;
+        }
+        table.symbols = vec![0; offsets[HUFFMAN_MAX_CODE_SIZE + 1]];
+        for (sym, &size) in code_sizes.iter().enumerate() {
+            if size > 0 {
+                table.symbols[offsets[size as usize]] = sym as u16;
+                offsets[size as usize] += 1;
+            }
+        }
+        Ok(table)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct Endpoint {
+    color5: [u8; 3],
+    inten: u8,
+}
+
+impl Endpoint {
+    /// The four colours a block can use, in selector order.
+    fn block_colors(&self) -> [[u8; 4]; 4] {
+        let base = self.color5.map(|c| ((c << 3) | (c >> 2)) as i32);
+        let mut colors = [[0u8; 4]; 4];
+        for (i, color) in colors.iter_mut().enumerate() {
+            let d = ETC1_INTEN_TABLES[self.inten as usize][i];
+            *color = [(base[0] + d).clamp(0, 255) as u8, (base[1] + d).clamp(0, 255) as u8,
+                      (base[2] + d).clamp(0, 255) as u8, 255];
+        }
+        colors
+    }
+}
+
+/// Move-to-front list of recently used selectors, with new entries replacing the back half in turn.
+struct SelectorHistory {
+    values: Vec<u32>,
+    rover: usize,
+}
+
+impl SelectorHistory {
+    fn new(size: usize) -> SelectorHistory {
+        SelectorHistory { values: vec![0; size], rover: size / 2 }
+    }
+
+    fn add(&mut self, value: u32) {
+        self.values[self.rover] = value;
+        self.rover += 1;
+        if self.rover == self.values.len() {
+            self.rover = self.values.len() / 2;
+        }
+    }
+
+    fn use_entry(&mut self, index: usize) {
+        if index > 0 {
+            self.values.swap(index / 2, index);
+        }
+    }
+}
+
+/// The codebooks and Huffman tables shared by all slices of an ETC1S file.
+struct Etc1sCodebooks {
+    endpoints: Vec<Endpoint>,
+    /// Selectors in row-major order, 0 (darkest) to 3 (brightest)
+    selectors: Vec<[u8; 16]>,
+    endpoint_pred_model: HuffmanTable,
+    delta_endpoint_model: HuffmanTable,
+    selector_model: HuffmanTable,
+    selector_history_rle_model: HuffmanTable,
+    selector_history_size: usize,
+}
+
+impl Etc1sCodebooks {
+    fn new(num_endpoints: usize, endpoint_data: &[u8], num_selectors: usize, selector_data: &[u8],
+           table_data: &[u8]) -> Result<Etc1sCodebooks, ImageError> {
+        let mut bits = BitReader::new(endpoint_data);
+        let color5_delta_models = [bits.read_huffman_table()?, bits.read_huffman_table()?, bits.read_huffman_table()?];
+        let inten_delta_model = bits.read_huffman_table()?;
+        let grayscale = bits.get_bits(1) != 0;
+        let mut endpoints = vec![Endpoint::default(); num_endpoints];
+        let mut prev_color5 = [16u8; 3];
+        let mut prev_inten = 0;
+        for e in endpoints.iter_mut() {
+            e.inten = ((bits.decode_huffman(&inten_delta_model)? + prev_inten) & 7) as u8;
+            prev_inten = e.inten as u32;
+            for c in 0..(if grayscale { 1 } else { 3 }) {
+                let model = if prev_color5[c] <= COLOR5_PAL0_PREV_HI {
+                    &color5_delta_models[0]
+                } else if prev_color5[c] <= COLOR5_PAL1_PREV_HI {
+                    &color5_delta_models[1]
+                } else {
+                    &color5_delta_models[2]
+                };
+                let delta = bits.decode_huffman(model)?;
+                e.color5[c] = ((prev_color5[c] as u32 + delta) & 31) as u8;
+                prev_color5[c] = e.color5[c];
+            }
+            if grayscale {
+                e.color5 = [e.color5[0]; 3];
+            }
+        }
+
+        let mut bits = BitReader::new(selector_data);
+        // global and hybrid selector codebooks were dropped from the format before it was finalised
+        if bits.get_bits(1) != 0 || bits.get_bits(1) != 0 {
+            return Err(invalid());
+        }
+        let mut selectors = vec![[0u8; 16]; num_selectors];
+        let raw = bits.get_bits(1) != 0;
+        let delta_model = if raw { HuffmanTable::default() } else { bits.read_huffman_table()? };
+        let mut prev_bytes = [0u32; 4];
+        for (i, selector) in selectors.iter_mut().enumerate() {
+            for (row, prev) in prev_bytes.iter_mut().enumerate() {
+                let byte = if raw || i == 0 {
+                    bits.get_bits(8)
+                } else {
+                    *prev ^ bits.decode_huffman(&delta_model)?
+                };
+                *prev = byte;
+                for x in 0..4 {
+                    selector[row * 4 + x] = ((byte >> (x * 2)) & 3) as u8;
+                }
+            }
+        }
+
+        let mut bits = BitReader::new(table_data);
+        let endpoint_pred_model = bits.read_huffman_table()?;
+        let delta_endpoint_model = bits.read_huffman_table()?;
+        let selector_model = bits.read_huffman_table()?;
+        let selector_history_rle_model = bits.read_huffman_table()?;
+        let selector_history_size = bits.get_bits(13) as usize;
+        if endpoint_pred_model.is_empty() || selector_history_size == 0 {
+            return Err(invalid());
+        }
+        Ok(Etc1sCodebooks {
+            endpoints,
+            selectors,
+            endpoint_pred_model,
+            delta_endpoint_model,
+            selector_model,
+            selector_history_rle_model,
+            selector_history_size,
+        })
+    }
+
+    /// Decodes a slice into an (endpoint, selector) codebook index pair for every block.
+    fn decode_slice(&self, data: &[u8], blocks_x: usize, blocks_y: usize) -> Result<Vec<(u32, u32)>, ImageError> {
+        let mut bits = BitReader::new(data);
+        let num_endpoints = self.endpoints.len() as u32;
+        let num_selectors = self.selectors.len() as u32;
+        let history_first = num_selectors;
+        let history_rle = num_selectors + self.selector_history_size as u32;
+        let mut history = SelectorHistory::new(self.selector_history_size);
+        let mut selector_rle_count = 0u32;
+
+        // per column: the prediction bits for the odd row, and the endpoint of each of the last two rows
+        let mut pred_bits_below = vec![0u32; blocks_x];
+        let mut row_endpoints = [vec![0u32; blocks_x], vec![0u32; blocks_x]];
+        let mut cur_pred_bits = 0u32;
+        let mut prev_pred_sym = 0u32;
+        let mut pred_repeat_count = 0u32;
+        let mut prev_endpoint = 0u32;
+        let mut blocks = Vec::with_capacity(blocks_x * blocks_y);
+
+        for by in 0..blocks_y {
+            let (cur, above) = (by & 1, (by & 1) ^ 1);
+            for bx in 0..blocks_x {
+                // one symbol predicts the endpoints of a 2x2 group of blocks
+                if bx & 1 == 0 {
+                    if by & 1 == 0 {
+                        if pred_repeat_count > 0 {
+                            pred_repeat_count -= 1;
+                            cur_pred_bits = prev_pred_sym;
+                        } else {
+                            cur_pred_bits = bits.decode_huffman(&self.endpoint_pred_model)?;
+                            if cur_pred_bits == ENDPOINT_PRED_REPEAT_LAST_SYMBOL {
+                                pred_repeat_count = bits.decode_vlc(ENDPOINT_PRED_COUNT_VLC_BITS)
+                                    + ENDPOINT_PRED_MIN_REPEAT_COUNT - 1;
+                                cur_pred_bits = prev_pred_sym;
+                            } else {
+                                prev_pred_sym = cur_pred_bits;
+                            }
+                        }
+                        pred_bits_below[bx] = cur_pred_bits >> 4;
+                    } else {
+                        cur_pred_bits = pred_bits_below[bx];
+                    }
+                }
+
+                let pred = cur_pred_bits & 3;
+                cur_pred_bits >>= 2;
+                let endpoint = match pred {
+                    0 if bx > 0 => prev_endpoint,
+                    1 if by > 0 => row_endpoints[above][bx],
+                    2 if bx > 0 && by > 0 => row_endpoints[above][bx - 1],
+                    3 => {
+                        let e = bits.decode_huffman(&self.delta_endpoint_model)? + prev_endpoint;
+                        if e >= num_endpoints { e - num_endpoints } else { e }
+                    },
+                    _ => return Err(invalid())
+                };
+                if endpoint >= num_endpoints {
+                    return Err(invalid());
+                }
+                row_endpoints[cur][bx] = endpoint;
+                prev_endpoint = endpoint;
+
+                let mut sym = if selector_rle_count > 0 {
+                    selector_rle_count -= 1;
+                    history_first
+                } else {
+                    bits.decode_huffman(&self.selector_model)?
+                };
+                if sym == history_rle {
+                    let run = bits.decode_huffman(&self.selector_history_rle_model)?;
+                    selector_rle_count = if run == SELECTOR_HISTORY_BUF_RLE_COUNT_TOTAL - 1 {
+                        bits.decode_vlc(7) + SELECTOR_HISTORY_BUF_RLE_COUNT_THRESH
+                    } else {
+                        run + SELECTOR_HISTORY_BUF_RLE_COUNT_THRESH
+                    };
+                    if selector_rle_count as usize > blocks_x * blocks_y {
+                        return Err(invalid());
+                    }
+                    selector_rle_count -= 1;
+                    sym = history_first;
+                }
+                let selector = if sym >= history_first {
+                    let index = (sym - history_first) as usize;
+                    if index >= history.values.len() {
+                        return Err(invalid());
+                    }
+                    let selector = history.values[index];
+                    history.use_entry(index);
+                    selector
+                } else {
+                    history.add(sym);
+                    sym
+                };
+                if selector >= num_selectors {
+                    return Err(invalid());
+                }
+                blocks.push((endpoint, selector));
+            }
+        }
+        Ok(blocks)
+    }
+}
+
+/// Where one mip level of one image lives in the file.
+#[derive(Clone, Copy)]
+struct BasisLevel {
+    width: usize,
+    height: usize,
+    blocks_x: usize,
+    blocks_y: usize,
+    rgb: (usize, usize),
+    alpha: Option<(usize, usize)>,
+}
+
+#[wasm_bindgen]
+/// A parsed `.basis` or Basis-compressed KTX2 file.
+pub struct BasisFile {
+    data: Box<[u8]>,
+    is_uastc: bool,
+    codebooks: Option<Etc1sCodebooks>,
+    /// The Zstandard-decompressed mip levels of a UASTC KTX2 file, which level offsets point into instead of `data`
+    level_data: Option<Box<[u8]>>,
+    /// Mip levels of each image, largest first
+    images: Vec<Vec<BasisLevel>>,
+}
+
+impl BasisFile {
+    fn parse_basis(data: &[u8]) -> Result<BasisFile, ImageError> {
+        let field = |offset: usize, size: usize| read_uint(data, offset, size).map(|v| v as usize);
+        if field(0, 2)? != 0x4273 || field(4, 2)? != BASIS_HEADER_SIZE {
+            return Err(invalid());
+        }
+        let total_slices = field(14, 3)?;
+        let total_images = field(17, 3)?;
+        let is_uastc = field(20, 1)? == 1;
+        let flags = field(21, 2)? as u32;
+        if field(23, 1)? as u32 == BASIS_TEX_TYPE_VIDEO_FRAMES {
+            return Err(ImageError::unknown_format("Basis video"));
+        }
+        let slice_desc_ofs = field(65, 4)?;
+
+        let mut images = vec![Vec::new(); total_images];
+        let mut i = 0;
+        while i < total_slices {
+            let desc = |i: usize, offset: usize, size: usize| field(slice_desc_ofs + i * BASIS_SLICE_DESC_SIZE + offset, size);
+            let (image, level) = (desc(i, 0, 3)?, desc(i, 3, 1)?);
+            let rgb = (desc(i, 13, 4)?, desc(i, 17, 4)?);
+            // with alpha, every RGB slice is followed by the matching alpha slice
+            let alpha = if !is_uastc && flags & BASIS_FLAG_HAS_ALPHA_SLICES != 0 {
+                i += 1;
+                Some((desc(i, 13, 4)?, desc(i, 17, 4)?))
+            } else {
+                None
+            };
+            let levels: &mut Vec<BasisLevel> = images.get_mut(image).ok_or_else(invalid)?;
+            if level != levels.len() {
+                return Err(invalid());
+            }
+            levels.push(BasisLevel {
+                width: desc(i, 5, 2)?,
+                height: desc(i, 7, 2)?,
+                blocks_x: desc(i, 9, 2)?,
+                blocks_y: desc(i, 11, 2)?,
+                rgb,
+                alpha,
+            });
+            i += 1;
+        }
+
+        let codebooks = if is_uastc {
+            None
+        } else {
+            let slice = |ofs: usize, size: usize| data.get(ofs..ofs + size).ok_or_else(invalid);
+            Some(Etc1sCodebooks::new(
+                field(39, 2)?, slice(field(41, 4)?, field(45, 3)?)?,
+                field(48, 2)?, slice(field(50, 4)?, field(54, 3)?)?,
+                slice(field(57, 4)?, field(61, 4)?)?,
+            )?)
+        };
+        Ok(BasisFile { data: data.into(), is_uastc, codebooks, level_data: None, images })
+    }
+
+    fn parse_ktx2(data: &[u8]) -> Result<BasisFile, ImageError> {
+        let field = |offset: usize, size: usize| read_uint(data, offset, size).map(|v| v as usize);
+        let (width, height) = (field(20, 4)?, field(24, 4)?.max(1));
+        let depth = field(28, 4)?.max(1);
+        let layers = field(32, 4)?.max(1);
+        let faces = field(36, 4)?.max(1);
+        let level_count = field(40, 4)?.max(1);
+        let supercompression = field(44, 4)? as u32;
+        let (sgd_ofs, sgd_len) = (field(64, 8)?, field(72, 8)?);
+        let level_ofs = |level: usize| field(80 + level * 24, 8);
+        let images_per_level = |level: usize| layers * faces * (depth >> level).max(1);
+        let mut images = vec![Vec::new(); layers * faces * depth];
+
+        if supercompression != KTX2_SUPERCOMPRESSION_BASISLZ {
+            if field(field(48, 4)? + 12, 1)? != KTX2_DF_MODEL_UASTC {
+                return Err(ImageError::unknown_format("KTX2"));
+            }
+            let zstd = match supercompression {
+                KTX2_SUPERCOMPRESSION_NONE => false,
+                KTX2_SUPERCOMPRESSION_ZSTD => true,
+                _ => return Err(ImageError::unknown_format("KTX2 supercompression"))
+            };
+            // each level holds the blocks of every image in turn, compressed as a whole with Zstandard
+            let mut level_data = Vec::new();
+            for level in 0..level_count {
+                let (w, h) = ((width >> level).max(1), (height >> level).max(1));
+                let (blocks_x, blocks_y) = ((w + 3) / 4, (h + 3) / 4);
+                let image_size = blocks_x * blocks_y * UASTC_BLOCK_SIZE;
+                let base = if zstd {
+                    let (ofs, len) = (level_ofs(level)?, field(88 + level * 24, 8)?);
+                    let packed = data.get(ofs..ofs + len).ok_or_else(invalid)?;
+                    let unpacked = decompress_with(Codec::Zstd, packed, field(96 + level * 24, 8)?)
+                        .map_err(|_| invalid())?;
+                    level_data.extend_from_slice(&unpacked);
+                    level_data.len() - unpacked.len()
+                } else {
+                    level_ofs(level)?
+                };
+                for (image, levels) in images.iter_mut().take(images_per_level(level)).enumerate() {
+                    levels.push(BasisLevel {
+                        width: w,
+                        height: h,
+                        blocks_x,
+                        blocks_y,
+                        rgb: (base + image * image_size, image_size),
+                        alpha: None,
+                    });
+                }
+            }
+            let level_data = if zstd { Some(level_data.into()) } else { None };
+            return Ok(BasisFile { data: data.into(), is_uastc: true, codebooks: None, level_data, images });
+        }
+
+        let sgd = data.get(sgd_ofs..sgd_ofs + sgd_len).ok_or_else(invalid)?;
+        let sgd_field = |offset: usize, size: usize| read_uint(sgd, offset, size).map(|v| v as usize);
+        let total_descs: usize = (0..level_count).map(images_per_level).sum();
+
+        let mut desc = 0;
+        for level in 0..level_count {
+            let base = level_ofs(level)?;
+            let (w, h) = ((width >> level).max(1), (height >> level).max(1));
+            for image in 0..images_per_level(level) {
+                let d = 20 + desc * 20;
+                let alpha_len = sgd_field(d + 16, 4)?;
+                images[image].push(BasisLevel {
+                    width: w,
+                    height: h,
+                    blocks_x: (w + 3) / 4,
+                    blocks_y: (h + 3) / 4,
+                    rgb: (base + sgd_field(d + 4, 4)?, sgd_field(d + 8, 4)?),
+                    alpha: if alpha_len > 0 { Some((base + sgd_field(d + 12, 4)?, alpha_len)) } else { None },
+                });
+                desc += 1;
+            }
+        }
+
+        let endpoints_ofs = 20 + total_descs * 20;
+        let (endpoints_len, selectors_len, tables_len) = (sgd_field(4, 4)?, sgd_field(8, 4)?, sgd_field(12, 4)?);
+        let selectors_ofs = endpoints_ofs + endpoints_len;
+        let tables_ofs = selectors_ofs + selectors_len;
+        let slice = |ofs: usize, len: usize| sgd.get(ofs..ofs + len).ok_or_else(invalid);
+        let codebooks = Etc1sCodebooks::new(
+            sgd_field(0, 2)?, slice(endpoints_ofs, endpoints_len)?,
+            sgd_field(2, 2)?, slice(selectors_ofs, selectors_len)?,
+            slice(tables_ofs, tables_len)?,
+        )?;
+        Ok(BasisFile { data: data.into(), is_uastc: false, codebooks: Some(codebooks), level_data: None, images })
+    }
+
+    /// The file this was parsed from.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    fn level(&self, image: usize, level: usize) -> Result<&BasisLevel, ImageError> {
+        self.images.get(image).and_then(|l| l.get(level)).ok_or_else(invalid)
+    }
+
+    fn decode_blocks(&self, level: &BasisLevel, slice: (usize, usize)) -> Result<Vec<(u32, u32)>, ImageError> {
+        let codebooks = self.codebooks.as_ref().ok_or_else(|| ImageError::unknown_format("UASTC"))?;
+        let data = self.data.get(slice.0..slice.0 + slice.1).ok_or_else(invalid)?;
+        codebooks.decode_slice(data, level.blocks_x, level.blocks_y)
+    }
+
+    /// Decodes a level into RGBA blocks: 16 pixels per block, blocks in row-major order.
+    fn decode_rgba_blocks(&self, level: &BasisLevel) -> Result<Vec<[[u8; 4]; 16]>, ImageError> {
+        if self.is_uastc {
+            let data = self.level_data.as_deref().unwrap_or(&self.data);
+            let size = level.blocks_x * level.blocks_y * UASTC_BLOCK_SIZE;
+            if level.rgb.1 < size {
+                return Err(invalid());
+            }
+            let blocks = data.get(level.rgb.0..level.rgb.0 + size).ok_or_else(invalid)?;
+            return blocks.chunks_exact(UASTC_BLOCK_SIZE).map(decode_uastc_block).collect();
+        }
+        let codebooks = self.codebooks.as_ref().ok_or_else(|| ImageError::unknown_format("UASTC"))?;
+        let rgb = self.decode_blocks(level, level.rgb)?;
+        let mut blocks: Vec<[[u8; 4]; 16]> = rgb.iter().map(|&(e, s)| {
+            let colors = codebooks.endpoints[e as usize].block_colors();
+            let selector = &codebooks.selectors[s as usize];
+            let mut block = [[0u8; 4]; 16];
+            for (px, &sel) in block.iter_mut().zip(selector.iter()) {
+                *px = colors[sel as usize];
+            }
+            block
+        }).collect();
+        if let Some(alpha) = level.alpha {
+            // alpha slices are greyscale ETC1S; their green channel is the alpha
+            for (block, (e, s)) in blocks.iter_mut().zip(self.decode_blocks(level, alpha)?) {
+                let colors = codebooks.endpoints[e as usize].block_colors();
+                for (px, &sel) in block.iter_mut().zip(codebooks.selectors[s as usize].iter()) {
+                    px[3] = colors[sel as usize][1];
+                }
+            }
+        }
+        Ok(blocks)
+    }
+
+    fn decode_rgba(&self, level: &BasisLevel) -> Result<Vec<u8>, ImageError> {
+        let blocks = self.decode_rgba_blocks(level)?;
+        let (w, h) = (level.width, level.height);
+        let mut out = vec![0u8; w * h * 4];
+        for y in 0..h {
+            for x in 0..w {
+                let block = &blocks[(y / 4) * level.blocks_x + x / 4];
+                let o = (y * w + x) * 4;
+                out[o..o + 4].copy_from_slice(&block[(y % 4) * 4 + x % 4]);
+            }
+        }
+        Ok(out)
+    }
+
+    fn transcode_etc1(&self, level: &BasisLevel) -> Result<Vec<u8>, ImageError> {
+        let codebooks = self.codebooks.as_ref().ok_or_else(|| ImageError::unknown_format("UASTC"))?;
+        let mut out = Vec::with_capacity(level.blocks_x * level.blocks_y * 8);
+        for (e, s) in self.decode_blocks(level, level.rgb)? {
+            let endpoint = &codebooks.endpoints[e as usize];
+            let c = endpoint.color5;
+            // differential mode with a zero delta, so both halves share the colour and intensity table
+            out.extend_from_slice(&[c[0] << 3, c[1] << 3, c[2] << 3, endpoint.inten << 5 | endpoint.inten << 2 | 2]);
+            let mut indices = 0u32;
+            for (i, &sel) in codebooks.selectors[s as usize].iter().enumerate() {
+                let (x, y) = (i % 4, i / 4);
+                let etc1 = SELECTOR_TO_ETC1[sel as usize];
+                indices |= (etc1 & 1) << (x * 4 + y) | (etc1 >> 1) << (x * 4 + y + 16);
+            }
+            out.extend_from_slice(&indices.to_be_bytes());
+        }
+        Ok(out)
+    }
+}
+
+#[wasm_bindgen]
+impl BasisFile {
+    /// Parses a `.basis` file or a KTX2 file, detected by its signature.
+    #[wasm_bindgen(constructor)]
+    pub fn new(data: &[u8]) -> Result<BasisFile, ImageError> {
+        if data.starts_with(&KTX2_IDENTIFIER) {
+            BasisFile::parse_ktx2(data)
+        } else {
+            BasisFile::parse_basis(data)
+        }
+    }
+
+    /// "ETC1S" or "UASTC".
+    #[wasm_bindgen(getter)]
+    pub fn format(&self) -> String {
+        if self.is_uastc { "UASTC" } else { "ETC1S" }.to_string()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn image_count(&self) -> usize {
+        self.images.len()
+    }
+
+    pub fn level_count(&self, image: usize) -> usize {
+        self.images.get(image).map(|l| l.len()).unwrap_or(0)
+    }
+
+    pub fn width(&self, image: usize, level: usize) -> usize {
+        self.level(image, level).map(|l| l.width).unwrap_or(0)
+    }
+
+    pub fn height(&self, image: usize, level: usize) -> usize {
+        self.level(image, level).map(|l| l.height).unwrap_or(0)
+    }
+
+    pub fn has_alpha(&self, image: usize, level: usize) -> bool {
+        self.level(image, level).map(|l| l.alpha.is_some()).unwrap_or(false)
+    }
+
+    /// Transcodes one mip level of one image.
+    ///
+    /// # Arguments
+    ///
+    /// * `image` - The image (or array layer / cubemap face) index
+    /// * `level` - The mip level, 0 being the largest
+    /// * `target` - "rgba" for RGBA data, top row first; "etc1" for ETC1 blocks (without alpha, ETC1S only);
+    ///   "bc7" or "astc" for BC7 or ASTC 4x4 blocks re-encoded from the decoded pixels
+    pub fn transcode(&self, image: usize, level: usize, target: &str) -> Result<Box<[u8]>, ImageError> {
+        let level = self.level(image, level)?;
+        let out = match target {
+            "rgba" => self.decode_rgba(level)?,
+            "etc1" => self.transcode_etc1(level)?,
+            "bc7" | "astc" => {
+                let func = if target == "bc7" { encode_bc7_block } else { encode_astc_4x4_block };
+                let rgba = self.decode_rgba(level)?;
+                encode_blocks(&rgba, level.width, level.height, func)
+            },
+            _ => return Err(ImageError::unknown_format(target))
+        };
+        Ok(out.into())
+    }
+}
@@ -0,0 +1,180 @@
+use wasm_bindgen::prelude::*;
+use crate::error::ImageError;
+use crate::pngenc::check_rgba_size;
+
+// Quick single-mode encoders for GPU block formats, for exporting to containers like DDS and KTX.
+// Both take a 4x4 block of RGBA pixels in row-major order and aim for speed over quality.
+
+const BC7_WEIGHTS4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+const ASTC_WEIGHTS2: [u32; 4] = [0, 21, 43, 64];
+
+fn channel_bounds(pixels: &[[u8; 4]; 16]) -> ([u8; 4], [u8; 4]) {
+    let mut lo = [255u8; 4];
+    let mut hi = [0u8; 4];
+    for px in pixels.iter() {
+        for c in 0..4 {
+            lo[c] = lo[c].min(px[c]);
+            hi[c] = hi[c].max(px[c]);
+        }
+    }
+    (lo, hi)
+}
+
+fn distance(a: [u32; 4], b: [u8; 4]) -> u32 {
+    (0..4).map(|c| {
+        let d = a[c] as i32 - b[c] as i32;
+        (d * d) as u32
+    }).sum()
+}
+
+/// Picks the index of the palette entry closest to each pixel.
+fn nearest_indices(pixels: &[[u8; 4]; 16], palette: &[[u32; 4]]) -> [u8; 16] {
+    let mut indices = [0u8; 16];
+    for (i, px) in pixels.iter().enumerate() {
+        let mut best = u32::MAX;
+        for (j, p) in palette.iter().enumerate() {
+            let d = distance(*p, *px);
+            if d < best {
+                best = d;
+                indices[i] = j as u8;
+            }
+        }
+    }
+    indices
+}
+
+/// Writes `bits` bits of `value` into a little-endian 128-bit block.
+fn put_bits(block: &mut u128, pos: &mut u32, bits: u32, value: u32) {
+    *block |= ((value as u128) & ((1u128 << bits) - 1)) << *pos;
+    *pos += bits;
+}
+
+/// Quantises an endpoint to 7 bits per channel plus a shared p-bit, choosing the p-bit that fits best.
+fn bc7_quantize_endpoint(e: [u8; 4]) -> ([u32; 4], u32) {
+    let mut best = ([0u32; 4], 0u32, u32::MAX);
+    for p in 0..2u32 {
+        let mut q = [0u32; 4];
+        let mut err = 0;
+        for c in 0..4 {
+            q[c] = ((e[c] as i32 - p as i32 + 1) / 2).clamp(0, 127) as u32;
+            let v = (q[c] << 1 | p) as i32;
+            err += ((v - e[c] as i32) * (v - e[c] as i32)) as u32;
+        }
+        if err < best.2 {
+            best = (q, p, err);
+        }
+    }
+    (best.0, best.1)
+}
+
+/// Encodes a block as BC7 mode 6 (one subset, RGBA endpoints with p-bits, 4-bit indices).
+pub fn encode_bc7_block(pixels: &[[u8; 4]; 16]) -> [u8; 16] {
+    let (lo, hi) = channel_bounds(pixels);
+    let (mut q0, mut p0) = bc7_quantize_endpoint(lo);
+    let (mut q1, mut p1) = bc7_quantize_endpoint(hi);
+    let palette = |q0: &[u32; 4], p0: u32, q1: &[u32; 4], p1: u32| -> Vec<[u32; 4]> {
+        BC7_WEIGHTS4.iter().map(|&w| {
+            let mut c = [0u32; 4];
+            for i in 0..4 {
+                let (e0, e1) = (q0[i] << 1 | p0, q1[i] << 1 | p1);
+                c[i] = ((64 - w) * e0 + w * e1 + 32) >> 6;
+            }
+            c
+        }).collect()
+    };
+    let mut indices = nearest_indices(pixels, &palette(&q0, p0, &q1, p1));
+    // the first index is stored with its top bit implied to be zero
+    if indices[0] >= 8 {
+        std::mem::swap(&mut q0, &mut q1);
+        std::mem::swap(&mut p0, &mut p1);
+        for i in indices.iter_mut() {
+            *i = 15 - *i;
+        }
+    }
+
+    let mut block = 0u128;
+    let mut pos = 0;
+    put_bits(&mut block, &mut pos, 7, 1 << 6);
+    for c in 0..4 {
+        put_bits(&mut block, &mut pos, 7, q0[c]);
+        put_bits(&mut block, &mut pos, 7, q1[c]);
+    }
+    put_bits(&mut block, &mut pos, 1, p0);
+    put_bits(&mut block, &mut pos, 1, p1);
+    for (i, &index) in indices.iter().enumerate() {
+        put_bits(&mut block, &mut pos, if i == 0 { 3 } else { 4 }, index as u32);
+    }
+    block.to_le_bytes()
+}
+
+/// Encodes a block as ASTC 4x4: one partition, LDR RGBA endpoints (CEM 12) at 8 bits and 2-bit weights.
+pub fn encode_astc_4x4_block(pixels: &[[u8; 4]; 16]) -> [u8; 16] {
+    let (mut lo, mut hi) = channel_bounds(pixels);
+    // the decoder applies blue contraction when the second endpoint is darker, so keep it the brighter one
+    let swapped = (hi[0] as u32 + hi[1] as u32 + hi[2] as u32) < (lo[0] as u32 + lo[1] as u32 + lo[2] as u32);
+    if swapped {
+        std::mem::swap(&mut lo, &mut hi);
+    }
+    let palette: Vec<[u32; 4]> = ASTC_WEIGHTS2.iter().map(|&w| {
+        let mut c = [0u32; 4];
+        for i in 0..4 {
+            let (e0, e1) = (lo[i] as u32 * 257, hi[i] as u32 * 257);
+            c[i] = ((e0 * (64 - w) + e1 * w + 32) >> 6) >> 8;
+        }
+        c
+    }).collect();
+    let indices = nearest_indices(pixels, &palette);
+
+    let mut block = 0u128;
+    let mut pos = 0;
+    // block mode: 4x4 weight grid, single plane, weights in the 0..3 range
+    put_bits(&mut block, &mut pos, 11, 0x042);
+    put_bits(&mut block, &mut pos, 2, 0);
+    put_bits(&mut block, &mut pos, 4, 12);
+    for c in 0..4 {
+        put_bits(&mut block, &mut pos, 8, lo[c] as u32);
+        put_bits(&mut block, &mut pos, 8, hi[c] as u32);
+    }
+    // weights are stored bit-reversed from the top of the block
+    for (i, &index) in indices.iter().enumerate() {
+        let top = 127 - 2 * i as u32;
+        block |= ((index as u128) & 1) << top;
+        block |= ((index as u128) >> 1 & 1) << (top - 1);
+    }
+    block.to_le_bytes()
+}
+
+/// Runs `func` over every 4x4 block of an RGBA image, clamping at the right and bottom edges.
+pub fn encode_blocks(data: &[u8], width: usize, height: usize, func: impl Fn(&[[u8; 4]; 16]) -> [u8; 16]) -> Vec<u8> {
+    let (bw, bh) = ((width + 3) / 4, (height + 3) / 4);
+    let mut out = Vec::with_capacity(bw * bh * 16);
+    for by in 0..bh {
+        for bx in 0..bw {
+            let mut pixels = [[0u8; 4]; 16];
+            for y in 0..4 {
+                for x in 0..4 {
+                    let sx = (bx * 4 + x).min(width - 1);
+                    let sy = (by * 4 + y).min(height - 1);
+                    let o = (sy * width + sx) * 4;
+                    pixels[y * 4 + x].copy_from_slice(&data[o..o + 4]);
+                }
+            }
+            out.extend_from_slice(&func(&pixels));
+        }
+    }
+    out
+}
+
+#[wasm_bindgen]
+/// Compresses RGBA data to BC7 (mode 6 only), with blocks in the same row order as the input.
+pub fn encode_bc7(width: u32, height: u32, data: &[u8]) -> Result<Box<[u8]>, ImageError> {
+    check_rgba_size(width, height, data)?;
+    Ok(encode_blocks(data, width as usize, height as usize, encode_bc7_block).into())
+}
+
+#[wasm_bindgen]
+/// Compresses RGBA data to LDR ASTC 4x4, with blocks in the same row order as the input.
+pub fn encode_astc_4x4(width: u32, height: u32, data: &[u8]) -> Result<Box<[u8]>, ImageError> {
+    check_rgba_size(width, height, data)?;
+    Ok(encode_blocks(data, width as usize, height as usize, encode_astc_4x4_block).into())
+}
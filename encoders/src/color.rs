@@ -0,0 +1,193 @@
+use std::cmp::min;
+use wasm_bindgen::prelude::*;
+use crate::error::{rgba_size, ImageError};
+use crate::resize::resize;
+
+pub fn srgb_to_linear_f32(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb_f32(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn build_lut(func: fn(f32) -> f32) -> [u8; 256] {
+    let mut lut = [0u8; 256];
+    for i in 0..256 {
+        lut[i] = (func(i as f32 / 255.0).clamp(0.0, 1.0) * 255.0).round() as u8;
+    }
+    lut
+}
+
+fn apply_lut(data: &mut [u8], lut: &[u8; 256]) {
+    for i in 0..(data.len() / 4) {
+        let o = i * 4;
+        data[o] = lut[data[o] as usize];
+        data[o + 1] = lut[data[o + 1] as usize];
+        data[o + 2] = lut[data[o + 2] as usize];
+    }
+}
+
+#[wasm_bindgen]
+/// Converts the RGB channels of RGBA data from sRGB to linear in place. Alpha is left untouched.
+pub fn srgb_to_linear(data: &mut [u8]) {
+    apply_lut(data, &build_lut(srgb_to_linear_f32));
+}
+
+#[wasm_bindgen]
+/// Converts the RGB channels of RGBA data from linear to sRGB in place. Alpha is left untouched.
+pub fn linear_to_srgb(data: &mut [u8]) {
+    apply_lut(data, &build_lut(linear_to_srgb_f32));
+}
+
+#[wasm_bindgen]
+pub fn premultiply_alpha(data: &mut [u8]) {
+    for i in 0..(data.len() / 4) {
+        let o = i * 4;
+        let a = data[o + 3] as u32;
+        for c in 0..3 {
+            data[o + c] = ((data[o + c] as u32 * a + 127) / 255) as u8;
+        }
+    }
+}
+
+#[wasm_bindgen]
+pub fn unpremultiply_alpha(data: &mut [u8]) {
+    for i in 0..(data.len() / 4) {
+        let o = i * 4;
+        let a = data[o + 3] as u32;
+        if a == 0 || a == 0xff {
+            continue;
+        }
+        for c in 0..3 {
+            data[o + c] = ((data[o + c] as u32 * 255 + a / 2) / a).min(255) as u8;
+        }
+    }
+}
+
+#[wasm_bindgen]
+/// Fills the colour of fully transparent pixels with the average of their visible neighbours,
+/// growing outwards one pixel per pass, so filtering and mipmapping don't pull in dark fringes.
+///
+/// # Arguments
+///
+/// * `data` - RGBA data to modify in place
+/// * `width`, `height` - The image size
+/// * `passes` - How many pixels to dilate by, or 0 to fill the whole image
+pub fn bleed_alpha(data: &mut [u8], width: usize, height: usize, passes: usize) {
+    let mut filled: Vec<bool> = (0..(width * height)).map(|i| data[i * 4 + 3] != 0).collect();
+    let neighbours = move |i: usize| {
+        let (x, y) = (i % width, i / width);
+        (y.saturating_sub(1)..min(y + 2, height))
+            .flat_map(move |ny| (x.saturating_sub(1)..min(x + 2, width)).map(move |nx| ny * width + nx))
+    };
+    // Each pass only looks at the frontier: the unfilled pixels next to one filled before it.
+    // `queued` keeps a pixel from joining a frontier twice, so filling the whole image is linear in its size.
+    let mut queued = vec![false; width * height];
+    let mut frontier: Vec<usize> = (0..(width * height))
+        .filter(|&i| !filled[i] && neighbours(i).any(|n| filled[n]))
+        .collect();
+    for &i in frontier.iter() {
+        queued[i] = true;
+    }
+    let mut pass = 0;
+    while !frontier.is_empty() && (passes == 0 || pass < passes) {
+        let colours: Vec<[u32; 3]> = frontier.iter().map(|&i| {
+            let mut sum = [0u32; 3];
+            let mut count = 0;
+            for n in neighbours(i).filter(|&n| filled[n]) {
+                sum[0] += data[n * 4] as u32;
+                sum[1] += data[n * 4 + 1] as u32;
+                sum[2] += data[n * 4 + 2] as u32;
+                count += 1;
+            }
+            [sum[0] / count, sum[1] / count, sum[2] / count]
+        }).collect();
+        for (&i, c) in frontier.iter().zip(colours) {
+            data[i * 4] = c[0] as u8;
+            data[i * 4 + 1] = c[1] as u8;
+            data[i * 4 + 2] = c[2] as u8;
+            filled[i] = true;
+        }
+        let mut next = Vec::new();
+        for &i in frontier.iter() {
+            for n in neighbours(i) {
+                if !filled[n] && !queued[n] {
+                    queued[n] = true;
+                    next.push(n);
+                }
+            }
+        }
+        frontier = next;
+        pass += 1;
+    }
+}
+
+#[wasm_bindgen]
+/// Applies the colour-space and alpha options chosen for an export.
+///
+/// # Arguments
+///
+/// * `data` - Decoded RGBA data to modify in place
+/// * `width`, `height` - The image size
+/// * `color_space` - The texture's colour-space flag (Unity `m_ColorSpace`: 0 = linear, 1 = sRGB), or -1 if unknown
+/// * `to_srgb` - Convert to sRGB for display unless the texture is already flagged as sRGB
+/// * `unpremultiply` - Undo premultiplied alpha
+/// * `bleed` - Fill transparent pixels with neighbouring colours
+pub fn apply_color_options(data: &mut [u8], width: usize, height: usize, color_space: i32, to_srgb: bool,
+                           unpremultiply: bool, bleed: bool) {
+    if unpremultiply {
+        unpremultiply_alpha(data);
+    }
+    if to_srgb && color_space != 1 {
+        linear_to_srgb(data);
+    }
+    if bleed {
+        bleed_alpha(data, width, height, 0);
+    }
+}
+
+#[wasm_bindgen]
+/// Copies one channel of a second texture into the alpha channel of `data`, e.g. to recombine an Android
+/// `ETC_RGB4` texture with its separate alpha texture. The alpha source is resized first if its size differs.
+///
+/// # Arguments
+///
+/// * `data` - RGBA data to modify in place
+/// * `width`, `height` - The size of `data`
+/// * `alpha` - RGBA data holding the alpha, in the same row order as `data`
+/// * `alpha_width`, `alpha_height` - The size of `alpha`
+/// * `channel` - The channel of `alpha` to use: 0 = R (usual for Unity's `_AlphaTex`), 1 = G, 2 = B, 3 = A
+pub fn merge_alpha(data: &mut [u8], width: usize, height: usize, alpha: &[u8], alpha_width: usize, alpha_height: usize,
+                   channel: usize) -> Result<(), ImageError> {
+    let size = rgba_size(width, height)?;
+    if data.len() < size {
+        return Err(ImageError::data_too_short("RGBA32", size, data.len()));
+    }
+    let alpha_size = rgba_size(alpha_width, alpha_height)?;
+    if alpha.len() < alpha_size {
+        return Err(ImageError::data_too_short("RGBA32", alpha_size, alpha.len()));
+    }
+    let channel = min(channel, 3);
+    let resized;
+    let (source, channel) = if alpha_width == width && alpha_height == height {
+        (alpha, channel)
+    } else {
+        // resize the channel on its own as opaque red, so it isn't weighted by the source's alpha
+        let red: Vec<u8> = alpha[..alpha_size].chunks_exact(4).flat_map(|px| [px[channel], 0, 0, 255]).collect();
+        resized = resize(&red, alpha_width, alpha_height, width, height, "bilinear", false);
+        (&resized[..], 0)
+    };
+    for i in 0..(width * height) {
+        data[i * 4 + 3] = source[i * 4 + channel];
+    }
+    Ok(())
+}
@@ -1,8 +1,9 @@
-use std::io::{BufReader, Cursor};
+use std::io::{BufReader, Cursor, Read};
 use std::panic;
-use lz4_flex::{compress, decompress};
+use lz4_flex::compress;
 use lz4_flex::block::DecompressError;
 use wasm_bindgen::prelude::*;
+use crate::error::CompressionError;
 
 #[wasm_bindgen]
 pub fn lz4_compress(data: &mut [u8]) -> Box<[u8]> {
@@ -12,8 +13,1051 @@ pub fn lz4_compress(data: &mut [u8]) -> Box<[u8]> {
 #[wasm_bindgen]
 pub fn lz4_decompress(data: &mut [u8], out_size: usize) -> Result<Box<[u8]>, JsValue> {
     panic::set_hook(Box::new(console_error_panic_hook::hook));
-    match decompress(data, out_size) {
+    match lz4_flex::decompress(data, out_size) {
         Ok(v) => Ok(v.into()),
         Err(e) => Err(JsValue::from(e.to_string()))
     }
 }
+
+// LZMA, as used by UnityFS blocks (5 property bytes followed by the stream) and by UnityWeb/UnityRaw
+// bundles (the ".lzma" format, which adds the uncompressed size as a 64-bit integer after the properties).
+
+const LZMA_PROPS_SIZE: usize = 5;
+const LZMA_ALONE_HEADER_SIZE: usize = LZMA_PROPS_SIZE + 8;
+const LZMA_STATES: usize = 12;
+const LZMA_POS_STATES_MAX: usize = 1 << 4;
+const LZMA_END_POS_MODEL_INDEX: u32 = 14;
+const LZMA_FULL_DISTANCES: usize = 128;
+const LZMA_ALIGN_BITS: u32 = 4;
+const LZMA_MATCH_MIN_LEN: usize = 2;
+const LZMA_PROB_INIT: u16 = 1024;
+
+struct RangeDecoder<I: AsRef<[u8]>> {
+    input: I,
+    pos: usize,
+    range: u32,
+    code: u32,
+    /// Set when the decoder needed more bytes than the input has
+    overrun: bool,
+}
+
+impl<I: AsRef<[u8]>> RangeDecoder<I> {
+    fn new(input: I, pos: usize) -> RangeDecoder<I> {
+        let mut rc = RangeDecoder { input, pos, range: 0xffffffff, code: 0, overrun: false };
+        // the first byte is always 0
+        rc.next_byte();
+        for _ in 0..4 {
+            rc.code = rc.code << 8 | rc.next_byte() as u32;
+        }
+        rc
+    }
+
+    #[inline]
+    fn next_byte(&mut self) -> u8 {
+        match self.input.as_ref().get(self.pos) {
+            Some(&b) => {
+                self.pos += 1;
+                b
+            },
+            None => {
+                self.overrun = true;
+                0
+            }
+        }
+    }
+
+    #[inline]
+    fn normalize(&mut self) {
+        if self.range < 1 << 24 {
+            self.range <<= 8;
+            self.code = self.code << 8 | self.next_byte() as u32;
+        }
+    }
+
+    #[inline]
+    fn bit(&mut self, prob: &mut u16) -> u32 {
+        let bound = (self.range >> 11) * *prob as u32;
+        let bit = if self.code < bound {
+            self.range = bound;
+            *prob += (2048 - *prob) >> 5;
+            0
+        } else {
+            self.range -= bound;
+            self.code -= bound;
+            *prob -= *prob >> 5;
+            1
+        };
+        self.normalize();
+        bit
+    }
+
+    fn direct_bits(&mut self, count: u32) -> u32 {
+        let mut result = 0;
+        for _ in 0..count {
+            self.range >>= 1;
+            let bit = if self.code >= self.range {
+                self.code -= self.range;
+                1
+            } else {
+                0
+            };
+            result = result << 1 | bit;
+            self.normalize();
+        }
+        result
+    }
+
+    fn tree(&mut self, probs: &mut [u16], bits: u32) -> u32 {
+        let mut m = 1;
+        for _ in 0..bits {
+            m = m << 1 | self.bit(&mut probs[m as usize]);
+        }
+        m - (1 << bits)
+    }
+
+    fn reverse_tree(&mut self, probs: &mut [u16], bits: u32) -> u32 {
+        let mut m = 1;
+        let mut symbol = 0;
+        for i in 0..bits {
+            let bit = self.bit(&mut probs[m as usize]);
+            m = m << 1 | bit;
+            symbol |= bit << i;
+        }
+        symbol
+    }
+}
+
+struct LenProbs {
+    choice: u16,
+    choice2: u16,
+    low: [[u16; 8]; LZMA_POS_STATES_MAX],
+    mid: [[u16; 8]; LZMA_POS_STATES_MAX],
+    high: [u16; 256],
+}
+
+impl LenProbs {
+    fn new() -> LenProbs {
+        LenProbs {
+            choice: LZMA_PROB_INIT,
+            choice2: LZMA_PROB_INIT,
+            low: [[LZMA_PROB_INIT; 8]; LZMA_POS_STATES_MAX],
+            mid: [[LZMA_PROB_INIT; 8]; LZMA_POS_STATES_MAX],
+            high: [LZMA_PROB_INIT; 256],
+        }
+    }
+
+    /// The match length minus 2.
+    fn decode<I: AsRef<[u8]>>(&mut self, rc: &mut RangeDecoder<I>, pos_state: usize) -> usize {
+        if rc.bit(&mut self.choice) == 0 {
+            rc.tree(&mut self.low[pos_state], 3) as usize
+        } else if rc.bit(&mut self.choice2) == 0 {
+            8 + rc.tree(&mut self.mid[pos_state], 3) as usize
+        } else {
+            16 + rc.tree(&mut self.high, 8) as usize
+        }
+    }
+
+    fn encode(&mut self, rc: &mut RangeEncoder, pos_state: usize, len: usize) {
+        if len < 8 {
+            rc.bit(&mut self.choice, 0);
+            rc.tree(&mut self.low[pos_state], 3, len as u32);
+        } else if len < 16 {
+            rc.bit(&mut self.choice, 1);
+            rc.bit(&mut self.choice2, 0);
+            rc.tree(&mut self.mid[pos_state], 3, (len - 8) as u32);
+        } else {
+            rc.bit(&mut self.choice, 1);
+            rc.bit(&mut self.choice2, 1);
+            rc.tree(&mut self.high, 8, (len - 16) as u32);
+        }
+    }
+}
+
+/// The adaptive probabilities of the LZMA model, shared by the decoder and the encoder.
+struct LzmaProbs {
+    literal: Vec<u16>,
+    is_match: [u16; LZMA_STATES * LZMA_POS_STATES_MAX],
+    is_rep: [u16; LZMA_STATES],
+    is_rep_g0: [u16; LZMA_STATES],
+    is_rep_g1: [u16; LZMA_STATES],
+    is_rep_g2: [u16; LZMA_STATES],
+    is_rep0_long: [u16; LZMA_STATES * LZMA_POS_STATES_MAX],
+    pos_slot: [[u16; 64]; 4],
+    pos_special: [u16; 1 + LZMA_FULL_DISTANCES - LZMA_END_POS_MODEL_INDEX as usize],
+    align: [u16; 1 << LZMA_ALIGN_BITS],
+    len: LenProbs,
+    rep_len: LenProbs,
+}
+
+impl LzmaProbs {
+    fn new(lc: u32, lp: u32) -> LzmaProbs {
+        LzmaProbs {
+            literal: vec![LZMA_PROB_INIT; 0x300 << (lc + lp)],
+            is_match: [LZMA_PROB_INIT; LZMA_STATES * LZMA_POS_STATES_MAX],
+            is_rep: [LZMA_PROB_INIT; LZMA_STATES],
+            is_rep_g0: [LZMA_PROB_INIT; LZMA_STATES],
+            is_rep_g1: [LZMA_PROB_INIT; LZMA_STATES],
+            is_rep_g2: [LZMA_PROB_INIT; LZMA_STATES],
+            is_rep0_long: [LZMA_PROB_INIT; LZMA_STATES * LZMA_POS_STATES_MAX],
+            pos_slot: [[LZMA_PROB_INIT; 64]; 4],
+            pos_special: [LZMA_PROB_INIT; 1 + LZMA_FULL_DISTANCES - LZMA_END_POS_MODEL_INDEX as usize],
+            align: [LZMA_PROB_INIT; 1 << LZMA_ALIGN_BITS],
+            len: LenProbs::new(),
+            rep_len: LenProbs::new(),
+        }
+    }
+}
+
+/// LZMA properties: literal context bits, literal position bits, position bits and the dictionary size.
+fn parse_lzma_props(props: &[u8]) -> Result<(u32, u32, u32, usize), String> {
+    if props.len() < LZMA_PROPS_SIZE {
+        return Err("LZMA data is too short".to_string());
+    }
+    let mut d = props[0] as u32;
+    if d >= 9 * 5 * 5 {
+        return Err("invalid LZMA properties".to_string());
+    }
+    let lc = d % 9;
+    d /= 9;
+    let (lp, pb) = (d % 5, d / 5);
+    let dict_size = u32::from_le_bytes([props[1], props[2], props[3], props[4]]).max(1 << 12);
+    Ok((lc, lp, pb, dict_size as usize))
+}
+
+/// An LZMA decoder over a complete input. The output so far doubles as the dictionary; when streaming,
+/// output that has been handed out and is further back than the dictionary reaches is dropped.
+struct LzmaDecoder<I: AsRef<[u8]>> {
+    rc: RangeDecoder<I>,
+    lc: u32,
+    lp: u32,
+    pb: u32,
+    dict_size: usize,
+    out_size: Option<usize>,
+    buf: Vec<u8>,
+    /// Bytes dropped from the front of `buf`
+    dropped: usize,
+    finished: bool,
+    state: usize,
+    reps: [usize; 4],
+    probs: LzmaProbs,
+}
+
+impl<I: AsRef<[u8]>> LzmaDecoder<I> {
+    /// `stream` is where the range coder data starts in `input`, after the properties in `props`.
+    /// Without an `out_size`, decoding stops at the end marker.
+    fn new(props: &[u8], input: I, stream: usize, out_size: Option<usize>) -> Result<LzmaDecoder<I>, String> {
+        let (lc, lp, pb, dict_size) = parse_lzma_props(props)?;
+        if input.as_ref().len() < stream + 5 {
+            return Err("LZMA data is too short".to_string());
+        }
+        Ok(LzmaDecoder {
+            rc: RangeDecoder::new(input, stream),
+            lc,
+            lp,
+            pb,
+            dict_size,
+            out_size,
+            buf: Vec::with_capacity(out_size.unwrap_or(0)),
+            dropped: 0,
+            finished: out_size == Some(0),
+            state: 0,
+            reps: [0; 4],
+            probs: LzmaProbs::new(lc, lp),
+        })
+    }
+
+    fn total(&self) -> usize {
+        self.dropped + self.buf.len()
+    }
+
+    /// The byte `distance + 1` bytes back from the end of the output.
+    #[inline]
+    fn back(&self, distance: usize) -> u8 {
+        self.buf[self.buf.len() - distance - 1]
+    }
+
+    fn decode_literal(&mut self) {
+        let total = self.total();
+        let prev = if self.buf.is_empty() { 0 } else { self.back(0) as usize };
+        let context = ((total & ((1 << self.lp) - 1)) << self.lc) + (prev >> (8 - self.lc));
+        let probs = &mut self.probs.literal[context * 0x300..(context + 1) * 0x300];
+        let mut symbol = 1usize;
+        if self.state >= 7 {
+            // after a match, the byte at rep0 predicts the literal until the first mismatching bit
+            let mut match_byte = self.buf[self.buf.len() - self.reps[0] - 1] as usize;
+            while symbol < 0x100 {
+                let match_bit = (match_byte >> 7) & 1;
+                match_byte <<= 1;
+                let bit = self.rc.bit(&mut probs[((1 + match_bit) << 8) + symbol]) as usize;
+                symbol = symbol << 1 | bit;
+                if match_bit != bit {
+                    break;
+                }
+            }
+        }
+        while symbol < 0x100 {
+            symbol = symbol << 1 | self.rc.bit(&mut probs[symbol]) as usize;
+        }
+        self.buf.push(symbol as u8);
+        self.state = match self.state {
+            0..=3 => 0,
+            4..=9 => self.state - 3,
+            _ => self.state - 6
+        };
+    }
+
+    fn decode_distance(&mut self, len: usize) -> usize {
+        let slot = self.rc.tree(&mut self.probs.pos_slot[len.min(3)], 6);
+        if slot < 4 {
+            return slot as usize;
+        }
+        let direct_bits = (slot >> 1) - 1;
+        let mut distance = (2 | (slot & 1)) << direct_bits;
+        if slot < LZMA_END_POS_MODEL_INDEX {
+            let base = (distance - slot) as usize;
+            distance += self.rc.reverse_tree(&mut self.probs.pos_special[base..], direct_bits);
+        } else {
+            distance += self.rc.direct_bits(direct_bits - LZMA_ALIGN_BITS) << LZMA_ALIGN_BITS;
+            distance += self.rc.reverse_tree(&mut self.probs.align, LZMA_ALIGN_BITS);
+        }
+        distance as usize
+    }
+
+    /// Copies `len` bytes from `distance + 1` bytes back, stopping at the output size.
+    fn copy_match(&mut self, distance: usize, len: usize) {
+        let len = match self.out_size {
+            Some(size) => len.min(size - self.total()),
+            None => len
+        };
+        let start = self.buf.len() - distance - 1;
+        if distance + 1 >= len {
+            self.buf.extend_from_within(start..start + len);
+        } else {
+            for i in 0..len {
+                let b = self.buf[start + i];
+                self.buf.push(b);
+            }
+        }
+    }
+
+    /// Decodes until at least `target` bytes have been output in total, or the stream ends.
+    fn run(&mut self, target: usize) -> Result<(), String> {
+        let pos_mask = (1 << self.pb) - 1;
+        while !self.finished && self.total() < target {
+            let pos_state = self.total() & pos_mask;
+            let state = self.state;
+            if self.rc.bit(&mut self.probs.is_match[(state << 4) + pos_state]) == 0 {
+                self.decode_literal();
+            } else {
+                let len;
+                if self.rc.bit(&mut self.probs.is_rep[state]) == 1 {
+                    if self.total() == 0 {
+                        return Err("LZMA data is corrupt".to_string());
+                    }
+                    if self.rc.bit(&mut self.probs.is_rep_g0[state]) == 0 {
+                        if self.rc.bit(&mut self.probs.is_rep0_long[(state << 4) + pos_state]) == 0 {
+                            // a single byte from rep0
+                            self.state = if state < 7 { 9 } else { 11 };
+                            let b = self.back(self.reps[0]);
+                            self.buf.push(b);
+                            self.check_end();
+                            continue;
+                        }
+                    } else {
+                        let distance;
+                        if self.rc.bit(&mut self.probs.is_rep_g1[state]) == 0 {
+                            distance = self.reps[1];
+                        } else {
+                            if self.rc.bit(&mut self.probs.is_rep_g2[state]) == 0 {
+                                distance = self.reps[2];
+                            } else {
+                                distance = self.reps[3];
+                                self.reps[3] = self.reps[2];
+                            }
+                            self.reps[2] = self.reps[1];
+                        }
+                        self.reps[1] = self.reps[0];
+                        self.reps[0] = distance;
+                    }
+                    len = self.probs.rep_len.decode(&mut self.rc, pos_state);
+                    self.state = if state < 7 { 8 } else { 11 };
+                } else {
+                    self.reps = [0, self.reps[0], self.reps[1], self.reps[2]];
+                    len = self.probs.len.decode(&mut self.rc, pos_state);
+                    self.state = if state < 7 { 7 } else { 10 };
+                    let distance = self.decode_distance(len);
+                    if distance == 0xffffffff {
+                        // the end marker
+                        self.finished = true;
+                        break;
+                    }
+                    self.reps[0] = distance;
+                }
+                let distance = self.reps[0];
+                if distance >= self.buf.len() || distance >= self.dict_size {
+                    return Err("LZMA data is corrupt".to_string());
+                }
+                self.copy_match(distance, len + LZMA_MATCH_MIN_LEN);
+            }
+            self.check_end();
+        }
+        if self.rc.overrun {
+            return Err("LZMA data is truncated".to_string());
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn check_end(&mut self) {
+        if self.out_size == Some(self.total()) {
+            self.finished = true;
+        }
+    }
+
+    /// Takes the first `count` bytes of output that haven't been taken yet, dropping history that is no
+    /// longer needed.
+    fn take(&mut self, taken: &mut usize, count: usize) -> Vec<u8> {
+        let count = count.min(self.buf.len() - *taken);
+        let chunk = self.buf[*taken..*taken + count].to_vec();
+        *taken += count;
+        if *taken > 2 * self.dict_size {
+            let drop = *taken - self.dict_size;
+            self.buf.drain(..drop);
+            self.dropped += drop;
+            *taken -= drop;
+        }
+        chunk
+    }
+}
+
+fn lzma_alone_size(data: &[u8]) -> Result<Option<usize>, String> {
+    if data.len() < LZMA_ALONE_HEADER_SIZE {
+        return Err("LZMA data is too short".to_string());
+    }
+    let mut size = [0u8; 8];
+    size.copy_from_slice(&data[LZMA_PROPS_SIZE..LZMA_ALONE_HEADER_SIZE]);
+    Ok(match u64::from_le_bytes(size) {
+        u64::MAX => None,
+        size => Some(std::convert::TryFrom::try_from(size).map_err(|_| "LZMA data is too large".to_string())?)
+    })
+}
+
+/// Decodes LZMA data in Unity's block format: 5 property bytes followed by the stream, with the
+/// uncompressed size stored elsewhere.
+pub fn lzma_decompress_raw(data: &[u8], out_size: usize) -> Result<Vec<u8>, String> {
+    let mut decoder = LzmaDecoder::new(data, data, LZMA_PROPS_SIZE, Some(out_size))?;
+    decoder.run(out_size)?;
+    if decoder.buf.len() != out_size {
+        return Err(format!("LZMA data ended after {} of {} bytes", decoder.buf.len(), out_size));
+    }
+    Ok(decoder.buf)
+}
+
+/// Decodes a ".lzma" file, whose header gives the uncompressed size (or marks it unknown, in which case
+/// the stream must end with an end marker).
+pub fn lzma_alone_decompress_raw(data: &[u8]) -> Result<Vec<u8>, String> {
+    let out_size = lzma_alone_size(data)?;
+    let mut decoder = LzmaDecoder::new(data, data, LZMA_ALONE_HEADER_SIZE, out_size)?;
+    decoder.run(usize::MAX)?;
+    if out_size.is_some_and(|size| decoder.buf.len() != size) {
+        return Err("LZMA data ended early".to_string());
+    }
+    Ok(decoder.buf)
+}
+
+#[wasm_bindgen]
+/// Decompresses an LZMA block as stored in UnityFS bundles: 5 property bytes followed by the stream.
+/// Fails unless exactly `out_size` bytes are decoded.
+pub fn lzma_decompress(data: &[u8], out_size: usize) -> Result<Box<[u8]>, JsValue> {
+    lzma_decompress_raw(data, out_size).map(|v| v.into()).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+/// Decompresses a ".lzma" (LZMA-alone) file, as used by UnityWeb and UnityRaw bundles.
+pub fn lzma_alone_decompress(data: &[u8]) -> Result<Box<[u8]>, JsValue> {
+    lzma_alone_decompress_raw(data).map(|v| v.into()).map_err(JsValue::from)
+}
+
+#[wasm_bindgen]
+/// Decompresses LZMA data a piece at a time, so that large blocks can be processed without holding
+/// all of the output at once. Only the dictionary's worth of history is kept.
+pub struct LzmaStream {
+    decoder: LzmaDecoder<Box<[u8]>>,
+    taken: usize,
+}
+
+#[wasm_bindgen]
+impl LzmaStream {
+    #[wasm_bindgen(constructor)]
+    /// Starts decoding a block in Unity's format (see `lzma_decompress`).
+    pub fn new(data: Box<[u8]>, out_size: usize) -> Result<LzmaStream, JsValue> {
+        let props = data.get(..LZMA_PROPS_SIZE).unwrap_or_default().to_vec();
+        let decoder = LzmaDecoder::new(&props, data, LZMA_PROPS_SIZE, Some(out_size))?;
+        Ok(LzmaStream { decoder, taken: 0 })
+    }
+
+    /// Starts decoding a ".lzma" (LZMA-alone) file.
+    pub fn alone(data: Box<[u8]>) -> Result<LzmaStream, JsValue> {
+        let out_size = lzma_alone_size(&data)?;
+        let props = data[..LZMA_PROPS_SIZE].to_vec();
+        let decoder = LzmaDecoder::new(&props, data, LZMA_ALONE_HEADER_SIZE, out_size)?;
+        Ok(LzmaStream { decoder, taken: 0 })
+    }
+
+    /// Decodes and returns up to `max_size` more bytes. Returns an empty array once everything has been read.
+    pub fn read(&mut self, max_size: usize) -> Result<Box<[u8]>, JsValue> {
+        let target = self.decoder.dropped + self.taken + max_size;
+        self.decoder.run(target)?;
+        Ok(self.decoder.take(&mut self.taken, max_size).into())
+    }
+
+    /// The number of bytes returned by `read` so far.
+    #[wasm_bindgen(getter)]
+    pub fn total_out(&self) -> usize {
+        self.decoder.dropped + self.taken
+    }
+
+    /// Whether the stream has ended and all of its output has been read.
+    #[wasm_bindgen(getter)]
+    pub fn finished(&self) -> bool {
+        self.decoder.finished && self.taken == self.decoder.buf.len()
+    }
+
+    /// The size given for the stream when it's known up front.
+    #[wasm_bindgen(getter)]
+    pub fn out_size(&self) -> Option<usize> {
+        self.decoder.out_size
+    }
+}
+
+/// A compression method used by one of the supported file formats.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Codec {
+    None,
+    Lz4,
+    Lz4Hc,
+    Lzma,
+    /// Raw deflate, without a header
+    Deflate,
+    Zlib,
+    GZip,
+    Zstd,
+    Brotli,
+}
+
+impl Codec {
+    pub fn from_name(name: &str) -> Option<Codec> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "none" => Codec::None,
+            "lz4" => Codec::Lz4,
+            "lz4hc" => Codec::Lz4Hc,
+            "lzma" => Codec::Lzma,
+            "deflate" => Codec::Deflate,
+            "zlib" => Codec::Zlib,
+            "gzip" => Codec::GZip,
+            "zstd" | "zstandard" => Codec::Zstd,
+            "brotli" => Codec::Brotli,
+            _ => return None
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Codec::None => "None",
+            Codec::Lz4 => "LZ4",
+            Codec::Lz4Hc => "LZ4HC",
+            Codec::Lzma => "LZMA",
+            Codec::Deflate => "Deflate",
+            Codec::Zlib => "Zlib",
+            Codec::GZip => "GZip",
+            Codec::Zstd => "Zstd",
+            Codec::Brotli => "Brotli",
+        }
+    }
+}
+
+/// Reads a decoder to the end, stopping one byte past `expected_size` so oversized data isn't read in full.
+fn read_to_size<R: Read>(codec: Codec, reader: R, expected_size: usize) -> Result<Vec<u8>, CompressionError> {
+    let mut out = Vec::with_capacity(expected_size);
+    reader.take(expected_size as u64 + 1).read_to_end(&mut out)
+        .map_err(|e| CompressionError::invalid_data(codec.name(), &e.to_string()))?;
+    if out.len() > expected_size {
+        return Err(CompressionError::output_too_large(codec.name(), expected_size));
+    }
+    Ok(out)
+}
+
+/// Decompresses `data`, which must decompress to exactly `expected_size` bytes.
+pub fn decompress_with(codec: Codec, data: &[u8], expected_size: usize) -> Result<Vec<u8>, CompressionError> {
+    let invalid = |e: String| CompressionError::invalid_data(codec.name(), &e);
+    let out = match codec {
+        Codec::None => data.to_vec(),
+        Codec::Lz4 | Codec::Lz4Hc => match lz4_flex::block::decompress(data, expected_size) {
+            Ok(out) => out,
+            Err(DecompressError::OutputTooSmall { .. }) =>
+                return Err(CompressionError::output_too_large(codec.name(), expected_size)),
+            Err(e) => return Err(invalid(e.to_string()))
+        },
+        Codec::Lzma => {
+            let mut decoder = LzmaDecoder::new(data, data, LZMA_PROPS_SIZE, Some(expected_size)).map_err(invalid)?;
+            decoder.run(expected_size).map_err(invalid)?;
+            decoder.buf
+        },
+        Codec::Deflate => read_to_size(codec, flate2::read::DeflateDecoder::new(data), expected_size)?,
+        Codec::Zlib => read_to_size(codec, flate2::read::ZlibDecoder::new(data), expected_size)?,
+        Codec::GZip => read_to_size(codec, flate2::read::GzDecoder::new(data), expected_size)?,
+        Codec::Zstd => {
+            let decoder = ruzstd::streaming_decoder::StreamingDecoder::new(data).map_err(|e| invalid(e.to_string()))?;
+            read_to_size(codec, decoder, expected_size)?
+        },
+        Codec::Brotli => read_to_size(codec, brotli_decompressor::Decompressor::new(data, 4096), expected_size)?,
+    };
+    if out.len() != expected_size {
+        return Err(CompressionError::size_mismatch(codec.name(), expected_size, out.len()));
+    }
+    Ok(out)
+}
+
+#[wasm_bindgen]
+/// Decompresses data with any of the supported codecs.
+///
+/// # Arguments
+///
+/// * `codec` - "None", "LZ4", "LZ4HC", "LZMA" (Unity's block format: properties, then the stream),
+///   "Deflate" (raw), "Zlib", "GZip", "Zstd" or "Brotli"; case doesn't matter
+/// * `data` - The compressed data
+/// * `expected_size` - The exact decompressed size; anything else is an error
+pub fn decompress(codec: &str, data: &[u8], expected_size: usize) -> Result<Box<[u8]>, CompressionError> {
+    let codec = Codec::from_name(codec).ok_or_else(|| CompressionError::unknown_codec(codec))?;
+    decompress_with(codec, data, expected_size).map(|v| v.into())
+}
+
+/// Hash chains over every 4-byte sequence in the data, for finding earlier occurrences of the bytes at
+/// a position. Only the last `window` positions are remembered, so callers cap it at the input
+/// length; positions are stored as `u32` to keep the chain at 4 bytes per remembered position.
+struct HashChain {
+    head: Vec<u32>,
+    chain: Vec<u32>,
+    hash_log: u32,
+    window: usize,
+    next: usize,
+}
+
+const HASH_CHAIN_NONE: u32 = u32::MAX;
+
+impl HashChain {
+    fn new(hash_log: u32, window: usize) -> HashChain {
+        let window = window.max(1);
+        HashChain { head: vec![HASH_CHAIN_NONE; 1 << hash_log], chain: vec![HASH_CHAIN_NONE; window], hash_log, window, next: 0 }
+    }
+
+    #[inline]
+    fn hash(&self, data: &[u8], pos: usize) -> usize {
+        let v = u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]);
+        (v.wrapping_mul(2654435761) >> (32 - self.hash_log)) as usize
+    }
+
+    /// Adds every position before `pos` that hasn't been added yet.
+    fn insert_until(&mut self, data: &[u8], pos: usize) {
+        let end = pos.min(data.len().saturating_sub(3));
+        while self.next < end {
+            let h = self.hash(data, self.next);
+            self.chain[self.next % self.window] = self.head[h];
+            self.head[h] = self.next as u32;
+            self.next += 1;
+        }
+    }
+
+    /// The longest earlier match for the bytes at `pos` as (length, offset back), at least 4 bytes
+    /// long and ending by `end`, or a length of 0. The search stops early at a match of `nice_len`.
+    fn find(&mut self, data: &[u8], pos: usize, end: usize, max_offset: usize, attempts: usize, nice_len: usize) -> (usize, usize) {
+        if pos + 4 > end {
+            return (0, 0);
+        }
+        self.insert_until(data, pos);
+        let mut best = (0, 0);
+        let mut candidate = self.head[self.hash(data, pos)];
+        for _ in 0..attempts {
+            if candidate == HASH_CHAIN_NONE || pos - candidate as usize > max_offset {
+                break;
+            }
+            let candidate_pos = candidate as usize;
+            // cheap rejection: a longer match has to agree at the current best length
+            if data[candidate_pos + best.0] == data[pos + best.0] {
+                let len = data[candidate_pos..end].iter().zip(&data[pos..end]).take_while(|(a, b)| a == b).count();
+                if len > best.0 {
+                    best = (len, pos - candidate_pos);
+                    if pos + len == end || len >= nice_len {
+                        break;
+                    }
+                }
+            }
+            candidate = self.chain[candidate_pos % self.window];
+        }
+        if best.0 < 4 { (0, 0) } else { best }
+    }
+}
+
+// LZ4HC: the LZ4 block format, with matches found by searching hash chains instead of a single hash table.
+
+const LZ4_MIN_MATCH: usize = 4;
+/// The last match must start at least this many bytes before the end of the block
+const LZ4_MF_LIMIT: usize = 12;
+/// The block must end with at least this many literals
+const LZ4_LAST_LITERALS: usize = 5;
+const LZ4_MAX_OFFSET: usize = 65535;
+
+fn lz4_write_length(out: &mut Vec<u8>, mut len: usize) {
+    while len >= 255 {
+        out.push(255);
+        len -= 255;
+    }
+    out.push(len as u8);
+}
+
+fn lz4_write_sequence(out: &mut Vec<u8>, literals: &[u8], matched: Option<(usize, usize)>) {
+    let match_len = matched.map_or(0, |(len, _)| len - LZ4_MIN_MATCH);
+    out.push((literals.len().min(15) << 4 | match_len.min(15)) as u8);
+    if literals.len() >= 15 {
+        lz4_write_length(out, literals.len() - 15);
+    }
+    out.extend_from_slice(literals);
+    if let Some((_, offset)) = matched {
+        out.extend_from_slice(&(offset as u16).to_le_bytes());
+        if match_len >= 15 {
+            lz4_write_length(out, match_len - 15);
+        }
+    }
+}
+
+/// Compresses `data` as a single LZ4 block. `level` is 1 to 12 like the reference LZ4HC: each level
+/// doubles how many earlier matches are tried, every third doubles the length that ends the search,
+/// and from level 3 a match is put off by a byte when the next position has a longer one.
+pub fn lz4hc_compress_block(data: &[u8], level: u32) -> Vec<u8> {
+    let level = level.clamp(1, 12);
+    let attempts = 1 << level;
+    let nice_len = 16 << (level / 3);
+    let mut out = Vec::with_capacity(data.len() / 2 + 16);
+    let mut anchor = 0;
+    if data.len() > LZ4_MF_LIMIT {
+        let mut chain = HashChain::new(16, (LZ4_MAX_OFFSET + 1).min(data.len()));
+        let match_end = data.len() - LZ4_LAST_LITERALS;
+        let last_start = data.len() - LZ4_MF_LIMIT;
+        let mut pos = 0;
+        let mut found = chain.find(data, pos, match_end, LZ4_MAX_OFFSET, attempts, nice_len);
+        while pos <= last_start {
+            if found.0 == 0 {
+                pos += 1;
+                found = chain.find(data, pos, match_end, LZ4_MAX_OFFSET, attempts, nice_len);
+                continue;
+            }
+            if level >= 3 && pos < last_start {
+                let next = chain.find(data, pos + 1, match_end, LZ4_MAX_OFFSET, attempts, nice_len);
+                if next.0 > found.0 {
+                    pos += 1;
+                    found = next;
+                    continue;
+                }
+            }
+            lz4_write_sequence(&mut out, &data[anchor..pos], Some(found));
+            pos += found.0;
+            anchor = pos;
+            found = chain.find(data, pos, match_end, LZ4_MAX_OFFSET, attempts, nice_len);
+        }
+    }
+    lz4_write_sequence(&mut out, &data[anchor..], None);
+    out
+}
+
+#[wasm_bindgen]
+/// Compresses data as a raw LZ4 block, like `lz4_compress` but with the LZ4HC compression levels
+/// (1 to 12) that Unity uses for "LZ4HC" bundles. The output decompresses with `lz4_decompress`.
+pub fn lz4hc_compress(data: &[u8], level: u32) -> Box<[u8]> {
+    lz4hc_compress_block(data, level).into()
+}
+
+// LZMA encoding: a greedy parser over hash chains, with a one-byte lazy check at higher levels. The
+// properties are those Unity writes (lc = 3, lp = 0, pb = 2).
+
+/// (pb * 5 + lp) * 9 + lc
+const LZMA_UNITY_PROPS: u8 = 0x5d;
+const LZMA_MATCH_MAX_LEN: usize = 273;
+
+struct RangeEncoder {
+    low: u64,
+    range: u32,
+    cache: u8,
+    cache_size: u64,
+    out: Vec<u8>,
+}
+
+impl RangeEncoder {
+    fn new(out: Vec<u8>) -> RangeEncoder {
+        RangeEncoder { low: 0, range: 0xffffffff, cache: 0, cache_size: 1, out }
+    }
+
+    fn shift_low(&mut self) {
+        if (self.low as u32) < 0xff000000 || self.low >> 32 != 0 {
+            let carry = (self.low >> 32) as u8;
+            let mut byte = self.cache;
+            loop {
+                self.out.push(byte.wrapping_add(carry));
+                byte = 0xff;
+                self.cache_size -= 1;
+                if self.cache_size == 0 {
+                    break;
+                }
+            }
+            self.cache = (self.low >> 24) as u8;
+        }
+        self.cache_size += 1;
+        self.low = (self.low & 0x00ffffff) << 8;
+    }
+
+    #[inline]
+    fn bit(&mut self, prob: &mut u16, bit: u32) {
+        let bound = (self.range >> 11) * *prob as u32;
+        if bit == 0 {
+            self.range = bound;
+            *prob += (2048 - *prob) >> 5;
+        } else {
+            self.low += bound as u64;
+            self.range -= bound;
+            *prob -= *prob >> 5;
+        }
+        while self.range < 1 << 24 {
+            self.range <<= 8;
+            self.shift_low();
+        }
+    }
+
+    fn direct_bits(&mut self, value: u32, count: u32) {
+        for i in (0..count).rev() {
+            self.range >>= 1;
+            if value >> i & 1 != 0 {
+                self.low += self.range as u64;
+            }
+            while self.range < 1 << 24 {
+                self.range <<= 8;
+                self.shift_low();
+            }
+        }
+    }
+
+    fn tree(&mut self, probs: &mut [u16], bits: u32, value: u32) {
+        let mut m = 1;
+        for i in (0..bits).rev() {
+            let bit = value >> i & 1;
+            self.bit(&mut probs[m as usize], bit);
+            m = m << 1 | bit;
+        }
+    }
+
+    fn reverse_tree(&mut self, probs: &mut [u16], bits: u32, mut value: u32) {
+        let mut m = 1;
+        for _ in 0..bits {
+            let bit = value & 1;
+            value >>= 1;
+            self.bit(&mut probs[m as usize], bit);
+            m = m << 1 | bit;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        for _ in 0..5 {
+            self.shift_low();
+        }
+        self.out
+    }
+}
+
+/// The dictionary size, number of match candidates tried and length that ends the search for an LZMA
+/// level from 0 to 9. The dictionary and fast bytes follow the LZMA SDK's presets: `1 << (level * 2 + 14)`
+/// up to level 5, 32 MiB for levels 6 and 7 and 64 MiB above, with 32 fast bytes below level 7.
+fn lzma_level_params(level: u32) -> (usize, usize, usize) {
+    let level = level.min(9);
+    let dict_size = match level {
+        0..=5 => 1 << (level * 2 + 14),
+        6 | 7 => 1 << 25,
+        _ => 1 << 26
+    };
+    (dict_size, 4 << level, if level < 7 { 32 } else { 64 })
+}
+
+struct LzmaEncoder<'a> {
+    data: &'a [u8],
+    rc: RangeEncoder,
+    probs: LzmaProbs,
+    state: usize,
+    reps: [usize; 4],
+}
+
+impl<'a> LzmaEncoder<'a> {
+    fn encode_literal(&mut self, pos: usize) {
+        let pos_state = pos & 3;
+        self.rc.bit(&mut self.probs.is_match[(self.state << 4) + pos_state], 0);
+        let prev = if pos > 0 { self.data[pos - 1] as usize } else { 0 };
+        let context = prev >> 5;
+        let probs = &mut self.probs.literal[context * 0x300..(context + 1) * 0x300];
+        let byte = self.data[pos] as usize;
+        let mut symbol = 1;
+        let mut matching = self.state >= 7;
+        let match_byte = if matching { self.data[pos - self.reps[0] - 1] as usize } else { 0 };
+        for i in (0..8).rev() {
+            let bit = byte >> i & 1;
+            if matching {
+                let match_bit = match_byte >> i & 1;
+                self.rc.bit(&mut probs[((1 + match_bit) << 8) + symbol], bit as u32);
+                matching = match_bit == bit;
+            } else {
+                self.rc.bit(&mut probs[symbol], bit as u32);
+            }
+            symbol = symbol << 1 | bit;
+        }
+        self.state = match self.state {
+            0..=3 => 0,
+            4..=9 => self.state - 3,
+            _ => self.state - 6
+        };
+    }
+
+    fn encode_match(&mut self, pos: usize, len: usize, distance: usize) {
+        let pos_state = pos & 3;
+        let state = self.state;
+        self.rc.bit(&mut self.probs.is_match[(state << 4) + pos_state], 1);
+        self.rc.bit(&mut self.probs.is_rep[state], 0);
+        let len = len - LZMA_MATCH_MIN_LEN;
+        self.probs.len.encode(&mut self.rc, pos_state, len);
+        let distance32 = distance as u32;
+        let slot = if distance32 < 4 {
+            distance32
+        } else {
+            let bits = 31 - distance32.leading_zeros();
+            bits << 1 | (distance32 >> (bits - 1) & 1)
+        };
+        self.rc.tree(&mut self.probs.pos_slot[len.min(3)], 6, slot);
+        if slot >= 4 {
+            let direct_bits = (slot >> 1) - 1;
+            let base = (2 | (slot & 1)) << direct_bits;
+            let reduced = distance32 - base;
+            if slot < LZMA_END_POS_MODEL_INDEX {
+                self.rc.reverse_tree(&mut self.probs.pos_special[(base - slot) as usize..], direct_bits, reduced);
+            } else {
+                self.rc.direct_bits(reduced >> LZMA_ALIGN_BITS, direct_bits - LZMA_ALIGN_BITS);
+                self.rc.reverse_tree(&mut self.probs.align, LZMA_ALIGN_BITS, reduced & ((1 << LZMA_ALIGN_BITS) - 1));
+            }
+        }
+        self.reps = [distance, self.reps[0], self.reps[1], self.reps[2]];
+        self.state = if state < 7 { 7 } else { 10 };
+    }
+
+    fn encode_rep(&mut self, pos: usize, len: usize, rep: usize) {
+        let pos_state = pos & 3;
+        let state = self.state;
+        self.rc.bit(&mut self.probs.is_match[(state << 4) + pos_state], 1);
+        self.rc.bit(&mut self.probs.is_rep[state], 1);
+        if rep == 0 {
+            self.rc.bit(&mut self.probs.is_rep_g0[state], 0);
+            self.rc.bit(&mut self.probs.is_rep0_long[(state << 4) + pos_state], 1);
+        } else {
+            self.rc.bit(&mut self.probs.is_rep_g0[state], 1);
+            if rep == 1 {
+                self.rc.bit(&mut self.probs.is_rep_g1[state], 0);
+            } else {
+                self.rc.bit(&mut self.probs.is_rep_g1[state], 1);
+                self.rc.bit(&mut self.probs.is_rep_g2[state], (rep == 3) as u32);
+            }
+            let distance = self.reps[rep];
+            self.reps.copy_within(0..rep, 1);
+            self.reps[0] = distance;
+        }
+        self.probs.rep_len.encode(&mut self.rc, pos_state, len - LZMA_MATCH_MIN_LEN);
+        self.state = if state < 7 { 8 } else { 11 };
+    }
+
+    /// The longest of the four repeated distances at `pos`, as (length, index).
+    fn longest_rep(&self, pos: usize, end: usize) -> (usize, usize) {
+        let mut best = (0, 0);
+        for (i, &rep) in self.reps.iter().enumerate() {
+            if rep >= pos {
+                continue;
+            }
+            let start = pos - rep - 1;
+            let len = self.data[start..end].iter().zip(&self.data[pos..end]).take_while(|(a, b)| a == b).count();
+            if len > best.0 {
+                best = (len, i);
+            }
+        }
+        best
+    }
+}
+
+/// Compresses `data` as an LZMA stream without an end marker, returning the 5 property bytes followed
+/// by the stream. `level` is 0 to 9; higher levels search further back and try more matches.
+pub fn lzma_compress_raw(data: &[u8], level: u32) -> Vec<u8> {
+    let (max_dict_size, attempts, nice_len) = lzma_level_params(level);
+    // the header only needs to cover distances that can actually occur, which keeps decoders that
+    // allocate the whole dictionary up front from allocating more than the data
+    let dict_size = max_dict_size.min(data.len().next_power_of_two()).max(1 << 12);
+    let mut header = vec![LZMA_UNITY_PROPS];
+    header.extend_from_slice(&(dict_size as u32).to_le_bytes());
+    let mut encoder = LzmaEncoder {
+        data,
+        rc: RangeEncoder::new(header),
+        probs: LzmaProbs::new(3, 0),
+        state: 0,
+        reps: [0; 4],
+    };
+    let mut chain = HashChain::new(18, dict_size.min(data.len()));
+    let lazy = level >= 5;
+    let mut pos = 0;
+    while pos < data.len() {
+        let end = data.len().min(pos + LZMA_MATCH_MAX_LEN);
+        let (rep_len, rep) = if pos > 0 { encoder.longest_rep(pos, end) } else { (0, 0) };
+        let (mut len, offset) = chain.find(data, pos, end, dict_size, attempts, nice_len);
+        // a repeated distance is much cheaper to code than a new one
+        if rep_len >= 2 && rep_len + 1 >= len {
+            encoder.encode_rep(pos, rep_len, rep);
+            pos += rep_len;
+            continue;
+        }
+        if lazy && len > 0 && pos + 1 < data.len() {
+            let next_end = data.len().min(pos + 1 + LZMA_MATCH_MAX_LEN);
+            if chain.find(data, pos + 1, next_end, dict_size, attempts, nice_len).0 > len + 1 {
+                len = 0;
+            }
+        }
+        if len == 0 {
+            encoder.encode_literal(pos);
+            pos += 1;
+        } else {
+            encoder.encode_match(pos, len, offset - 1);
+            pos += len;
+        }
+    }
+    encoder.rc.finish()
+}
+
+#[wasm_bindgen]
+/// Compresses data as an LZMA block for a UnityFS bundle: 5 property bytes followed by the stream. The
+/// output decompresses with `lzma_decompress` given the original size.
+///
+/// # Arguments
+///
+/// * `data` - The data to compress
+/// * `level` - 0 (fastest) to 9 (smallest)
+pub fn lzma_compress(data: &[u8], level: u32) -> Box<[u8]> {
+    lzma_compress_raw(data, level).into()
+}
+
+#[wasm_bindgen]
+/// Compresses data as a ".lzma" (LZMA-alone) file, with the uncompressed size in the header, as used
+/// by UnityWeb and UnityRaw bundles.
+pub fn lzma_alone_compress(data: &[u8], level: u32) -> Box<[u8]> {
+    let raw = lzma_compress_raw(data, level);
+    let mut out = Vec::with_capacity(raw.len() + 8);
+    out.extend_from_slice(&raw[..LZMA_PROPS_SIZE]);
+    out.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    out.extend_from_slice(&raw[LZMA_PROPS_SIZE..]);
+    out.into()
+}
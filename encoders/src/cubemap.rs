@@ -0,0 +1,154 @@
+use std::f32::consts::PI;
+use wasm_bindgen::prelude::*;
+
+// Faces are given in Unity's order (+X, -X, +Y, -Y, +Z, -Z), each `face_size` square and concatenated.
+// Within a face, s runs left to right and t top to bottom, following the usual cubemap convention:
+//
+//   face  s     t
+//   +X    -z    -y
+//   -X    +z    -y
+//   +Y    +x    +z
+//   -Y    +x    -z
+//   +Z    +x    -y
+//   -Z    -x    -y
+//
+// Outputs are bottom row first like decoded textures, so they can go straight to `encode_png(..., true)`.
+
+/// Picks the face and the (s, t) coordinates in 0..1 that a direction hits.
+fn direction_to_face(x: f32, y: f32, z: f32) -> (usize, f32, f32) {
+    let (ax, ay, az) = (x.abs(), y.abs(), z.abs());
+    let (face, sc, tc, ma) = if ax >= ay && ax >= az {
+        if x > 0.0 { (0, -z, -y, ax) } else { (1, z, -y, ax) }
+    } else if ay >= az {
+        if y > 0.0 { (2, x, z, ay) } else { (3, x, -z, ay) }
+    } else if z > 0.0 {
+        (4, x, -y, az)
+    } else {
+        (5, -x, -y, az)
+    };
+    (face, (sc / ma + 1.0) * 0.5, (tc / ma + 1.0) * 0.5)
+}
+
+/// Reads a texel of a face, with (x, y) counted from the top-left.
+fn texel(faces: &[f32], face_size: usize, face: usize, x: usize, y: usize, flip: bool) -> [f32; 4] {
+    let row = if flip { face_size - 1 - y } else { y };
+    let o = ((face * face_size + row) * face_size + x) * 4;
+    if o + 4 > faces.len() {
+        return [0.0; 4];
+    }
+    [faces[o], faces[o + 1], faces[o + 2], faces[o + 3]]
+}
+
+fn sample_bilinear(faces: &[f32], face_size: usize, face: usize, s: f32, t: f32, flip: bool) -> [f32; 4] {
+    let max = (face_size - 1) as f32;
+    let fx = (s * face_size as f32 - 0.5).clamp(0.0, max);
+    let fy = (t * face_size as f32 - 0.5).clamp(0.0, max);
+    let (x0, y0) = (fx.floor() as usize, fy.floor() as usize);
+    let (x1, y1) = ((x0 + 1).min(face_size - 1), (y0 + 1).min(face_size - 1));
+    let (wx, wy) = (fx - x0 as f32, fy - y0 as f32);
+    let a = texel(faces, face_size, face, x0, y0, flip);
+    let b = texel(faces, face_size, face, x1, y0, flip);
+    let c = texel(faces, face_size, face, x0, y1, flip);
+    let d = texel(faces, face_size, face, x1, y1, flip);
+    let mut out = [0f32; 4];
+    for i in 0..4 {
+        let top = a[i] + (b[i] - a[i]) * wx;
+        let bottom = c[i] + (d[i] - c[i]) * wx;
+        out[i] = top + (bottom - top) * wy;
+    }
+    out
+}
+
+fn equirect(faces: &[f32], face_size: usize, width: usize, height: usize, flip: bool) -> Vec<f32> {
+    let mut out = vec![0f32; width * height * 4];
+    if face_size == 0 {
+        return out;
+    }
+    for y in 0..height {
+        let lat = PI * 0.5 - (y as f32 + 0.5) / height as f32 * PI;
+        for x in 0..width {
+            // the centre of the panorama looks down +Z
+            let lon = (x as f32 + 0.5) / width as f32 * 2.0 * PI - PI;
+            let (dx, dy, dz) = (lat.cos() * lon.sin(), lat.sin(), lat.cos() * lon.cos());
+            let (face, s, t) = direction_to_face(dx, dy, dz);
+            let o = ((height - 1 - y) * width + x) * 4;
+            out[o..o + 4].copy_from_slice(&sample_bilinear(faces, face_size, face, s, t, flip));
+        }
+    }
+    out
+}
+
+/// Face positions in the cross, in face-size units from the top-left, and whether the face is rotated 180°.
+fn cross_layout(vertical: bool) -> (usize, usize, [(usize, usize, bool); 6]) {
+    if vertical {
+        (3, 4, [(2, 1, false), (0, 1, false), (1, 0, false), (1, 2, false), (1, 1, false), (1, 3, true)])
+    } else {
+        (4, 3, [(2, 1, false), (0, 1, false), (1, 0, false), (1, 2, false), (1, 1, false), (3, 1, false)])
+    }
+}
+
+fn cross(faces: &[f32], face_size: usize, vertical: bool, flip: bool) -> (usize, usize, Vec<f32>) {
+    let (cols, rows, layout) = cross_layout(vertical);
+    let (width, height) = (cols * face_size, rows * face_size);
+    let mut out = vec![0f32; width * height * 4];
+    for (face, &(cx, cy, rotated)) in layout.iter().enumerate() {
+        for y in 0..face_size {
+            for x in 0..face_size {
+                let (sx, sy) = if rotated { (face_size - 1 - x, face_size - 1 - y) } else { (x, y) };
+                let px = texel(faces, face_size, face, sx, sy, flip);
+                let oy = height - 1 - (cy * face_size + y);
+                let o = (oy * width + cx * face_size + x) * 4;
+                out[o..o + 4].copy_from_slice(&px);
+            }
+        }
+    }
+    (width, height, out)
+}
+
+fn to_f32(data: &[u8]) -> Vec<f32> {
+    data.iter().map(|&v| v as f32).collect()
+}
+
+fn to_u8(data: &[f32]) -> Box<[u8]> {
+    data.iter().map(|&v| v.round().clamp(0.0, 255.0) as u8).collect::<Vec<u8>>().into()
+}
+
+#[wasm_bindgen]
+/// Converts the six faces of a cubemap into an equirectangular panorama using bilinear sampling.
+///
+/// # Arguments
+///
+/// * `faces` - The six decoded RGBA faces in Unity's order (+X, -X, +Y, -Y, +Z, -Z)
+/// * `face_size` - The width and height of one face
+/// * `width`, `height` - The size of the panorama (usually 2:1)
+/// * `flip` - The faces are bottom row first, as decoded
+pub fn cubemap_to_equirect(faces: &[u8], face_size: usize, width: usize, height: usize, flip: bool) -> Box<[u8]> {
+    to_u8(&equirect(&to_f32(faces), face_size, width, height, flip))
+}
+
+#[wasm_bindgen]
+/// `cubemap_to_equirect` for float RGBA faces, keeping values outside 0..1 for HDR output.
+pub fn cubemap_to_equirect_f32(faces: &[f32], face_size: usize, width: usize, height: usize, flip: bool) -> Box<[f32]> {
+    equirect(faces, face_size, width, height, flip).into()
+}
+
+#[wasm_bindgen]
+/// Lays the six faces of a cubemap out as a cross with transparent gaps.
+/// The horizontal cross is 4x3 faces; the vertical cross is 3x4 faces with -Z at the bottom, upside down.
+/// Takes the same `faces`, `face_size` and `flip` as `cubemap_to_equirect`.
+pub fn cubemap_to_cross(faces: &[u8], face_size: usize, vertical: bool, flip: bool) -> Box<[u8]> {
+    to_u8(&cross(&to_f32(faces), face_size, vertical, flip).2)
+}
+
+#[wasm_bindgen]
+/// `cubemap_to_cross` for float RGBA faces.
+pub fn cubemap_to_cross_f32(faces: &[f32], face_size: usize, vertical: bool, flip: bool) -> Box<[f32]> {
+    cross(faces, face_size, vertical, flip).2.into()
+}
+
+#[wasm_bindgen]
+/// The width and height in pixels of a cross made from `face_size` faces.
+pub fn cross_dimensions(face_size: usize, vertical: bool) -> Box<[usize]> {
+    let (cols, rows, _) = cross_layout(vertical);
+    [cols * face_size, rows * face_size].into()
+}
@@ -0,0 +1,248 @@
+use std::cmp::{max, min};
+use wasm_bindgen::prelude::*;
+use crate::resize::{fit_dimensions, resize};
+use crate::texdec::{decode, expected_data_size, get_format_block_info, get_mipmap_offset_and_size};
+
+/// Formats tried when guessing, most common first so that ties favour them.
+const CANDIDATE_FORMATS: &[&str] = &[
+    "RGBA32", "BGRA32", "ARGB32", "RGB24", "RGB565", "RGBA4444", "ARGB4444", "Alpha8", "R8", "RG16", "R16",
+    "RHalf", "RGBAHalf", "RFloat", "RGBAFloat",
+    "DXT1", "DXT5", "BC4", "BC5", "BC7", "ETC_RGB4", "ETC2_RGB", "ETC2_RGBA8", "EAC_R", "ATC_RGB4", "ATC_RGBA8",
+    "PVRTC_RGBA4", "PVRTC_RGBA2", "ASTC_RGBA_4x4", "ASTC_RGBA_6x6", "ASTC_RGBA_8x8",
+];
+const MAX_DIMENSION: usize = 16384;
+/// Without hints, only sizes up to this aspect ratio are considered.
+const MAX_ASPECT: usize = 8;
+/// Candidates are scored on (about) this many rows from the top of the first mipmap.
+const SAMPLE_ROWS: usize = 64;
+const THUMBNAIL_SIZE: usize = 128;
+
+/// A possible layout for raw texture data.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FormatGuess {
+    pub format: &'static str,
+    pub width: usize,
+    pub height: usize,
+    pub mip_count: usize,
+    /// How image-like the decoded data is, from 0 (noise) to 1
+    pub score: f32,
+}
+
+#[wasm_bindgen]
+/// Candidate layouts for raw texture data, best first, with a contact sheet of their previews.
+pub struct FormatGuesses {
+    guesses: Vec<FormatGuess>,
+    /// The size of the contact sheet, which is a grid of square cells in the order of the guesses
+    pub sheet_width: usize,
+    pub sheet_height: usize,
+    pub cell_size: usize,
+    sheet: Box<[u8]>,
+}
+
+#[wasm_bindgen]
+impl FormatGuesses {
+    #[wasm_bindgen(getter)]
+    pub fn count(&self) -> usize {
+        self.guesses.len()
+    }
+
+    pub fn format(&self, index: usize) -> String {
+        self.guesses.get(index).map(|g| g.format.to_string()).unwrap_or_default()
+    }
+
+    pub fn width(&self, index: usize) -> usize {
+        self.guesses.get(index).map_or(0, |g| g.width)
+    }
+
+    pub fn height(&self, index: usize) -> usize {
+        self.guesses.get(index).map_or(0, |g| g.height)
+    }
+
+    pub fn mip_count(&self, index: usize) -> usize {
+        self.guesses.get(index).map_or(0, |g| g.mip_count)
+    }
+
+    pub fn score(&self, index: usize) -> f32 {
+        self.guesses.get(index).map_or(0.0, |g| g.score)
+    }
+
+    /// RGBA previews of every guess, each scaled to fit and centred in its cell. Rows keep the order of
+    /// the source data, so Unity textures appear upside down.
+    #[wasm_bindgen(getter)]
+    pub fn contact_sheet(&self) -> Box<[u8]> {
+        self.sheet.clone()
+    }
+}
+
+impl FormatGuesses {
+    pub fn guesses(&self) -> &[FormatGuess] {
+        &self.guesses
+    }
+}
+
+fn dimension_candidates(hint: usize) -> Vec<usize> {
+    if hint > 0 {
+        return vec![hint];
+    }
+    (0..).map(|i| 1 << i).take_while(|&d| d <= MAX_DIMENSION).collect()
+}
+
+/// Lists every format, size and mipmap count whose mipmap chain is exactly `length` bytes long.
+fn enumerate_layouts(length: usize, width_hint: usize, height_hint: usize) -> Vec<(&'static str, usize, usize, usize)> {
+    let mut layouts = Vec::new();
+    if length == 0 || length > i32::MAX as usize {
+        return layouts;
+    }
+    for &format in CANDIDATE_FORMATS {
+        let (bw, bh, _) = get_format_block_info(format);
+        let mut widths = dimension_candidates(width_hint);
+        let mut heights = dimension_candidates(height_hint);
+        // with only one side known, the other can be anything that fills the data without mipmaps
+        if width_hint > 0 && height_hint == 0 {
+            let row = expected_data_size(format, width_hint, bh).unwrap_or(0);
+            if row > 0 && length % row == 0 {
+                heights.push(length / row * bh);
+            }
+        } else if height_hint > 0 && width_hint == 0 {
+            let column = expected_data_size(format, bw, height_hint).unwrap_or(0);
+            if column > 0 && length % column == 0 {
+                widths.push(length / column * bw);
+                widths.sort_unstable();
+            }
+        }
+        widths.dedup();
+        heights.sort_unstable();
+        heights.dedup();
+        for &width in widths.iter() {
+            for &height in heights.iter() {
+                let unhinted = width_hint == 0 && height_hint == 0;
+                if unhinted && (width > height * MAX_ASPECT || height > width * MAX_ASPECT) {
+                    continue;
+                }
+                if width > MAX_DIMENSION || height > MAX_DIMENSION || expected_data_size(format, width, height).map_or(true, |s| s > length) {
+                    continue;
+                }
+                let max_mips = (usize::BITS - max(width, height).leading_zeros()) as usize;
+                for mips in 1..=max_mips {
+                    let size = get_mipmap_offset_and_size(mips as i32, format, width as i32, height as i32).0 as usize;
+                    if size == length {
+                        layouts.push((format, width, height, mips));
+                    }
+                    if size >= length {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    layouts
+}
+
+/// Rates how much RGBA data looks like an image rather than noise, from 0 to 1.
+///
+/// Real images are mostly smooth, so large differences between neighbouring pixels count against a
+/// candidate, as do seams along block edges (a sign of the wrong block format) and having almost no
+/// information at all (e.g. every block decoding to the same colour).
+fn image_score(pixels: &[u8], width: usize, height: usize, block_width: usize) -> f32 {
+    let diff = |a: usize, b: usize| -> u32 {
+        (0..4).map(|c| (pixels[a * 4 + c] as i32 - pixels[b * 4 + c] as i32).unsigned_abs()).sum()
+    };
+    let (mut inner, mut inner_count, mut edge, mut edge_count, mut vertical, mut vertical_count) = (0u64, 0u64, 0u64, 0u64, 0u64, 0u64);
+    let mut histogram = [0u32; 256];
+    for y in 0..height {
+        for x in 0..width {
+            let i = y * width + x;
+            let luma = (pixels[i * 4] as u32 * 299 + pixels[i * 4 + 1] as u32 * 587 + pixels[i * 4 + 2] as u32 * 114) / 1000;
+            histogram[(luma * pixels[i * 4 + 3] as u32 / 255) as usize] += 1;
+            if x > 0 {
+                if block_width > 1 && x % block_width == 0 {
+                    edge += diff(i, i - 1) as u64;
+                    edge_count += 1;
+                } else {
+                    inner += diff(i, i - 1) as u64;
+                    inner_count += 1;
+                }
+            }
+            if y > 0 {
+                vertical += diff(i, i - width) as u64;
+                vertical_count += 1;
+            }
+        }
+    }
+    let mean = |sum: u64, count: u64| if count > 0 { sum as f32 / count as f32 / 4.0 } else { 0.0 };
+    let horizontal = mean(inner + edge, inner_count + edge_count);
+    let mut cost = (horizontal + mean(vertical, vertical_count)) / 64.0;
+    if edge_count > 0 {
+        cost += (mean(edge, edge_count) / (mean(inner, inner_count) + 1.0) - 1.0).max(0.0) * 0.1;
+    }
+    let total = (width * height) as f32;
+    let entropy: f32 = histogram.iter().filter(|&&n| n > 0)
+        .map(|&n| -(n as f32 / total) * (n as f32 / total).log2()).sum();
+    cost += (1.0 - entropy).max(0.0) * 0.5;
+    1.0 / (1.0 + cost)
+}
+
+/// Decodes the top of the first mipmap of a candidate layout and scores it.
+fn score_layout(data: &[u8], format: &str, width: usize, height: usize) -> f32 {
+    let (bw, bh, _) = get_format_block_info(format);
+    // PVRTC blocks are twiddled, so it can't be decoded a strip at a time
+    let rows = if format.starts_with("PVRTC") { height } else { min(height, max(SAMPLE_ROWS / bh, 1) * bh) };
+    let size = match expected_data_size(format, width, rows) {
+        Some(size) if size <= data.len() => size,
+        _ => return 0.0
+    };
+    match decode(format, &mut data[..size].to_vec(), width, rows, false, "", &[]) {
+        Ok(pixels) => image_score(&pixels, width, rows, bw),
+        Err(_) => 0.0
+    }
+}
+
+fn contact_sheet(data: &[u8], guesses: &[FormatGuess]) -> (Box<[u8]>, usize, usize) {
+    if guesses.is_empty() {
+        return ([].into(), 0, 0);
+    }
+    let columns = (guesses.len() as f64).sqrt().ceil() as usize;
+    let rows = (guesses.len() + columns - 1) / columns;
+    let (sheet_width, sheet_height) = (columns * THUMBNAIL_SIZE, rows * THUMBNAIL_SIZE);
+    let mut sheet = vec![0u8; sheet_width * sheet_height * 4];
+    for (n, guess) in guesses.iter().enumerate() {
+        let size = expected_data_size(guess.format, guess.width, guess.height).unwrap_or(0);
+        let pixels = match decode(guess.format, &mut data[..size].to_vec(), guess.width, guess.height, false, "", &[]) {
+            Ok(pixels) => pixels,
+            Err(_) => continue
+        };
+        let (tw, th) = fit_dimensions(guess.width, guess.height, THUMBNAIL_SIZE);
+        let thumbnail = resize(&pixels, guess.width, guess.height, tw, th, "bilinear", false);
+        let x0 = n % columns * THUMBNAIL_SIZE + (THUMBNAIL_SIZE - tw) / 2;
+        let y0 = n / columns * THUMBNAIL_SIZE + (THUMBNAIL_SIZE - th) / 2;
+        for y in 0..th {
+            let o = ((y0 + y) * sheet_width + x0) * 4;
+            sheet[o..o + tw * 4].copy_from_slice(&thumbnail[y * tw * 4..(y + 1) * tw * 4]);
+        }
+    }
+    (sheet.into(), sheet_width, sheet_height)
+}
+
+#[wasm_bindgen]
+/// Guesses the format and size of raw pixel data with no format tag.
+///
+/// Every candidate format, size and mipmap count whose mipmap chain (as laid out by
+/// `get_mipmap_offset_and_size`) exactly fills the data is decoded and ranked by how image-like it looks.
+///
+/// # Arguments
+///
+/// * `data` - The raw texture data, including any mipmaps
+/// * `width_hint`, `height_hint` - The width and height if known, or 0 to try powers of two
+/// * `max_results` - How many of the best guesses to return and put on the contact sheet
+pub fn guess_texture_format(data: &[u8], width_hint: usize, height_hint: usize, max_results: usize) -> FormatGuesses {
+    let mut guesses: Vec<FormatGuess> = enumerate_layouts(data.len(), width_hint, height_hint).into_iter()
+        .map(|(format, width, height, mip_count)| FormatGuess {
+            format, width, height, mip_count, score: score_layout(data, format, width, height)
+        })
+        .collect();
+    // stable, so equally good guesses stay in the order of CANDIDATE_FORMATS
+    guesses.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+    guesses.truncate(max_results);
+    let (sheet, sheet_width, sheet_height) = contact_sheet(data, &guesses);
+    FormatGuesses { guesses, sheet_width, sheet_height, cell_size: THUMBNAIL_SIZE, sheet }
+}
@@ -0,0 +1,242 @@
+use wasm_bindgen::prelude::*;
+use crate::error::{rgba_size, ImageError};
+
+/// Changed pixels are grouped into regions on a grid of tiles this size.
+const REGION_TILE: usize = 8;
+/// Window size and step for SSIM.
+const SSIM_WINDOW: usize = 8;
+const SSIM_STEP: usize = 4;
+
+#[wasm_bindgen]
+/// The result of comparing two images of the same size.
+pub struct ImageDiff {
+    pub width: usize,
+    pub height: usize,
+    /// Peak signal-to-noise ratio over all four channels in dB, infinite for identical images
+    pub psnr: f64,
+    /// Mean structural similarity of the luminance, 1.0 for identical images
+    pub ssim: f64,
+    /// The largest absolute difference of any channel
+    pub max_error: u8,
+    /// The number of pixels with a channel differing by more than the threshold
+    pub changed_pixels: usize,
+    max_channel_error: [u8; 4],
+    regions: Vec<u32>,
+    heatmap: Box<[u8]>,
+}
+
+#[wasm_bindgen]
+impl ImageDiff {
+    /// The largest absolute difference of each channel, as [R, G, B, A].
+    #[wasm_bindgen(getter)]
+    pub fn max_channel_error(&self) -> Box<[u8]> {
+        self.max_channel_error.into()
+    }
+
+    /// Bounding boxes of the changed areas as consecutive (x, y, width, height) values,
+    /// with rows counted in the order of the input data.
+    #[wasm_bindgen(getter)]
+    pub fn regions(&self) -> Box<[u32]> {
+        self.regions.clone().into()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn region_count(&self) -> usize {
+        self.regions.len() / 4
+    }
+
+    /// An RGBA image in the same row order as the inputs: unchanged pixels are a dimmed greyscale of
+    /// the second image, and changed ones go from yellow to red as the error grows.
+    #[wasm_bindgen(getter)]
+    pub fn heatmap(&self) -> Box<[u8]> {
+        self.heatmap.clone()
+    }
+}
+
+fn pixel(data: &[u8], i: usize) -> [u8; 4] {
+    [data[i * 4], data[i * 4 + 1], data[i * 4 + 2], data[i * 4 + 3]]
+}
+
+fn luminance(px: [u8; 4]) -> f64 {
+    0.299 * px[0] as f64 + 0.587 * px[1] as f64 + 0.114 * px[2] as f64
+}
+
+fn window_ssim(a: &[f64], b: &[f64], width: usize, x0: usize, y0: usize, w: usize, h: usize) -> f64 {
+    const C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+    const C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+    let n = (w * h) as f64;
+    let (mut sum_a, mut sum_b) = (0f64, 0f64);
+    for y in y0..y0 + h {
+        for x in x0..x0 + w {
+            sum_a += a[y * width + x];
+            sum_b += b[y * width + x];
+        }
+    }
+    let (mean_a, mean_b) = (sum_a / n, sum_b / n);
+    let (mut var_a, mut var_b, mut cov) = (0f64, 0f64, 0f64);
+    for y in y0..y0 + h {
+        for x in x0..x0 + w {
+            let da = a[y * width + x] - mean_a;
+            let db = b[y * width + x] - mean_b;
+            var_a += da * da;
+            var_b += db * db;
+            cov += da * db;
+        }
+    }
+    let (var_a, var_b, cov) = (var_a / n, var_b / n, cov / n);
+    ((2.0 * mean_a * mean_b + C1) * (2.0 * cov + C2))
+        / ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2))
+}
+
+fn ssim(a: &[f64], b: &[f64], width: usize, height: usize) -> f64 {
+    // images smaller than a window are compared as a whole
+    let (ww, wh) = (SSIM_WINDOW.min(width), SSIM_WINDOW.min(height));
+    let mut total = 0f64;
+    let mut count = 0;
+    let mut y = 0;
+    loop {
+        let mut x = 0;
+        loop {
+            total += window_ssim(a, b, width, x, y, ww, wh);
+            count += 1;
+            if x + ww >= width {
+                break;
+            }
+            x = (x + SSIM_STEP).min(width - ww);
+        }
+        if y + wh >= height {
+            break;
+        }
+        y = (y + SSIM_STEP).min(height - wh);
+    }
+    total / count as f64
+}
+
+/// Groups changed tiles into 8-connected regions and returns the tight pixel bounds of each.
+fn changed_regions(changed: &[bool], width: usize, height: usize) -> Vec<u32> {
+    let (tw, th) = ((width + REGION_TILE - 1) / REGION_TILE, (height + REGION_TILE - 1) / REGION_TILE);
+    // per tile bounds of the changed pixels: min x, min y, max x, max y
+    let mut tiles: Vec<Option<[usize; 4]>> = vec![None; tw * th];
+    for y in 0..height {
+        for x in 0..width {
+            if !changed[y * width + x] {
+                continue;
+            }
+            let t = &mut tiles[(y / REGION_TILE) * tw + x / REGION_TILE];
+            *t = Some(match *t {
+                Some(b) => [b[0].min(x), b[1].min(y), b[2].max(x), b[3].max(y)],
+                None => [x, y, x, y]
+            });
+        }
+    }
+
+    let mut visited = vec![false; tw * th];
+    let mut regions = Vec::new();
+    let mut stack = Vec::new();
+    for start in 0..tw * th {
+        if visited[start] || tiles[start].is_none() {
+            continue;
+        }
+        visited[start] = true;
+        stack.push(start);
+        let mut bounds = tiles[start].unwrap();
+        while let Some(t) = stack.pop() {
+            let b = tiles[t].unwrap();
+            bounds = [bounds[0].min(b[0]), bounds[1].min(b[1]), bounds[2].max(b[2]), bounds[3].max(b[3])];
+            let (tx, ty) = ((t % tw) as isize, (t / tw) as isize);
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let (nx, ny) = (tx + dx, ty + dy);
+                    if nx < 0 || ny < 0 || nx >= tw as isize || ny >= th as isize {
+                        continue;
+                    }
+                    let n = ny as usize * tw + nx as usize;
+                    if !visited[n] && tiles[n].is_some() {
+                        visited[n] = true;
+                        stack.push(n);
+                    }
+                }
+            }
+        }
+        regions.extend_from_slice(&[
+            bounds[0] as u32,
+            bounds[1] as u32,
+            (bounds[2] - bounds[0] + 1) as u32,
+            (bounds[3] - bounds[1] + 1) as u32,
+        ]);
+    }
+    regions
+}
+
+#[wasm_bindgen]
+/// Compares two RGBA images of the same size, e.g. two versions of a texture,
+/// or an encoder's output against the reference decoder.
+///
+/// # Arguments
+///
+/// * `a`, `b` - RGBA data of the two images
+/// * `width`, `height` - The size of both images
+/// * `threshold` - A pixel counts as changed when any channel differs by more than this
+///
+/// # Returns
+///
+/// * The comparison, or an error if either image holds fewer than `width * height * 4` bytes
+pub fn compare_images(a: &[u8], b: &[u8], width: usize, height: usize, threshold: u8) -> Result<ImageDiff, ImageError> {
+    let size = rgba_size(width, height)?;
+    for data in [a, b] {
+        if data.len() < size {
+            return Err(ImageError::data_too_short("RGBA32", size, data.len()));
+        }
+    }
+    let pixels = width * height;
+    let mut max_channel_error = [0u8; 4];
+    let mut squared_error = 0f64;
+    let mut changed = vec![false; pixels];
+    let mut changed_pixels = 0;
+    let mut luma_a = vec![0f64; pixels];
+    let mut luma_b = vec![0f64; pixels];
+    let mut heatmap = vec![0u8; pixels * 4];
+
+    for i in 0..pixels {
+        let (pa, pb) = (pixel(a, i), pixel(b, i));
+        let mut error = 0u8;
+        for c in 0..4 {
+            let e = (pa[c] as i32 - pb[c] as i32).unsigned_abs() as u8;
+            max_channel_error[c] = max_channel_error[c].max(e);
+            error = error.max(e);
+            squared_error += e as f64 * e as f64;
+        }
+        luma_a[i] = luminance(pa);
+        luma_b[i] = luminance(pb);
+
+        let out = &mut heatmap[i * 4..i * 4 + 4];
+        if error > threshold {
+            changed[i] = true;
+            changed_pixels += 1;
+            out.copy_from_slice(&[255, 255 - error, 0, 255]);
+        } else {
+            let grey = (luma_b[i] * pb[3] as f64 / 255.0 / 3.0) as u8;
+            out.copy_from_slice(&[grey, grey, grey, 255]);
+        }
+    }
+
+    let psnr = if squared_error == 0.0 {
+        f64::INFINITY
+    } else {
+        let mse = squared_error / (pixels * 4) as f64;
+        10.0 * (255.0 * 255.0 / mse).log10()
+    };
+    let ssim = if pixels == 0 { 1.0 } else { ssim(&luma_a, &luma_b, width, height) };
+
+    Ok(ImageDiff {
+        width,
+        height,
+        psnr,
+        ssim,
+        max_error: *max_channel_error.iter().max().unwrap(),
+        changed_pixels,
+        max_channel_error,
+        regions: changed_regions(&changed, width, height),
+        heatmap: heatmap.into(),
+    })
+}
@@ -0,0 +1,158 @@
+use std::fmt;
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageErrorKind {
+    /// The texture format isn't one the decoder knows
+    UnknownFormat,
+    /// The input holds fewer bytes than the format and size need
+    DataTooShort,
+    /// The caller-provided output buffer can't hold the decoded image
+    OutputTooSmall,
+    /// The width or height is zero, or the image is too large to address
+    InvalidDimensions,
+    /// The data is corrupt or uses a feature the decoder doesn't support
+    InvalidData,
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// Why a texture couldn't be decoded or encoded. Thrown to JS from functions that return a `Result`.
+pub struct ImageError {
+    pub kind: ImageErrorKind,
+    /// The number of bytes (or for `InvalidDimensions`, the width) that was needed
+    pub expected: usize,
+    /// The number of bytes (or for `InvalidDimensions`, the height) that was given
+    pub actual: usize,
+    format: String,
+}
+
+#[wasm_bindgen]
+impl ImageError {
+    #[wasm_bindgen(getter)]
+    pub fn format(&self) -> String {
+        self.format.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn message(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl ImageError {
+    pub fn unknown_format(format: &str) -> ImageError {
+        ImageError { kind: ImageErrorKind::UnknownFormat, expected: 0, actual: 0, format: format.to_string() }
+    }
+
+    pub fn data_too_short(format: &str, expected: usize, actual: usize) -> ImageError {
+        ImageError { kind: ImageErrorKind::DataTooShort, expected, actual, format: format.to_string() }
+    }
+
+    pub fn output_too_small(expected: usize, actual: usize) -> ImageError {
+        ImageError { kind: ImageErrorKind::OutputTooSmall, expected, actual, format: String::new() }
+    }
+
+    pub fn invalid_dimensions(width: usize, height: usize) -> ImageError {
+        ImageError { kind: ImageErrorKind::InvalidDimensions, expected: width, actual: height, format: String::new() }
+    }
+
+    pub fn invalid_data(format: &str) -> ImageError {
+        ImageError { kind: ImageErrorKind::InvalidData, expected: 0, actual: 0, format: format.to_string() }
+    }
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ImageErrorKind::UnknownFormat => write!(f, "unknown texture format \"{}\"", self.format),
+            ImageErrorKind::DataTooShort => write!(f, "{} data is too short: expected {} bytes, got {}",
+                                                   self.format, self.expected, self.actual),
+            ImageErrorKind::OutputTooSmall => write!(f, "output buffer is too small: expected {} bytes, got {}",
+                                                     self.expected, self.actual),
+            ImageErrorKind::InvalidDimensions => write!(f, "invalid image size {}x{}", self.expected, self.actual),
+            ImageErrorKind::InvalidData => write!(f, "{} data is invalid or unsupported", self.format),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompressionErrorKind {
+    /// The codec name isn't one `decompress` knows
+    UnknownCodec,
+    /// The data decompressed to fewer bytes than expected, or to more when the whole output is known
+    SizeMismatch,
+    /// The data would decompress to more bytes than expected; decoding stops there, so the full size
+    /// isn't known
+    OutputTooLarge,
+    /// The data is corrupt or truncated
+    InvalidData,
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// Why data couldn't be decompressed. Thrown to JS from `decompress`.
+pub struct CompressionError {
+    pub kind: CompressionErrorKind,
+    /// The number of bytes the data should have decompressed to
+    pub expected: usize,
+    /// The number of bytes it did decompress to, for `SizeMismatch`; 0 for the other kinds
+    pub actual: usize,
+    codec: String,
+    detail: String,
+}
+
+#[wasm_bindgen]
+impl CompressionError {
+    #[wasm_bindgen(getter)]
+    pub fn codec(&self) -> String {
+        self.codec.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn message(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl CompressionError {
+    pub fn unknown_codec(codec: &str) -> CompressionError {
+        CompressionError { kind: CompressionErrorKind::UnknownCodec, expected: 0, actual: 0, codec: codec.to_string(), detail: String::new() }
+    }
+
+    pub fn size_mismatch(codec: &str, expected: usize, actual: usize) -> CompressionError {
+        CompressionError { kind: CompressionErrorKind::SizeMismatch, expected, actual, codec: codec.to_string(), detail: String::new() }
+    }
+
+    pub fn output_too_large(codec: &str, expected: usize) -> CompressionError {
+        CompressionError { kind: CompressionErrorKind::OutputTooLarge, expected, actual: 0, codec: codec.to_string(), detail: String::new() }
+    }
+
+    pub fn invalid_data(codec: &str, detail: &str) -> CompressionError {
+        CompressionError { kind: CompressionErrorKind::InvalidData, expected: 0, actual: 0, codec: codec.to_string(), detail: detail.to_string() }
+    }
+}
+
+impl fmt::Display for CompressionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            CompressionErrorKind::UnknownCodec => write!(f, "unknown compression codec \"{}\"", self.codec),
+            CompressionErrorKind::SizeMismatch => write!(f, "{} data decompressed to {} bytes, expected {}",
+                                                         self.codec, self.actual, self.expected),
+            CompressionErrorKind::OutputTooLarge =>
+                write!(f, "{} data decompresses to more than the expected {} bytes", self.codec, self.expected),
+            CompressionErrorKind::InvalidData => write!(f, "{} data is invalid: {}", self.codec, self.detail),
+        }
+    }
+}
+
+impl std::error::Error for CompressionError {}
+
+/// The number of bytes in a `width` x `height` RGBA image, or an error if that overflows.
+pub fn rgba_size(width: usize, height: usize) -> Result<usize, ImageError> {
+    width.checked_mul(height).and_then(|p| p.checked_mul(4)).ok_or_else(|| ImageError::invalid_dimensions(width, height))
+}
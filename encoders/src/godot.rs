@@ -0,0 +1,371 @@
+use std::io::Cursor;
+use wasm_bindgen::prelude::*;
+use crate::basis::BasisFile;
+use crate::error::ImageError;
+use crate::texdec::{decode, expected_data_size};
+
+// Godot's imported texture files: `.stex` (Godot 3 StreamTexture, "GDST"), `.ctex` (Godot 4 CompressedTexture2D, "GST2")
+// and `.ctexarray`/`.ccube`/`.ccubearray` (Godot 4 CompressedTextureLayered, "GSTL").
+// Each image is stored either as raw `Image::Format` data with all of its mipmaps, as one PNG or WebP file per
+// mipmap, or (Godot 4 only) as a Basis Universal file holding all mipmaps.
+
+const FORMAT_MASK_IMAGE_FORMAT: u32 = (1 << 20) - 1;
+const FORMAT_BIT_LOSSLESS: u32 = 1 << 20;
+const FORMAT_BIT_LOSSY: u32 = 1 << 21;
+const FORMAT_BIT_HAS_MIPMAPS: u32 = 1 << 23;
+
+const DATA_FORMAT_IMAGE: u32 = 0;
+const DATA_FORMAT_PNG: u32 = 1;
+const DATA_FORMAT_WEBP: u32 = 2;
+const DATA_FORMAT_BASIS_UNIVERSAL: u32 = 3;
+
+/// Godot 4 stores normal maps as basis RGBA with the green channel moved to alpha.
+const BASIS_DECOMPRESS_RG_AS_RA: u32 = 3;
+
+/// `texdec` names for Godot 3's `Image::Format`.
+const GODOT3_FORMATS: [&str; 37] = [
+    "L8", "LA16", "R8", "RG16", "RGB24", "RGBA32", "RGBA4444", "RGBA5551",
+    "RFloat", "RGFloat", "RGBFloat", "RGBAFloat", "RHalf", "RGHalf", "RGBHalf", "RGBAHalf", "RGB9e5Float",
+    "DXT1", "DXT3", "DXT5", "BC4", "BC5", "BC7", "BC6H", "BC6H",
+    "PVRTC_RGB2", "PVRTC_RGBA2", "PVRTC_RGB4", "PVRTC_RGBA4",
+    "ETC_RGB4", "EAC_R", "EAC_R_SIGNED", "EAC_RG", "EAC_RG_SIGNED", "ETC2_RGB", "ETC2_RGBA8", "ETC2_RGBA1",
+];
+
+/// `texdec` names for Godot 4's `Image::Format`, with "_RA_AS_RG" marking formats holding green in alpha.
+const GODOT4_FORMATS: [&str; 39] = [
+    "L8", "LA16", "R8", "RG16", "RGB24", "RGBA32", "RGBA4444", "BGR565",
+    "RFloat", "RGFloat", "RGBFloat", "RGBAFloat", "RHalf", "RGHalf", "RGBHalf", "RGBAHalf", "RGB9e5Float",
+    "DXT1", "DXT3", "DXT5", "BC4", "BC5", "BC7", "BC6H", "BC6H",
+    "ETC_RGB4", "EAC_R", "EAC_R_SIGNED", "EAC_RG", "EAC_RG_SIGNED", "ETC2_RGB", "ETC2_RGBA8", "ETC2_RGBA1",
+    "ETC2_RGBA8_RA_AS_RG", "DXT5_RA_AS_RG",
+    "ASTC_RGBA_4x4", "ASTC_HDR_4x4", "ASTC_RGBA_8x8", "ASTC_HDR_8x8",
+];
+
+fn invalid() -> ImageError {
+    ImageError::invalid_data("Godot texture")
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn read(&mut self, size: usize) -> Result<&'a [u8], ImageError> {
+        let bytes = self.data.get(self.pos..self.pos + size).ok_or_else(invalid)?;
+        self.pos += size;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u32, ImageError> {
+        self.read(2).map(|b| u16::from_le_bytes([b[0], b[1]]) as u32)
+    }
+
+    fn u32(&mut self) -> Result<u32, ImageError> {
+        self.read(4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len().saturating_sub(self.pos)
+    }
+}
+
+/// One layer (array slice or cubemap face) of a texture, with its mipmaps.
+enum GodotLayer {
+    /// Raw data of each mipmap, in the layer's format
+    Image { format: &'static str, width: usize, height: usize, mipmaps: Vec<Box<[u8]>> },
+    /// A PNG or WebP file per mipmap
+    Embedded { webp: bool, mipmaps: Vec<Box<[u8]>> },
+    Basis { file: Box<BasisFile>, rg_as_ra: bool },
+}
+
+#[wasm_bindgen]
+/// A Godot `.stex`, `.ctex`, `.ctexarray`, `.ccube` or `.ccubearray` texture.
+pub struct GodotTexture {
+    /// 3 or 4
+    pub version: u32,
+    pub width: usize,
+    pub height: usize,
+    kind: &'static str,
+    layers: Vec<GodotLayer>,
+}
+
+fn format_name(version: u32, format: u32) -> Result<&'static str, ImageError> {
+    let formats: &[&'static str] = if version == 3 { &GODOT3_FORMATS } else { &GODOT4_FORMATS };
+    formats.get(format as usize).copied().ok_or_else(|| ImageError::unknown_format(&format!("Godot format {}", format)))
+}
+
+/// Strips the "PNG " or "WEBP" tag Godot 3 puts before embedded images.
+fn embedded_file(data: &[u8]) -> &[u8] {
+    if data.starts_with(b"PNG ") || data.starts_with(b"WEBP") { &data[4..] } else { data }
+}
+
+/// Reads raw data for `levels` mipmaps (or as many as fit, if `levels` is 0).
+fn read_mipmaps(r: &mut Reader, format: &str, width: usize, height: usize, levels: usize)
+                -> Result<Vec<Box<[u8]>>, ImageError> {
+    let base_format = format.trim_end_matches("_RA_AS_RG");
+    let mut mipmaps = Vec::new();
+    let (mut w, mut h) = (width, height);
+    loop {
+        let size = expected_data_size(base_format, w, h).ok_or_else(|| ImageError::unknown_format(format))?;
+        if levels == 0 && size > r.remaining() && !mipmaps.is_empty() {
+            break;
+        }
+        mipmaps.push(r.read(size)?.into());
+        if mipmaps.len() == levels || (w == 1 && h == 1) {
+            break;
+        }
+        w = (w / 2).max(1);
+        h = (h / 2).max(1);
+    }
+    Ok(mipmaps)
+}
+
+/// Reads a Godot 4 image: the data format, its own size and format, then the mipmaps.
+fn read_godot4_layer(r: &mut Reader) -> Result<(GodotLayer, usize, usize), ImageError> {
+    let data_format = r.u32()?;
+    let (width, height) = (r.u16()? as usize, r.u16()? as usize);
+    let levels = r.u32()? as usize + 1;
+    let format = r.u32()?;
+    let layer = match data_format {
+        DATA_FORMAT_IMAGE => {
+            let format = format_name(4, format)?;
+            GodotLayer::Image { format, width, height, mipmaps: read_mipmaps(r, format, width, height, levels)? }
+        },
+        DATA_FORMAT_PNG | DATA_FORMAT_WEBP => {
+            let mut mipmaps = Vec::new();
+            for _ in 0..levels {
+                let size = r.u32()? as usize;
+                mipmaps.push(embedded_file(r.read(size)?).into());
+            }
+            GodotLayer::Embedded { webp: data_format == DATA_FORMAT_WEBP, mipmaps }
+        },
+        DATA_FORMAT_BASIS_UNIVERSAL => {
+            let size = r.u32()? as usize;
+            let data = r.read(size)?;
+            if data.len() < 4 {
+                return Err(invalid());
+            }
+            let decompress_format = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+            GodotLayer::Basis {
+                file: Box::new(BasisFile::new(&data[4..])?),
+                rg_as_ra: decompress_format == BASIS_DECOMPRESS_RG_AS_RA,
+            }
+        },
+        _ => return Err(invalid())
+    };
+    Ok((layer, width, height))
+}
+
+impl GodotTexture {
+    fn parse_stex(r: &mut Reader) -> Result<GodotTexture, ImageError> {
+        let width = r.u16()? as usize;
+        r.u16()?;  // custom width
+        let height = r.u16()? as usize;
+        r.u16()?;  // custom height
+        r.u32()?;  // texture flags
+        let data_format = r.u32()?;
+        let layer = if data_format & (FORMAT_BIT_LOSSLESS | FORMAT_BIT_LOSSY) != 0 {
+            let mut mipmaps = Vec::new();
+            for _ in 0..r.u32()? {
+                let size = r.u32()? as usize;
+                mipmaps.push(embedded_file(r.read(size)?).into());
+            }
+            GodotLayer::Embedded { webp: data_format & FORMAT_BIT_LOSSY != 0, mipmaps }
+        } else {
+            let format = format_name(3, data_format & FORMAT_MASK_IMAGE_FORMAT)?;
+            let levels = if data_format & FORMAT_BIT_HAS_MIPMAPS != 0 { 0 } else { 1 };
+            GodotLayer::Image { format, width, height, mipmaps: read_mipmaps(r, format, width, height, levels)? }
+        };
+        Ok(GodotTexture { version: 3, width, height, kind: "Texture2D", layers: vec![layer] })
+    }
+
+    fn parse_ctex(r: &mut Reader) -> Result<GodotTexture, ImageError> {
+        r.u32()?;  // version
+        let (width, height) = (r.u32()? as usize, r.u32()? as usize);
+        r.read(20)?;  // flags, mipmap limit, reserved
+        let (layer, _, _) = read_godot4_layer(r)?;
+        Ok(GodotTexture { version: 4, width, height, kind: "Texture2D", layers: vec![layer] })
+    }
+
+    fn parse_ctex_layered(r: &mut Reader) -> Result<GodotTexture, ImageError> {
+        r.u32()?;  // version
+        let layer_count = r.u32()?;
+        let kind = match r.u32()? {
+            0 => "Texture2DArray",
+            1 => "Cubemap",
+            2 => "CubemapArray",
+            _ => return Err(invalid())
+        };
+        r.read(20)?;  // flags, mipmap limit, reserved
+        let mut layers = Vec::new();
+        let (mut width, mut height) = (0, 0);
+        for _ in 0..layer_count {
+            let (layer, w, h) = read_godot4_layer(r)?;
+            width = w;
+            height = h;
+            layers.push(layer);
+        }
+        Ok(GodotTexture { version: 4, width, height, kind, layers })
+    }
+
+    fn layer(&self, layer: usize) -> Result<&GodotLayer, ImageError> {
+        self.layers.get(layer).ok_or_else(invalid)
+    }
+}
+
+/// Decodes a PNG to RGBA, top row first.
+fn decode_png(data: &[u8]) -> Result<(usize, usize, Vec<u8>), ImageError> {
+    let mut decoder = png::Decoder::new(Cursor::new(data));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|_| ImageError::invalid_data("PNG"))?;
+    let mut buf = vec![0u8; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buf).map_err(|_| ImageError::invalid_data("PNG"))?;
+    let (width, height) = (info.width as usize, info.height as usize);
+    let channels = info.color_type.samples();
+    let mut out = vec![0u8; width * height * 4];
+    for (i, px) in out.chunks_exact_mut(4).enumerate() {
+        let s = &buf[i * channels..(i + 1) * channels];
+        px.copy_from_slice(&match channels {
+            1 => [s[0], s[0], s[0], 255],
+            2 => [s[0], s[0], s[0], s[1]],
+            3 => [s[0], s[1], s[2], 255],
+            _ => [s[0], s[1], s[2], s[3]]
+        });
+    }
+    Ok((width, height, out))
+}
+
+/// Moves the alpha channel back to green, for normal maps stored as RA.
+fn ra_to_rg(data: &mut [u8]) {
+    for px in data.chunks_exact_mut(4) {
+        px[1] = px[3];
+        px[2] = 0;
+        px[3] = 255;
+    }
+}
+
+#[wasm_bindgen]
+impl GodotTexture {
+    /// Parses a Godot texture, detected by its magic.
+    #[wasm_bindgen(constructor)]
+    pub fn new(data: &[u8]) -> Result<GodotTexture, ImageError> {
+        let mut r = Reader { data, pos: 4 };
+        match data.get(..4) {
+            Some(b"GDST") => GodotTexture::parse_stex(&mut r),
+            Some(b"GST2") => GodotTexture::parse_ctex(&mut r),
+            Some(b"GSTL") => GodotTexture::parse_ctex_layered(&mut r),
+            _ => Err(ImageError::unknown_format("Godot texture"))
+        }
+    }
+
+    /// "Texture2D", "Texture2DArray", "Cubemap" or "CubemapArray".
+    #[wasm_bindgen(getter)]
+    pub fn kind(&self) -> String {
+        self.kind.to_string()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn layer_count(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// How a layer is stored: "image", "png", "webp" or "basis".
+    pub fn data_format(&self, layer: usize) -> String {
+        match self.layers.get(layer) {
+            Some(GodotLayer::Image { .. }) => "image",
+            Some(GodotLayer::Embedded { webp: false, .. }) => "png",
+            Some(GodotLayer::Embedded { webp: true, .. }) => "webp",
+            Some(GodotLayer::Basis { .. }) => "basis",
+            None => ""
+        }.to_string()
+    }
+
+    /// The `texdec` format name of a raw layer, or an empty string for other layers.
+    pub fn format(&self, layer: usize) -> String {
+        match self.layers.get(layer) {
+            Some(GodotLayer::Image { format, .. }) => format.to_string(),
+            _ => String::new()
+        }
+    }
+
+    pub fn mipmap_count(&self, layer: usize) -> usize {
+        match self.layers.get(layer) {
+            Some(GodotLayer::Image { mipmaps, .. }) | Some(GodotLayer::Embedded { mipmaps, .. }) => mipmaps.len(),
+            Some(GodotLayer::Basis { file, .. }) => file.level_count(0),
+            None => 0
+        }
+    }
+
+    /// The width of a mipmap; for embedded images this needs the image to be decoded.
+    pub fn mipmap_width(&self, layer: usize, level: usize) -> usize {
+        match self.layers.get(layer) {
+            Some(GodotLayer::Basis { file, .. }) => file.width(0, level),
+            Some(GodotLayer::Embedded { .. }) => self.decode(layer, level).map(|d| d.width).unwrap_or(0),
+            Some(GodotLayer::Image { width, .. }) => (width >> level).max(1),
+            None => 0
+        }
+    }
+
+    pub fn mipmap_height(&self, layer: usize, level: usize) -> usize {
+        match self.layers.get(layer) {
+            Some(GodotLayer::Basis { file, .. }) => file.height(0, level),
+            Some(GodotLayer::Embedded { .. }) => self.decode(layer, level).map(|d| d.height).unwrap_or(0),
+            Some(GodotLayer::Image { height, .. }) => (height >> level).max(1),
+            None => 0
+        }
+    }
+
+    /// The stored bytes of a mipmap: a PNG or WebP file for embedded layers, raw data for raw layers,
+    /// and the whole Basis file (all mipmaps) for Basis layers.
+    pub fn raw_data(&self, layer: usize, level: usize) -> Result<Box<[u8]>, ImageError> {
+        match self.layer(layer)? {
+            GodotLayer::Image { mipmaps, .. } | GodotLayer::Embedded { mipmaps, .. } =>
+                mipmaps.get(level).cloned().ok_or_else(invalid),
+            GodotLayer::Basis { file, .. } => Ok(file.data().into())
+        }
+    }
+
+    /// Decodes a mipmap of a layer to RGBA, top row first.
+    /// Lossy WebP layers can't be decoded here; use `raw_data` and let the browser decode them.
+    pub fn decode(&self, layer: usize, level: usize) -> Result<GodotImage, ImageError> {
+        let (width, height, pixels) = match self.layer(layer)? {
+            GodotLayer::Image { format, width, height, mipmaps } => {
+                let mut data = mipmaps.get(level).ok_or_else(invalid)?.to_vec();
+                let (w, h) = ((width >> level).max(1), (height >> level).max(1));
+                let mut pixels = decode(format.trim_end_matches("_RA_AS_RG"), &mut data, w, h, false, "", &[])?.into_vec();
+                if format.ends_with("_RA_AS_RG") {
+                    ra_to_rg(&mut pixels);
+                }
+                (w, h, pixels)
+            },
+            GodotLayer::Embedded { webp: false, mipmaps } => decode_png(mipmaps.get(level).ok_or_else(invalid)?)?,
+            GodotLayer::Embedded { webp: true, .. } => return Err(ImageError::unknown_format("WebP")),
+            GodotLayer::Basis { file, rg_as_ra } => {
+                let mut pixels = file.transcode(0, level, "rgba")?.into_vec();
+                if *rg_as_ra {
+                    ra_to_rg(&mut pixels);
+                }
+                (file.width(0, level), file.height(0, level), pixels)
+            }
+        };
+        Ok(GodotImage { width, height, pixels: pixels.into() })
+    }
+}
+
+#[wasm_bindgen]
+/// A decoded mipmap of a Godot texture.
+pub struct GodotImage {
+    pub width: usize,
+    pub height: usize,
+    pixels: Box<[u8]>,
+}
+
+#[wasm_bindgen]
+impl GodotImage {
+    /// RGBA data, top row first.
+    #[wasm_bindgen(getter)]
+    pub fn pixels(&self) -> Box<[u8]> {
+        self.pixels.clone()
+    }
+}
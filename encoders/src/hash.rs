@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use wasm_bindgen::prelude::*;
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// FNV-1a over the dimensions and the RGBA bytes, so identical pixels at different sizes don't collide.
+pub fn content_hash_u64(data: &[u8], width: usize, height: usize) -> u64 {
+    let mut hash = FNV_OFFSET;
+    let len = (width * height * 4).min(data.len());
+    for &b in (width as u32).to_le_bytes().iter().chain((height as u32).to_le_bytes().iter()).chain(data[..len].iter()) {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
+
+/// Averages the luminance of RGBA data over a `grid_w` x `grid_h` grid.
+/// Colours are weighted by alpha, so fully transparent areas count as black whatever colour they hold.
+fn luminance_grid(data: &[u8], width: usize, height: usize, grid_w: usize, grid_h: usize) -> Vec<f32> {
+    let mut out = vec![0f32; grid_w * grid_h];
+    for gy in 0..grid_h {
+        let y0 = gy * height / grid_h;
+        let y1 = ((gy + 1) * height).div_ceil(grid_h);
+        for gx in 0..grid_w {
+            let x0 = gx * width / grid_w;
+            let x1 = ((gx + 1) * width).div_ceil(grid_w);
+            let mut sum = 0f32;
+            let mut count = 0;
+            for y in y0..y1.min(height) {
+                for x in x0..x1.min(width) {
+                    let o = (y * width + x) * 4;
+                    if o + 4 > data.len() {
+                        continue;
+                    }
+                    let luma = 0.299 * data[o] as f32 + 0.587 * data[o + 1] as f32 + 0.114 * data[o + 2] as f32;
+                    sum += luma * data[o + 3] as f32 / 255.0;
+                    count += 1;
+                }
+            }
+            out[gy * grid_w + gx] = if count > 0 { sum / count as f32 } else { 0.0 };
+        }
+    }
+    out
+}
+
+/// Difference hash: one bit per horizontally adjacent pair of a 9x8 luminance grid.
+pub fn dhash_u64(data: &[u8], width: usize, height: usize) -> u64 {
+    if width == 0 || height == 0 {
+        return 0;
+    }
+    let grid = luminance_grid(data, width, height, 9, 8);
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if grid[y * 9 + x] < grid[y * 9 + x + 1] {
+                hash |= 1;
+            }
+        }
+    }
+    hash
+}
+
+/// DCT hash: the lowest 8x8 frequencies of a 32x32 luminance grid, thresholded at their median.
+pub fn phash_u64(data: &[u8], width: usize, height: usize) -> u64 {
+    if width == 0 || height == 0 {
+        return 0;
+    }
+    const N: usize = 32;
+    let grid = luminance_grid(data, width, height, N, N);
+    let mut cosines = [[0f32; N]; 8];
+    for (u, row) in cosines.iter_mut().enumerate() {
+        for (x, c) in row.iter_mut().enumerate() {
+            *c = ((2 * x + 1) as f32 * u as f32 * PI / (2 * N) as f32).cos();
+        }
+    }
+    // separable DCT-II, keeping only the coefficients we need
+    let mut rows = vec![0f32; N * 8];
+    for y in 0..N {
+        for u in 0..8 {
+            rows[y * 8 + u] = (0..N).map(|x| grid[y * N + x] * cosines[u][x]).sum();
+        }
+    }
+    let mut coefficients = [0f32; 64];
+    for v in 0..8 {
+        for u in 0..8 {
+            coefficients[v * 8 + u] = (0..N).map(|y| rows[y * 8 + u] * cosines[v][y]).sum();
+        }
+    }
+    // the DC term only says how bright the image is, so it is left out of the median
+    let mut sorted: Vec<f32> = coefficients[1..].to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let median = sorted[sorted.len() / 2];
+    let mut hash = 0u64;
+    for c in coefficients.iter() {
+        hash <<= 1;
+        if *c > median {
+            hash |= 1;
+        }
+    }
+    hash
+}
+
+#[wasm_bindgen]
+/// An exact hash of decoded RGBA data, as 16 hex digits.
+pub fn content_hash(data: &[u8], width: usize, height: usize) -> String {
+    format!("{:016x}", content_hash_u64(data, width, height))
+}
+
+#[wasm_bindgen]
+/// A 64-bit perceptual hash of RGBA data, as 16 hex digits.
+/// Similar images give hashes a small `hash_distance` apart, regardless of their size.
+///
+/// # Arguments
+///
+/// * `data` - RGBA data
+/// * `width`, `height` - The size of the image
+/// * `method` - "dhash" (gradients, fast) or "phash" (DCT, more robust to colour and filtering changes)
+pub fn perceptual_hash(data: &[u8], width: usize, height: usize, method: &str) -> String {
+    let hash = match method {
+        "phash" => phash_u64(data, width, height),
+        _ => dhash_u64(data, width, height)
+    };
+    format!("{:016x}", hash)
+}
+
+#[wasm_bindgen]
+/// The number of differing bits between two hex hashes, or 64 if either can't be parsed.
+pub fn hash_distance(a: &str, b: &str) -> u32 {
+    match (u64::from_str_radix(a, 16), u64::from_str_radix(b, 16)) {
+        (Ok(a), Ok(b)) => (a ^ b).count_ones(),
+        _ => 64
+    }
+}
+
+#[derive(Clone, Copy)]
+struct HashEntry {
+    content: u64,
+    dhash: u64,
+    phash: u64,
+}
+
+struct BkNode {
+    hash: u64,
+    /// Every item with exactly this hash
+    items: Vec<usize>,
+    /// Child nodes by their distance from this one
+    children: Vec<(u32, usize)>,
+}
+
+/// A BK-tree over 64-bit hashes by Hamming distance. A search only visits subtrees whose distance from
+/// their parent is within `max_distance` of the query's, so near duplicates are found without comparing every pair.
+#[derive(Default)]
+struct BkTree {
+    nodes: Vec<BkNode>,
+}
+
+impl BkTree {
+    fn insert(&mut self, hash: u64, item: usize) {
+        let new = BkNode { hash, items: vec![item], children: Vec::new() };
+        if self.nodes.is_empty() {
+            self.nodes.push(new);
+            return;
+        }
+        let mut node = 0;
+        loop {
+            let distance = (self.nodes[node].hash ^ hash).count_ones();
+            if distance == 0 {
+                self.nodes[node].items.push(item);
+                return;
+            }
+            match self.nodes[node].children.iter().find(|&&(d, _)| d == distance) {
+                Some(&(_, child)) => node = child,
+                None => {
+                    let index = self.nodes.len();
+                    self.nodes.push(new);
+                    self.nodes[node].children.push((distance, index));
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Adds every item whose hash is at most `max_distance` bits from `hash` to `out`.
+    fn find(&self, hash: u64, max_distance: u32, out: &mut Vec<usize>) {
+        let mut stack = if self.nodes.is_empty() { vec![] } else { vec![0] };
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            let distance = (node.hash ^ hash).count_ones();
+            if distance <= max_distance {
+                out.extend_from_slice(&node.items);
+            }
+            for &(d, child) in node.children.iter() {
+                if d + max_distance >= distance && d <= distance + max_distance {
+                    stack.push(child);
+                }
+            }
+        }
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Default)]
+/// Collects hashes of many images and groups the ones that are exact or near duplicates.
+pub struct DuplicateFinder {
+    entries: Vec<HashEntry>,
+}
+
+#[wasm_bindgen]
+impl DuplicateFinder {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> DuplicateFinder {
+        DuplicateFinder::default()
+    }
+
+    /// Hashes an RGBA image and returns its index.
+    pub fn add(&mut self, data: &[u8], width: usize, height: usize) -> usize {
+        self.entries.push(HashEntry {
+            content: content_hash_u64(data, width, height),
+            dhash: dhash_u64(data, width, height),
+            phash: phash_u64(data, width, height),
+        });
+        self.entries.len() - 1
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn count(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn content_hash(&self, index: usize) -> String {
+        self.entries.get(index).map(|e| format!("{:016x}", e.content)).unwrap_or_default()
+    }
+
+    /// Assigns every image a group: the index of the first image it duplicates, or its own index.
+    ///
+    /// Images are near duplicates when both their dHash and pHash are at most `max_distance` bits apart
+    /// (0 only matches identical content; around 5-10 catches re-encodes, resizes and atlas variants).
+    /// Matches are transitive, so a chain of similar images ends up in one group.
+    pub fn groups(&self, max_distance: u32) -> Box<[usize]> {
+        let n = self.entries.len();
+        let mut parent: Vec<usize> = (0..n).collect();
+        fn root(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+        fn join(parent: &mut [usize], i: usize, j: usize) {
+            let (ri, rj) = (root(parent, i), root(parent, j));
+            // keep the smallest index as the root so it doubles as the group id
+            if ri < rj {
+                parent[rj] = ri;
+            } else if rj < ri {
+                parent[ri] = rj;
+            }
+        }
+
+        let mut by_content = HashMap::new();
+        for (i, e) in self.entries.iter().enumerate() {
+            let first = *by_content.entry(e.content).or_insert(i);
+            join(&mut parent, first, i);
+        }
+        if max_distance > 0 {
+            // look up dHash neighbours in a BK-tree, then check their pHash too
+            let mut tree = BkTree::default();
+            for (i, e) in self.entries.iter().enumerate() {
+                tree.insert(e.dhash, i);
+            }
+            let mut near = Vec::new();
+            for (i, a) in self.entries.iter().enumerate() {
+                near.clear();
+                tree.find(a.dhash, max_distance, &mut near);
+                for &j in near.iter() {
+                    if j > i && (a.phash ^ self.entries[j].phash).count_ones() <= max_distance {
+                        join(&mut parent, i, j);
+                    }
+                }
+            }
+        }
+        (0..n).map(|i| root(&mut parent, i)).collect::<Vec<usize>>().into()
+    }
+}
@@ -0,0 +1,174 @@
+use wasm_bindgen::prelude::*;
+use crate::color::linear_to_srgb_f32;
+use crate::error::{rgba_size, ImageError};
+
+// Reconstructs HDR colour from textures that pack it into 8-bit channels, like Unity's lightmaps and
+// reflection probes on platforms without float textures. Outputs are linear RGBA floats, with alpha set to 1.
+
+/// Unity's `unity_Lightmap_HDR` values for RGBM lightmaps, in linear and gamma colour space.
+const RGBM_LINEAR: [f32; 4] = [34.49324, 2.2, 0.0, 1.0];
+const RGBM_GAMMA: [f32; 4] = [5.0, 1.0, 0.0, 1.0];
+/// Double-LDR lightmaps store half the colour; 4.59 is 2 converted to linear.
+const DLDR_LINEAR: [f32; 4] = [4.59482, 1.0, 0.0, 0.0];
+const DLDR_GAMMA: [f32; 4] = [2.0, 1.0, 0.0, 0.0];
+
+/// XYZ' to linear RGB, for LogLuv.
+const LOGLUV_INVERSE_M: [[f32; 3]; 3] = [
+    [6.0014, -2.7008, -1.7996],
+    [-1.3320, 3.1029, -5.7721],
+    [0.3008, -1.0882, 5.6268],
+];
+
+#[wasm_bindgen]
+/// Unity's `DecodeHDR`: colour = rgb * x * pow(w * (a - 1) + 1, y).
+///
+/// # Arguments
+///
+/// * `data` - RGBA data
+/// * `x`, `y`, `w` - The decode instructions, e.g. a reflection probe's `_HDR` vector or `unity_Lightmap_HDR`.
+///   `w` = 0 ignores alpha (dLDR), `w` = 1 uses it as a multiplier (RGBM).
+pub fn decode_unity_hdr(data: &[u8], x: f32, y: f32, w: f32) -> Box<[f32]> {
+    let mut out = vec![0f32; data.len() / 4 * 4];
+    for (px, o) in data.chunks_exact(4).zip(out.chunks_exact_mut(4)) {
+        let alpha = w * (px[3] as f32 / 255.0 - 1.0) + 1.0;
+        let scale = x * alpha.max(0.0).powf(y);
+        for c in 0..3 {
+            o[c] = px[c] as f32 / 255.0 * scale;
+        }
+        o[3] = 1.0;
+    }
+    out.into()
+}
+
+#[wasm_bindgen]
+/// RGBM: colour = rgb * a * `range`, with alpha raised to `exponent` first (2.2 for Unity's linear-space lightmaps).
+pub fn decode_rgbm(data: &[u8], range: f32, exponent: f32) -> Box<[f32]> {
+    decode_unity_hdr(data, range, exponent, 1.0)
+}
+
+#[wasm_bindgen]
+/// LogLuv (32-bit): chromaticity in R and G, log luminance in B (high byte) and A (low byte).
+pub fn decode_logluv(data: &[u8]) -> Box<[f32]> {
+    let mut out = vec![0f32; data.len() / 4 * 4];
+    for (px, o) in data.chunks_exact(4).zip(out.chunks_exact_mut(4)) {
+        let le = px[2] as f32 + px[3] as f32 / 255.0;
+        let y = ((le - 127.0) / 2.0).exp2();
+        let v = px[1] as f32 / 255.0;
+        let z = if v > 0.0 { y / v } else { 0.0 };
+        let xyz = [px[0] as f32 / 255.0 * z, y, z];
+        for c in 0..3 {
+            o[c] = (0..3).map(|i| xyz[i] * LOGLUV_INVERSE_M[i][c]).sum::<f32>().max(0.0);
+        }
+        o[3] = 1.0;
+    }
+    out.into()
+}
+
+#[wasm_bindgen]
+/// Reconstructs HDR colour using one of Unity's standard encodings.
+///
+/// # Arguments
+///
+/// * `data` - RGBA data
+/// * `encoding` - "rgbm", "dldr" or "logluv"
+/// * `linear` - If the project uses linear colour space, which changes the RGBM and dLDR decode instructions
+pub fn decode_hdr(data: &[u8], encoding: &str, linear: bool) -> Result<Box<[f32]>, ImageError> {
+    let instructions = match (encoding, linear) {
+        ("rgbm", true) => RGBM_LINEAR,
+        ("rgbm", false) => RGBM_GAMMA,
+        ("dldr", true) => DLDR_LINEAR,
+        ("dldr", false) => DLDR_GAMMA,
+        ("logluv", _) => return Ok(decode_logluv(data)),
+        _ => return Err(ImageError::unknown_format(encoding))
+    };
+    Ok(decode_unity_hdr(data, instructions[0], instructions[1], instructions[3]))
+}
+
+fn aces(x: f32) -> f32 {
+    // Narkowicz's fit of the ACES filmic curve
+    (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)
+}
+
+#[wasm_bindgen]
+/// Converts linear float RGBA to 8-bit sRGB RGBA for display or PNG export.
+///
+/// # Arguments
+///
+/// * `data` - Linear RGBA floats
+/// * `exposure` - Stops to brighten (positive) or darken (negative) by before tonemapping
+/// * `operator` - "clamp", "reinhard" or "aces"
+pub fn tonemap(data: &[f32], exposure: f32, operator: &str) -> Box<[u8]> {
+    let scale = exposure.exp2();
+    let mut out = vec![0u8; data.len() / 4 * 4];
+    for (px, o) in data.chunks_exact(4).zip(out.chunks_exact_mut(4)) {
+        for c in 0..3 {
+            let v = (px[c] * scale).max(0.0);
+            let mapped = match operator {
+                "reinhard" => v / (1.0 + v),
+                "aces" => aces(v),
+                _ => v
+            };
+            o[c] = (linear_to_srgb_f32(mapped.clamp(0.0, 1.0)) * 255.0).round() as u8;
+        }
+        o[3] = (px[3].clamp(0.0, 1.0) * 255.0).round() as u8;
+    }
+    out.into()
+}
+
+/// Shared-exponent RGBE, as used by Radiance files.
+fn to_rgbe(r: f32, g: f32, b: f32) -> [u8; 4] {
+    let v = r.max(g).max(b);
+    if v < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+    // v = m * 2^e with m in [0.5, 1)
+    let mut e = v.log2().floor() as i32 + 1;
+    if v / (e as f32).exp2() >= 1.0 {
+        e += 1;
+    }
+    let scale = 256.0 / (e as f32).exp2();
+    let channel = |c: f32| (c.max(0.0) * scale).min(255.0) as u8;
+    [channel(r), channel(g), channel(b), (e + 128).clamp(0, 255) as u8]
+}
+
+#[wasm_bindgen]
+/// Encodes linear float RGBA as a Radiance `.hdr` (RGBE) file. Alpha is dropped.
+///
+/// # Arguments
+///
+/// * `width`, `height` - The image size
+/// * `data` - Linear RGBA floats
+/// * `flip` - Whether the rows of `data` are bottom first and should be flipped
+pub fn encode_hdr(width: u32, height: u32, data: &[f32], flip: bool) -> Result<Box<[u8]>, ImageError> {
+    let (w, h) = (width as usize, height as usize);
+    if w == 0 || h == 0 {
+        return Err(ImageError::invalid_dimensions(w, h));
+    }
+    let size = rgba_size(w, h)?;
+    if data.len() < size {
+        return Err(ImageError::data_too_short("RGBAFloat", size * 4, data.len() * 4));
+    }
+    let mut out = format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", h, w).into_bytes();
+    let mut scanline = vec![[0u8; 4]; w];
+    for y in 0..h {
+        let row = if flip { h - 1 - y } else { y };
+        for (x, px) in scanline.iter_mut().enumerate() {
+            let o = (row * w + x) * 4;
+            *px = to_rgbe(data[o], data[o + 1], data[o + 2]);
+        }
+        if !(8..0x8000).contains(&w) {
+            // run-length encoding can't describe scanlines of this width
+            scanline.iter().for_each(|px| out.extend_from_slice(px));
+            continue;
+        }
+        // each channel in turn, as literal runs of up to 128 bytes
+        out.extend_from_slice(&[2, 2, (w >> 8) as u8, w as u8]);
+        for c in 0..4 {
+            for chunk in scanline.chunks(128) {
+                out.push(chunk.len() as u8);
+                out.extend(chunk.iter().map(|px| px[c]));
+            }
+        }
+    }
+    Ok(out.into())
+}
@@ -0,0 +1,43 @@
+use wasm_bindgen::prelude::*;
+use crate::error::ImageError;
+use crate::pngenc::{check_rgba_size, encode_png_with_options};
+use crate::qoienc::encode_qoi;
+use crate::tgaenc::{encode_tga, encode_tga_rle};
+use crate::webpenc::encode_webp;
+
+#[wasm_bindgen]
+/// Encodes RGBA data in the named image format: "png", "webp" (lossless), "qoi", "tga" or "tga-rle".
+/// Unknown formats fall back to PNG. Fails if `data` doesn't hold a whole image.
+pub fn encode_image(format: &str, width: u32, height: u32, data: &[u8], flip: bool) -> Result<Box<[u8]>, ImageError> {
+    encode_image_with_options(format, width, height, data, flip, "fast", "sub")
+}
+
+#[wasm_bindgen]
+/// Like `encode_image`, with a chosen PNG compression level and filter strategy.
+///
+/// # Arguments
+///
+/// * `png_compression` - "fast", "default" or "best" -- only used for PNG
+/// * `png_filter` - "none", "sub", "up", "avg", "paeth" or "adaptive" -- only used for PNG
+pub fn encode_image_with_options(format: &str, width: u32, height: u32, data: &[u8], flip: bool,
+                                 png_compression: &str, png_filter: &str) -> Result<Box<[u8]>, ImageError> {
+    check_rgba_size(width, height, data)?;
+    Ok(match format {
+        "webp" => encode_webp(width, height, data, flip),
+        "qoi" => encode_qoi(width, height, data, flip),
+        "tga" => encode_tga(width, height, data, flip),
+        "tga-rle" => encode_tga_rle(width, height, data, flip),
+        _ => return encode_png_with_options(width, height, data, flip, png_compression, png_filter)
+    })
+}
+
+#[wasm_bindgen]
+/// The file extension (including the dot) for an `encode_image` format.
+pub fn image_extension(format: &str) -> String {
+    match format {
+        "webp" => ".webp",
+        "qoi" => ".qoi",
+        "tga" | "tga-rle" => ".tga",
+        _ => ".png"
+    }.to_string()
+}
@@ -1,5 +1,32 @@
 pub mod utils;
+pub mod error;
 pub mod pngenc;
 pub mod texdec;
 pub mod fp16;
 pub mod compress;
+pub mod swizzle;
+pub mod normal;
+pub mod color;
+pub mod resize;
+pub mod sprite;
+pub mod simd;
+pub mod webpenc;
+pub mod qoienc;
+pub mod tgaenc;
+pub mod imgenc;
+pub mod animenc;
+pub mod cubemap;
+pub mod hash;
+pub mod diff;
+pub mod blockenc;
+pub mod basis;
+pub mod godot;
+pub mod unreal;
+pub mod hdr;
+pub mod pvrtc;
+pub mod uastc;
+pub mod detect;
+pub mod texarray;
+
+#[cfg(all(feature = "parallel", target_arch = "wasm32"))]
+pub use wasm_bindgen_rayon::init_thread_pool;
@@ -0,0 +1,59 @@
+use wasm_bindgen::prelude::*;
+use crate::error::ImageError;
+use crate::texdec::decode;
+
+fn to_unorm(v: f32) -> u8 {
+    ((v * 0.5 + 0.5).clamp(0.0, 1.0) * 255.0 + 0.5) as u8
+}
+
+fn to_snorm(v: u8) -> f32 {
+    v as f32 / 255.0 * 2.0 - 1.0
+}
+
+#[wasm_bindgen]
+/// Rebuilds a tangent-space normal map into the usual RGB = XYZ display in place.
+///
+/// # Arguments
+///
+/// * `data` - Decoded RGBA data
+/// * `layout` - Where X and Y are stored: "RG" (BC5, EAC_RG), "AG" (DXT5nm) or "auto",
+///   which multiplies red by alpha like Unity's `UnpackNormalmapRGorAG` so both layouts work
+/// * `flip_green` - Negate Y to convert between OpenGL and DirectX conventions
+pub fn unpack_normal_map(data: &mut [u8], layout: &str, flip_green: bool) {
+    for i in 0..(data.len() / 4) {
+        let o = i * 4;
+        let x = match layout {
+            "RG" => to_snorm(data[o]),
+            "AG" => to_snorm(data[o + 3]),
+            _ => to_snorm(((data[o] as u32 * data[o + 3] as u32 + 127) / 255) as u8)
+        };
+        let y = if flip_green { -to_snorm(data[o + 1]) } else { to_snorm(data[o + 1]) };
+        let z = (1.0 - x * x - y * y).max(0.0).sqrt();
+        data[o] = to_unorm(x);
+        data[o + 1] = to_unorm(y);
+        data[o + 2] = to_unorm(z);
+        data[o + 3] = 0xff;
+    }
+}
+
+#[wasm_bindgen]
+/// Rebuilds Z from the X and Y stored in the red and green channels.
+pub fn reconstruct_normal_z(data: &mut [u8]) {
+    unpack_normal_map(data, "RG", false);
+}
+
+#[wasm_bindgen]
+/// Converts DXT5nm data (X in alpha, Y in green) into a regular RGB normal map.
+pub fn unswizzle_dxt5nm(data: &mut [u8]) {
+    unpack_normal_map(data, "AG", false);
+}
+
+#[wasm_bindgen]
+/// Decodes a texture of arbitrary format as a normal map.
+/// Takes the same arguments as `texdec::decode`, plus `flip_green` (see `unpack_normal_map`).
+pub fn decode_normal_map(format: &str, data: &mut [u8], width: usize, height: usize, is_xbox: bool, platform: &str,
+                         platform_blob: &[u8], flip_green: bool) -> Result<Box<[u8]>, ImageError> {
+    let mut out = decode(format, data, width, height, is_xbox, platform, platform_blob)?;
+    unpack_normal_map(&mut out, "auto", flip_green);
+    Ok(out)
+}
@@ -2,8 +2,9 @@ use std::io::{Cursor, Read, Write};
 use std::panic;
 use png::Decoder;
 use wasm_bindgen::prelude::*;
+use crate::error::{rgba_size, ImageError};
 
-fn flip_v(width: usize, height: usize, data: &[u8]) -> Box<[u8]> {
+pub(crate) fn flip_v(width: usize, height: usize, data: &[u8]) -> Box<[u8]> {
     let mut out = Vec::new();
     for y in 0..height {
         for x in 0..width {
@@ -19,21 +20,80 @@ fn flip_v(width: usize, height: usize, data: &[u8]) -> Box<[u8]> {
     out.into()
 }
 
-#[wasm_bindgen]
-pub fn encode_png(width: u32, height: u32, data: &[u8], flip: bool) -> Box<[u8]> {
-    panic::set_hook(Box::new(console_error_panic_hook::hook));
+/// Checks that `data` holds a whole non-empty `width` x `height` RGBA image and returns its size in bytes.
+pub(crate) fn check_rgba_size(width: u32, height: u32, data: &[u8]) -> Result<usize, ImageError> {
     if width == 0 || height == 0 {
-        return [].into();
+        return Err(ImageError::invalid_dimensions(width as usize, height as usize));
     }
+    let size = rgba_size(width as usize, height as usize)?;
+    if data.len() < size {
+        return Err(ImageError::data_too_short("RGBA32", size, data.len()));
+    }
+    Ok(size)
+}
+
+#[wasm_bindgen]
+pub fn encode_png(width: u32, height: u32, data: &[u8], flip: bool) -> Result<Box<[u8]>, ImageError> {
+    encode_png_with_options(width, height, data, flip, "fast", "sub")
+}
+
+fn png_compression(level: &str) -> png::Compression {
+    match level {
+        "default" => png::Compression::Default,
+        "best" => png::Compression::Best,
+        _ => png::Compression::Fast
+    }
+}
+
+#[wasm_bindgen]
+/// Encodes RGBA data as a PNG with a chosen compression level and filter strategy.
+///
+/// # Arguments
+///
+/// * `width`, `height` - The image size
+/// * `data` - RGBA data
+/// * `flip` - Flip the image vertically (decoded textures are stored bottom row first)
+/// * `compression` - "fast", "default" or "best"
+/// * `filter` - "none", "sub", "up", "avg", "paeth", or "adaptive" to pick the best filter for each row
+pub fn encode_png_with_options(width: u32, height: u32, data: &[u8], flip: bool, compression: &str,
+                               filter: &str) -> Result<Box<[u8]>, ImageError> {
+    panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let size = check_rgba_size(width, height, data)?;
     let mut w = Vec::new();
     let mut encoder  = png::Encoder::new(&mut w, width, height);
     encoder.set_color(png::ColorType::Rgba);
     encoder.set_depth(png::BitDepth::Eight);
-    encoder.set_compression(png::Compression::Fast);
+    encoder.set_compression(png_compression(compression));
+    match filter {
+        "none" => encoder.set_filter(png::FilterType::NoFilter),
+        "up" => encoder.set_filter(png::FilterType::Up),
+        "avg" => encoder.set_filter(png::FilterType::Avg),
+        "paeth" => encoder.set_filter(png::FilterType::Paeth),
+        "adaptive" => encoder.set_adaptive_filter(png::AdaptiveFilterType::Adaptive),
+        _ => encoder.set_filter(png::FilterType::Sub)
+    }
     let mut writer = encoder.write_header().unwrap();
-    let mut datavec = (if flip {flip_v(width as usize, height as usize, &data)} else {data.into()})[0..(width * height * 4) as usize].to_vec();
-    datavec.extend(std::iter::repeat(0).take(((width * height * 4) - datavec.len() as u32) as usize));
+    let datavec = if flip {flip_v(width as usize, height as usize, data)} else {data[..size].into()};
     writer.write_image_data(&datavec).unwrap();
     writer.finish().unwrap();
-    w.into()
+    Ok(w.into())
+}
+
+#[wasm_bindgen]
+/// Encodes one channel of RGBA data (0 = R, 1 = G, 2 = B, 3 = A) as a greyscale PNG.
+pub fn encode_channel_png(width: u32, height: u32, data: &[u8], channel: usize, flip: bool)
+                          -> Result<Box<[u8]>, ImageError> {
+    panic::set_hook(Box::new(console_error_panic_hook::hook));
+    let size = check_rgba_size(width, height, data)?;
+    let mut w = Vec::new();
+    let mut encoder  = png::Encoder::new(&mut w, width, height);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_compression(png::Compression::Fast);
+    let mut writer = encoder.write_header().unwrap();
+    let rgba = if flip {flip_v(width as usize, height as usize, data)} else {data[..size].into()};
+    let grey: Vec<u8> = rgba.chunks_exact(4).map(|px| px[channel.min(3)]).collect();
+    writer.write_image_data(&grey).unwrap();
+    writer.finish().unwrap();
+    Ok(w.into())
 }
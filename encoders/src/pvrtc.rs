@@ -0,0 +1,228 @@
+use std::cmp::max;
+use crate::error::ImageError;
+
+// PVRTC2 (PVRTC-II) decoding. Blocks are laid out and interpolated like PVRTC1, but the colour word has a
+// single opacity flag for both colours (bit 31) and a hard transition flag (bit 15) in place of colour A's.
+//
+// Colours are upscaled and modulated the way the PowerVR SDK's PVRTC1 decompressor does it: the four
+// nearest block colours are blended at their stored precision (5 bits of colour, 4 of alpha) and only
+// then widened to 8 bits, and the modulation blend rounds down.
+//
+// Blocks with the hard transition flag set (and so the 2bpp "local palette" sub-mode, which builds on it)
+// aren't supported: the image is rejected as invalid data rather than decoded approximately.
+
+/// 4bpp and 2bpp interpolated modulation weights, out of 8.
+const MODULATION_WEIGHTS: [i32; 4] = [0, 3, 5, 8];
+const PUNCH_THROUGH_WEIGHTS: [i32; 4] = [0, 4, 4, 8];
+
+/// How a 2bpp block with the modulation mode flag fills in the pixels it doesn't store.
+#[derive(Clone, Copy, PartialEq)]
+enum Interpolation {
+    /// One bit per pixel, choosing colour A or B
+    None,
+    /// Average of the stored pixels above, below, left and right
+    Both,
+    Horizontal,
+    Vertical
+}
+
+#[derive(Clone, Copy)]
+struct Pvrtc2Block {
+    modulation: u32,
+    /// RGB at 5 bits and alpha at 4
+    color_a: [i32; 4],
+    color_b: [i32; 4],
+    mode: bool,
+    hard: bool
+}
+
+fn expand3(v: u32) -> i32 {
+    ((v << 2) | (v >> 1)) as i32
+}
+
+fn expand4(v: u32) -> i32 {
+    ((v << 1) | (v >> 3)) as i32
+}
+
+impl Pvrtc2Block {
+    fn new(block: &[u8]) -> Pvrtc2Block {
+        let modulation = u32::from_le_bytes([block[0], block[1], block[2], block[3]]);
+        let color = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+        let opaque = color >> 31 != 0;
+        let (color_a, color_b) = if opaque {
+            (
+                [(color >> 10 & 0x1f) as i32, (color >> 5 & 0x1f) as i32, expand4(color >> 1 & 0xf), 15],
+                [(color >> 26 & 0x1f) as i32, (color >> 21 & 0x1f) as i32, (color >> 16 & 0x1f) as i32, 15]
+            )
+        } else {
+            (
+                [expand4(color >> 8 & 0xf), expand4(color >> 4 & 0xf), expand3(color >> 1 & 7), (color >> 12 & 7) as i32 * 2],
+                [expand4(color >> 24 & 0xf), expand4(color >> 20 & 0xf), expand4(color >> 16 & 0xf), (color >> 28 & 7) as i32 * 2]
+            )
+        };
+        Pvrtc2Block { modulation, color_a, color_b, mode: color & 1 != 0, hard: color >> 15 & 1 != 0 }
+    }
+
+    /// For 2bpp blocks, how unstored pixels are filled in and the modulation bits with the mode bits
+    /// replaced: the first pixel's low bit picks between averaging both ways or one way, and the 11th
+    /// pixel's low bit picks which way.
+    fn modulation_2bpp(&self) -> (Interpolation, u32) {
+        let mut bits = self.modulation;
+        if !self.mode {
+            return (Interpolation::None, bits);
+        }
+        let mut interpolation = Interpolation::Both;
+        if bits & 1 != 0 {
+            interpolation = if bits >> 20 & 1 != 0 { Interpolation::Vertical } else { Interpolation::Horizontal };
+            bits = bits & !(1 << 20) | (bits >> 21 & 1) << 20;
+        }
+        bits = bits & !1 | (bits >> 1 & 1);
+        (interpolation, bits)
+    }
+}
+
+/// The index of block (x, y) in PVRTC's twiddled (Morton) order, with y in the lowest bit.
+/// Grids that aren't powers of two are stored row by row.
+fn block_index(x: usize, y: usize, blocks_x: usize, blocks_y: usize) -> usize {
+    if !blocks_x.is_power_of_two() || !blocks_y.is_power_of_two() {
+        return y * blocks_x + x;
+    }
+    let min_dim = blocks_x.min(blocks_y);
+    let mut index = 0;
+    let mut shift = 0;
+    let mut bit = 1;
+    while bit < min_dim {
+        if y & bit != 0 { index |= 1 << shift; }
+        if x & bit != 0 { index |= 1 << (shift + 1); }
+        bit <<= 1;
+        shift += 2;
+    }
+    let rest = if blocks_x > blocks_y { x } else { y } / min_dim;
+    index | rest << shift
+}
+
+/// The number of blocks across and down a PVRTC2 image, which is at least 2x2.
+pub fn pvrtc2_block_count(width: usize, height: usize, is2bpp: bool) -> (usize, usize) {
+    let block_width = if is2bpp { 8 } else { 4 };
+    (max((width + block_width - 1) / block_width, 2), max((height + 3) / 4, 2))
+}
+
+/// The modulation weight (out of 8) and punch-through flag of every pixel of the block grid, which
+/// covers whole blocks and so can be larger than the image.
+fn modulation_weights(blocks: &[Pvrtc2Block], blocks_x: usize, blocks_y: usize, is2bpp: bool) -> Vec<(i32, bool)> {
+    let block_width = if is2bpp { 8 } else { 4 };
+    let (grid_width, grid_height) = (blocks_x * block_width, blocks_y * 4);
+    let mut weights = vec![(0, false); grid_width * grid_height];
+    if !is2bpp {
+        for (i, block) in blocks.iter().enumerate() {
+            let (bx, by) = (i % blocks_x * 4, i / blocks_x * 4);
+            for p in 0..16 {
+                let bits = (block.modulation >> (p * 2) & 3) as usize;
+                weights[(by + p / 4) * grid_width + bx + p % 4] = if block.mode {
+                    (PUNCH_THROUGH_WEIGHTS[bits], bits == 2)
+                } else {
+                    (MODULATION_WEIGHTS[bits], false)
+                };
+            }
+        }
+        return weights;
+    }
+
+    // stored 2-bit values first (1-bit values become 0 or 3), then the gaps of interpolated blocks
+    let mut stored = vec![0usize; grid_width * grid_height];
+    let mut modes = vec![Interpolation::None; grid_width * grid_height];
+    for (i, block) in blocks.iter().enumerate() {
+        let (bx, by) = (i % blocks_x * 8, i / blocks_x * 4);
+        let (interpolation, mut bits) = block.modulation_2bpp();
+        for p in 0..32 {
+            let (x, y) = (bx + p % 8, by + p / 8);
+            modes[y * grid_width + x] = interpolation;
+            if interpolation == Interpolation::None {
+                stored[y * grid_width + x] = if bits & 1 != 0 { 3 } else { 0 };
+                bits >>= 1;
+            } else if (x ^ y) & 1 == 0 {
+                stored[y * grid_width + x] = (bits & 3) as usize;
+                bits >>= 2;
+            }
+        }
+    }
+    let at = |x: usize, y: usize| MODULATION_WEIGHTS[stored[(y % grid_height) * grid_width + x % grid_width]];
+    for y in 0..grid_height {
+        for x in 0..grid_width {
+            let (left, right) = (x + grid_width - 1, x + 1);
+            let (up, down) = (y + grid_height - 1, y + 1);
+            let weight = match modes[y * grid_width + x] {
+                _ if (x ^ y) & 1 == 0 => at(x, y),
+                Interpolation::None => at(x, y),
+                Interpolation::Both => (at(x, up) + at(x, down) + at(left, y) + at(right, y) + 2) / 4,
+                Interpolation::Horizontal => (at(left, y) + at(right, y) + 1) / 2,
+                Interpolation::Vertical => (at(x, up) + at(x, down) + 1) / 2
+            };
+            weights[y * grid_width + x] = (weight, false);
+        }
+    }
+    weights
+}
+
+/// Decodes PVRTC2 data into RGBA. `data` must hold every block of the image and `out` `width * height * 4` bytes.
+pub fn decode_pvrtc2_image(data: &[u8], out: &mut [u8], width: usize, height: usize, is2bpp: bool) -> Result<(), ImageError> {
+    let (blocks_x, blocks_y) = pvrtc2_block_count(width, height, is2bpp);
+    let block_width = if is2bpp { 8 } else { 4 };
+    let mut blocks = Vec::with_capacity(blocks_x * blocks_y);
+    for by in 0..blocks_y {
+        for bx in 0..blocks_x {
+            let i = block_index(bx, by, blocks_x, blocks_y) * 8;
+            let block = Pvrtc2Block::new(&data[i..i + 8]);
+            if block.hard {
+                return Err(ImageError::invalid_data(if is2bpp { "PVRTC2_2BPP" } else { "PVRTC2_4BPP" }));
+            }
+            blocks.push(block);
+        }
+    }
+    let weights = modulation_weights(&blocks, blocks_x, blocks_y, is2bpp);
+    let grid_width = blocks_x * block_width;
+    // the blended colours are scaled by block_width * 4 (2 to the power of `shift`); dividing that out
+    // while widening 5-bit colour and 4-bit alpha to 8 bits
+    let shift = if is2bpp { 5 } else { 4 };
+    let bw = block_width as i32;
+    for y in 0..height {
+        for x in 0..width {
+            // bilinear upscale of the colours between the centres of the four nearest blocks
+            let px = (x + grid_width - block_width / 2) % grid_width;
+            let py = (y + blocks_y * 4 - 2) % (blocks_y * 4);
+            let (bx0, wx) = (px / block_width, (px % block_width) as i32);
+            let (by0, wy) = (py / 4, (py % 4) as i32);
+            let (bx1, by1) = ((bx0 + 1) % blocks_x, (by0 + 1) % blocks_y);
+            let corners = [
+                (&blocks[by0 * blocks_x + bx0], (bw - wx) * (4 - wy)),
+                (&blocks[by0 * blocks_x + bx1], wx * (4 - wy)),
+                (&blocks[by1 * blocks_x + bx0], (bw - wx) * wy),
+                (&blocks[by1 * blocks_x + bx1], wx * wy)
+            ];
+            let mut a = [0i32; 4];
+            let mut b = [0i32; 4];
+            for &(block, w) in corners.iter() {
+                for c in 0..4 {
+                    a[c] += block.color_a[c] * w;
+                    b[c] += block.color_b[c] * w;
+                }
+            }
+            for c in 0..3 {
+                a[c] = (a[c] >> (shift + 2)) + (a[c] >> (shift - 3));
+                b[c] = (b[c] >> (shift + 2)) + (b[c] >> (shift - 3));
+            }
+            a[3] = (a[3] >> shift) + (a[3] >> (shift - 4));
+            b[3] = (b[3] >> shift) + (b[3] >> (shift - 4));
+
+            let (weight, transparent) = weights[y * grid_width + x];
+            let o = (y * width + x) * 4;
+            for c in 0..4 {
+                out[o + c] = ((a[c] * (8 - weight) + b[c] * weight) / 8) as u8;
+            }
+            if transparent {
+                out[o + 3] = 0;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use std::panic;
+use wasm_bindgen::prelude::*;
+use crate::pngenc::flip_v;
+
+const QOI_OP_INDEX: u8 = 0x00;
+const QOI_OP_DIFF: u8 = 0x40;
+const QOI_OP_LUMA: u8 = 0x80;
+const QOI_OP_RUN: u8 = 0xc0;
+const QOI_OP_RGB: u8 = 0xfe;
+const QOI_OP_RGBA: u8 = 0xff;
+
+fn qoi_hash(px: [u8; 4]) -> usize {
+    (px[0] as usize * 3 + px[1] as usize * 5 + px[2] as usize * 7 + px[3] as usize * 11) % 64
+}
+
+#[wasm_bindgen]
+/// Encodes RGBA data as a QOI image (sRGB, 4 channels).
+pub fn encode_qoi(width: u32, height: u32, data: &[u8], flip: bool) -> Box<[u8]> {
+    panic::set_hook(Box::new(console_error_panic_hook::hook));
+    if width == 0 || height == 0 {
+        return [].into();
+    }
+    let rgba = if flip {flip_v(width as usize, height as usize, data)} else {data.into()};
+    let pixels = (width * height) as usize;
+
+    let mut out = Vec::with_capacity(14 + pixels + 8);
+    out.extend_from_slice(b"qoif");
+    out.extend_from_slice(&width.to_be_bytes());
+    out.extend_from_slice(&height.to_be_bytes());
+    out.push(4);
+    out.push(0);
+
+    let mut index = [[0u8; 4]; 64];
+    let mut prev = [0u8, 0, 0, 0xff];
+    let mut run = 0;
+    for i in 0..pixels {
+        let px = if i * 4 + 4 <= rgba.len() {
+            [rgba[i * 4], rgba[i * 4 + 1], rgba[i * 4 + 2], rgba[i * 4 + 3]]
+        } else {
+            [0, 0, 0, 0]
+        };
+        if px == prev {
+            run += 1;
+            if run == 62 || i == pixels - 1 {
+                out.push(QOI_OP_RUN | (run - 1));
+                run = 0;
+            }
+            continue;
+        }
+        if run > 0 {
+            out.push(QOI_OP_RUN | (run - 1));
+            run = 0;
+        }
+        let h = qoi_hash(px);
+        if index[h] == px {
+            out.push(QOI_OP_INDEX | h as u8);
+        } else {
+            index[h] = px;
+            if px[3] == prev[3] {
+                let vr = px[0].wrapping_sub(prev[0]) as i8;
+                let vg = px[1].wrapping_sub(prev[1]) as i8;
+                let vb = px[2].wrapping_sub(prev[2]) as i8;
+                let vg_r = vr.wrapping_sub(vg);
+                let vg_b = vb.wrapping_sub(vg);
+                if (-2..2).contains(&vr) && (-2..2).contains(&vg) && (-2..2).contains(&vb) {
+                    out.push(QOI_OP_DIFF | ((vr + 2) as u8) << 4 | ((vg + 2) as u8) << 2 | (vb + 2) as u8);
+                } else if (-8..8).contains(&vg_r) && (-32..32).contains(&vg) && (-8..8).contains(&vg_b) {
+                    out.push(QOI_OP_LUMA | (vg + 32) as u8);
+                    out.push(((vg_r + 8) as u8) << 4 | (vg_b + 8) as u8);
+                } else {
+                    out.extend_from_slice(&[QOI_OP_RGB, px[0], px[1], px[2]]);
+                }
+            } else {
+                out.extend_from_slice(&[QOI_OP_RGBA, px[0], px[1], px[2], px[3]]);
+            }
+        }
+        prev = px;
+    }
+    out.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+    out.into()
+}
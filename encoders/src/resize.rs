@@ -0,0 +1,216 @@
+use std::cmp::max;
+use std::f32::consts::PI;
+use wasm_bindgen::prelude::*;
+use crate::error::ImageError;
+use crate::color::{linear_to_srgb_f32, srgb_to_linear_f32};
+use crate::swizzle::{get_platform_swizzle, Swizzle};
+use crate::texdec::{decode, get_mipmap_offset_and_size, DecodedImage};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Filter {
+    Box,
+    Bilinear,
+    Lanczos3,
+}
+
+impl Filter {
+    pub fn from_name(name: &str) -> Filter {
+        match name {
+            "box" => Filter::Box,
+            "lanczos" | "lanczos3" => Filter::Lanczos3,
+            _ => Filter::Bilinear
+        }
+    }
+
+    fn support(&self) -> f32 {
+        match self {
+            Filter::Box => 0.5,
+            Filter::Bilinear => 1.0,
+            Filter::Lanczos3 => 3.0,
+        }
+    }
+
+    fn weight(&self, x: f32) -> f32 {
+        match self {
+            Filter::Box => if x >= -0.5 && x < 0.5 { 1.0 } else { 0.0 },
+            Filter::Bilinear => (1.0 - x.abs()).max(0.0),
+            Filter::Lanczos3 => {
+                if x == 0.0 {
+                    1.0
+                } else if x.abs() >= 3.0 {
+                    0.0
+                } else {
+                    let px = PI * x;
+                    3.0 * px.sin() * (px / 3.0).sin() / (px * px)
+                }
+            }
+        }
+    }
+}
+
+/// Source indices and normalised weights contributing to each destination sample along one axis.
+fn compute_weights(src_len: usize, dst_len: usize, filter: Filter) -> Vec<(usize, Vec<f32>)> {
+    let scale = src_len as f32 / dst_len as f32;
+    let filter_scale = scale.max(1.0);
+    let support = filter.support() * filter_scale;
+    let mut out = Vec::with_capacity(dst_len);
+    for i in 0..dst_len {
+        let center = (i as f32 + 0.5) * scale;
+        let left = max(0, (center - support).floor() as i64) as usize;
+        let right = ((center + support).ceil() as usize).min(src_len);
+        let mut weights: Vec<f32> = (left..right)
+            .map(|j| filter.weight((j as f32 + 0.5 - center) / filter_scale))
+            .collect();
+        let total: f32 = weights.iter().sum();
+        if total != 0.0 {
+            for w in weights.iter_mut() {
+                *w /= total;
+            }
+        } else {
+            // the filter fell between samples; take the nearest one
+            let nearest = (center as usize).min(src_len - 1);
+            out.push((nearest, vec![1.0]));
+            continue;
+        }
+        out.push((left, weights));
+    }
+    out
+}
+
+fn resample_rows(src: &[f32], width: usize, height: usize, new_width: usize, filter: Filter) -> Vec<f32> {
+    let weights = compute_weights(width, new_width, filter);
+    let mut out = vec![0f32; new_width * height * 4];
+    for y in 0..height {
+        for (x, (start, w)) in weights.iter().enumerate() {
+            let mut acc = [0f32; 4];
+            for (k, wk) in w.iter().enumerate() {
+                let s = (y * width + start + k) * 4;
+                for c in 0..4 {
+                    acc[c] += src[s + c] * wk;
+                }
+            }
+            let d = (y * new_width + x) * 4;
+            out[d..d + 4].copy_from_slice(&acc);
+        }
+    }
+    out
+}
+
+fn resample_columns(src: &[f32], width: usize, height: usize, new_height: usize, filter: Filter) -> Vec<f32> {
+    let weights = compute_weights(height, new_height, filter);
+    let mut out = vec![0f32; width * new_height * 4];
+    for (y, (start, w)) in weights.iter().enumerate() {
+        for x in 0..width {
+            let mut acc = [0f32; 4];
+            for (k, wk) in w.iter().enumerate() {
+                let s = ((start + k) * width + x) * 4;
+                for c in 0..4 {
+                    acc[c] += src[s + c] * wk;
+                }
+            }
+            let d = (y * width + x) * 4;
+            out[d..d + 4].copy_from_slice(&acc);
+        }
+    }
+    out
+}
+
+#[wasm_bindgen]
+/// Resizes RGBA data.
+///
+/// # Arguments
+///
+/// * `data` - RGBA data
+/// * `width`, `height` - The size of the source image
+/// * `new_width`, `new_height` - The size of the output image
+/// * `filter` - "box", "bilinear" or "lanczos"
+/// * `gamma_correct` - Filter colours in linear light instead of on the raw sRGB values
+pub fn resize(data: &[u8], width: usize, height: usize, new_width: usize, new_height: usize, filter: &str,
+              gamma_correct: bool) -> Box<[u8]> {
+    if width == 0 || height == 0 || new_width == 0 || new_height == 0 {
+        return [].into();
+    }
+    if width == new_width && height == new_height {
+        return data[0..width * height * 4].into();
+    }
+    let filter = Filter::from_name(filter);
+
+    // work in premultiplied floats so transparent pixels don't bleed their colour into the result
+    let mut pixels = vec![0f32; width * height * 4];
+    for i in 0..(width * height) {
+        let a = data[i * 4 + 3] as f32 / 255.0;
+        for c in 0..3 {
+            let v = data[i * 4 + c] as f32 / 255.0;
+            pixels[i * 4 + c] = if gamma_correct { srgb_to_linear_f32(v) } else { v } * a;
+        }
+        pixels[i * 4 + 3] = a;
+    }
+
+    let pixels = resample_rows(&pixels, width, height, new_width, filter);
+    let pixels = resample_columns(&pixels, new_width, height, new_height, filter);
+
+    let mut out = vec![0u8; new_width * new_height * 4];
+    for i in 0..(new_width * new_height) {
+        let a = pixels[i * 4 + 3].clamp(0.0, 1.0);
+        for c in 0..3 {
+            let mut v = if a > 0.0 { (pixels[i * 4 + c] / a).clamp(0.0, 1.0) } else { 0.0 };
+            if gamma_correct {
+                v = linear_to_srgb_f32(v);
+            }
+            out[i * 4 + c] = (v * 255.0).round() as u8;
+        }
+        out[i * 4 + 3] = (a * 255.0).round() as u8;
+    }
+    out.into()
+}
+
+/// Scales `width` x `height` down to fit within `max_dim`, keeping the aspect ratio.
+pub fn fit_dimensions(width: usize, height: usize, max_dim: usize) -> (usize, usize) {
+    if width <= max_dim && height <= max_dim {
+        return (width, height);
+    }
+    if width >= height {
+        (max_dim, max(1, height * max_dim / width))
+    } else {
+        (max(1, width * max_dim / height), max_dim)
+    }
+}
+
+#[wasm_bindgen]
+/// Decodes a downscaled preview of a texture.
+///
+/// The smallest mipmap that is still at least as large as the thumbnail is decoded,
+/// so only a fraction of a large texture has to be touched. Console-swizzled textures pad their mip chains,
+/// so those always use the top mipmap.
+///
+/// # Arguments
+///
+/// * `format` - A string holding the texture format (e.g. "RGBA32")
+/// * `data` - The image data, including any mipmaps
+/// * `width`, `height` - The size of the top mipmap
+/// * `max_dim` - The maximum width or height of the thumbnail
+/// * `is_xbox` - If the platform is XBox 360 -- used to determine if bytes should be swapped
+/// * `platform` - The build target name (e.g. "Switch", "PS4", "Nintendo 3DS") -- used to undo console swizzling
+/// * `platform_blob` - The texture's `m_PlatformBlob` -- Switch textures are only deswizzled when it isn't empty
+pub fn decode_thumbnail(format: &str, data: &mut [u8], width: usize, height: usize, max_dim: usize, is_xbox: bool,
+                        platform: &str, platform_blob: &[u8]) -> Result<DecodedImage, ImageError> {
+    let (tw, th) = fit_dimensions(width, height, max(1, max_dim));
+
+    let mut mip = 0;
+    let mut mip_info = get_mipmap_offset_and_size(0, format, width as i32, height as i32);
+    while get_platform_swizzle(platform, platform_blob) == Swizzle::None {
+        let next = get_mipmap_offset_and_size(mip + 1, format, width as i32, height as i32);
+        let next_end = get_mipmap_offset_and_size(mip + 2, format, width as i32, height as i32).0;
+        if (next.1 as usize) < tw || (next.2 as usize) < th || next_end as usize > data.len()
+            || (next.1 == mip_info.1 && next.2 == mip_info.2) {
+            break;
+        }
+        mip += 1;
+        mip_info = next;
+    }
+    let (mw, mh) = (mip_info.1 as usize, mip_info.2 as usize);
+    // hand the decoder everything from the mip onwards; it checks that the mip itself is complete
+    let start = (mip_info.0 as usize).min(data.len());
+    let decoded = decode(format, &mut data[start..], mw, mh, is_xbox, platform, platform_blob)?;
+    Ok(DecodedImage::new(tw, th, resize(&decoded, mw, mh, tw, th, "bilinear", true)))
+}
@@ -0,0 +1,112 @@
+//! SIMD128 fast paths for the uncompressed converters in `texdec`.
+//!
+//! Each function converts as many whole vectors as fit in both buffers and returns the number of pixels written;
+//! the caller finishes the remainder with its scalar loop. The results match the scalar code exactly.
+//! Without the `simd128` target feature every function is a no-op that returns 0.
+
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+use core::arch::wasm32::*;
+
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+unsafe fn store_interleaved(dst: *mut u8, rg: v128, ba: v128) {
+    v128_store(dst as *mut v128, u16x8_shuffle::<0, 8, 1, 9, 2, 10, 3, 11>(rg, ba));
+    v128_store(dst.add(16) as *mut v128, u16x8_shuffle::<4, 12, 5, 13, 6, 14, 7, 15>(rg, ba));
+}
+
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+pub fn rgb565_to_rgba(src: &[u8], dst: &mut [u8], pixels: usize) -> usize {
+    let n = pixels.min(src.len() / 2).min(dst.len() / 4) / 8 * 8;
+    for i in (0..n).step_by(8) {
+        unsafe {
+            let d = v128_load(src.as_ptr().add(i * 2) as *const v128);
+            let r = v128_or(v128_and(u16x8_shr(d, 8), u16x8_splat(0xf8)), u16x8_shr(d, 13));
+            let g = v128_or(v128_and(u16x8_shr(d, 3), u16x8_splat(0xfc)), v128_and(u16x8_shr(d, 9), u16x8_splat(3)));
+            let b = v128_and(v128_or(u16x8_shl(d, 3), v128_and(u16x8_shr(d, 2), u16x8_splat(7))), u16x8_splat(0xff));
+            let rg = v128_or(r, u16x8_shl(g, 8));
+            let ba = v128_or(b, u16x8_splat(0xff00));
+            store_interleaved(dst.as_mut_ptr().add(i * 4), rg, ba);
+        }
+    }
+    n
+}
+
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+pub fn argb4444_to_rgba(src: &[u8], dst: &mut [u8], pixels: usize) -> usize {
+    let n = pixels.min(src.len() / 2).min(dst.len() / 4) / 8 * 8;
+    let nibble = u16x8_splat(0xf);
+    for i in (0..n).step_by(8) {
+        unsafe {
+            let d = v128_load(src.as_ptr().add(i * 2) as *const v128);
+            let c0 = v128_and(u16x8_shr(d, 4), nibble);
+            let c1 = v128_and(u16x8_shr(d, 8), nibble);
+            let c2 = u16x8_shr(d, 12);
+            let c3 = v128_and(d, nibble);
+            store_interleaved(dst.as_mut_ptr().add(i * 4), v128_or(c0, u16x8_shl(c1, 8)), v128_or(c2, u16x8_shl(c3, 8)));
+        }
+    }
+    n
+}
+
+/// Converts four half floats (zero-extended to u32 lanes) to f32.
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+fn half_to_f32x4(h: v128) -> v128 {
+    let sign = u32x4_shl(v128_and(h, u32x4_splat(0x8000)), 16);
+    let magnitude = u32x4_shl(v128_and(h, u32x4_splat(0x7fff)), 13);
+    // rebias the exponent by multiplying with 2^112; this also normalises subnormals
+    let value = f32x4_mul(magnitude, f32x4_splat(f32::from_bits(0x77800000)));
+    let inf_nan = u32x4_eq(v128_and(h, u32x4_splat(0x7c00)), u32x4_splat(0x7c00));
+    v128_or(v128_or(value, v128_and(inf_nan, u32x4_splat(0x7f800000))), sign)
+}
+
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+pub fn rgbahalf_to_rgba(src: &[u8], dst: &mut [u8], pixels: usize) -> usize {
+    let n = pixels.min(src.len() / 8).min(dst.len() / 4) / 2 * 2;
+    let scale = f32x4_splat(255.0);
+    for i in (0..n).step_by(2) {
+        unsafe {
+            let h = v128_load(src.as_ptr().add(i * 8) as *const v128);
+            let lo = i32x4_trunc_sat_f32x4(f32x4_mul(half_to_f32x4(u32x4_extend_low_u16x8(h)), scale));
+            let hi = i32x4_trunc_sat_f32x4(f32x4_mul(half_to_f32x4(u32x4_extend_high_u16x8(h)), scale));
+            let words = i16x8_narrow_i32x4(lo, hi);
+            v128_store64_lane::<0>(u8x16_narrow_i16x8(words, words), dst.as_mut_ptr().add(i * 4) as *mut u64);
+        }
+    }
+    n
+}
+
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+pub fn rgbafloat_to_rgba(src: &[u8], dst: &mut [u8], pixels: usize) -> usize {
+    let n = pixels.min(src.len() / 16).min(dst.len() / 4) / 4 * 4;
+    for i in (0..n).step_by(4) {
+        unsafe {
+            let p = src.as_ptr().add(i * 16);
+            let a = i32x4_trunc_sat_f32x4(v128_load(p as *const v128));
+            let b = i32x4_trunc_sat_f32x4(v128_load(p.add(16) as *const v128));
+            let c = i32x4_trunc_sat_f32x4(v128_load(p.add(32) as *const v128));
+            let d = i32x4_trunc_sat_f32x4(v128_load(p.add(48) as *const v128));
+            let bytes = u8x16_narrow_i16x8(i16x8_narrow_i32x4(a, b), i16x8_narrow_i32x4(c, d));
+            v128_store(dst.as_mut_ptr().add(i * 4) as *mut v128, bytes);
+        }
+    }
+    n
+}
+
+#[cfg(not(all(target_arch = "wasm32", target_feature = "simd128")))]
+pub fn rgb565_to_rgba(_src: &[u8], _dst: &mut [u8], _pixels: usize) -> usize {
+    0
+}
+
+#[cfg(not(all(target_arch = "wasm32", target_feature = "simd128")))]
+pub fn argb4444_to_rgba(_src: &[u8], _dst: &mut [u8], _pixels: usize) -> usize {
+    0
+}
+
+#[cfg(not(all(target_arch = "wasm32", target_feature = "simd128")))]
+pub fn rgbahalf_to_rgba(_src: &[u8], _dst: &mut [u8], _pixels: usize) -> usize {
+    0
+}
+
+#[cfg(not(all(target_arch = "wasm32", target_feature = "simd128")))]
+pub fn rgbafloat_to_rgba(_src: &[u8], _dst: &mut [u8], _pixels: usize) -> usize {
+    0
+}
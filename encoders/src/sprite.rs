@@ -0,0 +1,256 @@
+use wasm_bindgen::prelude::*;
+
+/// Unity `SpritePackingRotation`, in the order of its values (`None` = 0 to `Rotate90` = 4)
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PackingRotation {
+    None,
+    FlipHorizontal,
+    FlipVertical,
+    Rotate180,
+    Rotate90,
+}
+
+impl PackingRotation {
+    /// The rotation from `SpriteSettings`: bits 2-5, which only apply when the sprite is packed (bit 0).
+    pub fn from_settings(settings_raw: u32) -> PackingRotation {
+        if settings_raw & 1 == 0 {
+            return PackingRotation::None;
+        }
+        match (settings_raw >> 2) & 0xf {
+            1 => PackingRotation::FlipHorizontal,
+            2 => PackingRotation::FlipVertical,
+            3 => PackingRotation::Rotate180,
+            4 => PackingRotation::Rotate90,
+            _ => PackingRotation::None
+        }
+    }
+}
+
+#[wasm_bindgen]
+#[derive(Clone, Copy, Default, Debug)]
+/// Everything needed to cut a sprite out of its texture, taken from the `Sprite` and its `SpriteRenderData`.
+/// All rectangles use Unity's convention of a bottom-left origin.
+pub struct SpriteInfo {
+    /// `m_RD.textureRect`, the area of the texture the sprite occupies
+    pub rect_x: f32,
+    pub rect_y: f32,
+    pub rect_width: f32,
+    pub rect_height: f32,
+    /// `m_RD.textureRectOffset`, where the texture rect sits inside the untrimmed sprite
+    pub offset_x: f32,
+    pub offset_y: f32,
+    /// The size of `m_Rect`, the untrimmed sprite
+    pub sprite_width: f32,
+    pub sprite_height: f32,
+    /// `m_Pivot`, normalised to the untrimmed sprite
+    pub pivot_x: f32,
+    pub pivot_y: f32,
+    pub pixels_to_units: f32,
+    /// `m_Border` (left, bottom, right, top) in pixels
+    pub border_left: f32,
+    pub border_bottom: f32,
+    pub border_right: f32,
+    pub border_top: f32,
+    /// `m_RD.settingsRaw`
+    pub settings_raw: u32,
+    /// Place the trimmed image back into a canvas the size of the untrimmed sprite
+    pub pad_to_rect: bool,
+}
+
+#[wasm_bindgen]
+impl SpriteInfo {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> SpriteInfo {
+        SpriteInfo { pixels_to_units: 100.0, pivot_x: 0.5, pivot_y: 0.5, ..Default::default() }
+    }
+}
+
+#[wasm_bindgen]
+/// A sprite image (bottom row first, like decoded textures) and its metadata relative to that image.
+pub struct ExtractedSprite {
+    pub width: usize,
+    pub height: usize,
+    /// The pivot in pixels from the bottom-left corner
+    pub pivot_x: f32,
+    pub pivot_y: f32,
+    pub border_left: f32,
+    pub border_bottom: f32,
+    pub border_right: f32,
+    pub border_top: f32,
+    data: Box<[u8]>
+}
+
+#[wasm_bindgen]
+impl ExtractedSprite {
+    #[wasm_bindgen(getter)]
+    pub fn data(&self) -> Box<[u8]> {
+        self.data.clone()
+    }
+}
+
+impl ExtractedSprite {
+    pub fn pixels(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+fn crop(data: &[u8], tex_width: usize, tex_height: usize, x: usize, y: usize, width: usize, height: usize) -> Vec<u8> {
+    let mut out = vec![0u8; width * height * 4];
+    for row in 0..height {
+        let sy = y + row;
+        if sy >= tex_height {
+            break;
+        }
+        let w = width.min(tex_width.saturating_sub(x));
+        let src = (sy * tex_width + x) * 4;
+        if src + w * 4 > data.len() {
+            break;
+        }
+        out[row * width * 4..(row * width + w) * 4].copy_from_slice(&data[src..src + w * 4]);
+    }
+    out
+}
+
+/// Undoes the packing rotation; returns the new image and its size.
+fn unrotate(data: Vec<u8>, width: usize, height: usize, rotation: PackingRotation) -> (Vec<u8>, usize, usize) {
+    if rotation == PackingRotation::None {
+        return (data, width, height);
+    }
+    let (nw, nh) = if rotation == PackingRotation::Rotate90 { (height, width) } else { (width, height) };
+    let mut out = vec![0u8; data.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let (dx, dy) = match rotation {
+                PackingRotation::FlipHorizontal => (width - 1 - x, y),
+                PackingRotation::FlipVertical => (x, height - 1 - y),
+                PackingRotation::Rotate180 => (width - 1 - x, height - 1 - y),
+                // the atlas holds the sprite turned a quarter counter-clockwise, so turn it back clockwise
+                PackingRotation::Rotate90 => (y, width - 1 - x),
+                PackingRotation::None => (x, y),
+            };
+            let s = (y * width + x) * 4;
+            let d = (dy * nw + dx) * 4;
+            out[d..d + 4].copy_from_slice(&data[s..s + 4]);
+        }
+    }
+    (out, nw, nh)
+}
+
+fn edge(ax: f32, ay: f32, bx: f32, by: f32, px: f32, py: f32) -> f32 {
+    (bx - ax) * (py - ay) - (by - ay) * (px - ax)
+}
+
+/// Clears every pixel whose centre lies outside all of the triangles.
+fn mask_triangles(data: &mut [u8], width: usize, height: usize, points: &[(f32, f32)], indices: &[u16]) {
+    let mut inside = vec![false; width * height];
+    for tri in indices.chunks_exact(3) {
+        if tri.iter().any(|&i| i as usize >= points.len()) {
+            continue;
+        }
+        let (ax, ay) = points[tri[0] as usize];
+        let (bx, by) = points[tri[1] as usize];
+        let (cx, cy) = points[tri[2] as usize];
+        let area = edge(ax, ay, bx, by, cx, cy);
+        if area == 0.0 {
+            continue;
+        }
+        let min_x = ax.min(bx).min(cx).floor().max(0.0) as usize;
+        let min_y = ay.min(by).min(cy).floor().max(0.0) as usize;
+        let max_x = (ax.max(bx).max(cx).ceil().max(0.0) as usize).min(width);
+        let max_y = (ay.max(by).max(cy).ceil().max(0.0) as usize).min(height);
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let px = x as f32 + 0.5;
+                let py = y as f32 + 0.5;
+                let w0 = edge(bx, by, cx, cy, px, py) * area;
+                let w1 = edge(cx, cy, ax, ay, px, py) * area;
+                let w2 = edge(ax, ay, bx, by, px, py) * area;
+                if w0 >= 0.0 && w1 >= 0.0 && w2 >= 0.0 {
+                    inside[y * width + x] = true;
+                }
+            }
+        }
+    }
+    for (i, keep) in inside.iter().enumerate() {
+        if !keep {
+            data[i * 4..i * 4 + 4].copy_from_slice(&[0, 0, 0, 0]);
+        }
+    }
+}
+
+#[wasm_bindgen]
+/// Cuts a sprite out of its decoded texture.
+///
+/// # Arguments
+///
+/// * `data` - The decoded RGBA texture, bottom row first
+/// * `tex_width`, `tex_height` - The size of the texture
+/// * `info` - The sprite's rects, pivot, border and packing settings
+/// * `vertices` - The sprite mesh positions as x, y pairs in units relative to the pivot (may be empty)
+/// * `indices` - The sprite mesh triangle list (may be empty)
+///
+/// Tightly packed sprites are masked with their mesh, so neighbouring sprites in the atlas don't show through.
+pub fn extract_sprite(data: &[u8], tex_width: usize, tex_height: usize, info: &SpriteInfo,
+                      vertices: &[f32], indices: &[u16]) -> ExtractedSprite {
+    // take every pixel the rect touches, but only those inside the texture
+    let x = (info.rect_x.max(0.0).floor() as usize).min(tex_width);
+    let y = (info.rect_y.max(0.0).floor() as usize).min(tex_height);
+    let w = ((info.rect_x + info.rect_width).max(0.0).ceil() as usize).min(tex_width).saturating_sub(x);
+    let h = ((info.rect_y + info.rect_height).max(0.0).ceil() as usize).min(tex_height).saturating_sub(y);
+
+    let cropped = crop(data, tex_width, tex_height, x, y, w, h);
+    let (mut image, width, height) = unrotate(cropped, w, h, PackingRotation::from_settings(info.settings_raw));
+
+    let packing_tight = (info.settings_raw >> 1) & 1 == 0;
+    if packing_tight && !indices.is_empty() && vertices.len() >= 2 {
+        let origin_x = info.sprite_width * info.pivot_x - info.offset_x;
+        let origin_y = info.sprite_height * info.pivot_y - info.offset_y;
+        let points: Vec<(f32, f32)> = vertices.chunks_exact(2)
+            .map(|v| (v[0] * info.pixels_to_units + origin_x, v[1] * info.pixels_to_units + origin_y))
+            .collect();
+        mask_triangles(&mut image, width, height, &points, indices);
+    }
+
+    let pivot_x = info.sprite_width * info.pivot_x;
+    let pivot_y = info.sprite_height * info.pivot_y;
+    if info.pad_to_rect {
+        let sw = info.sprite_width.max(0.0).round() as usize;
+        let sh = info.sprite_height.max(0.0).round() as usize;
+        // an offset outside the canvas crops the image instead of shifting it out of bounds
+        let (ox, oy) = (info.offset_x.round() as isize, info.offset_y.round() as isize);
+        let (src_x, dst_x) = ((-ox).max(0) as usize, ox.max(0) as usize);
+        let (src_y, dst_y) = ((-oy).max(0) as usize, oy.max(0) as usize);
+        let copy = width.saturating_sub(src_x).min(sw.saturating_sub(dst_x));
+        let mut canvas = vec![0u8; sw * sh * 4];
+        for row in 0..height.saturating_sub(src_y).min(sh.saturating_sub(dst_y)) {
+            let s = ((row + src_y) * width + src_x) * 4;
+            let d = ((row + dst_y) * sw + dst_x) * 4;
+            canvas[d..d + copy * 4].copy_from_slice(&image[s..s + copy * 4]);
+        }
+        return ExtractedSprite {
+            width: sw,
+            height: sh,
+            pivot_x,
+            pivot_y,
+            border_left: info.border_left,
+            border_bottom: info.border_bottom,
+            border_right: info.border_right,
+            border_top: info.border_top,
+            data: canvas.into()
+        };
+    }
+
+    let trim_right = info.sprite_width - info.offset_x - width as f32;
+    let trim_top = info.sprite_height - info.offset_y - height as f32;
+    ExtractedSprite {
+        width,
+        height,
+        pivot_x: pivot_x - info.offset_x,
+        pivot_y: pivot_y - info.offset_y,
+        border_left: (info.border_left - info.offset_x).max(0.0),
+        border_bottom: (info.border_bottom - info.offset_y).max(0.0),
+        border_right: (info.border_right - trim_right).max(0.0),
+        border_top: (info.border_top - trim_top).max(0.0),
+        data: image.into()
+    }
+}
@@ -0,0 +1,193 @@
+use wasm_bindgen::prelude::*;
+
+/// Memory layouts used by console GPUs that have to be undone before decoding.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Swizzle {
+    None,
+    /// Nintendo Switch (Tegra X1) block-linear, with the block height in GOBs (0 to detect it)
+    Tegra(usize),
+    /// PS4/PS5 (GNM) 8x8 micro-tiling
+    Gnm,
+    /// Nintendo 3DS (PICA200) 8x8 Morton tiles, stored upside-down
+    Ctr,
+}
+
+/// Picks the layout to undo for a texture from the given build target.
+///
+/// Unity only swizzles Switch textures that have a platform blob, which also holds the block height.
+pub fn get_platform_swizzle(platform: &str, platform_blob: &[u8]) -> Swizzle {
+    match platform {
+        "Switch" if platform_blob.is_empty() => Swizzle::None,
+        "Switch" => Swizzle::Tegra(tegra_blob_block_height(platform_blob)),
+        "PS4" | "PS5" => Swizzle::Gnm,
+        "Nintendo 3DS" => Swizzle::Ctr,
+        _ => Swizzle::None
+    }
+}
+
+const GOB_WIDTH: usize = 64;
+const GOB_HEIGHT: usize = 8;
+const GOB_SIZE: usize = GOB_WIDTH * GOB_HEIGHT;
+
+/// Picks the number of GOBs per block the same way the Tegra driver does for mip 0.
+pub fn tegra_block_height(height_in_blocks: usize) -> usize {
+    let height_and_half = height_in_blocks + height_in_blocks / 2;
+    if height_and_half >= 128 {
+        16
+    } else if height_and_half >= 64 {
+        8
+    } else if height_and_half >= 32 {
+        4
+    } else if height_and_half >= 16 {
+        2
+    } else {
+        1
+    }
+}
+
+/// Reads the number of GOBs per block from a Switch texture's `m_PlatformBlob`, where it's stored
+/// as a power of two at offset 8. Returns 0 (detect it from the height) if the blob is too short.
+pub fn tegra_blob_block_height(platform_blob: &[u8]) -> usize {
+    match platform_blob.get(8..12) {
+        Some(&[a, b, c, d]) => match i32::from_le_bytes([a, b, c, d]) {
+            log2 @ 0..=5 => 1 << log2,
+            _ => 0
+        },
+        _ => 0
+    }
+}
+
+fn tegra_gob_address(x: usize, y: usize, block_height: usize, width_in_gobs: usize) -> usize {
+    let block_rows = y / (GOB_HEIGHT * block_height);
+    let gob_in_block = (y % (GOB_HEIGHT * block_height)) / GOB_HEIGHT;
+    let gob = block_rows * GOB_SIZE * block_height * width_in_gobs
+        + (x / GOB_WIDTH) * GOB_SIZE * block_height
+        + gob_in_block * GOB_SIZE;
+
+    let gx = x % GOB_WIDTH;
+    let gy = y % GOB_HEIGHT;
+    gob + (gx / 32) * 256 + (gy / 2) * 64 + ((gx % 32) / 16) * 32 + (gy % 2) * 16 + (gx % 16)
+}
+
+#[wasm_bindgen]
+/// Converts a Tegra X1 block-linear surface into linear rows of blocks.
+///
+/// # Arguments
+///
+/// * `data` - The swizzled surface
+/// * `width`, `height` - The image size in pixels
+/// * `block_width`, `block_height` - The size of a format block in pixels (1x1 for uncompressed formats)
+/// * `bytes_per_block` - The size of a format block (or pixel) in bytes
+/// * `gobs_per_block` - The block height in GOBs, or 0 to detect it from the image height
+pub fn deswizzle_tegra(data: &[u8], width: usize, height: usize, block_width: usize, block_height: usize,
+                       bytes_per_block: usize, gobs_per_block: usize) -> Box<[u8]> {
+    let wb = (width + block_width - 1) / block_width;
+    let hb = (height + block_height - 1) / block_height;
+    let gobs = if gobs_per_block == 0 { tegra_block_height(hb) } else { gobs_per_block };
+    let row_bytes = wb * bytes_per_block;
+    let width_in_gobs = (row_bytes + GOB_WIDTH - 1) / GOB_WIDTH;
+
+    let mut out = vec![0u8; row_bytes * hb];
+    for y in 0..hb {
+        for x in 0..row_bytes {
+            let src = tegra_gob_address(x, y, gobs, width_in_gobs);
+            if src < data.len() {
+                out[y * row_bytes + x] = data[src];
+            }
+        }
+    }
+    out.into()
+}
+
+fn morton_xy(i: usize) -> (usize, usize) {
+    let x = (i & 1) | ((i >> 1) & 2) | ((i >> 2) & 4);
+    let y = ((i >> 1) & 1) | ((i >> 2) & 2) | ((i >> 3) & 4);
+    (x, y)
+}
+
+/// Undoes 8x8 element tiles laid out row-major, with elements in Morton order inside each tile.
+fn untile_morton(data: &[u8], wb: usize, hb: usize, bytes_per_block: usize, flip: bool) -> Box<[u8]> {
+    let tiles_x = (wb + 7) / 8;
+    let tiles_y = (hb + 7) / 8;
+    let mut out = vec![0u8; wb * hb * bytes_per_block];
+    let mut src = 0;
+    for ty in 0..tiles_y {
+        for tx in 0..tiles_x {
+            for i in 0..64 {
+                let (mx, my) = morton_xy(i);
+                let x = tx * 8 + mx;
+                let y = ty * 8 + my;
+                if x < wb && y < hb && src + bytes_per_block <= data.len() {
+                    let dy = if flip { hb - y - 1 } else { y };
+                    let dst = (dy * wb + x) * bytes_per_block;
+                    out[dst..dst + bytes_per_block].copy_from_slice(&data[src..src + bytes_per_block]);
+                }
+                src += bytes_per_block;
+            }
+        }
+    }
+    out.into()
+}
+
+#[wasm_bindgen]
+/// Converts a PS4/PS5 tiled surface (8x8 elements per tile) into linear rows of blocks.
+pub fn deswizzle_gnm(data: &[u8], width: usize, height: usize, block_width: usize, block_height: usize,
+                     bytes_per_block: usize) -> Box<[u8]> {
+    let wb = (width + block_width - 1) / block_width;
+    let hb = (height + block_height - 1) / block_height;
+    untile_morton(data, wb, hb, bytes_per_block, false)
+}
+
+#[wasm_bindgen]
+/// Converts a 3DS tiled surface of uncompressed pixels into linear rows, bottom row first.
+pub fn deswizzle_ctr(data: &[u8], width: usize, height: usize, bytes_per_pixel: usize) -> Box<[u8]> {
+    untile_morton(data, width, height, bytes_per_pixel, true)
+}
+
+#[wasm_bindgen]
+/// Reorders 3DS ETC1/ETC1A4 data into linear block rows (top row first).
+///
+/// Every 8x8 tile holds 2x2 blocks in Z order, and the colour half of each block is stored
+/// little-endian, so it is byte-reversed here to match the standard ETC1 layout.
+/// ETC1A4 blocks keep their 8 bytes of 4-bit alpha in front of the colour data.
+pub fn untile_ctr_etc(data: &[u8], width: usize, height: usize, has_alpha: bool) -> Box<[u8]> {
+    let block_size = if has_alpha { 16 } else { 8 };
+    let wb = (width + 3) / 4;
+    let hb = (height + 3) / 4;
+    let tiles_x = (wb + 1) / 2;
+    let tiles_y = (hb + 1) / 2;
+    let mut out = vec![0u8; wb * hb * block_size];
+    let mut src = 0;
+    for ty in 0..tiles_y {
+        for tx in 0..tiles_x {
+            for i in 0..4 {
+                let x = tx * 2 + (i & 1);
+                let y = ty * 2 + (i >> 1);
+                if x < wb && y < hb && src + block_size <= data.len() {
+                    let dst = (y * wb + x) * block_size;
+                    let color = block_size - 8;
+                    out[dst..dst + color].copy_from_slice(&data[src..src + color]);
+                    for b in 0..8 {
+                        out[dst + color + b] = data[src + block_size - 1 - b];
+                    }
+                }
+                src += block_size;
+            }
+        }
+    }
+    out.into()
+}
+
+/// Undoes the platform swizzle for a texture of the given format.
+/// Returns `None` if the data is already linear.
+pub fn deswizzle(swizzle: Swizzle, data: &[u8], width: usize, height: usize,
+                 block_width: usize, block_height: usize, bytes_per_block: usize) -> Option<Box<[u8]>> {
+    match swizzle {
+        Swizzle::None => None,
+        Swizzle::Tegra(gobs_per_block) => Some(deswizzle_tegra(data, width, height, block_width, block_height, bytes_per_block, gobs_per_block)),
+        Swizzle::Gnm => Some(deswizzle_gnm(data, width, height, block_width, block_height, bytes_per_block)),
+        // 3DS hardware only samples uncompressed and ETC1(A4) textures; the latter are handled by the ETC decoders
+        Swizzle::Ctr if block_width == 1 && block_height == 1 => Some(deswizzle_ctr(data, width, height, bytes_per_block)),
+        Swizzle::Ctr => None,
+    }
+}
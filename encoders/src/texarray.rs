@@ -0,0 +1,279 @@
+use std::cmp::max;
+use wasm_bindgen::prelude::*;
+use crate::error::{rgba_size, ImageError};
+use crate::pngenc::encode_png;
+use crate::texdec::{decode, expected_data_size, DecodedImage};
+
+// Texture2DArray, CubemapArray and Texture3D data. Arrays store one complete mipmap chain per slice, while
+// 3D textures store each mipmap level for every depth slice in turn, with the depth halving along with
+// the width and height.
+
+const DDS_FLAGS: u32 = 0x1 | 0x2 | 0x4 | 0x8 | 0x1000 | 0x20000;
+const DDS_FLAG_DEPTH: u32 = 0x800000;
+const DDS_CAPS: u32 = 0x8 | 0x1000 | 0x400000;
+const DDS_CAPS2_CUBEMAP_ALL_FACES: u32 = 0xfe00;
+const DDS_CAPS2_VOLUME: u32 = 0x200000;
+const DXGI_FORMAT_R8G8B8A8_UNORM: u32 = 28;
+const D3D10_RESOURCE_DIMENSION_TEXTURE2D: u32 = 3;
+const D3D10_RESOURCE_DIMENSION_TEXTURE3D: u32 = 4;
+const D3D10_RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+
+const KTX_IDENTIFIER: [u8; 12] = [0xab, b'K', b'T', b'X', b' ', b'1', b'1', 0xbb, b'\r', b'\n', 0x1a, b'\n'];
+const GL_UNSIGNED_BYTE: u32 = 0x1401;
+const GL_RGBA: u32 = 0x1908;
+const GL_RGBA8: u32 = 0x8058;
+
+fn out_of_range() -> ImageError {
+    ImageError::invalid_data("Texture array")
+}
+
+#[wasm_bindgen]
+/// A texture made of `depth` slices of the same size and format, such as a Texture2DArray,
+/// a CubemapArray (six slices per cubemap) or a Texture3D.
+pub struct TextureArray {
+    format: String,
+    platform: String,
+    data: Vec<u8>,
+    pub width: usize,
+    pub height: usize,
+    pub depth: usize,
+    pub mip_count: usize,
+    /// Mipmaps also halve the depth and are stored level by level (Texture3D)
+    pub volume: bool,
+    /// Every six slices are the faces of a cubemap (CubemapArray); only affects DDS and KTX output
+    pub cubemap: bool,
+}
+
+#[wasm_bindgen]
+impl TextureArray {
+    #[wasm_bindgen(constructor)]
+    /// # Arguments
+    ///
+    /// * `format` - The texdec format name of every slice (e.g. "DXT5")
+    /// * `data` - The image data of all slices and mipmaps
+    /// * `width`, `height`, `depth` - The size of the top mipmap and the number of slices
+    /// * `mip_count` - The number of mipmaps
+    /// * `volume` - If this is a 3D texture, whose mipmaps are laid out level by level
+    /// * `platform` - The build target name, used to undo console swizzling
+    pub fn new(format: &str, data: Box<[u8]>, width: usize, height: usize, depth: usize, mip_count: usize,
+               volume: bool, platform: &str) -> Result<TextureArray, ImageError> {
+        if width == 0 || height == 0 || depth == 0 {
+            return Err(ImageError::invalid_dimensions(width, height));
+        }
+        if expected_data_size(format, width, height).is_none() {
+            return Err(ImageError::unknown_format(format));
+        }
+        let texture = TextureArray {
+            format: format.to_string(),
+            platform: platform.to_string(),
+            data: data.into_vec(),
+            width,
+            height,
+            depth,
+            mip_count: max(mip_count, 1),
+            volume,
+            cubemap: false,
+        };
+        let size = texture.chain_size().and_then(|s| if volume { Some(s) } else { s.checked_mul(depth) })
+            .unwrap_or(usize::MAX);
+        if texture.data.len() < size {
+            return Err(ImageError::data_too_short(format, size, texture.data.len()));
+        }
+        Ok(texture)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn format(&self) -> String {
+        self.format.clone()
+    }
+
+    pub fn mip_width(&self, mip: usize) -> usize {
+        max(self.width >> mip, 1)
+    }
+
+    pub fn mip_height(&self, mip: usize) -> usize {
+        max(self.height >> mip, 1)
+    }
+
+    /// The number of slices in a mipmap, which only shrinks for 3D textures.
+    pub fn mip_depth(&self, mip: usize) -> usize {
+        if self.volume { max(self.depth >> mip, 1) } else { self.depth }
+    }
+
+    /// Decodes one slice of a mipmap to RGBA, with rows in the order they are stored.
+    pub fn decode_slice(&self, slice: usize, mip: usize) -> Result<DecodedImage, ImageError> {
+        if mip >= self.mip_count || slice >= self.mip_depth(mip) {
+            return Err(out_of_range());
+        }
+        let offset = self.slice_offset(slice, mip).ok_or_else(out_of_range)?;
+        let (w, h) = (self.mip_width(mip), self.mip_height(mip));
+        let size = self.slice_size(mip).ok_or_else(out_of_range)?;
+        let mut data = self.data[offset..offset + size].to_vec();
+        // arrays have no platform blob, so Switch slices are read as linear
+        let pixels = decode(&self.format, &mut data, w, h, self.platform == "XBox 360", &self.platform, &[])?;
+        Ok(DecodedImage::new(w, h, pixels))
+    }
+
+    /// Decodes one slice of a mipmap and encodes it as a PNG.
+    ///
+    /// # Arguments
+    ///
+    /// * `flip` - Whether the rows are stored bottom first (as in Unity) and should be flipped
+    pub fn encode_slice_png(&self, slice: usize, mip: usize, flip: bool) -> Result<Box<[u8]>, ImageError> {
+        let image = self.decode_slice(slice, mip)?;
+        encode_png(image.width as u32, image.height as u32, image.pixels(), flip)
+    }
+
+    /// Decodes every slice of a mipmap and tiles them into one image, left to right and then down.
+    ///
+    /// # Arguments
+    ///
+    /// * `mip` - The mipmap to tile
+    /// * `columns` - The number of slices per row, or 0 for a roughly square grid
+    /// * `flip` - Whether the rows are stored bottom first (as in Unity); the tiles are then laid out
+    ///   so that flipping the whole atlas puts the first slice at the top left
+    pub fn atlas(&self, mip: usize, columns: usize, flip: bool) -> Result<DecodedImage, ImageError> {
+        let count = self.mip_depth(mip);
+        let columns = if columns == 0 { (count as f64).sqrt().ceil() as usize } else { columns.min(count) };
+        let rows = (count + columns - 1) / columns;
+        let (w, h) = (self.mip_width(mip), self.mip_height(mip));
+        let (atlas_width, atlas_height) = (w * columns, h * rows);
+        let mut out = vec![0u8; rgba_size(atlas_width, atlas_height)?];
+        for slice in 0..count {
+            let image = self.decode_slice(slice, mip)?;
+            let row = if flip { rows - 1 - slice / columns } else { slice / columns };
+            let (x0, y0) = (slice % columns * w, row * h);
+            for y in 0..h {
+                let o = ((y0 + y) * atlas_width + x0) * 4;
+                out[o..o + w * 4].copy_from_slice(&image.pixels()[y * w * 4..(y + 1) * w * 4]);
+            }
+        }
+        Ok(DecodedImage::new(atlas_width, atlas_height, out.into()))
+    }
+
+    /// Decodes every slice and mipmap into an uncompressed RGBA8 DDS file with a DX10 header, as a
+    /// 2D texture array, cubemap array or volume texture.
+    ///
+    /// # Arguments
+    ///
+    /// * `flip` - Whether the rows are stored bottom first (as in Unity); DDS rows are top first
+    pub fn to_dds(&self, flip: bool) -> Result<Box<[u8]>, ImageError> {
+        let mut header = [0u32; 31];
+        header[0] = 124;
+        header[1] = DDS_FLAGS | if self.volume { DDS_FLAG_DEPTH } else { 0 };
+        header[2] = self.height as u32;
+        header[3] = self.width as u32;
+        header[4] = (self.width * 4) as u32;
+        header[5] = if self.volume { self.depth as u32 } else { 0 };
+        header[6] = self.mip_count as u32;
+        // pixel format: a FourCC pointing at the DX10 header
+        header[18] = 32;
+        header[19] = 0x4;
+        header[20] = u32::from_le_bytes(*b"DX10");
+        header[26] = DDS_CAPS;
+        header[27] = if self.volume {
+            DDS_CAPS2_VOLUME
+        } else if self.cubemap {
+            DDS_CAPS2_CUBEMAP_ALL_FACES
+        } else {
+            0
+        };
+        let (dimension, misc, array_size) = if self.volume {
+            (D3D10_RESOURCE_DIMENSION_TEXTURE3D, 0, 1)
+        } else if self.cubemap {
+            (D3D10_RESOURCE_DIMENSION_TEXTURE2D, D3D10_RESOURCE_MISC_TEXTURECUBE, self.depth / 6)
+        } else {
+            (D3D10_RESOURCE_DIMENSION_TEXTURE2D, 0, self.depth)
+        };
+        let dx10 = [DXGI_FORMAT_R8G8B8A8_UNORM, dimension, misc, array_size as u32, 0];
+
+        let mut out = b"DDS ".to_vec();
+        header.iter().chain(dx10.iter()).for_each(|v| out.extend_from_slice(&v.to_le_bytes()));
+        let mut push_slice = |slice: usize, mip: usize| -> Result<(), ImageError> {
+            let image = self.decode_slice(slice, mip)?;
+            push_rows(&mut out, image.pixels(), image.width, flip);
+            Ok(())
+        };
+        if self.volume {
+            for mip in 0..self.mip_count {
+                for slice in 0..self.mip_depth(mip) {
+                    push_slice(slice, mip)?;
+                }
+            }
+        } else {
+            // for cubemap arrays, each element's faces are consecutive slices
+            for slice in 0..self.depth {
+                for mip in 0..self.mip_count {
+                    push_slice(slice, mip)?;
+                }
+            }
+        }
+        Ok(out.into())
+    }
+
+    /// Decodes every slice and mipmap into an uncompressed RGBA8 KTX (version 1) file, as a 2D texture
+    /// array, cubemap array or 3D texture. Rows are written in the order they are stored, which for Unity
+    /// textures is KTX's default bottom-first orientation.
+    pub fn to_ktx(&self) -> Result<Box<[u8]>, ImageError> {
+        let (pixel_depth, elements, faces) = if self.volume {
+            (self.depth, 0, 1)
+        } else if self.cubemap {
+            (0, self.depth / 6, 6)
+        } else {
+            (0, self.depth, 1)
+        };
+        let header = [
+            0x04030201, GL_UNSIGNED_BYTE, 1, GL_RGBA, GL_RGBA8, GL_RGBA, self.width as u32, self.height as u32,
+            pixel_depth as u32, elements as u32, faces, self.mip_count as u32, 0
+        ];
+        let mut out = KTX_IDENTIFIER.to_vec();
+        header.iter().for_each(|v| out.extend_from_slice(&v.to_le_bytes()));
+        for mip in 0..self.mip_count {
+            let count = self.mip_depth(mip);
+            let slice_bytes = self.mip_width(mip) * self.mip_height(mip) * 4;
+            out.extend_from_slice(&((slice_bytes * count) as u32).to_le_bytes());
+            for slice in 0..count {
+                out.extend_from_slice(self.decode_slice(slice, mip)?.pixels());
+            }
+        }
+        Ok(out.into())
+    }
+}
+
+impl TextureArray {
+    /// The number of bytes in one slice of a mipmap.
+    fn slice_size(&self, mip: usize) -> Option<usize> {
+        expected_data_size(&self.format, self.mip_width(mip), self.mip_height(mip))
+    }
+
+    /// The size of the first `mips` mipmaps: of one slice for arrays, or of every slice for 3D textures.
+    fn levels_size(&self, mips: usize) -> Option<usize> {
+        (0..mips).try_fold(0usize, |offset, level| {
+            let level_size = if self.volume {
+                self.slice_size(level)?.checked_mul(self.mip_depth(level))?
+            } else {
+                self.slice_size(level)?
+            };
+            offset.checked_add(level_size)
+        })
+    }
+
+    fn chain_size(&self) -> Option<usize> {
+        self.levels_size(self.mip_count)
+    }
+
+    /// Where a slice of a mipmap starts in the data.
+    fn slice_offset(&self, slice: usize, mip: usize) -> Option<usize> {
+        let slice_stride = if self.volume { self.slice_size(mip)? } else { self.chain_size()? };
+        self.levels_size(mip)?.checked_add(slice.checked_mul(slice_stride)?)
+    }
+}
+
+fn push_rows(out: &mut Vec<u8>, pixels: &[u8], width: usize, flip: bool) {
+    let rows = pixels.chunks_exact(width * 4);
+    if flip {
+        rows.rev().for_each(|row| out.extend_from_slice(row));
+    } else {
+        rows.for_each(|row| out.extend_from_slice(row));
+    }
+}
@@ -1,10 +1,15 @@
 use std::cmp::{max, min};
-use std::io::Write;
 use std::mem::swap;
 use wasm_bindgen::prelude::*;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 extern crate console_error_panic_hook;
 use std::panic;
+use crate::error::{rgba_size, ImageError, ImageErrorKind};
 use crate::fp16::fp16_ieee_to_fp32_value;
+use crate::pvrtc::decode_pvrtc2_image;
+use crate::simd;
+use crate::swizzle::{deswizzle, get_platform_swizzle, untile_ctr_etc};
 use texture2ddecoder;
 use texture2ddecoder::{decode_astc as decode_astc_, decode_atc_rgb4_block, decode_atc_rgba8_block, decode_bc1_block, decode_bc3_block, decode_bc4_block, decode_bc5_block, decode_bc6_block, decode_bc7_block, decode_eacr_block, decode_eacr_signed_block, decode_eacrg_block, decode_eacrg_signed_block, decode_etc1_block, decode_etc2_a8_block, decode_etc2_rgb_block, decode_etc2_rgba1_block, decode_etc2_rgba8_block, decode_pvrtc as decode_pvrtc_};
 use wasm_bindgen_test::console_log;
@@ -33,6 +38,65 @@ pub fn bgr2rgb(data: &mut [u8]) {
     }
 }
 
+#[wasm_bindgen]
+/// Rearranges the channels of RGBA data in place.
+///
+/// `order` holds one character per output channel: "R", "G", "B" or "A" to copy a source channel,
+/// or "0"/"1" for a constant 0 or 255. For example "BGRA" swaps red and blue, "GGG1" shows green
+/// as greyscale and "AAA1" shows alpha as greyscale.
+pub fn swizzle_channels(data: &mut [u8], order: &str) {
+    let mut sources = [4usize; 4];
+    for (i, c) in order.chars().take(4).enumerate() {
+        sources[i] = match c.to_ascii_uppercase() {
+            'R' => 0,
+            'G' => 1,
+            'B' => 2,
+            'A' => 3,
+            '0' => 4,
+            _ => 5
+        };
+    }
+    for i in 0..(data.len() / 4) {
+        let o = i * 4;
+        let px = [data[o], data[o + 1], data[o + 2], data[o + 3], 0, 0xff];
+        for c in 0..4 {
+            data[o + c] = px[sources[c]];
+        }
+    }
+}
+
+#[wasm_bindgen]
+/// Replaces RGBA data with a greyscale view of one channel (0 = R, 1 = G, 2 = B, 3 = A).
+pub fn isolate_channel(data: &mut [u8], channel: usize) {
+    let c = ["RRR1", "GGG1", "BBB1", "AAA1"][channel.min(3)];
+    swizzle_channels(data, c);
+}
+
+#[wasm_bindgen]
+pub fn alpha_to_greyscale(data: &mut [u8]) {
+    swizzle_channels(data, "AAA1");
+}
+
+#[wasm_bindgen]
+/// Blends RGBA data over a grey checkerboard in place, leaving it fully opaque.
+pub fn composite_checkerboard(data: &mut [u8], width: usize, height: usize, cell_size: usize) {
+    let cell = max(1, cell_size);
+    for y in 0..height {
+        for x in 0..width {
+            let o = (y * width + x) * 4;
+            if o + 4 > data.len() {
+                return;
+            }
+            let bg: u32 = if ((x / cell) + (y / cell)) % 2 == 0 { 0xcc } else { 0x99 };
+            let a = data[o + 3] as u32;
+            for c in 0..3 {
+                data[o + c] = ((data[o + c] as u32 * a + bg * (255 - a) + 127) / 255) as u8;
+            }
+            data[o + 3] = 0xff;
+        }
+    }
+}
+
 pub fn copy_block_buffer(
     bx: usize,
     by: usize,
@@ -59,307 +123,389 @@ pub fn copy_block_buffer(
     }
 }
 
-fn encode_data(data: Vec<u32>) -> Vec<u8> {
-    let mut outdata = Vec::new();
-    for i in 0..data.len() {
-        outdata.write_all(&data[i as usize].to_le_bytes()).expect("dxt1");
+/// Runs a decoder that produces little-endian `u32` pixels, writing straight into `out` when it is suitably aligned.
+fn decode_u32_into<R>(out: &mut [u8], pixels: usize, func: impl FnOnce(&mut [u32]) -> R) -> R {
+    let out = &mut out[..pixels * 4];
+    let (head, body, _) = unsafe { out.align_to_mut::<u32>() };
+    if cfg!(target_endian = "little") && head.is_empty() && body.len() == pixels {
+        return func(body);
     }
-    outdata
+    let mut buf = vec![0u32; pixels];
+    let result = func(&mut buf);
+    for (i, v) in buf.iter().enumerate() {
+        out[i * 4..i * 4 + 4].copy_from_slice(&v.to_le_bytes());
+    }
+    result
 }
 
-#[wasm_bindgen]
-pub fn decode_a8(data: &mut [u8]) -> Box<[u8]> {
-    let mut out = Vec::new();
-    for i in 0..data.len() {
-        out.write_all(&[0, 0, 0, data[i]]).expect("a8");
+/// Checks that `data` holds a whole `width` x `height` image in `format`, then allocates an RGBA buffer
+/// and fills it with `func`.
+fn decode_alloc(format: &str, data: &[u8], width: usize, height: usize, func: impl FnOnce(&mut [u8]))
+                -> Result<Box<[u8]>, ImageError> {
+    try_decode_alloc(format, data, width, height, |out| {
+        func(out);
+        Ok(())
+    })
+}
+
+/// Like `decode_alloc`, for decoders that can reject the data itself.
+fn try_decode_alloc(format: &str, data: &[u8], width: usize, height: usize,
+                    func: impl FnOnce(&mut [u8]) -> Result<(), ImageError>) -> Result<Box<[u8]>, ImageError> {
+    check_data_size(format, data, width, height)?;
+    let mut out = vec![0u8; rgba_size(width, height)?];
+    func(&mut out)?;
+    Ok(out.into())
+}
+
+fn flip_v_in_place(data: &mut [u8], width: usize, height: usize) {
+    let row = width * 4;
+    for y in 0..height / 2 {
+        let (top, bottom) = data.split_at_mut((height - y - 1) * row);
+        top[y * row..(y + 1) * row].swap_with_slice(&mut bottom[..row]);
+    }
+}
+
+/// Calls `func` for every horizontal strip of `row_size` bytes in `out`, on the thread pool when the
+/// `parallel` feature is enabled.
+fn for_each_strip(out: &mut [u8], row_size: usize, func: impl Fn(usize, &mut [u8]) + Send + Sync) {
+    let _ = try_for_each_strip(out, row_size, |i, strip| {
+        func(i, strip);
+        Ok(())
+    });
+}
+
+/// Like `for_each_strip`, stopping at the first strip that fails.
+fn try_for_each_strip(out: &mut [u8], row_size: usize, func: impl Fn(usize, &mut [u8]) -> Result<(), ImageError> + Send + Sync)
+                      -> Result<(), ImageError> {
+    #[cfg(feature = "parallel")]
+    let strips = out.par_chunks_mut(row_size);
+    #[cfg(not(feature = "parallel"))]
+    let strips = out.chunks_mut(row_size);
+    strips.enumerate().try_for_each(|(i, strip)| func(i, strip))
+}
+
+pub fn decode_a8_into(data: &[u8], out: &mut [u8]) {
+    for i in 0..min(data.len(), out.len() / 4) {
+        out[i * 4..i * 4 + 4].copy_from_slice(&[0, 0, 0, data[i]]);
     }
-    out.into()
 }
 
 #[wasm_bindgen]
-pub fn decode_argb4444(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    let mut out = Vec::new();
-    for i in 0..(width * height) {
-        out.write_all(&[
-            (data[(i * 2) as usize] & 0xf0) >> 4,
-            data[(i * 2 + 1) as usize] & 0x0f,
-            (data[(i * 2 + 1) as usize] & 0xf0) >> 4,
-            data[(i * 2) as usize] & 0x0f
-        ]).expect("argb4444");
+pub fn decode_a8(data: &mut [u8]) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("Alpha8", data, data.len(), 1, |out| decode_a8_into(data, out))
+}
+
+pub fn decode_argb4444_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
+    for i in simd::argb4444_to_rgba(data, out, width * height)..(width * height) {
+        out[i * 4] = (data[i * 2] & 0xf0) >> 4;
+        out[i * 4 + 1] = data[i * 2 + 1] & 0x0f;
+        out[i * 4 + 2] = (data[i * 2 + 1] & 0xf0) >> 4;
+        out[i * 4 + 3] = data[i * 2] & 0x0f;
     }
-    out.into()
 }
 
 #[wasm_bindgen]
-pub fn decode_rgb24(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    let mut out = Vec::new();
+pub fn decode_argb4444(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("ARGB4444", data, width, height, |out| decode_argb4444_into(data, out, width, height))
+}
+
+pub fn decode_rgb24_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
     for i in 0..(width * height) {
-        out.write_all(&[
-            data[(i * 3) as usize],
-            data[(i * 3 + 1) as usize],
-            data[(i * 3 + 2) as usize],
+        out[i * 4..i * 4 + 4].copy_from_slice(&[
+            data[i * 3],
+            data[i * 3 + 1],
+            data[i * 3 + 2],
             0xff
-        ]).expect("rgb24");
+        ]);
     }
-    out.into()
 }
 
 #[wasm_bindgen]
-pub fn decode_argb32(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    let mut out = Vec::new();
+pub fn decode_rgb24(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("RGB24", data, width, height, |out| decode_rgb24_into(data, out, width, height))
+}
+
+pub fn decode_argb32_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
     for i in 0..(width * height) {
-        out.write_all(&[
-            data[(i * 4 + 3) as usize],
-            data[(i * 4) as usize],
-            data[(i * 4 + 1) as usize],
-            data[(i * 4 + 2) as usize]
-        ]).expect("argb32");
+        out[i * 4..i * 4 + 4].copy_from_slice(&[
+            data[i * 4 + 3],
+            data[i * 4],
+            data[i * 4 + 1],
+            data[i * 4 + 2]
+        ]);
     }
-    out.into()
 }
 
 #[wasm_bindgen]
-pub fn decode_rgb565(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    let mut out = Vec::new();
-    for i in 0..(width * height) {
-        let d: u32 = ((data[(i * 2 + 1) as usize] as u32) << 8) | (data[(i * 2) as usize] as u32);
-        out.write_all(&[
-            ((d >> 8 & 0xf8) | (d >> 13)) as u8,
-            ((d >> 3 & 0xfc) | (d >> 9 & 3)) as u8,
-            ((d << 3) | (d >> 2 & 7)) as u8,
-            0xff
-        ]).expect("rgb565");
+pub fn decode_argb32(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("ARGB32", data, width, height, |out| decode_argb32_into(data, out, width, height))
+}
+
+pub fn decode_rgb565_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
+    for i in simd::rgb565_to_rgba(data, out, width * height)..(width * height) {
+        let d: u32 = ((data[i * 2 + 1] as u32) << 8) | (data[i * 2] as u32);
+        out[i * 4] = ((d >> 8 & 0xf8) | (d >> 13)) as u8;
+        out[i * 4 + 1] = ((d >> 3 & 0xfc) | (d >> 9 & 3)) as u8;
+        out[i * 4 + 2] = ((d << 3) | (d >> 2 & 7)) as u8;
+        out[i * 4 + 3] = 0xff;
     }
-    out.into()
 }
 
 #[wasm_bindgen]
-pub fn decode_bgr565(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    let mut outdata = decode_rgb565(data, width, height);
-    bgr2rgb(&mut outdata);
-    outdata
+pub fn decode_rgb565(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("RGB565", data, width, height, |out| decode_rgb565_into(data, out, width, height))
+}
+
+pub fn decode_bgr565_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
+    decode_rgb565_into(data, out, width, height);
+    bgr2rgb(&mut out[..width * height * 4]);
 }
 
 #[wasm_bindgen]
-pub fn decode_r16(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    let mut out = Vec::new();
-    for i in 0..(width * height) as usize {
-        out.write_all(&[
+pub fn decode_bgr565(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("BGR565", data, width, height, |out| decode_bgr565_into(data, out, width, height))
+}
+
+pub fn decode_r16_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
+    for i in 0..(width * height) {
+        out[i * 4..i * 4 + 4].copy_from_slice(&[
             (((((data[i * 2 + 1] as u32) << 8) | (data[i * 2] as u32)) as u32 * 255 + 32895) >> 16) as u8,
             0,
             0,
             0xff
-        ]).expect("r16");
+        ]);
     }
-    out.into()
 }
 
 #[wasm_bindgen]
-pub fn decode_rgba4444(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    let mut out = Vec::new();
+pub fn decode_r16(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("R16", data, width, height, |out| decode_r16_into(data, out, width, height))
+}
+
+pub fn decode_rgba4444_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
     for i in 0..(width * height) {
-        out.write_all(&[
-            data[(i * 2) as usize] & 0x0f,
-            (data[(i * 2) as usize] & 0xf0) >> 4,
-            data[(i * 2 + 1) as usize] & 0x0f,
-            (data[(i * 2 + 1) as usize] & 0xf0) >> 4
-        ]).expect("rgba4444");
+        out[i * 4..i * 4 + 4].copy_from_slice(&[
+            data[i * 2] & 0x0f,
+            (data[i * 2] & 0xf0) >> 4,
+            data[i * 2 + 1] & 0x0f,
+            (data[i * 2 + 1] & 0xf0) >> 4
+        ]);
     }
-    out.into()
 }
 
 #[wasm_bindgen]
-pub fn decode_bgra4444(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    let mut outdata = decode_rgba4444(data, width, height);
-    bgr2rgb(&mut outdata);
-    outdata
+pub fn decode_rgba4444(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("RGBA4444", data, width, height, |out| decode_rgba4444_into(data, out, width, height))
+}
+
+pub fn decode_bgra4444_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
+    decode_rgba4444_into(data, out, width, height);
+    bgr2rgb(&mut out[..width * height * 4]);
 }
 
 #[wasm_bindgen]
-pub fn decode_rgba5551(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    let mut out = Vec::new();
+pub fn decode_bgra4444(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("BGRA4444", data, width, height, |out| decode_bgra4444_into(data, out, width, height))
+}
+
+pub fn decode_rgba5551_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
     for i in 0..(width * height) {
         let color = u16::from_le_bytes([data[i * 2], data[i * 2 + 1]]);
-        out.write_all(&[
+        out[i * 4..i * 4 + 4].copy_from_slice(&[
             ((color & 0x001F) << 3) as u8,
             ((color & 0x03E0) >> 2) as u8,
             ((color & 0x7C00) >> 7) as u8,
             (if (color & 0x8000) == 0x8000 {0xff} else {0x00}) as u8
-        ]).expect("rgba5551");
+        ]);
     }
-    out.into()
 }
 
 #[wasm_bindgen]
-pub fn decode_bgra5551(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    let mut out = Vec::new();
+pub fn decode_rgba5551(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("RGBA5551", data, width, height, |out| decode_rgba5551_into(data, out, width, height))
+}
+
+pub fn decode_bgra5551_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
     for i in 0..(width * height) {
         let color = u16::from_le_bytes([data[i * 2], data[i * 2 + 1]]);
-        out.write_all(&[
+        out[i * 4..i * 4 + 4].copy_from_slice(&[
             ((color & 0x7C00) >> 7) as u8,
             ((color & 0x03E0) >> 2) as u8,
             ((color & 0x001F) << 3) as u8,
             (if (color & 0x8000) == 0x8000 {0xff} else {0x00}) as u8
-        ]).expect("bgra5551");
+        ]);
     }
-    out.into()
 }
 
 #[wasm_bindgen]
-pub fn decode_rgba1010102(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    let mut out = Vec::new();
+pub fn decode_bgra5551(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("BGRA5551", data, width, height, |out| decode_bgra5551_into(data, out, width, height))
+}
+
+pub fn decode_rgba1010102_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
     for i in 0..(width * height) {
         let color = u32::from_le_bytes([data[i * 4], data[i * 4 + 1], data[i * 4 + 2], data[i * 4 + 3]]);
-        out.write_all(&[
+        out[i * 4..i * 4 + 4].copy_from_slice(&[
             ((color & 0x3FF00000) >> 20) as u8,
             ((color & 0x000FFC00) >> 10) as u8,
             (color & 0x000003FF) as u8,
             (color & 0xC0000000) as u8
-        ]).expect("rgba1010102");
+        ]);
     }
-    out.into()
 }
 
-
-
 #[wasm_bindgen]
-pub fn decode_bgra32(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    let mut out = Vec::new();
-    for i in 0..(width * height) as usize {
-        out.write_all(&[
+pub fn decode_rgba1010102(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("RGBA1010102", data, width, height, |out| decode_rgba1010102_into(data, out, width, height))
+}
+
+pub fn decode_bgra32_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
+    for i in 0..(width * height) {
+        out[i * 4..i * 4 + 4].copy_from_slice(&[
             data[i * 4 + 2],
             data[i * 4 + 1],
             data[i * 4],
             data[i * 4 + 3]
-        ]).expect("bgra32");
+        ]);
     }
-    out.into()
 }
 
 #[wasm_bindgen]
-pub fn decode_rhalf(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    let mut out = Vec::new();
+pub fn decode_bgra32(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("BGRA32", data, width, height, |out| decode_bgra32_into(data, out, width, height))
+}
+
+pub fn decode_rhalf_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
     for i in 0..(width * height) {
         let d = ((data[i * 2 + 1] as u16) << 8) | (data[i * 2] as u16);
-        out.write_all(&[
+        out[i * 4..i * 4 + 4].copy_from_slice(&[
             (fp16_ieee_to_fp32_value(d) * 255.0) as u8,
             0,
             0,
             0xff
-        ]).expect("rhalf");
+        ]);
     }
-    out.into()
 }
 
 #[wasm_bindgen]
-pub fn decode_rghalf(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    let mut out = Vec::new();
+pub fn decode_rhalf(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("RHalf", data, width, height, |out| decode_rhalf_into(data, out, width, height))
+}
+
+pub fn decode_rghalf_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
     for i in 0..(width * height) {
         let r = ((data[i * 4 + 1] as u16) << 8) | (data[i * 4] as u16);
         let g = ((data[i * 4 + 3] as u16) << 8) | (data[i * 4 + 2] as u16);
-        out.write_all(&[
+        out[i * 4..i * 4 + 4].copy_from_slice(&[
             (fp16_ieee_to_fp32_value(r) * 255.0) as u8,
             (fp16_ieee_to_fp32_value(g) * 255.0) as u8,
             0,
             0xff
-        ]).expect("rghalf");
+        ]);
     }
-    out.into()
 }
 
 #[wasm_bindgen]
-pub fn decode_rgbhalf(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    let mut out = Vec::new();
+pub fn decode_rghalf(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("RGHalf", data, width, height, |out| decode_rghalf_into(data, out, width, height))
+}
+
+pub fn decode_rgbhalf_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
     for i in 0..(width * height) {
         let r = ((data[i * 6 + 1] as u16) << 8) | (data[i * 6] as u16);
         let g = ((data[i * 6 + 3] as u16) << 8) | (data[i * 6 + 2] as u16);
         let b = ((data[i * 6 + 5] as u16) << 8) | (data[i * 6 + 4] as u16);
-        out.write_all(&[
+        out[i * 4..i * 4 + 4].copy_from_slice(&[
             (fp16_ieee_to_fp32_value(r) * 255.0) as u8,
             (fp16_ieee_to_fp32_value(g) * 255.0) as u8,
             (fp16_ieee_to_fp32_value(b) * 255.0) as u8,
-        ]).expect("rgbhalf");
+            0xff
+        ]);
     }
-    out.into()
 }
 
 #[wasm_bindgen]
-pub fn decode_rgbahalf(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    let mut out = Vec::new();
-    for i in 0..(width * height) {
-        let r = ((data[i * 8 + 1] as u16) << 8) | (data[i * 8] as u16);
-        let g = ((data[i * 8 + 3] as u16) << 8) | (data[i * 8 + 2] as u16);
-        let b = ((data[i * 8 + 5] as u16) << 8) | (data[i * 8 + 4] as u16);
-        let a = ((data[i * 8 + 7] as u16) << 8) | (data[i * 8 + 6] as u16);
-        out.write_all(&[
-            (fp16_ieee_to_fp32_value(r) * 255.0) as u8,
-            (fp16_ieee_to_fp32_value(g) * 255.0) as u8,
-            (fp16_ieee_to_fp32_value(b) * 255.0) as u8,
-            (fp16_ieee_to_fp32_value(a) * 255.0) as u8,
-        ]).expect("rgbahalf");
+pub fn decode_rgbhalf(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("RGBHalf", data, width, height, |out| decode_rgbhalf_into(data, out, width, height))
+}
+
+pub fn decode_rgbahalf_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
+    for i in simd::rgbahalf_to_rgba(data, out, width * height)..(width * height) {
+        for c in 0..4 {
+            let d = ((data[i * 8 + c * 2 + 1] as u16) << 8) | (data[i * 8 + c * 2] as u16);
+            out[i * 4 + c] = (fp16_ieee_to_fp32_value(d) * 255.0) as u8;
+        }
     }
-    out.into()
 }
 
 #[wasm_bindgen]
-pub fn decode_rfloat(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    let mut out = Vec::new();
+pub fn decode_rgbahalf(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("RGBAHalf", data, width, height, |out| decode_rgbahalf_into(data, out, width, height))
+}
+
+pub fn decode_rfloat_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
     for i in 0..(width * height) {
-        out.write_all(&[
+        out[i * 4..i * 4 + 4].copy_from_slice(&[
             f32::from_le_bytes([data[i * 4], data[i * 4 + 1], data[i * 4 + 2], data[i * 4 + 3]]) as u8,
             0,
             0,
             0xff
-        ]).expect("rfloat");
+        ]);
     }
-    out.into()
 }
 
 #[wasm_bindgen]
-pub fn decode_rgfloat(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    let mut out = Vec::new();
+pub fn decode_rfloat(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("RFloat", data, width, height, |out| decode_rfloat_into(data, out, width, height))
+}
+
+pub fn decode_rgfloat_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
     for i in 0..(width * height) {
-        out.write_all(&[
+        out[i * 4..i * 4 + 4].copy_from_slice(&[
             f32::from_le_bytes([data[i * 8], data[i * 8 + 1], data[i * 8 + 2], data[i * 8 + 3]]) as u8,
             f32::from_le_bytes([data[i * 8 + 4], data[i * 8 + 5], data[i * 8 + 6], data[i * 8 + 7]]) as u8,
             0,
             0xff
-        ]).expect("rgfloat");
+        ]);
     }
-    out.into()
 }
 
 #[wasm_bindgen]
-pub fn decode_rgbfloat(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    let mut out = Vec::new();
+pub fn decode_rgfloat(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("RGFloat", data, width, height, |out| decode_rgfloat_into(data, out, width, height))
+}
+
+pub fn decode_rgbfloat_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
     for i in 0..(width * height) {
-        out.write_all(&[
+        out[i * 4..i * 4 + 4].copy_from_slice(&[
             f32::from_le_bytes([data[i * 12], data[i * 12 + 1], data[i * 12 + 2], data[i * 12 + 3]]) as u8,
             f32::from_le_bytes([data[i * 12 + 4], data[i * 12 + 5], data[i * 12 + 6], data[i * 12 + 7]]) as u8,
             f32::from_le_bytes([data[i * 12 + 8], data[i * 12 + 9], data[i * 12 + 10], data[i * 12 + 11]]) as u8,
-        ]).expect("rgbfloat");
+            0xff
+        ]);
     }
-    out.into()
 }
 
 #[wasm_bindgen]
-pub fn decode_rgbafloat(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    let mut out = Vec::new();
-    for i in 0..(width * height) {
-        out.write_all(&[
-            f32::from_le_bytes([data[i * 16], data[i * 16 + 1], data[i * 16 + 2], data[i * 16 + 3]]) as u8,
-            f32::from_le_bytes([data[i * 16 + 4], data[i * 16 + 5], data[i * 16 + 6], data[i * 16 + 7]]) as u8,
-            f32::from_le_bytes([data[i * 16 + 8], data[i * 16 + 9], data[i * 16 + 10], data[i * 16 + 11]]) as u8,
-            f32::from_le_bytes([data[i * 16 + 12], data[i * 16 + 13], data[i * 16 + 14], data[i * 16 + 15]]) as u8,
-        ]).expect("rgbafloat");
+pub fn decode_rgbfloat(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("RGBFloat", data, width, height, |out| decode_rgbfloat_into(data, out, width, height))
+}
+
+pub fn decode_rgbafloat_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
+    for i in simd::rgbafloat_to_rgba(data, out, width * height)..(width * height) {
+        for c in 0..4 {
+            let o = i * 16 + c * 4;
+            out[i * 4 + c] = f32::from_le_bytes([data[o], data[o + 1], data[o + 2], data[o + 3]]) as u8;
+        }
     }
-    out.into()
 }
 
 #[wasm_bindgen]
-pub fn decode_yuy2(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    let mut out = Vec::new();
-    out.resize(width * height * 4, 0);
+pub fn decode_rgbafloat(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("RGBAFloat", data, width, height, |out| decode_rgbafloat_into(data, out, width, height))
+}
+
+pub fn decode_yuy2_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
     let mut p = 0;
     let mut o = 0;
     for _ in 0..height {
@@ -391,13 +537,14 @@ pub fn decode_yuy2(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
             o += 4;
         }
     }
-    out.into()
 }
 
 #[wasm_bindgen]
-pub fn decode_rgb9e5float(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    let mut out = Vec::new();
-    out.resize(width * height * 4, 0);
+pub fn decode_yuy2(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("YUY2", data, width, height, |out| decode_yuy2_into(data, out, width, height))
+}
+
+pub fn decode_rgb9e5float_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
     for i in 0..(width * height) {
         let n = u32::from_le_bytes([data[i * 4], data[i * 4 + 1], data[i * 4 + 2], data[i * 4 + 3]]);
         let scale = n >> 27 & 0x1f;
@@ -410,121 +557,251 @@ pub fn decode_rgb9e5float(data: &mut [u8], width: usize, height: usize) -> Box<[
         out[i * 4 + 2] = (b * scalef * 255.0).floor() as u8;
         out[i * 4 + 3] = 0xff;
     }
-    out.into()
 }
 
 #[wasm_bindgen]
-pub fn decode_rg16(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    let mut out = Vec::new();
-    out.resize(width * height * 4, 0);
+pub fn decode_rgb9e5float(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("RGB9e5Float", data, width, height, |out| decode_rgb9e5float_into(data, out, width, height))
+}
+
+pub fn decode_rg16_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
     for i in 0..(width * height) {
         out[i * 4] = data[i * 2];
         out[i * 4 + 1] = data[i * 2 + 1];
         out[i * 4 + 2] = 0;
         out[i * 4 + 3] = 0xff;
     }
-    out.into()
 }
 
 #[wasm_bindgen]
-pub fn decode_r8(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    let mut out = Vec::new();
-    out.resize(width * height * 4, 0);
+pub fn decode_rg16(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("RG16", data, width, height, |out| decode_rg16_into(data, out, width, height))
+}
+
+pub fn decode_r8_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
     for i in 0..(width * height) {
         out[i * 4] = data[i];
         out[i * 4 + 1] = 0;
         out[i * 4 + 2] = 0;
         out[i * 4 + 3] = 0xff;
     }
-    out.into()
 }
 
 #[wasm_bindgen]
-pub fn decode_l8(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    let mut out = Vec::new();
-    out.resize(width * height * 4, 0);
+pub fn decode_r8(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("R8", data, width, height, |out| decode_r8_into(data, out, width, height))
+}
+
+pub fn decode_l8_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
     for i in 0..(width * height) {
         out[i * 4] = data[i];
         out[i * 4 + 1] = data[i];
         out[i * 4 + 2] = data[i];
         out[i * 4 + 3] = 0xff;
     }
-    out.into()
 }
 
 #[wasm_bindgen]
-pub fn decode_la16(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    let mut out = Vec::new();
-    out.resize(width * height * 4, 0);
+pub fn decode_l8(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("L8", data, width, height, |out| decode_l8_into(data, out, width, height))
+}
+
+pub fn decode_la16_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
     for i in 0..(width * height) {
         out[i * 4] = data[i * 2];
         out[i * 4 + 1] = data[i * 2];
         out[i * 4 + 2] = data[i * 2];
         out[i * 4 + 3] = data[i * 2 + 1];
     }
-    out.into()
 }
 
 #[wasm_bindgen]
-pub fn decode_rg32(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    let mut out = Vec::new();
+pub fn decode_la16(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("LA16", data, width, height, |out| decode_la16_into(data, out, width, height))
+}
+
+pub fn decode_rg32_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
     for i in 0..(width * height) {
-        out.write_all(&[
+        out[i * 4..i * 4 + 4].copy_from_slice(&[
             (((((data[i * 4 + 1] as u32) << 8) | (data[i * 4] as u32)) as u32 * 255 + 32895) >> 16) as u8,
             (((((data[i * 4 + 3] as u32) << 8) | (data[i * 4 + 2] as u32)) as u32 * 255 + 32895) >> 16) as u8,
             0,
             0xff
-        ]).expect("rg32");
+        ]);
     }
-    out.into()
 }
 
 #[wasm_bindgen]
-pub fn decode_rgb48(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    let mut out = Vec::new();
+pub fn decode_rg32(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("RG32", data, width, height, |out| decode_rg32_into(data, out, width, height))
+}
+
+pub fn decode_rgb48_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
     for i in 0..(width * height) {
-        out.write_all(&[
+        out[i * 4..i * 4 + 4].copy_from_slice(&[
             (((((data[i * 6 + 1] as u32) << 8) | (data[i * 6] as u32)) as u32 * 255 + 32895) >> 16) as u8,
             (((((data[i * 6 + 3] as u32) << 8) | (data[i * 6 + 2] as u32)) as u32 * 255 + 32895) >> 16) as u8,
             (((((data[i * 6 + 5] as u32) << 8) | (data[i * 6 + 4] as u32)) as u32 * 255 + 32895) >> 16) as u8,
             0xff
-        ]).expect("r16");
+        ]);
     }
-    out.into()
 }
 
 #[wasm_bindgen]
-pub fn decode_rgba64(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    let mut out = Vec::new();
+pub fn decode_rgb48(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("RGB48", data, width, height, |out| decode_rgb48_into(data, out, width, height))
+}
+
+pub fn decode_rgba64_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
     for i in 0..(width * height) {
-        out.write_all(&[
+        out[i * 4..i * 4 + 4].copy_from_slice(&[
             (((((data[i * 8 + 1] as u32) << 8) | (data[i * 8] as u32)) as u32 * 255 + 32895) >> 16) as u8,
             (((((data[i * 8 + 3] as u32) << 8) | (data[i * 8 + 2] as u32)) as u32 * 255 + 32895) >> 16) as u8,
             (((((data[i * 8 + 5] as u32) << 8) | (data[i * 8 + 4] as u32)) as u32 * 255 + 32895) >> 16) as u8,
             (((((data[i * 8 + 7] as u32) << 8) | (data[i * 8 + 6] as u32)) as u32 * 255 + 32895) >> 16) as u8,
-        ]).expect("r16");
+        ]);
     }
-    out.into()
 }
 
-fn decode_generic_blocky(data: &mut [u8], width: usize, height: usize, func: impl Fn(&[u8], &mut [u32]), stride: usize) -> Box<[u8]> {
-    let mut i = 0;
-    let mut out: Vec<u32> = Vec::new();
-    out.resize((width * height) as usize, 0);
-    for by in 0..(((height + 3) / 4) as usize) {
-        for bx in 0..(((width + 3) / 4) as usize) {
-            let mut outblk = [0u32; 16];
-            func(&data[i..i + stride], &mut outblk);
-            copy_block_buffer(bx, by, width, height, 4, 4, &outblk, &mut out);
-            i += stride;
+#[wasm_bindgen]
+pub fn decode_rgba64(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("RGBA64", data, width, height, |out| decode_rgba64_into(data, out, width, height))
+}
+
+/// Maps a signed normalised 8-bit value from [-1, 1] to [0, 255].
+fn snorm8_to_unorm8(v: u8) -> u8 {
+    let s = max(v as i8 as i32, -127);
+    (((s + 127) * 255 + 127) / 254) as u8
+}
+
+/// Maps a signed normalised 16-bit value from [-1, 1] to [0, 255].
+fn snorm16_to_unorm8(v: u16) -> u8 {
+    let s = max(v as i16 as i32, -32767);
+    (((s + 32767) * 255 + 32767) / 65534) as u8
+}
+
+/// Decodes signed normalised formats with `channels` channels of 8 or 16 bits (`wide`). Missing colour
+/// channels are 0 and missing alpha is opaque.
+pub fn decode_snorm_into(data: &[u8], out: &mut [u8], width: usize, height: usize, channels: usize, wide: bool) {
+    let size = if wide { 2 } else { 1 };
+    for i in 0..(width * height) {
+        let mut px = [0, 0, 0, 0xff];
+        for (c, v) in px.iter_mut().enumerate().take(channels) {
+            let o = (i * channels + c) * size;
+            *v = if wide {
+                snorm16_to_unorm8(((data[o + 1] as u16) << 8) | (data[o] as u16))
+            } else {
+                snorm8_to_unorm8(data[o])
+            };
         }
+        out[i * 4..i * 4 + 4].copy_from_slice(&px);
     }
-    encode_data(out).into()
 }
 
 #[wasm_bindgen]
-pub fn decode_dxt1(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    decode_generic_blocky(data, width, height, decode_bc1_block, 8)
+pub fn decode_r16_signed(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("R16_SIGNED", data, width, height, |out| decode_snorm_into(data, out, width, height, 1, true))
+}
+
+#[wasm_bindgen]
+pub fn decode_rg16_signed(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("RG16_SIGNED", data, width, height, |out| decode_snorm_into(data, out, width, height, 2, false))
+}
+
+#[wasm_bindgen]
+pub fn decode_rgba64_signed(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("RGBA64_SIGNED", data, width, height, |out| decode_snorm_into(data, out, width, height, 4, true))
+}
+
+/// Unsigned float with a 5-bit exponent and `mantissa_bits` of mantissa, as packed into B10G11R11.
+fn small_ufloat_to_f32(v: u32, mantissa_bits: u32) -> f32 {
+    let exponent = (v >> mantissa_bits & 0x1f) as i32;
+    let mantissa = (v & ((1 << mantissa_bits) - 1)) as f32 / (1 << mantissa_bits) as f32;
+    match exponent {
+        0 => mantissa * (-14f32).exp2(),
+        31 => if mantissa == 0.0 { f32::INFINITY } else { 0.0 },
+        _ => (1.0 + mantissa) * ((exponent - 15) as f32).exp2()
+    }
+}
+
+pub fn decode_b10g11r11_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
+    for i in 0..(width * height) {
+        let d = u32::from_le_bytes([data[i * 4], data[i * 4 + 1], data[i * 4 + 2], data[i * 4 + 3]]);
+        out[i * 4..i * 4 + 4].copy_from_slice(&[
+            (small_ufloat_to_f32(d & 0x7ff, 6) * 255.0) as u8,
+            (small_ufloat_to_f32(d >> 11 & 0x7ff, 6) * 255.0) as u8,
+            (small_ufloat_to_f32(d >> 22, 5) * 255.0) as u8,
+            0xff
+        ]);
+    }
+}
+
+#[wasm_bindgen]
+pub fn decode_b10g11r11(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("B10G11R11_UFloat", data, width, height, |out| decode_b10g11r11_into(data, out, width, height))
+}
+
+/// Looks every index up in an RGBA `palette`. Indices past the end of the palette are transparent black.
+pub fn decode_p8_into(data: &[u8], palette: &[u8], out: &mut [u8], width: usize, height: usize) {
+    for i in 0..(width * height) {
+        let entry = data[i] as usize * 4;
+        let px = palette.get(entry..entry + 4).unwrap_or(&[0, 0, 0, 0]);
+        out[i * 4..i * 4 + 4].copy_from_slice(px);
+    }
+}
+
+#[wasm_bindgen]
+/// Decodes 8-bit palettised data, as used by old XNA and Godot assets.
+///
+/// The palette isn't part of the pixel data, so P8 has no entry in `decode`: containers store it
+/// separately (Godot 2's `FORMAT_INDEXED_ALPHA` images keep 256 RGBA entries after the indices) and
+/// the caller passes it in.
+///
+/// # Arguments
+///
+/// * `data` - One palette index per pixel
+/// * `palette` - Up to 256 RGBA entries
+/// * `width`, `height` - The image size
+pub fn decode_p8(data: &[u8], palette: &[u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    let size = rgba_size(width, height)?;
+    if data.len() < width * height {
+        return Err(ImageError::data_too_short("P8", width * height, data.len()));
+    }
+    let mut out = vec![0u8; size];
+    decode_p8_into(data, palette, &mut out, width, height);
+    Ok(out.into())
+}
+
+fn decode_generic_blocky(data: &[u8], out: &mut [u8], width: usize, height: usize, func: impl Fn(&[u8], &mut [u32]) + Send + Sync, stride: usize) {
+    if width == 0 || height == 0 {
+        return;
+    }
+    let blocks_x = (width + 3) / 4;
+    // each strip is one row of blocks, so the strips can be decoded independently
+    for_each_strip(&mut out[..width * height * 4], width * 4 * 4, |by, strip| {
+        let rows = strip.len() / (width * 4);
+        let mut outblk = [0u32; 16];
+        for bx in 0..blocks_x {
+            let i = (by * blocks_x + bx) * stride;
+            func(&data[i..i + stride], &mut outblk);
+            let copy_width = min(4, width - bx * 4);
+            for y in 0..rows {
+                for x in 0..copy_width {
+                    let o = (y * width + bx * 4 + x) * 4;
+                    strip[o..o + 4].copy_from_slice(&outblk[y * 4 + x].to_le_bytes());
+                }
+            }
+        }
+    });
+}
+
+pub fn decode_dxt1_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
+    decode_generic_blocky(data, out, width, height, decode_bc1_block, 8)
+}
+
+#[wasm_bindgen]
+pub fn decode_dxt1(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("DXT1", data, width, height, |out| decode_dxt1_into(data, out, width, height))
 }
 
 fn decode_bc2_block(data: &[u8], outbuf: &mut [u32]) {
@@ -549,108 +826,315 @@ fn decode_bc2_block(data: &[u8], outbuf: &mut [u32]) {
     }
 }
 
+pub fn decode_dxt3_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
+    decode_generic_blocky(data, out, width, height, decode_bc2_block, 16)
+}
+
+#[wasm_bindgen]
+pub fn decode_dxt3(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("DXT3", data, width, height, |out| decode_dxt3_into(data, out, width, height))
+}
+
+pub fn decode_dxt5_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
+    decode_generic_blocky(data, out, width, height, decode_bc3_block, 16)
+}
+
+#[wasm_bindgen]
+pub fn decode_dxt5(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("DXT5", data, width, height, |out| decode_dxt5_into(data, out, width, height))
+}
+
+pub fn decode_pvrtc_into(data: &[u8], out: &mut [u8], width: usize, height: usize, is2bpp: bool) -> Result<(), ImageError> {
+    let format = if is2bpp { "PVRTC_RGBA2" } else { "PVRTC_RGBA4" };
+    decode_u32_into(out, width * height, |pixels| {
+        decode_pvrtc_(data, width, height, pixels, is2bpp).map_err(|_| ImageError::invalid_data(format))
+    })
+}
+
+#[wasm_bindgen]
+pub fn decode_pvrtc(data: &mut [u8], width: usize, height: usize, is2bpp: bool) -> Result<Box<[u8]>, ImageError> {
+    let format = if is2bpp { "PVRTC_RGBA2" } else { "PVRTC_RGBA4" };
+    try_decode_alloc(format, data, width, height, |out| decode_pvrtc_into(data, out, width, height, is2bpp))
+}
+
+/// Decodes PVRTC2. Hard transition blocks aren't supported and make this fail with `InvalidData`.
+pub fn decode_pvrtc2_into(data: &[u8], out: &mut [u8], width: usize, height: usize, is2bpp: bool) -> Result<(), ImageError> {
+    decode_pvrtc2_image(data, out, width, height, is2bpp)
+}
+
+#[wasm_bindgen]
+pub fn decode_pvrtc2(data: &mut [u8], width: usize, height: usize, is2bpp: bool) -> Result<Box<[u8]>, ImageError> {
+    let format = if is2bpp { "PVRTC2_2BPP" } else { "PVRTC2_4BPP" };
+    try_decode_alloc(format, data, width, height, |out| decode_pvrtc2_into(data, out, width, height, is2bpp))
+}
+
+pub fn decode_etc1_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
+    decode_generic_blocky(data, out, width, height, decode_etc1_block, 8)
+}
+
+#[wasm_bindgen]
+pub fn decode_etc1(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("ETC_RGB4", data, width, height, |out| decode_etc1_into(data, out, width, height))
+}
+
+fn decode_etc1a4_block(data: &[u8], outbuf: &mut [u32]) {
+    let alpha = u64::from_le_bytes([data[0], data[1], data[2], data[3], data[4], data[5], data[6], data[7]]);
+    let mut colors = [0u32; 16];
+    decode_etc1_block(&data[8..], &mut colors);
+    for y in 0..4 {
+        for x in 0..4 {
+            let a = ((alpha >> ((x * 4 + y) * 4)) & 0xf) as u32 * 17;
+            outbuf[y * 4 + x] = colors[y * 4 + x] & 0x00ffffff | (a << 24);
+        }
+    }
+}
+
+pub fn decode_etc1a4_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
+    decode_generic_blocky(data, out, width, height, decode_etc1a4_block, 16)
+}
+
 #[wasm_bindgen]
-pub fn decode_dxt3(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    decode_generic_blocky(data, width, height, decode_bc2_block, 16)
+pub fn decode_etc1a4(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("ETC2_RGBA8_3DS", data, width, height, |out| decode_etc1a4_into(data, out, width, height))
+}
+
+pub fn decode_etc1_3ds_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
+    let linear = untile_ctr_etc(data, width, height, false);
+    decode_etc1_into(&linear, out, width, height);
+    flip_v_in_place(out, width, height);
 }
 
 #[wasm_bindgen]
-pub fn decode_dxt5(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    decode_generic_blocky(data, width, height, decode_bc3_block, 16)
+pub fn decode_etc1_3ds(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("ETC_RGB4_3DS", data, width, height, |out| decode_etc1_3ds_into(data, out, width, height))
+}
+
+pub fn decode_etc1a4_3ds_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
+    let linear = untile_ctr_etc(data, width, height, true);
+    decode_etc1a4_into(&linear, out, width, height);
+    flip_v_in_place(out, width, height);
 }
 
 #[wasm_bindgen]
-pub fn decode_pvrtc(data: &mut [u8], width: usize, height: usize, is2bpp: bool) -> Box<[u8]> {
-    let mut out: Vec<u32> = Vec::new();
-    out.resize((width * height) as usize, 0);
-    decode_pvrtc_(&data, width, height, &mut out, is2bpp).expect("pvrtc");
-    encode_data(out).into()
+pub fn decode_etc1a4_3ds(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("ETC2_RGBA8_3DS", data, width, height, |out| decode_etc1a4_3ds_into(data, out, width, height))
+}
+
+pub fn decode_etc2_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
+    decode_generic_blocky(data, out, width, height, decode_etc2_rgb_block, 8)
 }
 
 #[wasm_bindgen]
-pub fn decode_etc1(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    decode_generic_blocky(data, width, height, decode_etc1_block, 8)
+pub fn decode_etc2(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("ETC2_RGB", data, width, height, |out| decode_etc2_into(data, out, width, height))
+}
+
+pub fn decode_etc2_a1_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
+    decode_generic_blocky(data, out, width, height, decode_etc2_rgba1_block, 8)
 }
 
 #[wasm_bindgen]
-pub fn decode_etc2(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    decode_generic_blocky(data, width, height, decode_etc2_rgb_block, 8)
+pub fn decode_etc2_a1(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("ETC2_RGBA1", data, width, height, |out| decode_etc2_a1_into(data, out, width, height))
+}
+
+pub fn decode_etc2_a8_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
+    decode_generic_blocky(data, out, width, height, decode_etc2_rgba8_block, 16)
 }
 
 #[wasm_bindgen]
-pub fn decode_etc2_a1(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    decode_generic_blocky(data, width, height, decode_etc2_rgba1_block, 8)
+pub fn decode_etc2_a8(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("ETC2_RGBA8", data, width, height, |out| decode_etc2_a8_into(data, out, width, height))
+}
+
+pub fn decode_eacr_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
+    decode_generic_blocky(data, out, width, height, decode_eacr_block, 8)
 }
 
 #[wasm_bindgen]
-pub fn decode_etc2_a8(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    decode_generic_blocky(data, width, height, decode_etc2_rgba8_block, 16)
+pub fn decode_eacr(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("EAC_R", data, width, height, |out| decode_eacr_into(data, out, width, height))
+}
+
+pub fn decode_eacr_signed_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
+    decode_generic_blocky(data, out, width, height, decode_eacr_signed_block, 8)
 }
 
 #[wasm_bindgen]
-pub fn decode_eacr(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    decode_generic_blocky(data, width, height, decode_eacr_block, 8)
+pub fn decode_eacr_signed(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("EAC_R_SIGNED", data, width, height, |out| decode_eacr_signed_into(data, out, width, height))
+}
+
+pub fn decode_eacrg_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
+    decode_generic_blocky(data, out, width, height, decode_eacrg_block, 16)
+}
+
+#[wasm_bindgen]
+pub fn decode_eacrg(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("EAC_RG", data, width, height, |out| decode_eacrg_into(data, out, width, height))
+}
+
+pub fn decode_eacrg_signed_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
+    decode_generic_blocky(data, out, width, height, decode_eacrg_signed_block, 16)
 }
 
 #[wasm_bindgen]
-pub fn decode_eacr_signed(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    decode_generic_blocky(data, width, height, decode_eacr_signed_block, 8)
+pub fn decode_eacrg_signed(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("EAC_RG_SIGNED", data, width, height, |out| decode_eacrg_signed_into(data, out, width, height))
+}
+
+pub fn decode_bc4_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
+    decode_generic_blocky(data, out, width, height, decode_bc4_block, 8)
 }
 
 #[wasm_bindgen]
-pub fn decode_eacrg(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    decode_generic_blocky(data, width, height, decode_eacrg_block, 16)
+pub fn decode_bc4(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("BC4", data, width, height, |out| decode_bc4_into(data, out, width, height))
+}
+
+pub fn decode_bc5_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
+    decode_generic_blocky(data, out, width, height, decode_bc5_block, 16)
 }
 
 #[wasm_bindgen]
-pub fn decode_eacrg_signed(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    decode_generic_blocky(data, width, height, decode_eacrg_signed_block, 16)
+pub fn decode_bc5(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("BC5", data, width, height, |out| decode_bc5_into(data, out, width, height))
+}
+
+pub fn decode_bc6h_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
+    decode_generic_blocky(data, out, width, height, |data: &[u8], outbuf: &mut [u32]| decode_bc6_block(data, outbuf, false), 16)
 }
 
 #[wasm_bindgen]
-pub fn decode_bc4(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    decode_generic_blocky(data, width, height, decode_bc4_block, 8)
+pub fn decode_bc6h(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("BC6H", data, width, height, |out| decode_bc6h_into(data, out, width, height))
+}
+
+pub fn decode_bc7_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
+    decode_generic_blocky(data, out, width, height, decode_bc7_block, 16)
 }
 
 #[wasm_bindgen]
-pub fn decode_bc5(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    decode_generic_blocky(data, width, height, decode_bc5_block, 16)
+pub fn decode_bc7(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("BC7", data, width, height, |out| decode_bc7_into(data, out, width, height))
+}
+
+pub fn decode_atc_rgb4_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
+    decode_generic_blocky(data, out, width, height, decode_atc_rgb4_block, 8)
 }
 
 #[wasm_bindgen]
-pub fn decode_bc6h(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    decode_generic_blocky(data, width, height, |data: &[u8], outbuf: &mut [u32]| decode_bc6_block(data, outbuf, false), 16)
+pub fn decode_atc_rgb4(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("ATC_RGB4", data, width, height, |out| decode_atc_rgb4_into(data, out, width, height))
+}
+
+pub fn decode_atc_rgba8_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
+    decode_generic_blocky(data, out, width, height, decode_atc_rgba8_block, 16)
 }
 
 #[wasm_bindgen]
-pub fn decode_bc7(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    decode_generic_blocky(data, width, height, decode_bc7_block, 16)
+pub fn decode_atc_rgba8(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("ATC_RGBA8", data, width, height, |out| decode_atc_rgba8_into(data, out, width, height))
+}
+
+/// ATC colour with DXT3-style explicit 4-bit alpha.
+fn decode_atc_explicit_block(data: &[u8], outbuf: &mut [u32]) {
+    let mut colors = [0u32; 16];
+    decode_atc_rgb4_block(&data[8..], &mut colors);
+    for (i, c) in colors.iter().enumerate() {
+        let alpha = (data[i / 2] >> (i % 2 * 4) & 0xf) as u32 * 17;
+        outbuf[i] = c & 0x00ffffff | (alpha << 24);
+    }
+}
+
+/// ATC colour with DXT5-style interpolated alpha.
+fn decode_atc_interpolated_block(data: &[u8], outbuf: &mut [u32]) {
+    let mut colors = [0u32; 16];
+    decode_atc_rgb4_block(&data[8..], &mut colors);
+    let (a0, a1) = (data[0] as u32, data[1] as u32);
+    let mut palette = [a0, a1, 0, 0, 0, 0, 0, 0xff];
+    if a0 > a1 {
+        for i in 1..7 {
+            palette[i + 1] = (a0 * (7 - i as u32) + a1 * i as u32) / 7;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = (a0 * (5 - i as u32) + a1 * i as u32) / 5;
+        }
+    }
+    let indices = data[2..8].iter().rev().fold(0u64, |acc, &b| acc << 8 | b as u64);
+    for (i, c) in colors.iter().enumerate() {
+        let alpha = palette[(indices >> (i * 3) & 7) as usize];
+        outbuf[i] = c & 0x00ffffff | (alpha << 24);
+    }
+}
+
+pub fn decode_atc_rgba_explicit_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
+    decode_generic_blocky(data, out, width, height, decode_atc_explicit_block, 16)
 }
 
 #[wasm_bindgen]
-pub fn decode_atc_rgb4(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    decode_generic_blocky(data, width, height, decode_atc_rgb4_block, 8)
+pub fn decode_atc_rgba_explicit(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("ATC_RGBA_EXPLICIT", data, width, height, |out| decode_atc_rgba_explicit_into(data, out, width, height))
+}
+
+pub fn decode_atc_rgba_interpolated_into(data: &[u8], out: &mut [u8], width: usize, height: usize) {
+    decode_generic_blocky(data, out, width, height, decode_atc_interpolated_block, 16)
 }
 
 #[wasm_bindgen]
-pub fn decode_atc_rgba8(data: &mut [u8], width: usize, height: usize) -> Box<[u8]> {
-    decode_generic_blocky(data, width, height, decode_atc_rgba8_block, 16)
+pub fn decode_atc_rgba_interpolated(data: &mut [u8], width: usize, height: usize) -> Result<Box<[u8]>, ImageError> {
+    decode_alloc("ATC_RGBA_INTERPOLATED", data, width, height, |out| decode_atc_rgba_interpolated_into(data, out, width, height))
+}
+
+pub fn decode_astc_into(data: &[u8], out: &mut [u8], width: usize, height: usize, block_width: usize, block_height: usize)
+                        -> Result<(), ImageError> {
+    if width == 0 || height == 0 {
+        return Ok(());
+    }
+    let format = format!("ASTC_RGBA_{}x{}", block_width, block_height);
+    let row_size = (width + block_width - 1) / block_width * 16;
+    // ASTC blocks don't depend on their neighbours, so each row of blocks is decoded as an image of its own
+    try_for_each_strip(&mut out[..width * height * 4], width * block_height * 4, |by, strip| {
+        let rows = strip.len() / (width * 4);
+        decode_u32_into(strip, width * rows, |pixels| {
+            decode_astc_(&data[by * row_size..(by + 1) * row_size], width, rows, block_width, block_height, pixels)
+                .map_err(|_| ImageError::invalid_data(&format))
+        })
+    })
 }
 
 #[wasm_bindgen]
-pub fn decode_astc(data: &mut [u8], width: usize, height: usize, block_width: usize, block_height: usize) -> Box<[u8]> {
-    let mut out: Vec<u32> = Vec::new();
-    out.resize((width * height) as usize, 0);
-    decode_astc_(&data, width, height, block_width, block_height, &mut out).expect("pvrtc");
-    encode_data(out).into()
+pub fn decode_astc(data: &mut [u8], width: usize, height: usize, block_width: usize, block_height: usize) -> Result<Box<[u8]>, ImageError> {
+    let format = format!("ASTC_RGBA_{}x{}", block_width, block_height);
+    try_decode_alloc(&format, data, width, height, |out| decode_astc_into(data, out, width, height, block_width, block_height))
 }
 
-pub fn get_format_pixel_size(format: &str) -> i32 {
+/// Resolves other names for formats, such as DXGI/Vulkan-style names and legacy Unity IDs, to the name
+/// `decode` matches on.
+pub fn canonical_format(format: &str) -> &str {
     match format {
+        "BGRA32Old" => "BGRA32",
+        // Unity calls format 61 ETC_RGBA8_3DS; it's ETC1 with 4-bit alpha, not ETC2
+        "ETC_RGBA8_3DS" => "ETC2_RGBA8_3DS",
+        "R16F" => "RHalf",
+        "RG16F" => "RGHalf",
+        "RGBA16F" => "RGBAHalf",
+        "R32F" | "R32_SFLOAT" => "RFloat",
+        "RG32F" | "R32G32_FLOAT" | "R32G32_SFLOAT" => "RGFloat",
+        "RGBA32F" | "R32G32B32A32_FLOAT" | "R32G32B32A32_SFLOAT" => "RGBAFloat",
+        "R11G11B10_FLOAT" | "R11G11B10Float" | "B10G11R11_UFLOAT_PACK32" => "B10G11R11_UFloat",
+        _ => format
+    }
+}
+
+pub fn get_format_pixel_size(format: &str) -> i32 {
+    match canonical_format(format) {
         "Alpha8" => 1,
         "ARGB4444" => 2,
         "RGB24" => 3,
         "RGBA32" => 4,
+        "ARGB32" => 4,
         "RGB565" => 2,
         "RGBA4444" => 2,
         "RGBA5551" => 2,
@@ -711,6 +1195,19 @@ pub fn get_format_pixel_size(format: &str) -> i32 {
         "RG32" => 4,
         "RGB48" => 6,
         "RGBA64" => 8,
+
+        "R8_SIGNED" => 1,
+        "RG16_SIGNED" | "R16_SIGNED" => 2,
+        "RGB24_SIGNED" => 3,
+        "RGBA32_SIGNED" | "RG32_SIGNED" => 4,
+        "RGB48_SIGNED" => 6,
+        "RGBA64_SIGNED" => 8,
+
+        "B10G11R11_UFloat" => 4,
+
+        // packed layouts that only Unity's GraphicsFormat uses
+        "BGR565" | "BGRA4444" | "BGRA5551" => 2,
+        "RGBA1010102" => 4,
         _ => 0
     }
 }
@@ -732,6 +1229,74 @@ pub fn get_format_pixel_rshift(format: &str) -> i32 {
     }
 }
 
+/// Returns the block width and height in pixels and the size of one block in bytes.
+/// Uncompressed formats are treated as 1x1 blocks.
+pub fn get_format_block_info(format: &str) -> (usize, usize, usize) {
+    match canonical_format(format) {
+        "DXT1" | "DXT1Crunched" | "BC4" | "ATC_RGB4" | "EAC_R" | "EAC_R_SIGNED" |
+        "ETC_RGB4" | "ETC_RGB4_3DS" | "ETC_RGB4Crunched" | "ETC2_RGB" | "ETC2_RGBA1" |
+        "PVRTC_RGB4" | "PVRTC_RGBA4" => (4, 4, 8),
+        "PVRTC_RGB2" | "PVRTC_RGBA2" | "PVRTC2_2BPP" => (8, 4, 8),
+        "PVRTC2_4BPP" => (4, 4, 8),
+        "DXT3" | "DXT5" | "DXT5Crunched" | "BC5" | "BC6H" | "BC7" | "ATC_RGBA8" |
+        "EAC_RG" | "EAC_RG_SIGNED" | "ETC2_RGBA8" | "ETC2_RGBA8_3DS" | "ETC_RGBA8Crunched" |
+        "ATC_RGBA_EXPLICIT" | "ATC_RGBA_INTERPOLATED" => (4, 4, 16),
+        "ASTC_RGB_4x4" | "ASTC_RGBA_4x4" | "ASTC_HDR_4x4" => (4, 4, 16),
+        "ASTC_RGB_5x5" | "ASTC_RGBA_5x5" | "ASTC_HDR_5x5" => (5, 5, 16),
+        "ASTC_RGB_6x6" | "ASTC_RGBA_6x6" | "ASTC_HDR_6x6" => (6, 6, 16),
+        "ASTC_RGB_8x8" | "ASTC_RGBA_8x8" | "ASTC_HDR_8x8" => (8, 8, 16),
+        "ASTC_RGB_10x10" | "ASTC_RGBA_10x10" | "ASTC_HDR_10x10" => (10, 10, 16),
+        "ASTC_RGB_12x12" | "ASTC_RGBA_12x12" | "ASTC_HDR_12x12" => (12, 12, 16),
+        _ => match astc_block_size(format) {
+            Some((bw, bh)) => (bw, bh, 16),
+            None => (1, 1, get_format_pixel_size(format) as usize)
+        }
+    }
+}
+
+/// Parses the block size out of an ASTC format name such as "ASTC_RGBA_8x5".
+fn astc_block_size(format: &str) -> Option<(usize, usize)> {
+    if !format.starts_with("ASTC_") {
+        return None;
+    }
+    let (bw, bh) = format.rsplit('_').next()?.split_once('x')?;
+    Some((bw.parse().ok()?, bh.parse().ok()?))
+}
+
+/// The number of bytes a `width` x `height` image in `format` takes up (without mipmaps),
+/// or `None` if the format is unknown.
+pub fn expected_data_size(format: &str, width: usize, height: usize) -> Option<usize> {
+    let (bw, bh, bpb) = get_format_block_info(format);
+    if bpb == 0 {
+        return None;
+    }
+    let mut blocks_x = (width + bw - 1) / bw;
+    let mut blocks_y = (height + bh - 1) / bh;
+    if format.starts_with("PVRTC") && width > 0 && height > 0 {
+        // PVRTC interpolates between neighbouring blocks, so images are at least 2x2 blocks
+        blocks_x = max(blocks_x, 2);
+        blocks_y = max(blocks_y, 2);
+    }
+    blocks_x.checked_mul(blocks_y)?.checked_mul(bpb)
+}
+
+#[wasm_bindgen]
+/// The number of bytes a `width` x `height` image in `format` takes up (without mipmaps), or 0 if the
+/// format is unknown.
+pub fn get_expected_data_size(format: &str, width: usize, height: usize) -> usize {
+    expected_data_size(format, width, height).unwrap_or(0)
+}
+
+fn check_data_size(format: &str, data: &[u8], width: usize, height: usize) -> Result<usize, ImageError> {
+    rgba_size(width, height)?;
+    let expected = expected_data_size(format, width, height)
+        .ok_or_else(|| ImageError::unknown_format(format))?;
+    if data.len() < expected {
+        return Err(ImageError::data_too_short(format, expected, data.len()));
+    }
+    Ok(expected)
+}
+
 #[wasm_bindgen]
 pub fn get_format_block_size(format: &str) -> i32 {
     match format {
@@ -746,26 +1311,49 @@ pub fn get_format_block_size(format: &str) -> i32 {
 #[wasm_bindgen]
 pub struct MipMapOffsetAndSize(pub i32, pub i32, pub i32);
 
+#[wasm_bindgen]
+pub struct DecodedImage {
+    pub width: usize,
+    pub height: usize,
+    /// The source data was truncated and the missing part was decoded from zeros
+    pub partial: bool,
+    data: Box<[u8]>
+}
+
+#[wasm_bindgen]
+impl DecodedImage {
+    #[wasm_bindgen(constructor)]
+    pub fn new(width: usize, height: usize, data: Box<[u8]>) -> DecodedImage {
+        DecodedImage { width, height, partial: false, data }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn data(&self) -> Box<[u8]> {
+        self.data.clone()
+    }
+}
+
+impl DecodedImage {
+    pub fn pixels(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn into_pixels(self) -> Box<[u8]> {
+        self.data
+    }
+}
+
 #[wasm_bindgen]
 pub fn get_mipmap_offset_and_size(mipmap: i32, format: &str, width: i32, height: i32) -> MipMapOffsetAndSize {
     let mut w = width;
     let mut h = height;
     let mut ofs = 0;
 
-    let pixel_size = get_format_pixel_size(&format);
-    let pixel_rshift = get_format_pixel_rshift(&format);
-    let block = get_format_block_size(&format);
     let (minw, minh) = get_format_min_pixel_size(&format);
 
     for _ in 0..mipmap {
-        let bw: i32 = if w % block != 0 {w + (block - w % block)} else {w};
-        let bh: i32 = if h % block != 0 {h + (block - h % block)} else {h};
-
-        let mut s = bw * bh;
-
-        s *= pixel_size;
-        s >>= pixel_rshift;
-        ofs += s;
+        // whole blocks, including PVRTC's minimum of 2x2 blocks
+        ofs += expected_data_size(format, w as usize, h as usize).unwrap_or(0) as i32;
         w = max(minw, w >> 1);
         h = max(minh, h >> 1);
     }
@@ -781,105 +1369,266 @@ pub fn get_mipmap_byte_size(mipmap: i32, format: &str, width: i32, height: i32)
 }
 
 #[wasm_bindgen]
-/// Decodes a texture of arbitrary format.
+/// Decodes a texture of arbitrary format into a caller-provided buffer.
 /// Crunched textures must be unpacked before decoding.
 ///
+/// Called from JS, `out` is copied into wasm memory and back, so this saves nothing over `decode` there;
+/// use a `DecodeBuffer` to keep the pixels in wasm memory instead.
+///
 /// # Arguments
 ///
 /// * `format` - A string holding the texture format (e.g. "RGBA32")
 /// * `data` - An array of bytes holding the compressed image data to decode
+/// * `out` - The buffer to write RGBA data to; must hold at least `width * height * 4` bytes
 /// * `width` - The overall width of the image
 /// * `height` - The overall height of the image
 /// * `is_xbox` - If the platform is XBox 360 -- used to determine if bytes should be swapped
+/// * `platform` - The build target name (e.g. "Switch", "PS4", "Nintendo 3DS") -- used to undo console swizzling
+/// * `platform_blob` - The texture's `m_PlatformBlob` -- Switch textures are only deswizzled when it isn't empty
 ///
 /// # Returns
 ///
-/// * A box containing the decompressed (raw) image data.
-pub fn decode(format: &str, data: &mut [u8], width: usize, height: usize, is_xbox: bool) -> Box<[u8]> {
-    if data.len() == 0 {return [].into()}
+/// * An error if the format is unknown, `data` is shorter than the image, or `out` can't hold it.
+pub fn decode_into(format: &str, data: &mut [u8], out: &mut [u8], width: usize, height: usize, is_xbox: bool,
+                   platform: &str, platform_blob: &[u8]) -> Result<(), ImageError> {
+    check_data_size(format, data, width, height)?;
+    let out_size = rgba_size(width, height)?;
+    if out.len() < out_size {
+        return Err(ImageError::output_too_small(out_size, out.len()));
+    }
+    let format = canonical_format(format);
+    let mut deswizzled;
+    let data = if format.ends_with("Crunched") {
+        data
+    } else {
+        let (bw, bh, bpb) = get_format_block_info(format);
+        match deswizzle(get_platform_swizzle(platform, platform_blob), data, width, height, bw, bh, bpb) {
+            Some(d) => {
+                deswizzled = d;
+                &mut deswizzled[..]
+            },
+            None => data
+        }
+    };
     match format {
-        "Alpha8" => decode_a8(data),
+        "Alpha8" => decode_a8_into(data, &mut out[..width * height * 4]),
         "ARGB4444" => {
             if is_xbox { swap_bytes_xbox(data) };
-            decode_argb4444(data, width, height)
+            decode_argb4444_into(data, out, width, height)
+        },
+        "RGB24" => decode_rgb24_into(data, out, width, height),
+        "RGBA32" => {
+            let len = min(data.len(), width * height * 4);
+            out[..len].copy_from_slice(&data[..len]);
         },
-        "RGB24" => decode_rgb24(data, width, height),
-        "RGBA32" => data.to_vec().into(),
         "RGB565" => {
             if is_xbox { swap_bytes_xbox(data) };
-            decode_rgb565(data, width, height)
+            decode_rgb565_into(data, out, width, height)
         },
-        "RGBA4444" => decode_rgba4444(data, width, height),
-        "RGBA5551" => decode_rgba5551(data, width, height),
-        "BGRA32" => decode_bgra32(data, width, height),
+        "RGBA4444" => {
+            if is_xbox { swap_bytes_xbox(data) };
+            decode_rgba4444_into(data, out, width, height)
+        },
+        "RGBA5551" => {
+            if is_xbox { swap_bytes_xbox(data) };
+            decode_rgba5551_into(data, out, width, height)
+        },
+        "BGRA32" => decode_bgra32_into(data, out, width, height),
 
-        "RHalf" => decode_rhalf(data, width, height),
-        "RGHalf" => decode_rghalf(data, width, height),
-        "RGBHalf" => decode_rgbhalf(data, width, height),
-        "RGBAHalf" => decode_rgbahalf(data, width, height),
+        "RHalf" | "RGHalf" | "RGBHalf" | "RGBAHalf" if is_xbox => {
+            // big-endian halves
+            swap_bytes_xbox(data);
+            return decode_into(format, data, out, width, height, false, platform, platform_blob);
+        },
+        "RHalf" => decode_rhalf_into(data, out, width, height),
+        "RGHalf" => decode_rghalf_into(data, out, width, height),
+        "RGBHalf" => decode_rgbhalf_into(data, out, width, height),
+        "RGBAHalf" => decode_rgbahalf_into(data, out, width, height),
 
-        "RFloat" => decode_rfloat(data, width, height),
-        "RGFloat" => decode_rgfloat(data, width, height),
-        "RGBFloat" => decode_rgbfloat(data, width, height),
-        "RGBAFloat" => decode_rgbafloat(data, width, height),
+        "RFloat" => decode_rfloat_into(data, out, width, height),
+        "RGFloat" => decode_rgfloat_into(data, out, width, height),
+        "RGBFloat" => decode_rgbfloat_into(data, out, width, height),
+        "RGBAFloat" => decode_rgbafloat_into(data, out, width, height),
 
-        "YUY2" => decode_yuy2(data, width, height),
+        "YUY2" => decode_yuy2_into(data, out, width, height),
 
-        "RGB9e5Float" => decode_rgb9e5float(data, width, height),
+        "RGB9e5Float" => decode_rgb9e5float_into(data, out, width, height),
 
-        "BC6H" => decode_bc6h(data, width, height),
-        "BC7" => decode_bc7(data, width, height),
-        "BC4" => decode_bc4(data, width, height),
-        "BC5" => decode_bc5(data, width, height),
+        "BC6H" => decode_bc6h_into(data, out, width, height),
+        "BC7" => decode_bc7_into(data, out, width, height),
+        "BC4" => decode_bc4_into(data, out, width, height),
+        "BC5" => decode_bc5_into(data, out, width, height),
 
         "DXT1" => {
             if is_xbox { swap_bytes_xbox(data) };
-            decode_dxt1(data, width, height)
+            decode_dxt1_into(data, out, width, height)
         },
         "DXT3" => {
-            decode_dxt3(data, width, height)
+            decode_dxt3_into(data, out, width, height)
         },
         "DXT5" => {
             if is_xbox { swap_bytes_xbox(data) };
-            decode_dxt5(data, width, height)
+            decode_dxt5_into(data, out, width, height)
+        },
+        "DXT1Crunched" => decode_dxt1_into(data, out, width, height),
+        "DXT5Crunched" => decode_dxt5_into(data, out, width, height),
+
+        "PVRTC_RGB2" | "PVRTC_RGBA2" => decode_pvrtc_into(data, out, width, height, true)?,
+        "PVRTC_RGB4" | "PVRTC_RGBA4" => decode_pvrtc_into(data, out, width, height, false)?,
+        "PVRTC2_2BPP" => decode_pvrtc2_into(data, out, width, height, true)?,
+        "PVRTC2_4BPP" => decode_pvrtc2_into(data, out, width, height, false)?,
+
+        "ATC_RGB4" => decode_atc_rgb4_into(data, out, width, height),
+        "ATC_RGBA8" => decode_atc_rgba8_into(data, out, width, height),
+        "ATC_RGBA_EXPLICIT" => decode_atc_rgba_explicit_into(data, out, width, height),
+        "ATC_RGBA_INTERPOLATED" => decode_atc_rgba_interpolated_into(data, out, width, height),
+
+        "EAC_R" => decode_eacr_into(data, out, width, height),
+        "EAC_R_SIGNED" => decode_eacr_signed_into(data, out, width, height),
+        "EAC_RG" => decode_eacrg_into(data, out, width, height),
+        "EAC_RG_SIGNED" => decode_eacrg_signed_into(data, out, width, height),
+
+        "ETC_RGB4" => decode_etc1_into(data, out, width, height),
+        "ETC_RGB4_3DS" => decode_etc1_3ds_into(data, out, width, height),
+        "ETC2_RGB" => decode_etc2_into(data, out, width, height),
+        "ETC2_RGBA1" => decode_etc2_a1_into(data, out, width, height),
+        "ETC2_RGBA8" => decode_etc2_a8_into(data, out, width, height),
+        "ETC2_RGBA8_3DS" => decode_etc1a4_3ds_into(data, out, width, height),
+        "ETC_RGB4Crunched" => decode_etc1_into(data, out, width, height),
+        "ETC_RGBA8Crunched" => decode_etc2_a8_into(data, out, width, height),
+
+        "ASTC_RGB_4x4" | "ASTC_RGBA_4x4" | "ASTC_HDR_4x4" => decode_astc_into(data, out, width, height, 4, 4)?,
+        "ASTC_RGB_5x5" | "ASTC_RGBA_5x5" | "ASTC_HDR_5x5" => decode_astc_into(data, out, width, height, 5, 5)?,
+        "ASTC_RGB_6x6" | "ASTC_RGBA_6x6" | "ASTC_HDR_6x6" => decode_astc_into(data, out, width, height, 6, 6)?,
+        "ASTC_RGB_8x8" | "ASTC_RGBA_8x8" | "ASTC_HDR_8x8" => decode_astc_into(data, out, width, height, 8, 8)?,
+        "ASTC_RGB_10x10" | "ASTC_RGBA_10x10" | "ASTC_HDR_10x10" => decode_astc_into(data, out, width, height, 10, 10)?,
+        "ASTC_RGB_12x12" | "ASTC_RGBA_12x12" | "ASTC_HDR_12x12" => decode_astc_into(data, out, width, height, 12, 12)?,
+
+        "L8" => decode_l8_into(data, out, width, height),
+        "LA16" => decode_la16_into(data, out, width, height),
+
+        "R8" => decode_r8_into(data, out, width, height),
+        "R16" => {
+            if is_xbox { swap_bytes_xbox(data) };
+            decode_r16_into(data, out, width, height)
+        },
+        "RG16" => decode_rg16_into(data, out, width, height),
+        "RG32" => decode_rg32_into(data, out, width, height),
+        "RGB48" => decode_rgb48_into(data, out, width, height),
+        "RGBA64" => decode_rgba64_into(data, out, width, height),
+
+        "R8_SIGNED" => decode_snorm_into(data, out, width, height, 1, false),
+        "RG16_SIGNED" => decode_snorm_into(data, out, width, height, 2, false),
+        "RGB24_SIGNED" => decode_snorm_into(data, out, width, height, 3, false),
+        "RGBA32_SIGNED" => decode_snorm_into(data, out, width, height, 4, false),
+        "R16_SIGNED" | "RG32_SIGNED" | "RGB48_SIGNED" | "RGBA64_SIGNED" => {
+            if is_xbox { swap_bytes_xbox(data) };
+            let channels = get_format_pixel_size(format) as usize / 2;
+            decode_snorm_into(data, out, width, height, channels, true)
         },
-        "DXT1Crunched" => decode_dxt1(data, width, height),
-        "DXT5Crunched" => decode_dxt5(data, width, height),
-
-        "PVRTC_RGB2" | "PVRTC_RGBA2" => decode_pvrtc(data, width, height, true),
-        "PVRTC_RGB4" | "PVRTC_RGBA4" => decode_pvrtc(data, width, height, false),
-
-        "ATC_RGB4" => decode_atc_rgb4(data, width, height),
-        "ATC_RGBA8" => decode_atc_rgba8(data, width, height),
-
-        "EAC_R" => decode_eacr(data, width, height),
-        "EAC_R_SIGNED" => decode_eacr_signed(data, width, height),
-        "EAC_RG" => decode_eacrg(data, width, height),
-        "EAC_RG_SIGNED" => decode_eacrg_signed(data, width, height),
-
-        "ETC_RGB4" | "ETC_RGB4_3DS" => decode_etc1(data, width, height),
-        "ETC2_RGB" => decode_etc2(data, width, height),
-        "ETC2_RGBA1" => decode_etc2_a1(data, width, height),
-        "ETC2_RGBA8" | "ETC2_RGBA8_3DS" => decode_etc2_a8(data, width, height),
-        "ETC_RGB4Crunched" => decode_etc1(data, width, height),
-        "ETC_RGBA8Crunched" => decode_etc2_a8(data, width, height),
-
-        "ASTC_RGB_4x4" | "ASTC_RGBA_4x4" | "ASTC_HDR_4x4" => decode_astc(data, width, height, 4, 4),
-        "ASTC_RGB_5x5" | "ASTC_RGBA_5x5" | "ASTC_HDR_5x5" => decode_astc(data, width, height, 5, 5),
-        "ASTC_RGB_6x6" | "ASTC_RGBA_6x6" | "ASTC_HDR_6x6" => decode_astc(data, width, height, 6, 6),
-        "ASTC_RGB_8x8" | "ASTC_RGBA_8x8" | "ASTC_HDR_8x8" => decode_astc(data, width, height, 8, 8),
-        "ASTC_RGB_10x10" | "ASTC_RGBA_10x10" | "ASTC_HDR_10x10" => decode_astc(data, width, height, 10, 10),
-        "ASTC_RGB_12x12" | "ASTC_RGBA_12x12" | "ASTC_HDR_12x12" => decode_astc(data, width, height, 12, 12),
-
-        "L8" => decode_l8(data, width, height),
-        "LA16" => decode_la16(data, width, height),
-
-        "R8" => decode_r8(data, width, height),
-        "R16" => decode_r16(data, width, height),
-        "RG16" => decode_rg16(data, width, height),
-        "RG32" => decode_rg32(data, width, height),
-        "RGB48" => decode_rgb48(data, width, height),
-        "RGBA64" => decode_rgba64(data, width, height),
-        _ => [].into()
+
+        "B10G11R11_UFloat" => decode_b10g11r11_into(data, out, width, height),
+
+        "BGR565" => decode_bgr565_into(data, out, width, height),
+        "BGRA4444" => decode_bgra4444_into(data, out, width, height),
+        "BGRA5551" => decode_bgra5551_into(data, out, width, height),
+        "RGBA1010102" => decode_rgba1010102_into(data, out, width, height),
+        _ => return Err(ImageError::unknown_format(format))
+    }
+    Ok(())
+}
+
+#[wasm_bindgen]
+/// Decodes a texture of arbitrary format.
+/// Crunched textures must be unpacked before decoding.
+///
+/// # Arguments
+///
+/// * `format` - A string holding the texture format (e.g. "RGBA32")
+/// * `data` - An array of bytes holding the compressed image data to decode
+/// * `width` - The overall width of the image
+/// * `height` - The overall height of the image
+/// * `is_xbox` - If the platform is XBox 360 -- used to determine if bytes should be swapped
+/// * `platform` - The build target name (e.g. "Switch", "PS4", "Nintendo 3DS") -- used to undo console swizzling
+/// * `platform_blob` - The texture's `m_PlatformBlob` -- Switch textures are only deswizzled when it isn't empty
+///
+/// # Returns
+///
+/// * A box containing the decompressed (raw) image data, or an error if the format is unknown or `data`
+///   is shorter than the image. Use `decode_lenient` to decode truncated data anyway.
+pub fn decode(format: &str, data: &mut [u8], width: usize, height: usize, is_xbox: bool, platform: &str,
+              platform_blob: &[u8]) -> Result<Box<[u8]>, ImageError> {
+    let mut out = vec![0u8; rgba_size(width, height)?];
+    decode_into(format, data, &mut out, width, height, is_xbox, platform, platform_blob)?;
+    Ok(out.into())
+}
+
+#[wasm_bindgen]
+/// Like `decode`, but data that is too short (e.g. a truncated `.resS` read) is padded with zeros
+/// instead of rejected, and the result is marked as `partial`.
+/// Unknown formats and invalid sizes are still errors.
+pub fn decode_lenient(format: &str, data: &mut [u8], width: usize, height: usize, is_xbox: bool, platform: &str,
+                      platform_blob: &[u8]) -> Result<DecodedImage, ImageError> {
+    match decode(format, data, width, height, is_xbox, platform, platform_blob) {
+        Ok(pixels) => Ok(DecodedImage::new(width, height, pixels)),
+        Err(e) if e.kind == ImageErrorKind::DataTooShort => {
+            let mut padded = data.to_vec();
+            padded.resize(e.expected, 0);
+            let pixels = decode(format, &mut padded, width, height, is_xbox, platform, platform_blob)?;
+            Ok(DecodedImage { partial: true, ..DecodedImage::new(width, height, pixels) })
+        },
+        Err(e) => Err(e)
+    }
+}
+
+#[wasm_bindgen]
+/// An output buffer that lives in wasm memory and can be reused between decodes, so decoding many textures
+/// doesn't allocate (and grow the wasm memory) for each one.
+///
+/// `view()` gives JS the pixels without copying them out. The view is invalidated by the next decode or
+/// by any call that grows the wasm memory -- including passing the view itself to another wasm function --
+/// so copy it (`view().slice()`) before doing anything else with it.
+#[derive(Default)]
+pub struct DecodeBuffer {
+    data: Vec<u8>
+}
+
+#[wasm_bindgen]
+impl DecodeBuffer {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> DecodeBuffer {
+        DecodeBuffer { data: Vec::new() }
+    }
+
+    /// Decodes into the buffer, growing it if needed. Takes the same arguments as `decode`.
+    pub fn decode(&mut self, format: &str, data: &mut [u8], width: usize, height: usize, is_xbox: bool,
+                  platform: &str, platform_blob: &[u8]) -> Result<(), ImageError> {
+        self.data.resize(rgba_size(width, height)?, 0);
+        decode_into(format, data, &mut self.data, width, height, is_xbox, platform, platform_blob)
+    }
+
+    /// A `Uint8Array` over the decoded pixels in wasm memory.
+    pub fn view(&self) -> js_sys::Uint8Array {
+        // SAFETY: the array aliases `self.data`; callers are told above not to keep it across calls
+        // that could move or free the buffer
+        unsafe { js_sys::Uint8Array::view(&self.data) }
+    }
+
+    pub fn ptr(&self) -> *const u8 {
+        self.data.as_ptr()
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+impl DecodeBuffer {
+    pub fn pixels(&self) -> &[u8] {
+        &self.data
     }
 }
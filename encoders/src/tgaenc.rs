@@ -0,0 +1,79 @@
+use std::panic;
+use wasm_bindgen::prelude::*;
+
+fn tga_header(width: u32, height: u32, image_type: u8, top_first: bool) -> [u8; 18] {
+    let mut header = [0u8; 18];
+    header[2] = image_type;
+    header[12..14].copy_from_slice(&(width as u16).to_le_bytes());
+    header[14..16].copy_from_slice(&(height as u16).to_le_bytes());
+    header[16] = 32;
+    // 8 alpha bits, and the origin is top-left when bit 5 is set
+    header[17] = 8 | if top_first { 0x20 } else { 0 };
+    header
+}
+
+fn bgra_pixel(data: &[u8], i: usize) -> [u8; 4] {
+    if i * 4 + 4 <= data.len() {
+        [data[i * 4 + 2], data[i * 4 + 1], data[i * 4], data[i * 4 + 3]]
+    } else {
+        [0, 0, 0, 0]
+    }
+}
+
+#[wasm_bindgen]
+/// Encodes RGBA data as an uncompressed 32-bit TGA.
+///
+/// TGA can store either row order, so nothing is moved: `flip` only sets the origin in the header.
+pub fn encode_tga(width: u32, height: u32, data: &[u8], flip: bool) -> Box<[u8]> {
+    panic::set_hook(Box::new(console_error_panic_hook::hook));
+    if width == 0 || height == 0 || width > 0xffff || height > 0xffff {
+        return [].into();
+    }
+    let pixels = (width * height) as usize;
+    let mut out = Vec::with_capacity(18 + pixels * 4);
+    // unflipped data is shown as-is, so its first row goes at the top
+    out.extend_from_slice(&tga_header(width, height, 2, !flip));
+    for i in 0..pixels {
+        out.extend_from_slice(&bgra_pixel(data, i));
+    }
+    out.into()
+}
+
+#[wasm_bindgen]
+/// Encodes RGBA data as a run-length encoded 32-bit TGA. Runs never cross rows.
+pub fn encode_tga_rle(width: u32, height: u32, data: &[u8], flip: bool) -> Box<[u8]> {
+    panic::set_hook(Box::new(console_error_panic_hook::hook));
+    if width == 0 || height == 0 || width > 0xffff || height > 0xffff {
+        return [].into();
+    }
+    let (w, h) = (width as usize, height as usize);
+    let mut out = Vec::new();
+    out.extend_from_slice(&tga_header(width, height, 10, !flip));
+    for y in 0..h {
+        let mut x = 0;
+        while x < w {
+            let px = bgra_pixel(data, y * w + x);
+            let mut run = 1;
+            while x + run < w && run < 128 && bgra_pixel(data, y * w + x + run) == px {
+                run += 1;
+            }
+            if run > 1 {
+                out.push(0x80 | (run - 1) as u8);
+                out.extend_from_slice(&px);
+                x += run;
+                continue;
+            }
+            // raw packet: extend until the next run of at least two
+            let start = x;
+            x += 1;
+            while x < w && x - start < 128 && (x + 1 >= w || bgra_pixel(data, y * w + x) != bgra_pixel(data, y * w + x + 1)) {
+                x += 1;
+            }
+            out.push((x - start - 1) as u8);
+            for i in start..x {
+                out.extend_from_slice(&bgra_pixel(data, y * w + i));
+            }
+        }
+    }
+    out.into()
+}
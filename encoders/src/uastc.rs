@@ -0,0 +1,244 @@
+use std::convert::TryInto;
+use crate::error::ImageError;
+
+// UASTC block decoding. Every 4x4 block is 16 bytes read least significant bit first: a variable length mode
+// code, transcoding hints (which only matter when converting to ETC1/BC1, so they're skipped), the partition
+// pattern and dual plane channel when the mode has them, then the endpoints and weights.
+//
+// The endpoints are ASTC-style BISE values, but packed differently from ASTC: the trits or quints of each group
+// of 5 or 3 values are stored first as one base 3 or base 5 number, followed by the low bits of every value.
+// Weights are plain bits, with the most significant bit of each subset's first weight left out (it's always 0).
+// Once unpacked, a block is an ASTC 4x4 block and is decoded with ASTC's unquantization, partitioning and
+// interpolation rules.
+
+const MODE_SOLID_COLOR: usize = 8;
+
+/// The code and code length of each mode; the 20th code is reserved.
+const MODE_CODES: [(u32, u32); 20] = [
+    (0x1, 4), (0x35, 6), (0x1d, 5), (0x3, 5), (0x13, 5), (0xb, 5), (0x1b, 5), (0x7, 5), (0x17, 5), (0xf, 5),
+    (0x2, 3), (0x0, 2), (0x6, 3), (0x1f, 5), (0xd, 5), (0x5, 7), (0x15, 6), (0x25, 6), (0x9, 4), (0x45, 7),
+];
+
+/// Per mode: the bits per weight, the endpoint range (an index into `BISE_RANGES`), the number of
+/// components (2 for luminance/alpha, 3 for RGB, 4 for RGBA) and the number of ETC1/BC1 hint bits.
+const MODE_WEIGHT_BITS: [u32; 19] = [4, 2, 3, 2, 2, 3, 2, 2, 0, 2, 4, 2, 3, 1, 2, 4, 2, 2, 5];
+const MODE_ENDPOINT_RANGES: [usize; 19] = [19, 20, 8, 7, 12, 20, 18, 12, 0, 8, 13, 13, 19, 20, 20, 20, 20, 20, 11];
+const MODE_COMPONENTS: [usize; 19] = [3, 3, 3, 3, 3, 3, 3, 3, 4, 4, 4, 4, 4, 4, 4, 2, 2, 2, 3];
+const MODE_HINT_BITS: [u32; 19] = [15, 15, 15, 15, 15, 15, 15, 15, 0, 23, 17, 17, 17, 23, 23, 23, 23, 23, 15];
+
+/// ASTC partition seeds of the 2-subset patterns UASTC shares with BC7 (modes 2, 4, 9 and 16).
+const PARTITION_SEEDS_2: [u32; 30] = [
+    28, 20, 16, 29, 91, 9, 107, 72, 149, 204, 50, 114, 496, 17, 78, 39,
+    252, 828, 43, 156, 116, 210, 476, 273, 684, 359, 246, 195, 694, 524,
+];
+/// ASTC partition seeds of the 3-subset patterns UASTC shares with BC7 (mode 3).
+const PARTITION_SEEDS_3: [u32; 11] = [260, 74, 32, 156, 183, 15, 745, 0, 335, 902, 254];
+/// ASTC partition seeds of the 2-subset patterns that are merges of BC7 3-subset patterns (mode 7).
+const PARTITION_SEEDS_BC7_3: [u32; 19] = [
+    36, 48, 61, 137, 161, 183, 226, 281, 302, 307, 479, 495, 593, 594, 605, 799, 812, 988, 993,
+];
+
+/// (bits, trits, quints) of each ASTC range, from 2 to 256 levels.
+const BISE_RANGES: [(u32, u32, u32); 21] = [
+    (1, 0, 0), (0, 1, 0), (2, 0, 0), (0, 0, 1), (1, 1, 0), (3, 0, 0), (1, 0, 1), (2, 1, 0), (4, 0, 0), (2, 0, 1),
+    (3, 1, 0), (5, 0, 0), (3, 0, 1), (4, 1, 0), (6, 0, 0), (4, 0, 1), (5, 1, 0), (7, 0, 0), (5, 0, 1), (6, 1, 0),
+    (8, 0, 0),
+];
+
+fn invalid() -> ImageError {
+    ImageError::invalid_data("UASTC")
+}
+
+struct BlockBits {
+    bits: u128,
+    pos: u32,
+}
+
+impl BlockBits {
+    fn get(&mut self, count: u32) -> u32 {
+        let v = self.bits.checked_shr(self.pos).unwrap_or(0) as u32 & ((1u64 << count) - 1) as u32;
+        self.pos += count;
+        v
+    }
+}
+
+/// ASTC's endpoint unquantization parameters for ranges with trits or quints: the multiplier C and the bit
+/// pattern of B, most significant first, where letters name the value's low bits ('b' being bit 1).
+const ENDPOINT_UNQUANT: [(u32, &str); 21] = [
+    (0, ""), (0, ""), (0, ""), (0, ""), (204, "000000000"), (0, ""), (113, "000000000"), (93, "b000b0bb0"),
+    (0, ""), (54, "b0000bb00"), (44, "cb000cbcb"), (0, ""), (26, "cb0000cbc"), (22, "dcb000dcb"), (0, ""),
+    (13, "dcb0000dc"), (11, "edcb000ed"), (0, ""), (6, "edcb0000e"), (5, "fedcb000f"), (0, ""),
+];
+
+/// Unquantizes an endpoint value (the trit or quint above the low bits) to 8 bits, per the ASTC specification.
+fn unquantize_endpoint(value: u32, range: usize) -> u8 {
+    let bits = BISE_RANGES[range].0;
+    let low = value & ((1 << bits) - 1);
+    let (c, pattern) = ENDPOINT_UNQUANT[range];
+    if c == 0 {
+        // only bits: replicate them
+        let mut v = 0;
+        let mut filled = 0;
+        while filled < 8 {
+            v = v << bits | low;
+            filled += bits;
+        }
+        return (v >> (filled - 8)) as u8;
+    }
+    let b = pattern.bytes().fold(0, |b, ch| b << 1 | if ch == b'0' { 0 } else { low >> (ch - b'a') & 1 });
+    let a = if low & 1 != 0 { 0x1ff } else { 0 };
+    let t = ((value >> bits) * c + b) ^ a;
+    ((a & 0x80) | (t >> 2)) as u8
+}
+
+/// Unquantizes a weight of `bits` bits to the range 0..=64.
+fn unquantize_weight(value: u32, bits: u32) -> u32 {
+    let mut v = 0;
+    let mut filled = 0;
+    while filled < 6 {
+        v = v << bits | value;
+        filled += bits;
+    }
+    let v = v >> (filled - 6);
+    if v > 32 { v + 1 } else { v }
+}
+
+fn hash52(mut p: u32) -> u32 {
+    p ^= p >> 15;
+    p = p.wrapping_sub(p << 17);
+    p = p.wrapping_add(p << 7);
+    p = p.wrapping_add(p << 4);
+    p ^= p >> 5;
+    p = p.wrapping_add(p << 16);
+    p ^= p >> 7;
+    p ^= p >> 3;
+    p ^= p << 6;
+    p ^= p >> 17;
+    p
+}
+
+/// The subset of texel (x, y) of a 4x4 block, from ASTC's partition hash.
+fn select_partition(seed: u32, x: u32, y: u32, subsets: u32) -> usize {
+    // blocks with fewer than 31 texels use doubled coordinates
+    let (x, y) = (x << 1, y << 1);
+    let seed = seed + (subsets - 1) * 1024;
+    let rnum = hash52(seed);
+    let mut s = [0u32; 8];
+    for (i, v) in s.iter_mut().enumerate() {
+        let n = rnum >> (i * 4) & 0xf;
+        *v = n * n;
+    }
+    let (sh1, sh2) = if seed & 1 != 0 {
+        (if seed & 2 != 0 { 4 } else { 5 }, if subsets == 3 { 6 } else { 5 })
+    } else {
+        (if subsets == 3 { 6 } else { 5 }, if seed & 2 != 0 { 4 } else { 5 })
+    };
+    let a = ((s[0] >> sh1) * x + (s[1] >> sh2) * y + (rnum >> 14)) & 0x3f;
+    let b = ((s[2] >> sh1) * x + (s[3] >> sh2) * y + (rnum >> 10)) & 0x3f;
+    let c = if subsets == 3 { ((s[4] >> sh1) * x + (s[5] >> sh2) * y + (rnum >> 6)) & 0x3f } else { 0 };
+    if a >= b && a >= c {
+        0
+    } else if b >= c {
+        1
+    } else {
+        2
+    }
+}
+
+/// The two 8-bit endpoints of a subset from its unquantized values.
+///
+/// Unlike ASTC, UASTC has no blue contraction: the endpoints are used as stored, in either order.
+/// (Transcoding to ASTC swaps them and inverts the weights where ASTC would contract.)
+fn endpoint_pair(v: &[u8], components: usize) -> ([u8; 4], [u8; 4]) {
+    match components {
+        2 => ([v[0], v[0], v[0], v[2]], [v[1], v[1], v[1], v[3]]),
+        _ => {
+            let (a0, a1) = if components == 4 { (v[6], v[7]) } else { (255, 255) };
+            ([v[0], v[2], v[4], a0], [v[1], v[3], v[5], a1])
+        }
+    }
+}
+
+/// Decodes a 16-byte UASTC block into its pixels, in row-major order.
+pub fn decode_uastc_block(block: &[u8]) -> Result<[[u8; 4]; 16], ImageError> {
+    let mut bits = BlockBits { bits: u128::from_le_bytes(block.try_into().map_err(|_| invalid())?), pos: 0 };
+    let mode = MODE_CODES.iter().take(19).position(|&(code, len)| bits.bits as u32 & ((1 << len) - 1) == code)
+        .ok_or_else(invalid)?;
+    bits.pos = MODE_CODES[mode].1;
+
+    if mode == MODE_SOLID_COLOR {
+        let color = [bits.get(8) as u8, bits.get(8) as u8, bits.get(8) as u8, bits.get(8) as u8];
+        return Ok([color; 16]);
+    }
+    bits.pos += MODE_HINT_BITS[mode];
+
+    let (subsets, seed) = match mode {
+        2 | 4 | 9 | 16 => (2, PARTITION_SEEDS_2.get(bits.get(5) as usize)),
+        7 => (2, PARTITION_SEEDS_BC7_3.get(bits.get(5) as usize)),
+        3 => (3, PARTITION_SEEDS_3.get(bits.get(4) as usize)),
+        _ => (1, Some(&0))
+    };
+    let seed = *seed.ok_or_else(invalid)?;
+    let dual_plane_channel = match mode {
+        6 | 11 | 13 => Some(bits.get(2) as usize),
+        17 => Some(3),
+        _ => None
+    };
+    let planes = if dual_plane_channel.is_some() { 2 } else { 1 };
+
+    let mut partition = [0usize; 16];
+    if subsets > 1 {
+        for (i, p) in partition.iter_mut().enumerate() {
+            *p = select_partition(seed, i as u32 % 4, i as u32 / 4, subsets);
+        }
+    }
+
+    // endpoints: the packed trits/quints of every group, then the low bits of each value
+    let components = MODE_COMPONENTS[mode];
+    let range = MODE_ENDPOINT_RANGES[mode];
+    let (ep_bits, trits, quints) = BISE_RANGES[range];
+    let total_values = components * 2 * subsets as usize;
+    let (group_size, base) = if trits != 0 { (5, 3u32) } else if quints != 0 { (3, 5) } else { (1, 1) };
+    let mut digits = Vec::with_capacity(total_values);
+    if base > 1 {
+        let groups = (total_values + group_size - 1) / group_size;
+        for g in 0..groups {
+            let count = (total_values - g * group_size).min(group_size);
+            // enough bits for base to the power of count
+            let packed_bits = 32 - base.pow(count as u32).saturating_sub(1).leading_zeros();
+            let mut packed = bits.get(packed_bits);
+            for _ in 0..count {
+                digits.push(packed % base);
+                packed /= base;
+            }
+        }
+    }
+    let mut endpoints = [0u8; 24];
+    for (i, e) in endpoints.iter_mut().take(total_values).enumerate() {
+        let value = bits.get(ep_bits) | digits.get(i).map_or(0, |d| d << ep_bits);
+        *e = unquantize_endpoint(value, range);
+    }
+
+    // weights, interleaved by plane; the first texel of each subset has an implied leading zero bit
+    let weight_bits = MODE_WEIGHT_BITS[mode];
+    let mut weights = [0u32; 32];
+    for (i, w) in weights.iter_mut().take(16 * planes).enumerate() {
+        let texel = i / planes;
+        let anchor = !partition[..texel].contains(&partition[texel]);
+        *w = unquantize_weight(bits.get(if anchor { weight_bits - 1 } else { weight_bits }), weight_bits);
+    }
+
+    let pairs: Vec<([u8; 4], [u8; 4])> = endpoints[..total_values].chunks(components * 2)
+        .map(|v| endpoint_pair(v, components)).collect();
+    let mut pixels = [[0u8; 4]; 16];
+    for (i, px) in pixels.iter_mut().enumerate() {
+        let (e0, e1) = pairs[partition[i]];
+        for c in 0..4 {
+            let w = if dual_plane_channel == Some(c) { weights[i * 2 + 1] } else { weights[i * planes] };
+            // endpoints are widened to 16 bits and the result is the top 8 bits
+            let (lo, hi) = (e0[c] as u32 * 257, e1[c] as u32 * 257);
+            px[c] = ((lo * (64 - w) + hi * w + 32) >> 6 >> 8) as u8;
+        }
+    }
+    Ok(pixels)
+}
@@ -0,0 +1,352 @@
+use wasm_bindgen::prelude::*;
+use crate::compress::{decompress_with, Codec};
+use crate::error::ImageError;
+use crate::texdec::decode;
+
+// Cooked Unreal Engine textures: `FTexturePlatformData` as serialized after a UTexture2D's properties,
+// with mip payloads stored inline, at the end of the package, or in the `.ubulk`/`.uptnl` files next to it.
+
+const BULKDATA_PAYLOAD_AT_END_OF_FILE: u32 = 0x1;
+const BULKDATA_SERIALIZE_COMPRESSED_ZLIB: u32 = 0x2;
+const BULKDATA_UNUSED: u32 = 0x20;
+const BULKDATA_FORCE_INLINE_PAYLOAD: u32 = 0x40;
+const BULKDATA_PAYLOAD_IN_SEPERATE_FILE: u32 = 0x100;
+const BULKDATA_OPTIONAL_PAYLOAD: u32 = 0x800;
+const BULKDATA_SIZE_64BIT: u32 = 0x2000;
+const BULKDATA_BAD_DATA_VERSION: u32 = 0x8000;
+const BULKDATA_NO_OFFSET_FIX_UP: u32 = 0x10000;
+
+const PACKED_DATA_CUBEMAP: u32 = 1 << 31;
+const PACKED_DATA_HAS_OPT_DATA: u32 = 1 << 30;
+const PACKED_DATA_NUM_SLICES: u32 = (1 << 30) - 1;
+
+/// `PACKAGE_FILE_TAG`, which also starts each compressed bulk data payload
+const PACKAGE_FILE_TAG: u64 = 0x9E2A83C1;
+/// The chunk size used by compressed payloads saved before it was written to the header
+const LOADING_COMPRESSION_CHUNK_SIZE: usize = 0x20000;
+
+/// UE5 cooked textures start with a placeholder for the derived data key.
+const UE5_PLACEHOLDER_DERIVED_DATA_SIZE: usize = 16;
+
+/// The `texdec` format for an `EPixelFormat` name, e.g. "PF_DXT1" -> "DXT1".
+pub fn texdec_format(pixel_format: &str) -> Option<&'static str> {
+    Some(match pixel_format {
+        "PF_DXT1" => "DXT1",
+        "PF_DXT3" => "DXT3",
+        "PF_DXT5" => "DXT5",
+        "PF_BC4" => "BC4",
+        "PF_BC5" => "BC5",
+        "PF_BC6H" => "BC6H",
+        "PF_BC7" => "BC7",
+        "PF_B8G8R8A8" => "BGRA32",
+        "PF_R8G8B8A8" => "RGBA32",
+        "PF_A8R8G8B8" => "ARGB32",
+        "PF_A2B10G10R10" => "RGBA1010102",
+        "PF_G8" | "PF_L8" => "L8",
+        "PF_A8" => "Alpha8",
+        "PF_R8" => "R8",
+        "PF_R8G8" => "RG16",
+        "PF_G16" => "R16",
+        "PF_G16R16" => "RG32",
+        "PF_A16B16G16R16" | "PF_R16G16B16A16_UNORM" => "RGBA64",
+        "PF_R5G6B5_UNORM" => "RGB565",
+        "PF_FloatRGBA" => "RGBAHalf",
+        "PF_R16F" => "RHalf",
+        "PF_G16R16F" => "RGHalf",
+        "PF_R32_FLOAT" => "RFloat",
+        "PF_G32R32F" => "RGFloat",
+        "PF_A32B32G32R32F" => "RGBAFloat",
+        "PF_ASTC_4x4" => "ASTC_RGBA_4x4",
+        "PF_ASTC_6x6" => "ASTC_RGBA_6x6",
+        "PF_ASTC_8x8" => "ASTC_RGBA_8x8",
+        "PF_ASTC_10x10" => "ASTC_RGBA_10x10",
+        "PF_ASTC_12x12" => "ASTC_RGBA_12x12",
+        "PF_ASTC_4x4_HDR" => "ASTC_HDR_4x4",
+        "PF_ASTC_6x6_HDR" => "ASTC_HDR_6x6",
+        "PF_ASTC_8x8_HDR" => "ASTC_HDR_8x8",
+        "PF_ASTC_10x10_HDR" => "ASTC_HDR_10x10",
+        "PF_ASTC_12x12_HDR" => "ASTC_HDR_12x12",
+        "PF_ETC1" => "ETC_RGB4",
+        "PF_ETC2_RGB" => "ETC2_RGB",
+        "PF_ETC2_RGBA" => "ETC2_RGBA8",
+        "PF_ETC2_R11_EAC" => "EAC_R",
+        "PF_ETC2_RG11_EAC" => "EAC_RG",
+        "PF_PVRTC2" => "PVRTC_RGBA2",
+        "PF_PVRTC4" => "PVRTC_RGBA4",
+        "PF_ATC_RGB" => "ATC_RGB4",
+        "PF_ATC_RGBA_E" => "ATC_RGBA_EXPLICIT",
+        "PF_ATC_RGBA_I" => "ATC_RGBA8",
+        _ => return None
+    })
+}
+
+fn invalid() -> ImageError {
+    ImageError::invalid_data("Unreal texture")
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn read(&mut self, size: usize) -> Result<&'a [u8], ImageError> {
+        let bytes = self.data.get(self.pos..self.pos + size).ok_or_else(invalid)?;
+        self.pos += size;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, ImageError> {
+        self.read(4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn i64(&mut self) -> Result<i64, ImageError> {
+        let b = self.read(8)?;
+        Ok(i64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+    }
+
+    /// An `FString`: a length including the terminator, negative for UTF-16.
+    fn fstring(&mut self) -> Result<String, ImageError> {
+        let len = self.u32()? as i32;
+        if len >= 0 {
+            let bytes = self.read(len as usize)?;
+            Ok(String::from_utf8_lossy(bytes.split(|&b| b == 0).next().unwrap_or(&[])).into_owned())
+        } else {
+            let bytes = self.read(-len as usize * 2)?;
+            let units: Vec<u16> = bytes.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).take_while(|&c| c != 0).collect();
+            Ok(String::from_utf16_lossy(&units))
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PayloadLocation {
+    Inline,
+    /// In the package itself, at an offset from the start of the `.uasset`
+    Package,
+    Bulk,
+    Optional,
+    None,
+}
+
+struct UnrealMip {
+    width: usize,
+    height: usize,
+    depth: usize,
+    flags: u32,
+    location: PayloadLocation,
+    offset: usize,
+    /// The size of the payload on disk
+    size: usize,
+    /// The size of the payload once decompressed
+    element_count: usize,
+    inline: Box<[u8]>,
+}
+
+#[wasm_bindgen]
+/// The cooked platform data of a UTexture2D (or cube/array texture): its pixel format and mips.
+pub struct UnrealTexture {
+    pub width: usize,
+    pub height: usize,
+    /// Array slices, or 6 for a cubemap
+    pub num_slices: usize,
+    pub is_cubemap: bool,
+    /// The first mip stored in this file; mips before it were stripped when cooking
+    pub first_mip: usize,
+    pixel_format: String,
+    mips: Vec<UnrealMip>,
+}
+
+fn read_mip(r: &mut Reader, ue5: bool, bulk_data_start_offset: u64) -> Result<UnrealMip, ImageError> {
+    let cooked = if ue5 { true } else { r.u32()? != 0 };
+    let mut flags = r.u32()?;
+    let (element_count, size) = if flags & BULKDATA_SIZE_64BIT != 0 {
+        (r.i64()? as usize, r.i64()? as usize)
+    } else {
+        (r.u32()? as usize, r.u32()? as usize)
+    };
+    let mut offset = r.i64()? as u64;
+    if flags & BULKDATA_BAD_DATA_VERSION != 0 {
+        r.read(2)?;
+        flags &= !BULKDATA_BAD_DATA_VERSION;
+    }
+    let location = if flags & BULKDATA_UNUSED != 0 || size == 0 {
+        PayloadLocation::None
+    } else if flags & BULKDATA_OPTIONAL_PAYLOAD != 0 {
+        PayloadLocation::Optional
+    } else if flags & BULKDATA_PAYLOAD_IN_SEPERATE_FILE != 0 {
+        PayloadLocation::Bulk
+    } else if flags & BULKDATA_FORCE_INLINE_PAYLOAD != 0 || flags & BULKDATA_PAYLOAD_AT_END_OF_FILE == 0 {
+        PayloadLocation::Inline
+    } else {
+        // only payloads in the package itself are offset by the start of its bulk data section
+        if flags & BULKDATA_NO_OFFSET_FIX_UP == 0 {
+            offset += bulk_data_start_offset;
+        }
+        PayloadLocation::Package
+    };
+    let inline = if location == PayloadLocation::Inline { r.read(size)?.into() } else { Box::new([]) as Box<[u8]> };
+
+    let (width, height) = (r.u32()? as usize, r.u32()? as usize);
+    let depth = r.u32()? as usize;
+    if !cooked {
+        r.fstring()?;  // derived data key
+    }
+    Ok(UnrealMip { width, height, depth, flags, location, offset: offset as usize, size, element_count, inline })
+}
+
+/// Decompresses a payload written by `FArchive::SerializeCompressed`: the package file tag and chunk size,
+/// a summary of the total sizes, then the sizes and zlib data of each chunk.
+fn decompress_payload(data: &[u8], expected_size: usize) -> Result<Vec<u8>, ImageError> {
+    let mut r = Reader { data, pos: 0 };
+    let (tag, chunk_size) = (r.i64()? as u64, r.i64()? as usize);
+    if tag & 0xFFFFFFFF != PACKAGE_FILE_TAG {
+        return Err(invalid());
+    }
+    let chunk_size = if chunk_size as u64 == PACKAGE_FILE_TAG { LOADING_COMPRESSION_CHUNK_SIZE } else { chunk_size };
+    let (_, total_size) = (r.i64()?, r.i64()? as usize);
+    if chunk_size == 0 || total_size != expected_size {
+        return Err(invalid());
+    }
+    let mut chunks = Vec::new();
+    for _ in 0..total_size.div_ceil(chunk_size) {
+        chunks.push((r.i64()? as usize, r.i64()? as usize));
+    }
+    let mut out = Vec::with_capacity(total_size);
+    for (compressed_size, size) in chunks {
+        let chunk = decompress_with(Codec::Zlib, r.read(compressed_size)?, size).map_err(|_| invalid())?;
+        out.extend_from_slice(&chunk);
+    }
+    if out.len() != expected_size {
+        return Err(invalid());
+    }
+    Ok(out)
+}
+
+impl UnrealTexture {
+    fn mip(&self, mip: usize) -> Result<&UnrealMip, ImageError> {
+        self.mips.get(mip).ok_or_else(invalid)
+    }
+}
+
+#[wasm_bindgen]
+impl UnrealTexture {
+    /// Parses `FTexturePlatformData`, as found in a cooked texture export after the "None" ending its properties,
+    /// the strip flags and the pixel format name and skip offset that precede each platform's data.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The export data (or the whole `.uexp`)
+    /// * `offset` - Where the platform data starts in `data`
+    /// * `ue5` - If the package was cooked by Unreal Engine 5
+    /// * `bulk_data_start_offset` - `BulkDataStartOffset` from the package summary, for payloads at the end of the package
+    #[wasm_bindgen(constructor)]
+    pub fn new(data: &[u8], offset: usize, ue5: bool, bulk_data_start_offset: u64) -> Result<UnrealTexture, ImageError> {
+        let mut r = Reader { data, pos: offset };
+        if ue5 {
+            r.read(UE5_PLACEHOLDER_DERIVED_DATA_SIZE)?;
+        }
+        let (width, height) = (r.u32()? as usize, r.u32()? as usize);
+        let packed = r.u32()?;
+        let pixel_format = r.fstring()?;
+        if packed & PACKED_DATA_HAS_OPT_DATA != 0 {
+            r.read(8)?;  // extended data, mips in the tail
+        }
+        let first_mip = r.u32()? as usize;
+        let mip_count = r.u32()? as usize;
+        let mut mips = Vec::new();
+        for _ in 0..mip_count {
+            mips.push(read_mip(&mut r, ue5, bulk_data_start_offset)?);
+        }
+        Ok(UnrealTexture {
+            width,
+            height,
+            num_slices: (packed & PACKED_DATA_NUM_SLICES).max(1) as usize,
+            is_cubemap: packed & PACKED_DATA_CUBEMAP != 0,
+            first_mip,
+            pixel_format,
+            mips,
+        })
+    }
+
+    /// The `EPixelFormat` name, e.g. "PF_DXT5".
+    #[wasm_bindgen(getter)]
+    pub fn pixel_format(&self) -> String {
+        self.pixel_format.clone()
+    }
+
+    /// The matching `texdec` format, or an empty string if it isn't supported.
+    #[wasm_bindgen(getter)]
+    pub fn format(&self) -> String {
+        texdec_format(&self.pixel_format).unwrap_or("").to_string()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn mip_count(&self) -> usize {
+        self.mips.len()
+    }
+
+    pub fn mip_width(&self, mip: usize) -> usize {
+        self.mips.get(mip).map(|m| m.width).unwrap_or(0)
+    }
+
+    pub fn mip_height(&self, mip: usize) -> usize {
+        self.mips.get(mip).map(|m| m.height).unwrap_or(0)
+    }
+
+    /// The number of slices in a mip: depth for volume textures, otherwise `num_slices`.
+    pub fn mip_depth(&self, mip: usize) -> usize {
+        self.mips.get(mip).map(|m| m.depth.max(1)).unwrap_or(0)
+    }
+
+    /// Where a mip's payload is: "inline", "package", "ubulk", "uptnl", or "none" if it wasn't cooked.
+    pub fn mip_location(&self, mip: usize) -> String {
+        match self.mips.get(mip).map(|m| m.location) {
+            Some(PayloadLocation::Inline) => "inline",
+            Some(PayloadLocation::Package) => "package",
+            Some(PayloadLocation::Bulk) => "ubulk",
+            Some(PayloadLocation::Optional) => "uptnl",
+            Some(PayloadLocation::None) | None => "none",
+        }.to_string()
+    }
+
+    /// The raw payload of a mip, decompressed if it was stored with zlib.
+    ///
+    /// # Arguments
+    ///
+    /// * `mip` - The mip index
+    /// * `package` - The `.uasset` followed by the `.uexp`, for "package" payloads
+    /// * `ubulk`, `uptnl` - The contents of the `.ubulk` and `.uptnl` files, or empty arrays if there are none
+    pub fn mip_data(&self, mip: usize, package: &[u8], ubulk: &[u8], uptnl: &[u8]) -> Result<Box<[u8]>, ImageError> {
+        let m = self.mip(mip)?;
+        let source = match m.location {
+            PayloadLocation::Inline => &m.inline[..],
+            PayloadLocation::Package => package,
+            PayloadLocation::Bulk => ubulk,
+            PayloadLocation::Optional => uptnl,
+            PayloadLocation::None => return Err(ImageError::data_too_short(&self.pixel_format, m.size, 0))
+        };
+        let offset = if m.location == PayloadLocation::Inline { 0 } else { m.offset };
+        let data = source.get(offset..offset + m.size)
+            .ok_or_else(|| ImageError::data_too_short(&self.pixel_format, offset + m.size, source.len()))?;
+        if m.flags & BULKDATA_SERIALIZE_COMPRESSED_ZLIB != 0 {
+            return decompress_payload(data, m.element_count).map(|v| v.into());
+        }
+        Ok(data.into())
+    }
+
+    /// Decodes the first slice of a mip to RGBA, top row first. Takes the same arguments as `mip_data`.
+    pub fn decode_mip(&self, mip: usize, package: &[u8], ubulk: &[u8], uptnl: &[u8]) -> Result<Box<[u8]>, ImageError> {
+        let format = texdec_format(&self.pixel_format).ok_or_else(|| ImageError::unknown_format(&self.pixel_format))?;
+        let m = self.mip(mip)?;
+        let mut data = self.mip_data(mip, package, ubulk, uptnl)?.into_vec();
+        let mut pixels = decode(format, &mut data, m.width, m.height, false, "", &[])?;
+        if self.pixel_format == "PF_G16" {
+            for px in pixels.chunks_exact_mut(4) {
+                px[1] = px[0];
+                px[2] = px[0];
+            }
+        }
+        Ok(pixels)
+    }
+}
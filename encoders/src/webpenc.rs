@@ -0,0 +1,667 @@
+use std::cmp::{max, min, Reverse};
+use std::collections::BinaryHeap;
+use std::panic;
+use wasm_bindgen::prelude::*;
+use crate::pngenc::flip_v;
+
+// VP8L (lossless WebP) bitstream writer.
+// Images with up to 256 colours are also tried with the colour indexing (palette) transform; everything else
+// uses subtract-green and the predictor. Both use LZ77 and a colour cache, which gets most of the gain for
+// texture data while keeping the encoder small.
+
+const CODE_LENGTH_ORDER: [usize; 19] = [17, 18, 0, 1, 2, 3, 4, 5, 16, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+const NUM_LENGTH_CODES: usize = 24;
+const NUM_DISTANCE_CODES: usize = 40;
+const PREDICTOR_BITS: usize = 4;
+const MIN_MATCH: usize = 4;
+const MAX_MATCH: usize = 4096;
+const MAX_DISTANCE: usize = (1 << 20) - 120;
+const HASH_BITS: usize = 16;
+const MAX_CHAIN: usize = 32;
+const MAX_CACHE_BITS: usize = 10;
+const COLOR_CACHE_MULTIPLIER: u32 = 0x1e35a7bd;
+
+pub(crate) struct BitWriter {
+    pub(crate) out: Vec<u8>,
+    acc: u64,
+    bits: usize,
+}
+
+impl BitWriter {
+    pub(crate) fn new() -> BitWriter {
+        BitWriter { out: Vec::new(), acc: 0, bits: 0 }
+    }
+
+    pub(crate) fn put(&mut self, value: u32, nbits: usize) {
+        if nbits == 0 {
+            return;
+        }
+        self.acc |= (value as u64 & ((1u64 << nbits) - 1)) << self.bits;
+        self.bits += nbits;
+        while self.bits >= 8 {
+            self.out.push(self.acc as u8);
+            self.acc >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    pub(crate) fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.out.push(self.acc as u8);
+        }
+        self.out
+    }
+}
+
+/// Builds length-limited Huffman code lengths for the given symbol counts.
+fn code_lengths(counts: &[u32], max_len: u8) -> Vec<u8> {
+    let mut counts = counts.to_vec();
+    loop {
+        let mut lengths = vec![0u8; counts.len()];
+        let used: Vec<usize> = (0..counts.len()).filter(|&i| counts[i] > 0).collect();
+        if used.len() <= 1 {
+            for &i in &used {
+                lengths[i] = 1;
+            }
+            return lengths;
+        }
+        // nodes: (weight, children), leaves are the symbol indices
+        let mut parent = vec![usize::MAX; counts.len() * 2];
+        let mut heap = BinaryHeap::new();
+        for &i in &used {
+            heap.push(Reverse((counts[i] as u64, i)));
+        }
+        let mut next = counts.len();
+        while heap.len() > 1 {
+            let Reverse((wa, a)) = heap.pop().unwrap();
+            let Reverse((wb, b)) = heap.pop().unwrap();
+            parent[a] = next;
+            parent[b] = next;
+            heap.push(Reverse((wa + wb, next)));
+            next += 1;
+        }
+        let mut too_long = false;
+        for &i in &used {
+            let mut depth = 0;
+            let mut n = i;
+            while parent[n] != usize::MAX {
+                n = parent[n];
+                depth += 1;
+            }
+            if depth > max_len as usize {
+                too_long = true;
+            }
+            lengths[i] = depth as u8;
+        }
+        if !too_long {
+            return lengths;
+        }
+        // flatten the distribution and try again
+        for c in counts.iter_mut().filter(|c| **c > 0) {
+            *c = max(1, *c >> 1);
+        }
+    }
+}
+
+/// Canonical codes for `lengths`, bit-reversed so they can be written LSB first.
+fn canonical_codes(lengths: &[u8]) -> Vec<u32> {
+    let mut bl_count = [0u32; 16];
+    for &l in lengths {
+        bl_count[l as usize] += 1;
+    }
+    bl_count[0] = 0;
+    let mut next_code = [0u32; 16];
+    let mut code = 0;
+    for bits in 1..16 {
+        code = (code + bl_count[bits - 1]) << 1;
+        next_code[bits] = code;
+    }
+    let mut codes = vec![0u32; lengths.len()];
+    for (i, &l) in lengths.iter().enumerate() {
+        if l > 0 {
+            let c = next_code[l as usize];
+            next_code[l as usize] += 1;
+            codes[i] = c.reverse_bits() >> (32 - l as u32);
+        }
+    }
+    codes
+}
+
+pub(crate) struct PrefixCode {
+    lengths: Vec<u8>,
+    codes: Vec<u32>,
+    /// A code with a single symbol takes no bits to write
+    single: bool,
+}
+
+impl PrefixCode {
+    fn new(counts: &[u32], max_len: u8) -> PrefixCode {
+        let lengths = code_lengths(counts, max_len);
+        let single = lengths.iter().filter(|&&l| l > 0).count() <= 1;
+        PrefixCode { codes: canonical_codes(&lengths), lengths, single }
+    }
+
+    fn put(&self, w: &mut BitWriter, symbol: usize) {
+        if !self.single {
+            w.put(self.codes[symbol], self.lengths[symbol] as usize);
+        }
+    }
+
+    fn write_header(&self, w: &mut BitWriter) {
+        let used: Vec<usize> = (0..self.lengths.len()).filter(|&i| self.lengths[i] > 0).collect();
+        if used.len() <= 2 && used.iter().all(|&s| s < 256) {
+            // simple code
+            w.put(1, 1);
+            let symbols = if used.is_empty() { vec![0] } else { used };
+            w.put(symbols.len() as u32 - 1, 1);
+            if symbols[0] < 2 {
+                w.put(0, 1);
+                w.put(symbols[0] as u32, 1);
+            } else {
+                w.put(1, 1);
+                w.put(symbols[0] as u32, 8);
+            }
+            if symbols.len() == 2 {
+                w.put(symbols[1] as u32, 8);
+            }
+            return;
+        }
+        w.put(0, 1);
+
+        // run-length encode the lengths with the code length alphabet (0-15 literal, 17/18 zero runs)
+        let mut tokens: Vec<(usize, u32)> = Vec::new();
+        let mut i = 0;
+        while i < self.lengths.len() {
+            let l = self.lengths[i];
+            if l == 0 {
+                let mut run = 1;
+                while i + run < self.lengths.len() && self.lengths[i + run] == 0 && run < 138 {
+                    run += 1;
+                }
+                if run >= 11 {
+                    tokens.push((18, run as u32 - 11));
+                } else if run >= 3 {
+                    tokens.push((17, run as u32 - 3));
+                } else {
+                    for _ in 0..run {
+                        tokens.push((0, 0));
+                    }
+                }
+                i += run;
+            } else {
+                tokens.push((l as usize, 0));
+                i += 1;
+            }
+        }
+        let mut counts = [0u32; 19];
+        for &(t, _) in &tokens {
+            counts[t] += 1;
+        }
+        let cl_code = PrefixCode::new(&counts, 7);
+        let mut num_codes = 19;
+        while num_codes > 4 && cl_code.lengths[CODE_LENGTH_ORDER[num_codes - 1]] == 0 {
+            num_codes -= 1;
+        }
+        w.put(num_codes as u32 - 4, 4);
+        for &sym in CODE_LENGTH_ORDER.iter().take(num_codes) {
+            w.put(cl_code.lengths[sym] as u32, 3);
+        }
+        // max_symbol: use the whole alphabet
+        w.put(0, 1);
+        for &(t, extra) in &tokens {
+            cl_code.put(w, t);
+            match t {
+                17 => w.put(extra, 3),
+                18 => w.put(extra, 7),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Splits a length or distance into its prefix symbol, extra bit count and extra bits value.
+fn prefix_encode(value: usize) -> (usize, usize, u32) {
+    let n = value - 1;
+    if n < 4 {
+        return (n, 0, 0);
+    }
+    let high = (usize::BITS - 1 - n.leading_zeros()) as usize;
+    let second = (n >> (high - 1)) & 1;
+    let extra_bits = high - 1;
+    (2 * high + second, extra_bits, (n & ((1 << extra_bits) - 1)) as u32)
+}
+
+#[derive(Clone, Copy)]
+enum Token {
+    Literal(u32),
+    Cache(usize),
+    Copy(usize, usize),
+}
+
+fn hash_pair(a: u32, b: u32) -> usize {
+    (((a as u64) << 32 | b as u64).wrapping_mul(0x9E3779B97F4A7C15) >> (64 - HASH_BITS)) as usize
+}
+
+fn find_matches(pixels: &[u32]) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; pixels.len()];
+    let insert = |i: usize, head: &mut Vec<usize>, prev: &mut Vec<usize>| {
+        if i + 1 < pixels.len() {
+            let h = hash_pair(pixels[i], pixels[i + 1]);
+            prev[i] = head[h];
+            head[h] = i;
+        }
+    };
+    let mut i = 0;
+    while i < pixels.len() {
+        let mut best_len = 0;
+        let mut best_dist = 0;
+        if i + MIN_MATCH <= pixels.len() {
+            let mut candidate = head[hash_pair(pixels[i], pixels[i + 1])];
+            let max_len = min(MAX_MATCH, pixels.len() - i);
+            let mut chain = 0;
+            while candidate != usize::MAX && chain < MAX_CHAIN && i - candidate <= MAX_DISTANCE {
+                let mut len = 0;
+                while len < max_len && pixels[candidate + len] == pixels[i + len] {
+                    len += 1;
+                }
+                if len > best_len {
+                    best_len = len;
+                    best_dist = i - candidate;
+                    if len == max_len {
+                        break;
+                    }
+                }
+                candidate = prev[candidate];
+                chain += 1;
+            }
+        }
+        if best_len >= MIN_MATCH {
+            tokens.push(Token::Copy(best_len, best_dist));
+            for j in i..i + best_len {
+                insert(j, &mut head, &mut prev);
+            }
+            i += best_len;
+        } else {
+            tokens.push(Token::Literal(pixels[i]));
+            insert(i, &mut head, &mut prev);
+            i += 1;
+        }
+    }
+    tokens
+}
+
+fn cache_index(p: u32, cache_bits: usize) -> usize {
+    (p.wrapping_mul(COLOR_CACHE_MULTIPLIER) >> (32 - cache_bits)) as usize
+}
+
+/// Replaces literals that are already in a colour cache of `1 << cache_bits` entries with cache references.
+/// Every decoded pixel goes into the cache, including the ones produced by copies.
+fn use_color_cache(tokens: &[Token], pixels: &[u32], cache_bits: usize) -> Vec<Token> {
+    let mut cache = vec![None; 1 << cache_bits];
+    let mut pos = 0;
+    tokens.iter().map(|&t| match t {
+        Token::Literal(p) => {
+            let index = cache_index(p, cache_bits);
+            pos += 1;
+            if cache[index] == Some(p) {
+                Token::Cache(index)
+            } else {
+                cache[index] = Some(p);
+                t
+            }
+        },
+        Token::Copy(len, _) => {
+            for &p in &pixels[pos..pos + len] {
+                cache[cache_index(p, cache_bits)] = Some(p);
+            }
+            pos += len;
+            t
+        },
+        Token::Cache(_) => t,
+    }).collect()
+}
+
+/// Symbol counts for the five prefix codes (green/length/cache, red, blue, alpha, distance).
+fn histograms(tokens: &[Token], cache_bits: usize) -> [Vec<u32>; 5] {
+    let cache_size = if cache_bits > 0 { 1 << cache_bits } else { 0 };
+    let mut green = vec![0u32; 256 + NUM_LENGTH_CODES + cache_size];
+    let mut red = vec![0u32; 256];
+    let mut blue = vec![0u32; 256];
+    let mut alpha = vec![0u32; 256];
+    let mut dist = vec![0u32; NUM_DISTANCE_CODES];
+    for t in tokens {
+        match *t {
+            Token::Literal(p) => {
+                green[(p >> 8 & 0xff) as usize] += 1;
+                red[(p >> 16 & 0xff) as usize] += 1;
+                blue[(p & 0xff) as usize] += 1;
+                alpha[(p >> 24) as usize] += 1;
+            },
+            Token::Cache(index) => green[256 + NUM_LENGTH_CODES + index] += 1,
+            Token::Copy(len, d) => {
+                green[256 + prefix_encode(len).0] += 1;
+                dist[prefix_encode(d + 120).0] += 1;
+            }
+        }
+    }
+    [green, red, blue, alpha, dist]
+}
+
+/// Estimated size in bits of the entropy-coded symbols (headers and extra bits are left out).
+fn estimate_bits(histograms: &[Vec<u32>; 5]) -> u64 {
+    histograms.iter().map(|counts| {
+        let lengths = code_lengths(counts, 15);
+        if lengths.iter().filter(|&&l| l > 0).count() <= 1 {
+            // a single symbol takes no bits
+            return 0;
+        }
+        counts.iter().zip(&lengths).map(|(&c, &l)| c as u64 * l as u64).sum::<u64>()
+    }).sum()
+}
+
+/// Writes an entropy-coded image with a single group of prefix codes. The main image also gets the colour
+/// cache size that gives the smallest estimated output.
+fn write_image_data(w: &mut BitWriter, pixels: &[u32], is_main: bool) {
+    let matches = find_matches(pixels);
+    let mut best = (estimate_bits(&histograms(&matches, 0)), 0, matches.clone());
+    if is_main {
+        for cache_bits in 1..=MAX_CACHE_BITS {
+            let tokens = use_color_cache(&matches, pixels, cache_bits);
+            // the cache codes make the green header bigger; charge roughly one bit per cache entry
+            let cost = estimate_bits(&histograms(&tokens, cache_bits)) + (1 << cache_bits);
+            if cost < best.0 {
+                best = (cost, cache_bits, tokens);
+            }
+        }
+    }
+    let (_, cache_bits, tokens) = best;
+    let codes = histograms(&tokens, cache_bits).map(|counts| PrefixCode::new(&counts, 15));
+
+    if cache_bits > 0 {
+        w.put(1, 1);
+        w.put(cache_bits as u32, 4);
+    } else {
+        w.put(0, 1);
+    }
+    if is_main {
+        // no meta prefix codes
+        w.put(0, 1);
+    }
+    for code in &codes {
+        code.write_header(w);
+    }
+    for t in &tokens {
+        match *t {
+            Token::Literal(p) => {
+                codes[0].put(w, (p >> 8 & 0xff) as usize);
+                codes[1].put(w, (p >> 16 & 0xff) as usize);
+                codes[2].put(w, (p & 0xff) as usize);
+                codes[3].put(w, (p >> 24) as usize);
+            },
+            Token::Cache(index) => codes[0].put(w, 256 + NUM_LENGTH_CODES + index),
+            Token::Copy(len, d) => {
+                let (sym, nbits, extra) = prefix_encode(len);
+                codes[0].put(w, 256 + sym);
+                w.put(extra, nbits);
+                let (sym, nbits, extra) = prefix_encode(d + 120);
+                codes[4].put(w, sym);
+                w.put(extra, nbits);
+            }
+        }
+    }
+}
+
+fn average2(a: u32, b: u32) -> u32 {
+    (((a ^ b) & 0xfefefefe) >> 1) + (a & b)
+}
+
+fn channel(p: u32, shift: u32) -> i32 {
+    (p >> shift & 0xff) as i32
+}
+
+fn select(l: u32, t: u32, tl: u32) -> u32 {
+    let mut pl = 0;
+    let mut pt = 0;
+    for shift in [0, 8, 16, 24] {
+        let p = channel(l, shift) + channel(t, shift) - channel(tl, shift);
+        pl += (p - channel(l, shift)).abs();
+        pt += (p - channel(t, shift)).abs();
+    }
+    if pl < pt { l } else { t }
+}
+
+fn clamp_add_subtract_full(a: u32, b: u32, c: u32) -> u32 {
+    let mut out = 0;
+    for shift in [0, 8, 16, 24] {
+        let v = (channel(a, shift) + channel(b, shift) - channel(c, shift)).clamp(0, 255);
+        out |= (v as u32) << shift;
+    }
+    out
+}
+
+fn clamp_add_subtract_half(a: u32, b: u32) -> u32 {
+    let mut out = 0;
+    for shift in [0, 8, 16, 24] {
+        let ca = channel(a, shift);
+        let v = (ca + (ca - channel(b, shift)) / 2).clamp(0, 255);
+        out |= (v as u32) << shift;
+    }
+    out
+}
+
+fn predict(mode: u32, pixels: &[u32], i: usize, width: usize) -> u32 {
+    let l = pixels[i - 1];
+    let t = pixels[i - width];
+    let tr = pixels[i - width + 1];
+    let tl = pixels[i - width - 1];
+    match mode {
+        0 => 0xff000000,
+        1 => l,
+        2 => t,
+        3 => tr,
+        4 => tl,
+        5 => average2(average2(l, tr), t),
+        6 => average2(l, tl),
+        7 => average2(l, t),
+        8 => average2(tl, t),
+        9 => average2(t, tr),
+        10 => average2(average2(l, tl), average2(t, tr)),
+        11 => select(l, t, tl),
+        12 => clamp_add_subtract_full(l, t, tl),
+        _ => clamp_add_subtract_half(average2(l, t), tl),
+    }
+}
+
+fn sub_pixels(a: u32, b: u32) -> u32 {
+    let mut out = 0;
+    for shift in [0, 8, 16, 24] {
+        out |= ((a >> shift & 0xff).wrapping_sub(b >> shift & 0xff) & 0xff) << shift;
+    }
+    out
+}
+
+fn residual_cost(r: u32) -> u32 {
+    let mut cost = 0;
+    for shift in [0, 8, 16, 24] {
+        let c = r >> shift & 0xff;
+        cost += min(c, 256 - c);
+    }
+    cost
+}
+
+/// Applies the predictor transform, choosing the best mode for each tile. Returns the residuals and tile modes.
+fn apply_predictor(pixels: &[u32], width: usize, height: usize) -> (Vec<u32>, Vec<u32>) {
+    let tile = 1 << PREDICTOR_BITS;
+    let tiles_x = (width + tile - 1) / tile;
+    let tiles_y = (height + tile - 1) / tile;
+    let mut modes = vec![0u32; tiles_x * tiles_y];
+    for ty in 0..tiles_y {
+        for tx in 0..tiles_x {
+            let mut best = (u64::MAX, 0);
+            for mode in 0..14 {
+                let mut cost = 0u64;
+                for y in max(1, ty * tile)..min(height, (ty + 1) * tile) {
+                    for x in max(1, tx * tile)..min(width, (tx + 1) * tile) {
+                        let i = y * width + x;
+                        cost += residual_cost(sub_pixels(pixels[i], predict(mode, pixels, i, width))) as u64;
+                    }
+                }
+                if cost < best.0 {
+                    best = (cost, mode);
+                }
+            }
+            modes[ty * tiles_x + tx] = best.1;
+        }
+    }
+    let mut residuals = vec![0u32; pixels.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let i = y * width + x;
+            let pred = if x == 0 && y == 0 {
+                0xff000000
+            } else if y == 0 {
+                pixels[i - 1]
+            } else if x == 0 {
+                pixels[i - width]
+            } else {
+                predict(modes[(y / tile) * tiles_x + x / tile], pixels, i, width)
+            };
+            residuals[i] = sub_pixels(pixels[i], pred);
+        }
+    }
+    (residuals, modes.iter().map(|m| 0xff000000 | m << 8).collect())
+}
+
+/// Returns the distinct colours of `pixels` in ascending order, or `None` if there are more than 256.
+fn find_palette(pixels: &[u32]) -> Option<Vec<u32>> {
+    let mut colors: Vec<u32> = Vec::new();
+    for &p in pixels {
+        if let Err(i) = colors.binary_search(&p) {
+            if colors.len() == 256 {
+                return None;
+            }
+            colors.insert(i, p);
+        }
+    }
+    Some(colors)
+}
+
+/// Colour indexing transform: writes the palette and returns the packed index image.
+/// Palettes of up to 16 colours bundle 2, 4 or 8 indices into each pixel's green channel.
+fn apply_palette(w: &mut BitWriter, pixels: &[u32], width: usize, height: usize, palette: &[u32]) -> Vec<u32> {
+    let width_bits = match palette.len() {
+        0..=2 => 3,
+        3..=4 => 2,
+        5..=16 => 1,
+        _ => 0,
+    };
+    w.put(1, 1);
+    w.put(3, 2);
+    w.put(palette.len() as u32 - 1, 8);
+    let deltas: Vec<u32> = (0..palette.len())
+        .map(|i| if i == 0 { palette[0] } else { sub_pixels(palette[i], palette[i - 1]) })
+        .collect();
+    write_image_data(w, &deltas, false);
+
+    let bits_per_index = 8 >> width_bits;
+    let packed_width = (width + (1 << width_bits) - 1) >> width_bits;
+    let mut packed = vec![0xff000000u32; packed_width * height];
+    for y in 0..height {
+        for x in 0..width {
+            let index = palette.binary_search(&pixels[y * width + x]).unwrap() as u32;
+            packed[y * packed_width + (x >> width_bits)] |= index << (8 + (x & ((1 << width_bits) - 1)) * bits_per_index);
+        }
+    }
+    packed
+}
+
+/// Writes the VP8L bitstream (signature, header and image) for top-row-first RGBA data.
+pub(crate) fn encode_vp8l(width: usize, height: usize, rgba: &[u8]) -> Vec<u8> {
+    let pixels: Vec<u32> = (0..width * height).map(|i| {
+        let o = i * 4;
+        let px = if o + 4 <= rgba.len() { &rgba[o..o + 4] } else { &[0, 0, 0, 0][..] };
+        (px[3] as u32) << 24 | (px[0] as u32) << 16 | (px[1] as u32) << 8 | px[2] as u32
+    }).collect();
+    let has_alpha = pixels.iter().any(|p| p >> 24 != 0xff);
+
+    let header = || {
+        let mut w = BitWriter::new();
+        w.put(0x2f, 8);
+        w.put(width as u32 - 1, 14);
+        w.put(height as u32 - 1, 14);
+        w.put(has_alpha as u32, 1);
+        w.put(0, 3);
+        w
+    };
+
+    let mut w = header();
+    let mut predicted = pixels.clone();
+    // subtract green
+    w.put(1, 1);
+    w.put(2, 2);
+    for p in predicted.iter_mut() {
+        let g = *p >> 8 & 0xff;
+        let r = ((*p >> 16 & 0xff).wrapping_sub(g)) & 0xff;
+        let b = ((*p & 0xff).wrapping_sub(g)) & 0xff;
+        *p = (*p & 0xff00ff00) | r << 16 | b;
+    }
+
+    // predictor
+    let (residuals, modes) = apply_predictor(&predicted, width, height);
+    w.put(1, 1);
+    w.put(0, 2);
+    w.put(PREDICTOR_BITS as u32 - 2, 3);
+    write_image_data(&mut w, &modes, false);
+
+    // no more transforms
+    w.put(0, 1);
+    write_image_data(&mut w, &residuals, true);
+    let out = w.finish();
+
+    match find_palette(&pixels) {
+        Some(palette) => {
+            let mut w = header();
+            let packed = apply_palette(&mut w, &pixels, width, height, &palette);
+            w.put(0, 1);
+            write_image_data(&mut w, &packed, true);
+            let indexed = w.finish();
+            if indexed.len() < out.len() { indexed } else { out }
+        },
+        None => out,
+    }
+}
+
+/// Wraps `chunks` (already-formed RIFF chunks) in a WebP RIFF container.
+pub(crate) fn riff_container(chunks: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(chunks.len() + 12);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(chunks.len() as u32 + 4).to_le_bytes());
+    out.extend_from_slice(b"WEBP");
+    out.extend_from_slice(chunks);
+    out
+}
+
+/// Appends a RIFF chunk, padding it to an even length.
+pub(crate) fn write_chunk(out: &mut Vec<u8>, fourcc: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(fourcc);
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+#[wasm_bindgen]
+/// Encodes RGBA data as a lossless WebP. Images larger than 16384 pixels in either direction can't be stored.
+pub fn encode_webp(width: u32, height: u32, data: &[u8], flip: bool) -> Box<[u8]> {
+    panic::set_hook(Box::new(console_error_panic_hook::hook));
+    if width == 0 || height == 0 || width > 16384 || height > 16384 {
+        return [].into();
+    }
+    let rgba = if flip {flip_v(width as usize, height as usize, data)} else {data.into()};
+    let mut chunks = Vec::new();
+    write_chunk(&mut chunks, b"VP8L", &encode_vp8l(width as usize, height as usize, &rgba));
+    riff_container(&chunks).into()
+}
@@ -0,0 +1,740 @@
+"""Writes the Basis Universal fixtures in this directory: `.basis` and `.ktx2` files and the reference decode
+of every image and mip level (`<file>_<image>_<level>.png`, RGBA).
+
+The files are built from the format documentation (the .basis file format, the KTX2 specification and the
+KHR_DF/BasisLZ and UASTC specifications) and share no code with the crate:
+
+* ETC1S files use small random codebooks and a block layout with long runs, so the slices exercise every
+  endpoint prediction, the selector history, history run lengths and repeated prediction symbols. Each
+  level is also written as ETC1 (`_0_0.etc1`), checked here against an ETC1 decoder.
+* UASTC blocks use random endpoints and weights in every mode and every partition pattern, decoded with
+  the ASTC rules except for blue contraction, which UASTC doesn't have: about half of the RGB(A) subsets
+  have endpoints in the order that ASTC would contract, and they decode as stored. `uastc.ktx2` compresses
+  its levels with the `zstd` command line tool; `uastc_raw.ktx2` holds the same blocks uncompressed.
+
+Run with `python3 make_basis_fixtures.py` from this directory. The output is deterministic.
+"""
+import random
+import struct
+import subprocess
+
+from make_fixtures import write_png
+
+
+# bit streams and Huffman codes
+
+class BitWriter:
+    """Writes bits least significant first."""
+
+    def __init__(self):
+        self.bits = []
+
+    def put(self, count, value):
+        assert 0 <= value < (1 << count)
+        self.bits += [value >> i & 1 for i in range(count)]
+
+    def put_code(self, code, length):
+        # Huffman codes go most significant bit first
+        self.bits += [code >> i & 1 for i in reversed(range(length))]
+
+    def put_vlc(self, value, chunk_bits):
+        while True:
+            chunk, value = value & ((1 << chunk_bits) - 1), value >> chunk_bits
+            self.put(chunk_bits + 1, chunk | (1 << chunk_bits if value else 0))
+            if not value:
+                return
+
+    def data(self):
+        padded = self.bits + [0] * (-len(self.bits) % 8)
+        return bytes(sum(padded[i + j] << j for j in range(8)) for i in range(0, len(padded), 8))
+
+
+def code_lengths(freqs, limit):
+    """Length-limited Huffman code lengths by package-merge."""
+    lengths = [0] * len(freqs)
+    used = sorted((f, [i]) for i, f in enumerate(freqs) if f > 0)
+    if len(used) == 1:
+        lengths[used[0][1][0]] = 1
+    if len(used) <= 1:
+        return lengths
+    current = used
+    for _ in range(limit - 1):
+        packages = [(current[k][0] + current[k + 1][0], current[k][1] + current[k + 1][1])
+                    for k in range(0, len(current) - 1, 2)]
+        current = sorted(used + packages, key=lambda t: t[0])
+    for _, symbols in current[:2 * len(used) - 2]:
+        for s in symbols:
+            lengths[s] += 1
+    return lengths
+
+
+def canonical_codes(lengths):
+    codes = [0] * len(lengths)
+    code = 0
+    for length in range(1, max(lengths + [0]) + 1):
+        for s, l in enumerate(lengths):
+            if l == length:
+                codes[s] = code
+                code += 1
+        code <<= 1
+    return codes
+
+
+class Huffman:
+    def __init__(self, total_syms):
+        self.freqs = [0] * total_syms
+        self.lengths = self.codes = None
+
+    def build(self):
+        self.lengths = code_lengths(self.freqs, 16)
+        self.codes = canonical_codes(self.lengths)
+
+    def put(self, w, sym):
+        w.put_code(self.codes[sym], self.lengths[sym])
+
+    def write_table(self, w):
+        total = len(self.freqs) if any(self.freqs) else 0
+        w.put(14, total)
+        if total == 0:
+            return
+        # code lengths as code length codes: 0-16 literally, 17/18 for runs of zeros, 19/20 to repeat the last
+        tokens = []
+        i = 0
+        while i < total:
+            l = self.lengths[i]
+            run = 1
+            while i + run < total and self.lengths[i + run] == l:
+                run += 1
+            if l == 0 and run >= 3:
+                run = min(run, 138)
+                tokens.append((18, 7, run - 11) if run >= 11 else (17, 3, run - 3))
+            elif l != 0 and i > 0 and self.lengths[i - 1] == l and run >= 3:
+                run = min(run, 134)
+                tokens.append((20, 7, run - 7) if run >= 7 else (19, 2, run - 3))
+            else:
+                run = 1
+                tokens.append((l, 0, 0))
+            i += run
+        freqs = [0] * 21
+        for sym, _, _ in tokens:
+            freqs[sym] += 1
+        lengths = code_lengths(freqs, 7)
+        codes = canonical_codes(lengths)
+        order = [17, 18, 19, 20, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15, 16]
+        count = max(k for k, sym in enumerate(order) if lengths[sym]) + 1
+        w.put(5, count)
+        for sym in order[:count]:
+            w.put(3, lengths[sym])
+        for sym, extra_bits, extra in tokens:
+            w.put_code(codes[sym], lengths[sym])
+            w.put(extra_bits, extra)
+
+
+# ETC1S
+
+INTEN_TABLES = [[-8, -2, 2, 8], [-17, -5, 5, 17], [-29, -9, 9, 29], [-42, -13, 13, 42],
+                [-60, -18, 18, 60], [-80, -24, 24, 80], [-106, -33, 33, 106], [-183, -47, 47, 183]]
+HISTORY_SIZE = 8
+
+
+def etc1s_colors(endpoint):
+    (r, g, b), inten = endpoint
+    base = [(c << 3) | (c >> 2) for c in (r, g, b)]
+    return [[min(255, max(0, c + d)) for c in base] for d in INTEN_TABLES[inten]]
+
+
+def etc1s_pixels(endpoints, selectors, blocks, blocks_x, width, height):
+    """Pixel rows (lists of RGB) for a slice's (endpoint, selector) pairs."""
+    pixels = []
+    for y in range(height):
+        for x in range(width):
+            e, s = blocks[(y // 4) * blocks_x + x // 4]
+            pixels.append(etc1s_colors(endpoints[e])[selectors[s][(y % 4) * 4 + x % 4]])
+    return pixels
+
+
+def etc1_block(endpoint, selector):
+    """An ETC1 block in differential mode with a zero delta."""
+    (r, g, b), inten = endpoint
+    index_bits = 0
+    for i, sel in enumerate(selector):
+        # ETC1 pixel indices (most significant bit, least significant bit) pick +small, +large, -small, -large
+        etc1 = [3, 2, 0, 1][sel]
+        x, y = i % 4, i // 4
+        index_bits |= (etc1 & 1) << (x * 4 + y) | (etc1 >> 1) << (x * 4 + y + 16)
+    return bytes([r << 3, g << 3, b << 3, inten << 5 | inten << 2 | 2]) + struct.pack('>I', index_bits)
+
+
+def decode_etc1_block(block):
+    """Decodes a differential ETC1 block (both halves use the same colour here) into 16 RGB pixels, row-major."""
+    assert block[3] & 2
+    base = []
+    for c in range(3):
+        c5 = block[c] >> 3
+        delta = block[c] & 7
+        assert delta == 0
+        base.append((c5 << 3) | (c5 >> 2))
+    table = [[2, 8], [5, 17], [9, 29], [13, 42], [18, 60], [24, 80], [33, 106], [47, 183]]
+    inten = block[3] >> 5
+    assert inten == (block[3] >> 2) & 7
+    bits = struct.unpack('>I', block[4:8])[0]
+    pixels = [None] * 16
+    for x in range(4):
+        for y in range(4):
+            lsb, msb = bits >> (x * 4 + y) & 1, bits >> (x * 4 + y + 16) & 1
+            small, large = table[inten]
+            d = [small, large, -small, -large][msb << 1 | lsb]
+            pixels[y * 4 + x] = [min(255, max(0, c + d)) for c in base]
+    return pixels
+
+
+class SelectorHistory:
+    def __init__(self):
+        self.values = [0] * HISTORY_SIZE
+        self.rover = HISTORY_SIZE // 2
+
+    def add(self, value):
+        self.values[self.rover] = value
+        self.rover += 1
+        if self.rover == HISTORY_SIZE:
+            self.rover = HISTORY_SIZE // 2
+
+    def use(self, index):
+        if index > 0:
+            self.values[index // 2], self.values[index] = self.values[index], self.values[index // 2]
+
+
+class Etc1sModels:
+    def __init__(self, num_endpoints, num_selectors):
+        self.num_endpoints, self.num_selectors = num_endpoints, num_selectors
+        self.endpoint_pred = Huffman(257)
+        self.delta_endpoint = Huffman(num_endpoints)
+        self.selector = Huffman(num_selectors + HISTORY_SIZE + 1)
+        self.history_rle = Huffman(64)
+
+    def all(self):
+        return [self.endpoint_pred, self.delta_endpoint, self.selector, self.history_rle]
+
+
+def etc1s_slice_ops(models, blocks, blocks_x, blocks_y):
+    """The symbols and raw bits of a slice, in the order they're written: ('h', model, symbol), ('vlc', bits, value)."""
+    ops = []
+    num_endpoints = models.num_endpoints
+    history = SelectorHistory()
+    history_first, history_rle = models.num_selectors, models.num_selectors + HISTORY_SIZE
+    rle_count = 0
+    prev_endpoint = 0
+    prev_pred_sym, pred_repeat = 0, 0
+    endpoint_at = lambda x, y: blocks[y * blocks_x + x][0]
+
+    def prediction(x, y):
+        if x >= blocks_x or y >= blocks_y:
+            return 0
+        e = endpoint_at(x, y)
+        if x > 0 and e == endpoint_at(x - 1, y):
+            return 0
+        if y > 0 and e == endpoint_at(x, y - 1):
+            return 1
+        if x > 0 and y > 0 and e == endpoint_at(x - 1, y - 1):
+            return 2
+        return 3
+
+    # prediction symbols of every 2x2 group, with runs of the last symbol sent as repeats
+    group_syms = []
+    for gy in range(0, blocks_y, 2):
+        for gx in range(0, blocks_x, 2):
+            group_syms.append(prediction(gx, gy) | prediction(gx + 1, gy) << 2 |
+                              prediction(gx, gy + 1) << 4 | prediction(gx + 1, gy + 1) << 6)
+    group_ops = []
+    i = 0
+    while i < len(group_syms):
+        run = 0
+        while i + run < len(group_syms) and group_syms[i + run] == prev_pred_sym:
+            run += 1
+        if run >= 3:
+            group_ops += [[('h', models.endpoint_pred, 256), ('vlc', 4, run - 3)]] + [[] for _ in range(run - 1)]
+            i += run
+        else:
+            group_ops.append([('h', models.endpoint_pred, group_syms[i])])
+            prev_pred_sym = group_syms[i]
+            i += 1
+
+    group = 0
+    for by in range(blocks_y):
+        for bx in range(blocks_x):
+            if bx % 2 == 0 and by % 2 == 0:
+                ops += group_ops[group]
+                group += 1
+            e, s = blocks[by * blocks_x + bx]
+            if prediction(bx, by) == 3:
+                ops.append(('h', models.delta_endpoint, (e - prev_endpoint) % num_endpoints))
+            prev_endpoint = e
+
+            if rle_count > 0:
+                assert history.values[0] == s
+                rle_count -= 1
+                continue
+            run = 0
+            rest = blocks[by * blocks_x + bx:]
+            while run < len(rest) and rest[run][1] == history.values[0]:
+                run += 1
+            if run >= 3:
+                ops.append(('h', models.selector, history_rle))
+                if run - 3 < 63:
+                    ops.append(('h', models.history_rle, run - 3))
+                else:
+                    ops += [('h', models.history_rle, 63), ('vlc', 7, run - 3)]
+                rle_count = run - 1
+            elif s in history.values:
+                index = history.values.index(s)
+                ops.append(('h', models.selector, history_first + index))
+                history.use(index)
+            else:
+                ops.append(('h', models.selector, s))
+                history.add(s)
+    return ops
+
+
+def write_ops(ops):
+    w = BitWriter()
+    for op in ops:
+        if op[0] == 'h':
+            op[1].put(w, op[2])
+        else:
+            w.put_vlc(op[2], op[1])
+    return w.data()
+
+
+def etc1s_codebooks(endpoints, selectors, models):
+    """The endpoint, selector and table data shared by every slice."""
+    w = BitWriter()
+    color_models = [Huffman(32) for _ in range(3)]
+    inten_model = Huffman(8)
+    ops = []
+    prev_color, prev_inten = [16, 16, 16], 0
+    for color, inten in endpoints:
+        ops.append((inten_model, (inten - prev_inten) % 8))
+        prev_inten = inten
+        for c in range(3):
+            model = color_models[0 if prev_color[c] <= 9 else 1 if prev_color[c] <= 21 else 2]
+            ops.append((model, (color[c] - prev_color[c]) % 32))
+            prev_color[c] = color[c]
+    for model, sym in ops:
+        model.freqs[sym] += 1
+    for model in color_models + [inten_model]:
+        model.build()
+        model.write_table(w)
+    w.put(1, 0)  # not greyscale
+    for model, sym in ops:
+        model.put(w, sym)
+    endpoint_data = w.data()
+
+    # selectors: each row byte as the XOR with the same row of the previous selector
+    w = BitWriter()
+    w.put(1, 0)  # no global codebook
+    w.put(1, 0)  # no hybrid codebook
+    w.put(1, 0)  # not raw
+    rows = [[sum(sel[r * 4 + x] << (x * 2) for x in range(4)) for r in range(4)] for sel in selectors]
+    delta_model = Huffman(256)
+    for prev, cur in zip(rows, rows[1:]):
+        for r in range(4):
+            delta_model.freqs[prev[r] ^ cur[r]] += 1
+    delta_model.build()
+    delta_model.write_table(w)
+    for i, cur in enumerate(rows):
+        for r in range(4):
+            if i == 0:
+                w.put(8, cur[r])
+            else:
+                delta_model.put(w, rows[i - 1][r] ^ cur[r])
+    selector_data = w.data()
+
+    w = BitWriter()
+    for model in models.all():
+        model.build()
+        model.write_table(w)
+    w.put(13, HISTORY_SIZE)
+    return endpoint_data, selector_data, w.data()
+
+
+def etc1s_layout(blocks_x, blocks_y, num_endpoints, num_selectors, rng):
+    """Block (endpoint, selector) pairs: a band of one endpoint and selector (repeated prediction symbols and a
+    long selector run), a diagonal (upper left predictions), then pairs of blocks with some random ones."""
+    blocks = []
+    for by in range(blocks_y):
+        for bx in range(blocks_x):
+            if by < 2:
+                e = 0
+            elif by < 4:
+                e = (bx - by) % num_endpoints
+            else:
+                e = (bx // 2 + by // 2 * 3) % num_endpoints
+            s = 0 if by < 4 else (bx // 3 + by) % num_selectors
+            if by >= 4 and rng.random() < 0.25:
+                e, s = rng.randrange(num_endpoints), rng.randrange(num_selectors)
+            blocks.append((e, s))
+    return blocks
+
+
+def random_codebooks(rng, num_endpoints, num_selectors, grey=False):
+    endpoints = []
+    for _ in range(num_endpoints):
+        if grey:
+            c = rng.randrange(32)
+            endpoints.append(((c, c, c), rng.randrange(8)))
+        else:
+            endpoints.append(((rng.randrange(32), rng.randrange(32), rng.randrange(32)), rng.randrange(8)))
+    selectors = [[rng.randrange(4) for _ in range(16)] for _ in range(num_selectors)]
+    return endpoints, selectors
+
+
+# UASTC
+
+# per mode: code, code length, weight bits, endpoint range, components, hint bits, subsets, pattern bits, dual plane
+UASTC_MODES = [
+    (0x1, 4, 4, 19, 3, 15, 1, 0, False), (0x35, 6, 2, 20, 3, 15, 1, 0, False), (0x1d, 5, 3, 8, 3, 15, 2, 5, False),
+    (0x3, 5, 2, 7, 3, 15, 3, 4, False), (0x13, 5, 2, 12, 3, 15, 2, 5, False), (0xb, 5, 3, 20, 3, 15, 1, 0, False),
+    (0x1b, 5, 2, 18, 3, 15, 1, 0, True), (0x7, 5, 2, 12, 3, 15, 2, 5, False), (0x17, 5, 0, 0, 4, 0, 1, 0, False),
+    (0xf, 5, 2, 8, 4, 23, 2, 5, False), (0x2, 3, 4, 13, 4, 17, 1, 0, False), (0x0, 2, 2, 13, 4, 17, 1, 0, True),
+    (0x6, 3, 3, 19, 4, 17, 1, 0, False), (0x1f, 5, 1, 20, 4, 23, 1, 0, True), (0xd, 5, 2, 20, 4, 23, 1, 0, False),
+    (0x5, 7, 4, 20, 2, 23, 1, 0, False), (0x15, 6, 2, 20, 2, 23, 2, 5, False), (0x25, 6, 2, 20, 2, 23, 1, 0, True),
+    (0x9, 4, 5, 11, 3, 15, 1, 0, False),
+]
+# ASTC partition seeds of UASTC's pattern tables: 2 subsets shared with BC7, 3 subsets, and 2-subset merges of
+# BC7 3-subset patterns (mode 7)
+SEEDS_2 = [28, 20, 16, 29, 91, 9, 107, 72, 149, 204, 50, 114, 496, 17, 78, 39,
+           252, 828, 43, 156, 116, 210, 476, 273, 684, 359, 246, 195, 694, 524]
+SEEDS_3 = [260, 74, 32, 156, 183, 15, 745, 0, 335, 902, 254]
+SEEDS_BC7_3 = [36, 48, 61, 137, 161, 183, 226, 281, 302, 307, 479, 495, 593, 594, 605, 799, 812, 988, 993]
+# (bits, trits, quints) of the ASTC ranges
+RANGES = [(1, 0, 0), (0, 1, 0), (2, 0, 0), (0, 0, 1), (1, 1, 0), (3, 0, 0), (1, 0, 1), (2, 1, 0), (4, 0, 0),
+          (2, 0, 1), (3, 1, 0), (5, 0, 0), (3, 0, 1), (4, 1, 0), (6, 0, 0), (4, 0, 1), (5, 1, 0), (7, 0, 0),
+          (5, 0, 1), (6, 1, 0), (8, 0, 0)]
+# ASTC colour unquantization: C and the bit pattern of B for each (trits or quints, bits)
+UNQUANT = {(3, 1): (204, '000000000'), (3, 2): (93, 'b000b0bb0'), (3, 3): (44, 'cb000cbcb'),
+           (3, 4): (22, 'dcb000dcb'), (3, 5): (11, 'edcb000ed'), (3, 6): (5, 'fedcb000f'),
+           (5, 1): (113, '000000000'), (5, 2): (54, 'b0000bb00'), (5, 3): (26, 'cb0000cbc'),
+           (5, 4): (13, 'dcb0000dc'), (5, 5): (6, 'edcb0000e')}
+WEIGHT_UNQUANT = {1: [0, 64], 2: [0, 21, 43, 64], 3: [0, 9, 18, 27, 37, 46, 55, 64],
+                  4: [0, 4, 8, 12, 17, 21, 25, 29, 35, 39, 43, 47, 52, 56, 60, 64]}
+WEIGHT_UNQUANT[5] = [(v << 1 | v >> 4) + ((v << 1 | v >> 4) > 32) for v in range(32)]
+
+
+def hash52(p):
+    m = 0xffffffff
+    p ^= p >> 15
+    p = (p - (p << 17)) & m
+    p = (p + (p << 7)) & m
+    p = (p + (p << 4)) & m
+    p ^= p >> 5
+    p = (p + (p << 16)) & m
+    p ^= p >> 7
+    p ^= p >> 3
+    p = (p ^ (p << 6)) & m
+    p ^= p >> 17
+    return p
+
+
+def astc_partition(seed, x, y, z, count, small_block):
+    """select_partition from the ASTC specification."""
+    if small_block:
+        x, y, z = x << 1, y << 1, z << 1
+    seed += (count - 1) * 1024
+    rnum = hash52(seed)
+    seeds = [(rnum >> s) & 0xf for s in (0, 4, 8, 12, 16, 20, 24, 28, 18, 22, 26)] + [((rnum >> 30) | (rnum << 2)) & 0xf]
+    seeds = [s * s for s in seeds]
+    if seed & 1:
+        sh1, sh2 = (4 if seed & 2 else 5), (6 if count == 3 else 5)
+    else:
+        sh1, sh2 = (6 if count == 3 else 5), (4 if seed & 2 else 5)
+    sh3 = sh1 if seed & 0x10 else sh2
+    seeds = [s >> sh for s, sh in zip(seeds, [sh1, sh2] * 4 + [sh3] * 4)]
+    a = (seeds[0] * x + seeds[1] * y + seeds[10] * z + (rnum >> 14)) & 0x3f
+    b = (seeds[2] * x + seeds[3] * y + seeds[11] * z + (rnum >> 10)) & 0x3f
+    c = (seeds[4] * x + seeds[5] * y + seeds[8] * z + (rnum >> 6)) & 0x3f
+    d = (seeds[6] * x + seeds[7] * y + seeds[9] * z + (rnum >> 2)) & 0x3f
+    if count < 4:
+        d = 0
+    if count < 3:
+        c = 0
+    if a >= b and a >= c and a >= d:
+        return 0
+    if b >= c and b >= d:
+        return 1
+    return 2 if c >= d else 3
+
+
+def unquantize_color(value, r):
+    bits, trits, quints = RANGES[r]
+    low = value & ((1 << bits) - 1)
+    if not trits and not quints:
+        replicated = 0
+        for i in range(8):
+            replicated |= (low >> (bits - 1 - i % bits) & 1) << (7 - i)
+        return replicated
+    c, pattern = UNQUANT[(3 if trits else 5, bits)]
+    b = 0
+    for ch in pattern:
+        b = b << 1 | (0 if ch == '0' else low >> (ord(ch) - ord('a')) & 1)
+    a = 0x1ff if low & 1 else 0
+    t = ((value >> bits) * c + b) ^ a
+    return (a & 0x80) | (t >> 2)
+
+
+def uastc_block(mode, rng):
+    """A random UASTC block in the given mode and its pixels, decoded with the ASTC rules."""
+    code, code_len, weight_bits, r, comps, hint_bits, subsets, pattern_bits, dual = UASTC_MODES[mode]
+    w = BitWriter()
+    w.put(code_len, code)
+    if mode == 8:
+        color = [rng.randrange(256) for _ in range(4)]
+        for c in color:
+            w.put(8, c)
+        # ETC1 hints, which only transcoders to ETC1 read
+        w.put(20, rng.getrandbits(20))
+        return w.data() + bytes(16 - len(w.data())), [color] * 16
+    w.put(hint_bits, rng.getrandbits(hint_bits))
+
+    partition = [0] * 16
+    if subsets > 1:
+        seeds = SEEDS_3 if subsets == 3 else SEEDS_BC7_3 if mode == 7 else SEEDS_2
+        pattern = rng.randrange(len(seeds))
+        w.put(pattern_bits, pattern)
+        partition = [astc_partition(seeds[pattern], i % 4, i // 4, 0, subsets, True) for i in range(16)]
+    ccs = None
+    if mode in (6, 11, 13):
+        ccs = rng.randrange(4)
+        w.put(2, ccs)
+    elif mode == 17:
+        ccs = 3
+
+    bits, trits, quints = RANGES[r]
+    levels = (1 << bits) * (3 if trits else 5 if quints else 1)
+    values = [rng.randrange(levels) for _ in range(comps * 2 * subsets)]
+    if trits or quints:
+        base, group = (3, 5) if trits else (5, 3)
+        for g in range(0, len(values), group):
+            digits = [v >> bits for v in values[g:g + group]]
+            packed = sum(d * base ** k for k, d in enumerate(digits))
+            w.put((base ** len(digits) - 1).bit_length(), packed)
+    for v in values:
+        w.put(bits, v & ((1 << bits) - 1))
+
+    planes = 2 if dual else 1
+    weights = []
+    for i in range(16 * planes):
+        texel = i // planes
+        anchor = partition[texel] not in partition[:texel]
+        n = weight_bits - 1 if anchor else weight_bits
+        weights.append(rng.getrandbits(n) if n else 0)
+        w.put(n, weights[-1])
+    data = w.data()
+    assert len(data) <= 16, (mode, len(w.bits))
+
+    ep = [unquantize_color(v, r) for v in values]
+    pixels = []
+    for i in range(16):
+        v = ep[partition[i] * comps * 2:(partition[i] + 1) * comps * 2]
+        if comps == 2:
+            e0, e1 = [v[0], v[0], v[0], v[2]], [v[1], v[1], v[1], v[3]]
+        else:
+            e0 = [v[0], v[2], v[4], v[6] if comps == 4 else 255]
+            e1 = [v[1], v[3], v[5], v[7] if comps == 4 else 255]
+        px = []
+        for c in range(4):
+            weight = WEIGHT_UNQUANT[weight_bits][weights[i * planes + (1 if c == ccs else 0)]]
+            lo, hi = e0[c] << 8 | e0[c], e1[c] << 8 | e1[c]
+            px.append(((lo * (64 - weight) + hi * weight + 32) >> 6) >> 8)
+        pixels.append(px)
+    return data + bytes(16 - len(data)), pixels
+
+
+def uastc_level(modes, blocks_x, blocks_y, width, height, rng):
+    data, blocks = b'', []
+    for mode in modes:
+        block, pixels = uastc_block(mode, rng)
+        data += block
+        blocks.append(pixels)
+    image = [blocks[(y // 4) * blocks_x + x // 4][(y % 4) * 4 + x % 4] for y in range(height) for x in range(width)]
+    return data, image
+
+
+# containers
+
+def crc16(data, crc=0):
+    crc = ~crc & 0xffff
+    for byte in data:
+        q = byte ^ (crc >> 8)
+        k = ((q >> 4) ^ q) & 0xffff
+        crc = (((crc << 8) ^ k) ^ (k << 5) ^ (k << 12)) & 0xffff
+    return ~crc & 0xffff
+
+
+def write_basis(name, uastc, slices, has_alpha, codebooks=None, num_endpoints=0, num_selectors=0):
+    """slices: (image, level, width, height, is_alpha, data)."""
+    header_size, desc_size = 77, 23
+    data = bytearray(header_size + desc_size * len(slices))
+    parts = list(codebooks) if codebooks else []
+    offsets = []
+    for part in parts + [s[5] for s in slices]:
+        offsets.append(len(data))
+        data += part
+    for i, (image, level, width, height, is_alpha, body) in enumerate(slices):
+        data[header_size + i * desc_size:header_size + i * desc_size + 3] = image.to_bytes(3, 'little')
+        struct.pack_into('<BBHHHHIIH', data, header_size + i * desc_size + 3, level, 1 if is_alpha else 0,
+                         width, height, (width + 3) // 4, (height + 3) // 4,
+                         offsets[len(parts) + i], len(body), crc16(body))
+    flags = (0 if uastc else 1) | (4 if has_alpha else 0)
+    images = max(s[0] for s in slices) + 1
+    struct.pack_into('<HHH', data, 0, 0x4273, 0x13, header_size)
+    struct.pack_into('<I', data, 8, len(data) - header_size)
+    struct.pack_into('<H', data, 12, crc16(data[header_size:]))
+    data[14:17] = len(slices).to_bytes(3, 'little')
+    data[17:20] = images.to_bytes(3, 'little')
+    struct.pack_into('<BHB', data, 20, 1 if uastc else 0, flags, 0)
+    if codebooks:
+        struct.pack_into('<HI', data, 39, num_endpoints, offsets[0])
+        data[45:48] = len(parts[0]).to_bytes(3, 'little')
+        struct.pack_into('<HI', data, 48, num_selectors, offsets[1])
+        data[54:57] = len(parts[1]).to_bytes(3, 'little')
+        struct.pack_into('<II', data, 57, offsets[2], len(parts[2]))
+    struct.pack_into('<I', data, 65, header_size)
+    struct.pack_into('<H', data, 6, crc16(data[8:header_size]))
+    open(name, 'wb').write(data)
+
+
+def write_ktx2(name, width, height, layers, levels, supercompression, dfd_model, channels, sgd=b''):
+    """levels: the data of each level, largest first, as (stored bytes, uncompressed length)."""
+    samples = len(channels)
+    dfd = struct.pack('<IIBBBBBBBB8B', 0, 2 | (24 + 16 * samples) << 16, dfd_model, 1, 2, 0, 3, 3, 0, 0,
+                      16 if dfd_model == 166 and supercompression == 0 else 0, 0, 0, 0, 0, 0, 0, 0)
+    for i, (channel, bit_length) in enumerate(channels):
+        dfd += struct.pack('<HBBIII', i * 64, bit_length, channel, 0, 0, 0xffffffff)
+    dfd = struct.pack('<I', len(dfd) + 4) + dfd
+
+    index_size = 80 + 24 * len(levels)
+    data = bytearray(index_size) + dfd
+    sgd_ofs = 0
+    if sgd:
+        data += bytes(-len(data) % 8)
+        sgd_ofs = len(data)
+        data += sgd
+    align = 16 if supercompression == 0 else 1
+    level_index = [None] * len(levels)
+    for level in reversed(range(len(levels))):
+        stored, uncompressed = levels[level]
+        data += bytes(-len(data) % align)
+        level_index[level] = (len(data), len(stored), uncompressed)
+        data += stored
+    data[:12] = bytes([0xab, 0x4b, 0x54, 0x58, 0x20, 0x32, 0x30, 0xbb, 0x0d, 0x0a, 0x1a, 0x0a])
+    struct.pack_into('<IIIIIIIII', data, 12, 0, 1, width, height, 0, layers if layers > 1 else 0, 1, len(levels),
+                     supercompression)
+    struct.pack_into('<IIIIQQ', data, 48, index_size, len(dfd), 0, 0, sgd_ofs, len(sgd))
+    for level, entry in enumerate(level_index):
+        struct.pack_into('<QQQ', data, 80 + level * 24, *entry)
+    open(name, 'wb').write(data)
+
+
+def write_level_png(name, width, height, rgba):
+    write_png(name, width, height, [c for px in rgba for c in px])
+
+
+def etc1s_fixtures(rng):
+    # .basis: one image with alpha, two levels
+    endpoints, selectors = random_codebooks(rng, 12, 10)
+    grey, _ = random_codebooks(rng, 6, 0, grey=True)
+    endpoints += grey
+    models = Etc1sModels(len(endpoints), len(selectors))
+    slices, decoded = [], []
+    for level, (width, height) in enumerate([(72, 20), (36, 10)]):
+        blocks_x, blocks_y = (width + 3) // 4, (height + 3) // 4
+        rgb = etc1s_layout(blocks_x, blocks_y, 12, len(selectors), rng)
+        alpha = [(12 + e % 6, s) for e, s in etc1s_layout(blocks_x, blocks_y, 6, len(selectors), rng)]
+        slices.append((0, level, width, height, False, etc1s_slice_ops(models, rgb, blocks_x, blocks_y)))
+        slices.append((0, level, width, height, True, etc1s_slice_ops(models, alpha, blocks_x, blocks_y)))
+        colors = etc1s_pixels(endpoints, selectors, rgb, blocks_x, width, height)
+        alphas = etc1s_pixels(endpoints, selectors, alpha, blocks_x, width, height)
+        decoded.append((width, height, [c + [a[1]] for c, a in zip(colors, alphas)], rgb, blocks_x))
+    for s in slices:
+        for op in s[5]:
+            if op[0] == 'h':
+                op[1].freqs[op[2]] += 1
+    codebooks = etc1s_codebooks(endpoints, selectors, models)
+    slices = [s[:5] + (write_ops(s[5]),) for s in slices]
+    write_basis('etc1s.basis', False, slices, True, codebooks, len(endpoints), len(selectors))
+    for level, (width, height, rgba, rgb, blocks_x) in enumerate(decoded):
+        write_level_png('etc1s_0_%d.png' % level, width, height, rgba)
+    # the level 0 colour as ETC1, which must decode to the same pixels
+    width, height, rgba, rgb, blocks_x = decoded[0]
+    etc1 = b''.join(etc1_block(endpoints[e], selectors[s]) for e, s in rgb)
+    for b, (e, s) in enumerate(rgb):
+        pixels = decode_etc1_block(etc1[b * 8:b * 8 + 8])
+        assert pixels == [etc1s_colors(endpoints[e])[sel] for sel in selectors[s]]
+    open('etc1s_0_0.etc1', 'wb').write(etc1)
+
+    # KTX2 (BasisLZ): two layers without alpha, three levels
+    endpoints, selectors = random_codebooks(rng, 20, 16)
+    models = Etc1sModels(len(endpoints), len(selectors))
+    level_ops, descs = [], []
+    for level, (width, height) in enumerate([(24, 16), (12, 8), (6, 4)]):
+        blocks_x, blocks_y = (width + 3) // 4, (height + 3) // 4
+        ops = []
+        for layer in range(2):
+            blocks = etc1s_layout(blocks_x, blocks_y, len(endpoints), len(selectors), rng)
+            ops.append(etc1s_slice_ops(models, blocks, blocks_x, blocks_y))
+            rgba = [c + [255] for c in etc1s_pixels(endpoints, selectors, blocks, blocks_x, width, height)]
+            write_level_png('etc1s_ktx2_%d_%d.png' % (layer, level), width, height, rgba)
+        level_ops.append(ops)
+    for ops in level_ops:
+        for layer_ops in ops:
+            for op in layer_ops:
+                if op[0] == 'h':
+                    op[1].freqs[op[2]] += 1
+    endpoint_data, selector_data, table_data = etc1s_codebooks(endpoints, selectors, models)
+    levels = []
+    for ops in level_ops:
+        level = b''
+        for layer_ops in ops:
+            body = write_ops(layer_ops)
+            descs.append(struct.pack('<IIIII', 0, len(level), len(body), 0, 0))
+            level += body
+        levels.append((level, 0))
+    sgd = struct.pack('<HHIIII', len(endpoints), len(selectors), len(endpoint_data), len(selector_data),
+                      len(table_data), 0) + b''.join(descs) + endpoint_data + selector_data + table_data
+    write_ktx2('etc1s.ktx2', 24, 16, 2, levels, 1, 163, [(0, 63)], sgd)
+
+
+def uastc_fixtures(rng):
+    # .basis: one image, two levels; level 0 has a block of every mode
+    slices = []
+    for level, (width, height) in enumerate([(20, 16), (10, 8)]):
+        blocks_x, blocks_y = (width + 3) // 4, (height + 3) // 4
+        modes = list(range(19)) + [7] if level == 0 else [rng.randrange(19) for _ in range(blocks_x * blocks_y)]
+        data, image = uastc_level(modes, blocks_x, blocks_y, width, height, rng)
+        slices.append((0, level, width, height, False, data))
+        write_level_png('uastc_0_%d.png' % level, width, height, image)
+    write_basis('uastc.basis', True, slices, True)
+
+    # KTX2: two layers, four levels, with many partitioned blocks
+    levels = []
+    for level, (width, height) in enumerate([(30, 18), (15, 9), (7, 4), (3, 2)]):
+        blocks_x, blocks_y = (width + 3) // 4, (height + 3) // 4
+        level_data = b''
+        for layer in range(2):
+            modes = [rng.randrange(19) for _ in range(blocks_x * blocks_y)]
+            if level == 0:
+                modes = [[2, 3, 4, 7, 9, 16][(i + layer) % 6] if i % 2 else m for i, m in enumerate(modes)]
+            data, image = uastc_level(modes, blocks_x, blocks_y, width, height, rng)
+            write_level_png('uastc_ktx2_%d_%d.png' % (layer, level), width, height, image)
+            level_data += data
+        levels.append(level_data)
+    write_ktx2('uastc_raw.ktx2', 30, 18, 2, [(l, len(l)) for l in levels], 0, 166, [(3, 127)])
+    compressed = [subprocess.run(['zstd', '-q', '-c', '-19'], input=l, stdout=subprocess.PIPE, check=True).stdout
+                  for l in levels]
+    write_ktx2('uastc.ktx2', 30, 18, 2, [(c, len(l)) for c, l in zip(compressed, levels)], 2, 166, [(3, 127)])
+
+
+if __name__ == '__main__':
+    rng = random.Random(40)
+    etc1s_fixtures(rng)
+    uastc_fixtures(rng)
@@ -0,0 +1,224 @@
+"""Writes the texture fixtures in this directory: random encoded data (`.bin`) and its reference decode
+(`.png`, RGBA, or greyscale for `_alpha.png`).
+
+The decoders here are written from the format documentation and share no code with the crate:
+
+* PVRTC2 follows the PowerVR SDK's PVRTC1 decompressor (PVRTDecompress), which decodes a 2x2 group
+  of words at a time and blends colours at their stored precision. Only the colour word layout
+  differs for PVRTC2. Hard transition blocks aren't generated.
+* ATC explicit and interpolated alpha are the DXT3 and DXT5 alpha blocks. Only alpha is written;
+  the colour half is plain ATC RGB.
+
+The signed, B10G11R11 and P8 fixtures are decoded by Mesa instead, see `make_gl_fixtures.py`.
+
+Run with `python3 make_fixtures.py` from this directory. The output is deterministic.
+"""
+import math
+import random
+import struct
+import zlib
+
+
+def write_png(name, width, height, pixels, channels=4):
+    def chunk(kind, body):
+        return struct.pack('>I', len(body)) + kind + body + struct.pack('>I', zlib.crc32(kind + body))
+    stride = width * channels
+    raw = b''.join(b'\0' + bytes(pixels[y * stride:(y + 1) * stride]) for y in range(height))
+    color_type = 6 if channels == 4 else 0
+    with open(name, 'wb') as f:
+        f.write(b'\x89PNG\r\n\x1a\n')
+        f.write(chunk(b'IHDR', struct.pack('>IIBBBBB', width, height, 8, color_type, 0, 0, 0)))
+        f.write(chunk(b'IDAT', zlib.compress(raw, 9)))
+        f.write(chunk(b'IEND', b''))
+
+
+# PVRTC2
+
+def twiddle(x, y, size_x, size_y):
+    min_dim, max_value = (size_x, y) if size_x <= size_y else (size_y, x)
+    twiddled, src_bit, dst_bit, shift = 0, 1, 1, 0
+    while src_bit < min_dim:
+        if y & src_bit:
+            twiddled |= dst_bit
+        if x & src_bit:
+            twiddled |= dst_bit << 1
+        src_bit <<= 1
+        dst_bit <<= 2
+        shift += 1
+    return twiddled | (max_value >> shift) << (2 * shift)
+
+
+def colour_a(c):
+    if c & 0x80000000:
+        return [(c & 0x7c00) >> 10, (c & 0x3e0) >> 5, (c & 0x1e) | ((c & 0x1e) >> 4), 0xf]
+    return [((c & 0xf00) >> 7) | ((c & 0xf00) >> 11), ((c & 0xf0) >> 3) | ((c & 0xf0) >> 7),
+            ((c & 0xe) << 1) | ((c & 0xe) >> 2), (c & 0x7000) >> 11]
+
+
+def colour_b(c):
+    if c & 0x80000000:
+        return [(c & 0x7c000000) >> 26, (c & 0x3e00000) >> 21, (c & 0x1f0000) >> 16, 0xf]
+    return [((c & 0xf000000) >> 23) | ((c & 0xf000000) >> 27), ((c & 0xf00000) >> 19) | ((c & 0xf00000) >> 23),
+            ((c & 0xf0000) >> 15) | ((c & 0xf0000) >> 19), (c & 0x70000000) >> 27]
+
+
+def interpolate(p, q, r, s, bpp):
+    """The upscaled colour for every pixel between the centres of words P, Q (right), R (below) and S."""
+    word_width = 8 if bpp == 2 else 4
+    q_minus_p = [q[i] - p[i] for i in range(4)]
+    s_minus_r = [s[i] - r[i] for i in range(4)]
+    hp = [v * word_width for v in p]
+    hr = [v * word_width for v in r]
+    pixels = [None] * (word_width * 4)
+    for x in range(word_width):
+        result = [4 * v for v in hp]
+        dy = [hr[i] - hp[i] for i in range(4)]
+        for y in range(4):
+            if bpp == 2:
+                px = [(v >> 7) + (v >> 2) for v in result[:3]] + [(result[3] >> 5) + (result[3] >> 1)]
+            else:
+                px = [(v >> 6) + (v >> 1) for v in result[:3]] + [(result[3] >> 4) + result[3]]
+            pixels[y * word_width + x] = px
+            result = [result[i] + dy[i] for i in range(4)]
+        hp = [hp[i] + q_minus_p[i] for i in range(4)]
+        hr = [hr[i] + s_minus_r[i] for i in range(4)]
+    return pixels
+
+
+def unpack_modulations(word, ox, oy, values, modes, bpp):
+    modulation, colour = word
+    mode = colour & 1
+    if bpp == 2:
+        if mode:
+            if modulation & 1:
+                mode = 3 if modulation & (1 << 20) else 2
+                if modulation & (1 << 21):
+                    modulation |= 1 << 20
+                else:
+                    modulation &= ~(1 << 20)
+            if modulation & 2:
+                modulation |= 1
+            else:
+                modulation &= ~1
+            for y in range(4):
+                for x in range(8):
+                    modes[x + ox][y + oy] = mode
+                    if ((x ^ y) & 1) == 0:
+                        values[x + ox][y + oy] = modulation & 3
+                        modulation >>= 2
+        else:
+            for y in range(4):
+                for x in range(8):
+                    modes[x + ox][y + oy] = mode
+                    values[x + ox][y + oy] = 3 if modulation & 1 else 0
+                    modulation >>= 1
+    else:
+        for y in range(4):
+            for x in range(4):
+                modes[x + ox][y + oy] = mode
+                v = modulation & 3
+                if mode:
+                    v = [0, 4, 14, 8][v]
+                else:
+                    v = [0, 3, 5, 8][v]
+                values[x + ox][y + oy] = v
+                modulation >>= 2
+
+
+def modulation_value(values, modes, x, y, bpp):
+    if bpp == 4:
+        return values[x][y]
+    rep = [0, 3, 5, 8]
+    if modes[x][y] == 0 or ((x ^ y) & 1) == 0:
+        return rep[values[x][y]]
+    if modes[x][y] == 1:
+        return (rep[values[x][y - 1]] + rep[values[x][y + 1]] + rep[values[x - 1][y]] + rep[values[x + 1][y]] + 2) // 4
+    if modes[x][y] == 2:
+        return (rep[values[x - 1][y]] + rep[values[x + 1][y]] + 1) // 2
+    return (rep[values[x][y - 1]] + rep[values[x][y + 1]] + 1) // 2
+
+
+def decode_pvrtc2(data, width, height, bpp):
+    word_width = 8 if bpp == 2 else 4
+    words_x = max(math.ceil(width / word_width), 2)
+    words_y = max(math.ceil(height / 4), 2)
+
+    def word(x, y):
+        i = twiddle(x % words_x, y % words_y, words_x, words_y) * 8
+        return struct.unpack('<II', data[i:i + 8])
+
+    full_width, full_height = words_x * word_width, words_y * 4
+    image = [None] * (full_width * full_height)
+    for wy in range(words_y):
+        for wx in range(words_x):
+            group = [word(wx, wy), word(wx + 1, wy), word(wx, wy + 1), word(wx + 1, wy + 1)]
+            values = [[0] * 8 for _ in range(16)]
+            modes = [[0] * 8 for _ in range(16)]
+            for (ox, oy), w in zip([(0, 0), (word_width, 0), (0, 4), (word_width, 4)], group):
+                unpack_modulations(w, ox, oy, values, modes, bpp)
+            a = interpolate(*[colour_a(w[1]) for w in group], bpp)
+            b = interpolate(*[colour_b(w[1]) for w in group], bpp)
+            for y in range(4):
+                for x in range(word_width):
+                    mod = modulation_value(values, modes, x + word_width // 2, y + 2, bpp)
+                    punch = mod > 10
+                    if punch:
+                        mod -= 10
+                    i = y * word_width + x
+                    px = [(a[i][c] * (8 - mod) + b[i][c] * mod) // 8 for c in range(4)]
+                    if punch:
+                        px[3] = 0
+                    gx = (wx * word_width + x + word_width // 2) % full_width
+                    gy = (wy * 4 + y + 2) % full_height
+                    image[gy * full_width + gx] = px
+    return [c for y in range(height) for x in range(width) for c in image[y * full_width + x]]
+
+
+def pvrtc2_fixture(name, width, height, bpp, rng):
+    word_width = 8 if bpp == 2 else 4
+    words = max(math.ceil(width / word_width), 2) * max(math.ceil(height / 4), 2)
+    data = b''
+    for _ in range(words):
+        colour = rng.getrandbits(32) & ~(1 << 15)
+        data += struct.pack('<II', rng.getrandbits(32), colour)
+    open(name + '.bin', 'wb').write(data)
+    write_png(name + '.png', width, height, decode_pvrtc2(data, width, height, bpp))
+
+
+# ATC alpha
+
+def explicit_alpha(block):
+    bits = int.from_bytes(block[:8], 'little')
+    return [(bits >> (i * 4) & 0xf) * 17 for i in range(16)]
+
+
+def interpolated_alpha(block):
+    a0, a1 = block[0], block[1]
+    if a0 > a1:
+        palette = [a0, a1] + [((7 - i) * a0 + i * a1) // 7 for i in range(1, 7)]
+    else:
+        palette = [a0, a1] + [((5 - i) * a0 + i * a1) // 5 for i in range(1, 5)] + [0, 255]
+    bits = int.from_bytes(block[2:8], 'little')
+    return [palette[bits >> (i * 3) & 7] for i in range(16)]
+
+
+def atc_alpha_fixture(name, width, height, alpha, rng):
+    blocks_x = width // 4
+    data = bytes(rng.getrandbits(8) for _ in range(width * height))
+    pixels = [0] * (width * height)
+    for b in range(len(data) // 16):
+        values = alpha(data[b * 16:b * 16 + 16])
+        bx, by = b % blocks_x * 4, b // blocks_x * 4
+        for i, v in enumerate(values):
+            pixels[(by + i // 4) * width + bx + i % 4] = v
+    open(name + '.bin', 'wb').write(data)
+    write_png(name + '_alpha.png', width, height, pixels, 1)
+
+
+if __name__ == '__main__':
+    rng = random.Random(45)
+    pvrtc2_fixture('pvrtc2_4bpp', 16, 16, 4, rng)
+    pvrtc2_fixture('pvrtc2_4bpp_wide', 32, 8, 4, rng)
+    pvrtc2_fixture('pvrtc2_2bpp', 32, 16, 2, rng)
+    atc_alpha_fixture('atc_explicit', 16, 16, explicit_alpha, rng)
+    atc_alpha_fixture('atc_interpolated', 16, 16, interpolated_alpha, rng)
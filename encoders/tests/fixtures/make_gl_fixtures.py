@@ -0,0 +1,186 @@
+"""Writes the signed, B10G11R11 and P8 fixtures in this directory, with their reference decodes taken
+from Mesa's OpenGL implementation rather than written by hand.
+
+* The signed formats and B10G11R11 are uploaded as `GL_*_SNORM` and `GL_R11F_G11F_B10F` textures on a
+  desktop GL context and read back as floats with `glGetTexImage`. Signed channels are then mapped
+  from [-1, 1] to [0, 255] with rounding, float channels are scaled by 255 and truncated (in single
+  precision, like the crate), and channels the format doesn't have keep GL's defaults.
+* P8 is uploaded as `GL_PALETTE8_RGBA8_OES` on an OpenGL ES 1 context, drawn into a framebuffer
+  with `glDrawTexiOES` and read back with `glReadPixels`. GL palettes always have 256 entries, so
+  the 200-entry palette is padded with transparent black, which is what the crate decodes indices
+  past the end of the palette to.
+
+Needs Mesa's EGL with the surfaceless platform (llvmpipe is enough). Run with
+`python3 make_gl_fixtures.py` from this directory. The output is deterministic.
+"""
+import ctypes as C
+import math
+import random
+import struct
+
+from make_fixtures import write_png
+
+EGL_PLATFORM_SURFACELESS_MESA = 0x31DD
+EGL_OPENGL_ES_API = 0x30A0
+EGL_OPENGL_API = 0x30A2
+EGL_CONTEXT_CLIENT_VERSION = 0x3098
+EGL_NONE = 0x3038
+
+GL_TEXTURE_2D = 0x0DE1
+GL_UNPACK_ALIGNMENT = 0x0CF5
+GL_PACK_ALIGNMENT = 0x0D05
+GL_TEXTURE_MIN_FILTER = 0x2801
+GL_TEXTURE_MAG_FILTER = 0x2800
+GL_NEAREST = 0x2600
+GL_BYTE = 0x1400
+GL_UNSIGNED_BYTE = 0x1401
+GL_SHORT = 0x1402
+GL_FLOAT = 0x1406
+GL_RED = 0x1903
+GL_RG = 0x8227
+GL_RGB = 0x1907
+GL_RGBA = 0x1908
+GL_R16_SNORM = 0x8F98
+GL_RG8_SNORM = 0x8F95
+GL_RGBA16_SNORM = 0x8F9B
+GL_R11F_G11F_B10F = 0x8C3A
+GL_UNSIGNED_INT_10F_11F_11F_REV = 0x8C3B
+GL_PALETTE8_RGBA8_OES = 0x8B96
+GL_TEXTURE_CROP_RECT_OES = 0x8B9D
+GL_FRAMEBUFFER_OES = 0x8D40
+GL_RENDERBUFFER_OES = 0x8D41
+GL_COLOR_ATTACHMENT0_OES = 0x8CE0
+GL_FRAMEBUFFER_COMPLETE_OES = 0x8CD5
+GL_RGBA8_OES = 0x8058
+
+egl = C.CDLL('libEGL.so.1')
+egl.eglGetProcAddress.restype = C.c_void_p
+egl.eglGetProcAddress.argtypes = [C.c_char_p]
+egl.eglInitialize.argtypes = [C.c_void_p, C.c_void_p, C.c_void_p]
+egl.eglCreateContext.restype = C.c_void_p
+egl.eglCreateContext.argtypes = [C.c_void_p, C.c_void_p, C.c_void_p, C.c_void_p]
+egl.eglMakeCurrent.argtypes = [C.c_void_p] * 4
+
+
+def proc(name, *argtypes, restype=None):
+    address = egl.eglGetProcAddress(name.encode())
+    assert address, name
+    return C.CFUNCTYPE(restype, *argtypes)(address)
+
+
+def make_current(api, attributes):
+    get_display = proc('eglGetPlatformDisplayEXT', C.c_uint, C.c_void_p, C.c_void_p, restype=C.c_void_p)
+    display = C.c_void_p(get_display(EGL_PLATFORM_SURFACELESS_MESA, None, None))
+    assert egl.eglInitialize(display, None, None)
+    assert egl.eglBindAPI(api)
+    attributes = (C.c_int * (len(attributes) + 1))(*attributes, EGL_NONE)
+    context = egl.eglCreateContext(display, None, None, attributes)
+    assert context
+    assert egl.eglMakeCurrent(display, None, None, C.c_void_p(context))
+
+
+def new_texture():
+    texture = C.c_uint()
+    proc('glGenTextures', C.c_int, C.c_void_p)(1, C.byref(texture))
+    proc('glBindTexture', C.c_uint, C.c_uint)(GL_TEXTURE_2D, texture)
+    parameter = proc('glTexParameteri', C.c_uint, C.c_uint, C.c_int)
+    parameter(GL_TEXTURE_2D, GL_TEXTURE_MIN_FILTER, GL_NEAREST)
+    parameter(GL_TEXTURE_2D, GL_TEXTURE_MAG_FILTER, GL_NEAREST)
+    proc('glPixelStorei', C.c_uint, C.c_int)(GL_UNPACK_ALIGNMENT, 1)
+
+
+def gl_decode(data, width, height, internal_format, format, type):
+    """Uploads `data` as a texture and returns its texels as RGBA floats."""
+    new_texture()
+    proc('glTexImage2D', C.c_uint, C.c_int, C.c_int, C.c_int, C.c_int, C.c_int, C.c_uint, C.c_uint, C.c_char_p)(
+        GL_TEXTURE_2D, 0, internal_format, width, height, 0, format, type, data)
+    out = (C.c_float * (width * height * 4))()
+    proc('glGetTexImage', C.c_uint, C.c_int, C.c_uint, C.c_uint, C.c_void_p)(GL_TEXTURE_2D, 0, GL_RGBA, GL_FLOAT, out)
+    assert proc('glGetError', restype=C.c_uint)() == 0
+    return list(out)
+
+
+def f32(v):
+    return struct.unpack('<f', struct.pack('<f', v))[0]
+
+
+def snorm_to_unorm(f):
+    return math.floor((f + 1) / 2 * 255 + 0.5)
+
+
+def float_to_unorm(f):
+    if math.isnan(f):
+        return 0
+    return max(0, min(255, int(f32(f * 255)))) if f != math.inf else 255
+
+
+def snorm_fixture(name, width, height, channels, bits, rng):
+    data = bytes(rng.getrandbits(8) for _ in range(width * height * channels * bits // 8))
+    internal_format, format = {
+        (1, 16): (GL_R16_SNORM, GL_RED), (2, 8): (GL_RG8_SNORM, GL_RG), (4, 16): (GL_RGBA16_SNORM, GL_RGBA),
+    }[channels, bits]
+    texels = gl_decode(data, width, height, internal_format, format, GL_BYTE if bits == 8 else GL_SHORT)
+    pixels = [snorm_to_unorm(f) if i % 4 < channels else round(f * 255) for i, f in enumerate(texels)]
+    open(name + '.bin', 'wb').write(data)
+    write_png(name + '.png', width, height, pixels)
+
+
+def b10g11r11_fixture(name, width, height, rng):
+    values = []
+    for _ in range(width * height):
+        # mostly in [0, 1], where the interesting rounding happens
+        channel = lambda bits: rng.randrange(0, 15 << bits) if rng.random() < 0.9 else rng.getrandbits(bits + 5)
+        values.append(channel(6) | channel(6) << 11 | channel(5) << 22)
+    data = struct.pack('<%dI' % len(values), *values)
+    texels = gl_decode(data, width, height, GL_R11F_G11F_B10F, GL_RGB, GL_UNSIGNED_INT_10F_11F_11F_REV)
+    pixels = [float_to_unorm(f) if i % 4 < 3 else round(f * 255) for i, f in enumerate(texels)]
+    open(name + '.bin', 'wb').write(data)
+    write_png(name + '.png', width, height, pixels)
+
+
+def p8_fixture(name, width, height, rng):
+    # 200 palette entries, so some indices fall past the end
+    palette = bytes(rng.getrandbits(8) for _ in range(200 * 4))
+    indices = bytes(rng.getrandbits(8) for _ in range(width * height))
+    new_texture()
+    data = palette.ljust(256 * 4, b'\0') + indices
+    proc('glCompressedTexImage2D', C.c_uint, C.c_int, C.c_uint, C.c_int, C.c_int, C.c_int, C.c_int, C.c_char_p)(
+        GL_TEXTURE_2D, 0, GL_PALETTE8_RGBA8_OES, width, height, 0, len(data), data)
+    proc('glEnable', C.c_uint)(GL_TEXTURE_2D)
+    crop = (C.c_int * 4)(0, 0, width, height)
+    proc('glTexParameteriv', C.c_uint, C.c_uint, C.c_void_p)(GL_TEXTURE_2D, GL_TEXTURE_CROP_RECT_OES, crop)
+
+    framebuffer, renderbuffer = C.c_uint(), C.c_uint()
+    proc('glGenFramebuffersOES', C.c_int, C.c_void_p)(1, C.byref(framebuffer))
+    proc('glBindFramebufferOES', C.c_uint, C.c_uint)(GL_FRAMEBUFFER_OES, framebuffer)
+    proc('glGenRenderbuffersOES', C.c_int, C.c_void_p)(1, C.byref(renderbuffer))
+    proc('glBindRenderbufferOES', C.c_uint, C.c_uint)(GL_RENDERBUFFER_OES, renderbuffer)
+    proc('glRenderbufferStorageOES', C.c_uint, C.c_uint, C.c_int, C.c_int)(
+        GL_RENDERBUFFER_OES, GL_RGBA8_OES, width, height)
+    proc('glFramebufferRenderbufferOES', C.c_uint, C.c_uint, C.c_uint, C.c_uint)(
+        GL_FRAMEBUFFER_OES, GL_COLOR_ATTACHMENT0_OES, GL_RENDERBUFFER_OES, renderbuffer)
+    status = proc('glCheckFramebufferStatusOES', C.c_uint, restype=C.c_uint)(GL_FRAMEBUFFER_OES)
+    assert status == GL_FRAMEBUFFER_COMPLETE_OES, hex(status)
+    proc('glViewport', C.c_int, C.c_int, C.c_int, C.c_int)(0, 0, width, height)
+
+    # the texture's first row is drawn at y = 0, which is also glReadPixels' first row
+    proc('glDrawTexiOES', C.c_int, C.c_int, C.c_int, C.c_int, C.c_int)(0, 0, 0, width, height)
+    out = (C.c_ubyte * (width * height * 4))()
+    proc('glPixelStorei', C.c_uint, C.c_int)(GL_PACK_ALIGNMENT, 1)
+    proc('glReadPixels', C.c_int, C.c_int, C.c_int, C.c_int, C.c_uint, C.c_uint, C.c_void_p)(
+        0, 0, width, height, GL_RGBA, GL_UNSIGNED_BYTE, out)
+    assert proc('glGetError', restype=C.c_uint)() == 0
+    open(name + '.bin', 'wb').write(indices)
+    open(name + '_palette.bin', 'wb').write(palette)
+    write_png(name + '.png', width, height, list(out))
+
+
+if __name__ == '__main__':
+    rng = random.Random(45)
+    make_current(EGL_OPENGL_API, [])
+    snorm_fixture('r16_signed', 8, 8, 1, 16, rng)
+    snorm_fixture('rg16_signed', 8, 8, 2, 8, rng)
+    snorm_fixture('rgba64_signed', 8, 8, 4, 16, rng)
+    b10g11r11_fixture('b10g11r11', 8, 8, rng)
+    make_current(EGL_OPENGL_ES_API, [EGL_CONTEXT_CLIENT_VERSION, 1])
+    p8_fixture('p8', 8, 8, rng)
@@ -15,3 +15,1287 @@ fn test_bgr2rgb() {
     texdec::bgr2rgb(&mut test_data);
     assert_eq!(test_data, [64u8, 0, 128, 255, 128, 0, 64, 255, 32, 0, 32, 255]);
 }
+
+#[wasm_bindgen_test]
+fn test_deswizzle_tegra_gob() {
+    let data: Vec<u8> = (0..=255u8).chain(0..=255u8).collect();
+    let out = swizzle::deswizzle_tegra(&data, 16, 8, 1, 1, 4, 1);
+    assert_eq!(out[0..16], data[0..16]);
+    assert_eq!(out[16..32], data[32..48]);
+    assert_eq!(out[64..80], data[16..32]);
+}
+
+#[wasm_bindgen_test]
+fn test_switch_platform_blob() {
+    let data: Vec<u8> = (0..2048).map(|i| (i * 7 % 251) as u8).collect();
+    // 32x16 RGBA32 is two GOBs wide and two high; without a platform blob, Switch textures are linear
+    assert_eq!(*texdec::decode("RGBA32", &mut data.clone(), 32, 16, false, "Switch", &[]).unwrap(), data[..]);
+
+    // the blob holds log2 of the GOBs per block at offset 8, which overrides the height-based guess of 2
+    let mut blob = [0u8; 12];
+    let one_gob = texdec::decode("RGBA32", &mut data.clone(), 32, 16, false, "Switch", &blob).unwrap();
+    assert_eq!(one_gob, swizzle::deswizzle_tegra(&data, 32, 16, 1, 1, 4, 1));
+    blob[8] = 1;
+    let two_gobs = texdec::decode("RGBA32", &mut data.clone(), 32, 16, false, "Switch", &blob).unwrap();
+    assert_eq!(two_gobs, swizzle::deswizzle_tegra(&data, 32, 16, 1, 1, 4, 2));
+    assert_ne!(one_gob, two_gobs);
+}
+
+#[wasm_bindgen_test]
+fn test_deswizzle_gnm_morton() {
+    let data: Vec<u8> = (0..64u8).collect();
+    let out = swizzle::deswizzle_gnm(&data, 8, 8, 1, 1, 1);
+    assert_eq!(out[0..4], [0u8, 1, 4, 5]);
+    assert_eq!(out[8..12], [2u8, 3, 6, 7]);
+}
+
+#[wasm_bindgen_test]
+fn test_unpack_normal_map() {
+    let mut bc5 = [128u8, 128, 0, 255];
+    normal::reconstruct_normal_z(&mut bc5);
+    assert_eq!(bc5, [128u8, 128, 255, 255]);
+
+    let mut dxt5nm = [255u8, 128, 255, 128];
+    normal::unpack_normal_map(&mut dxt5nm, "auto", false);
+    assert_eq!(dxt5nm, [128u8, 128, 255, 255]);
+}
+
+#[wasm_bindgen_test]
+fn test_srgb_roundtrip() {
+    let mut data: Vec<u8> = (0..=255u8).flat_map(|v| [v, v, v, 128]).collect();
+    let orig = data.clone();
+    color::srgb_to_linear(&mut data);
+    assert_eq!(data[4 * 128..4 * 128 + 4], [55u8, 55, 55, 128]);
+    color::linear_to_srgb(&mut data);
+    assert_eq!(data[4 * 255..], orig[4 * 255..]);
+    assert_eq!(data[4 * 128..4 * 128 + 4], orig[4 * 128..4 * 128 + 4]);
+}
+
+#[wasm_bindgen_test]
+fn test_premultiply() {
+    let mut data = [255u8, 128, 0, 128, 10, 20, 30, 0];
+    color::premultiply_alpha(&mut data);
+    assert_eq!(data, [128u8, 64, 0, 128, 0, 0, 0, 0]);
+    color::unpremultiply_alpha(&mut data);
+    assert_eq!(data[0..4], [255u8, 128, 0, 128]);
+}
+
+#[wasm_bindgen_test]
+fn test_bleed_alpha() {
+    // the straightforward version: every pass scans the image for unfilled pixels next to filled ones
+    fn bleed_by_scanning(data: &mut [u8], width: usize, height: usize, passes: usize) {
+        let mut filled: Vec<bool> = (0..(width * height)).map(|i| data[i * 4 + 3] != 0).collect();
+        let mut pass = 0;
+        while passes == 0 || pass < passes {
+            let mut changed = Vec::new();
+            for y in 0..height {
+                for x in 0..width {
+                    if filled[y * width + x] {
+                        continue;
+                    }
+                    let (mut sum, mut count) = ([0u32; 3], 0);
+                    for ny in y.saturating_sub(1)..(y + 2).min(height) {
+                        for nx in x.saturating_sub(1)..(x + 2).min(width) {
+                            if filled[ny * width + nx] {
+                                for c in 0..3 {
+                                    sum[c] += data[(ny * width + nx) * 4 + c] as u32;
+                                }
+                                count += 1;
+                            }
+                        }
+                    }
+                    if let [Some(r), Some(g), Some(b)] = sum.map(|s| s.checked_div(count)) {
+                        changed.push((y * width + x, [r, g, b]));
+                    }
+                }
+            }
+            if changed.is_empty() {
+                break;
+            }
+            for (i, c) in changed {
+                for k in 0..3 {
+                    data[i * 4 + k] = c[k] as u8;
+                }
+                filled[i] = true;
+            }
+            pass += 1;
+        }
+    }
+
+    let (width, height) = (37, 23);
+    let mut seed = 7u32;
+    let image: Vec<u8> = (0..width * height).flat_map(|i| {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        // a few scattered opaque pixels and an opaque block in one corner
+        let opaque = (seed >> 16) % 41 == 3 || (i % width < 5 && i / width < 4);
+        [(seed >> 8) as u8, (seed >> 16) as u8, (seed >> 24) as u8, if opaque { 255 } else { 0 }]
+    }).collect();
+    for &passes in [0usize, 1, 2, 5].iter() {
+        let (mut expected, mut actual) = (image.clone(), image.clone());
+        bleed_by_scanning(&mut expected, width, height, passes);
+        color::bleed_alpha(&mut actual, width, height, passes);
+        assert_eq!(actual, expected, "{} passes", passes);
+    }
+
+    // nothing visible: nothing to bleed
+    let mut clear = vec![9u8, 9, 9, 0, 1, 2, 3, 0];
+    color::bleed_alpha(&mut clear, 2, 1, 0);
+    assert_eq!(clear, [9u8, 9, 9, 0, 1, 2, 3, 0]);
+}
+
+#[wasm_bindgen_test]
+fn test_resize_box_average() {
+    let data = [0u8, 0, 0, 255, 255, 255, 255, 255, 0, 0, 0, 255, 255, 255, 255, 255];
+    let out = resize::resize(&data, 2, 2, 1, 1, "box", false);
+    assert_eq!(*out, [128u8, 128, 128, 255]);
+    let out = resize::resize(&data, 2, 2, 1, 1, "box", true);
+    assert_eq!(*out, [188u8, 188, 188, 255]);
+}
+
+#[wasm_bindgen_test]
+fn test_decode_thumbnail_uses_mip() {
+    // 4x4 RGBA32 with a 2x2 and 1x1 mip chain
+    let mut data = vec![0u8; (16 + 4 + 1) * 4];
+    for i in 16..20 {
+        data[i * 4..i * 4 + 4].copy_from_slice(&[255, 0, 0, 255]);
+    }
+    let thumb = resize::decode_thumbnail("RGBA32", &mut data, 4, 4, 2, false, "", &[]).unwrap();
+    assert_eq!((thumb.width, thumb.height), (2, 2));
+    assert_eq!(thumb.pixels()[0..4], [255u8, 0, 0, 255]);
+
+    // swizzled mip chains aren't laid out linearly, so the top mip is used
+    let thumb = resize::decode_thumbnail("RGBA32", &mut data, 4, 4, 2, false, "PS4", &[]).unwrap();
+    assert_eq!(thumb.pixels()[0..4], [0u8, 0, 0, 0]);
+
+    // Xbox 360 RGB565 is byte-swapped
+    let mut data = vec![0x00u8, 0x1f, 0x00, 0x1f, 0x00, 0x1f, 0x00, 0x1f];
+    let thumb = resize::decode_thumbnail("RGB565", &mut data, 2, 2, 2, true, "XBox 360", &[]).unwrap();
+    assert_eq!(thumb.pixels()[0..4], [0u8, 0, 0xff, 0xff]);
+}
+
+#[wasm_bindgen_test]
+fn test_decode_thumbnail_block_mips() {
+    // 16x16 textures with noise for data, so decoding from the wrong offset gives different pixels;
+    // the offset of mip 1 is the size of mip 0 in whole blocks
+    let mut seed = 7u32;
+    let mut data: Vec<u8> = (0..2048).map(|_| {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        (seed >> 16) as u8
+    }).collect();
+    for (format, mip1_offset, mip1_size) in [
+        ("DXT1", 128, 32), ("BC7", 256, 64),
+        ("ETC_RGB4", 128, 32), ("ETC2_RGBA8", 256, 64), ("EAC_R", 128, 32), ("EAC_RG", 256, 64),
+        ("ATC_RGB4", 128, 32), ("ATC_RGBA8", 256, 64),
+        ("PVRTC_RGBA4", 128, 32), ("PVRTC_RGB2", 64, 32),
+        ("ASTC_RGBA_4x4", 256, 64), ("ASTC_RGBA_6x6", 144, 64), ("ASTC_RGBA_8x8", 64, 16),
+    ] {
+        let mip1 = texdec::decode(format, &mut data[mip1_offset..mip1_offset + mip1_size].to_vec(), 8, 8, false, "", &[]).unwrap();
+        let thumb = resize::decode_thumbnail(format, &mut data, 16, 16, 8, false, "", &[]).unwrap();
+        assert_eq!((thumb.width, thumb.height), (8, 8), "{}", format);
+        assert!(thumb.pixels() == &mip1[..], "{} decoded the wrong mip", format);
+    }
+}
+
+#[wasm_bindgen_test]
+fn test_decode_etc1a4_3ds_unity_name() {
+    // 8x8 3DS ETC1A4: one opaque block and three transparent ones
+    let mut data = vec![0u8; 4 * 16];
+    data[..8].fill(0xff);
+    let pixels = texdec::decode("ETC_RGBA8_3DS", &mut data.clone(), 8, 8, false, "Nintendo 3DS", &[]).unwrap();
+    assert_eq!(*pixels, *texdec::decode_etc1a4_3ds(&mut data, 8, 8).unwrap());
+    assert_eq!(pixels.chunks(4).filter(|p| p[3] == 255).count(), 16);
+    assert_eq!(texdec::get_expected_data_size("ETC_RGBA8_3DS", 8, 8), 64);
+}
+
+#[wasm_bindgen_test]
+fn test_extract_sprite_rotate90() {
+    // 3x2 texture, sprite is the right 2x2 area packed rotated
+    let mut tex = vec![0u8; 3 * 2 * 4];
+    for (i, v) in [(1usize, 1u8), (2, 2), (4, 3), (5, 4)] {
+        tex[i * 4..i * 4 + 4].copy_from_slice(&[v, 0, 0, 255]);
+    }
+    let mut info = sprite::SpriteInfo::new();
+    info.rect_x = 1.0;
+    info.rect_width = 2.0;
+    info.rect_height = 2.0;
+    info.sprite_width = 2.0;
+    info.sprite_height = 2.0;
+    info.settings_raw = (4 << 2) | 2 | 1;
+    let out = sprite::extract_sprite(&tex, 3, 2, &info, &[], &[]);
+    assert_eq!((out.width, out.height), (2, 2));
+    let reds: Vec<u8> = out.pixels().chunks(4).map(|p| p[0]).collect();
+    assert_eq!(reds, [2u8, 4, 1, 3]);
+}
+
+#[wasm_bindgen_test]
+fn test_extract_sprite_packed_atlas() {
+    // A 3x2 sprite that shows as ABC over DEF (rows are stored bottom first), packed into a 7x5 atlas at (2, 1)
+    // as Unity lays out each SpritePackingRotation, with its values 0-4
+    let (a, b, c, d, e, f) = (1u8, 2, 3, 4, 5, 6);
+    let sprite_rows = [d, e, f, a, b, c];
+    let packings: [(u32, usize, usize, [u8; 6]); 5] = [
+        (0, 3, 2, [d, e, f, a, b, c]),
+        (1, 3, 2, [f, e, d, c, b, a]),  // FlipHorizontal
+        (2, 3, 2, [a, b, c, d, e, f]),  // FlipVertical
+        (3, 3, 2, [c, b, a, f, e, d]),  // Rotate180
+        (4, 2, 3, [a, d, b, e, c, f]),  // Rotate90: turned a quarter counter-clockwise
+    ];
+    for &(rotation, w, h, packed) in packings.iter() {
+        let mut atlas: Vec<u8> = (0..7 * 5).flat_map(|i| [100 + i as u8, 0, 0, 255]).collect();
+        for (i, &v) in packed.iter().enumerate() {
+            let (x, y) = (2 + i % w, 1 + i / w);
+            atlas[(y * 7 + x) * 4] = v;
+        }
+        let mut info = sprite::SpriteInfo::new();
+        info.rect_x = 2.0;
+        info.rect_y = 1.0;
+        info.rect_width = w as f32;
+        info.rect_height = h as f32;
+        info.sprite_width = 3.0;
+        info.sprite_height = 2.0;
+        // packed, rectangle packing so no mesh masking
+        info.settings_raw = (rotation << 2) | 2 | 1;
+        let out = sprite::extract_sprite(&atlas, 7, 5, &info, &[], &[]);
+        assert_eq!((out.width, out.height), (3, 2), "rotation {}", rotation);
+        let reds: Vec<u8> = out.pixels().chunks(4).map(|p| p[0]).collect();
+        assert_eq!(reds, sprite_rows, "rotation {}", rotation);
+
+        // without the packed bit the rotation bits are ignored
+        info.settings_raw = (rotation << 2) | 2;
+        let out = sprite::extract_sprite(&atlas, 7, 5, &info, &[], &[]);
+        let reds: Vec<u8> = out.pixels().chunks(4).map(|p| p[0]).collect();
+        assert_eq!((out.width, out.height, reds), (w, h, packed.to_vec()), "unpacked rotation {}", rotation);
+    }
+}
+
+#[wasm_bindgen_test]
+fn test_extract_sprite_clamps_rects() {
+    let atlas: Vec<u8> = (0..4 * 4).flat_map(|i| [i as u8, 0, 0, 255]).collect();
+    let mut info = sprite::SpriteInfo::new();
+    // a fractional rect hanging off the right and top of the texture
+    info.rect_x = 2.5;
+    info.rect_y = 1.25;
+    info.rect_width = 3.0;
+    info.rect_height = 5.0;
+    info.sprite_width = 3.0;
+    info.sprite_height = 5.0;
+    info.settings_raw = 2;
+    let out = sprite::extract_sprite(&atlas, 4, 4, &info, &[], &[]);
+    let reds: Vec<u8> = out.pixels().chunks(4).map(|p| p[0]).collect();
+    assert_eq!((out.width, out.height, reds), (2, 3, vec![6, 7, 10, 11, 14, 15]));
+
+    // padding with an offset that pushes the image past the canvas crops it
+    info.pad_to_rect = true;
+    info.sprite_width = 2.0;
+    info.sprite_height = 2.0;
+    info.offset_x = -1.0;
+    info.offset_y = 1.0;
+    let out = sprite::extract_sprite(&atlas, 4, 4, &info, &[], &[]);
+    let reds: Vec<u8> = out.pixels().chunks(4).map(|p| p[0]).collect();
+    assert_eq!((out.width, out.height, reds), (2, 2, vec![0, 0, 7, 0]));
+}
+
+#[wasm_bindgen_test]
+fn test_extract_sprite_tight_mask() {
+    let tex = vec![255u8; 4 * 4 * 4];
+    let mut info = sprite::SpriteInfo::new();
+    info.rect_width = 4.0;
+    info.rect_height = 4.0;
+    info.sprite_width = 4.0;
+    info.sprite_height = 4.0;
+    info.pivot_x = 0.0;
+    info.pivot_y = 0.0;
+    info.pixels_to_units = 1.0;
+    // lower-left half triangle
+    let out = sprite::extract_sprite(&tex, 4, 4, &info, &[0.0, 0.0, 4.0, 0.0, 0.0, 4.0], &[0, 1, 2]);
+    assert_eq!(out.pixels()[3], 255);
+    assert_eq!(out.pixels()[(3 * 4 + 3) * 4 + 3], 0);
+}
+
+#[wasm_bindgen_test]
+fn test_swizzle_channels() {
+    let mut data = [10u8, 20, 30, 40];
+    texdec::swizzle_channels(&mut data, "BGRA");
+    assert_eq!(data, [30u8, 20, 10, 40]);
+    texdec::swizzle_channels(&mut data, "AA01");
+    assert_eq!(data, [40u8, 40, 0, 255]);
+}
+
+#[wasm_bindgen_test]
+fn test_composite_checkerboard() {
+    let mut data = [255u8, 0, 0, 0, 255, 0, 0, 255];
+    texdec::composite_checkerboard(&mut data, 2, 1, 1);
+    assert_eq!(data, [0xccu8, 0xcc, 0xcc, 255, 255, 0, 0, 255]);
+}
+
+#[wasm_bindgen_test]
+fn test_decode_into_and_buffer() {
+    use encoders::error::ImageErrorKind;
+    // 2x1 RGB565: pure red, pure blue
+    let mut src = vec![0x00u8, 0xf8, 0x1f, 0x00];
+    let expected = [255u8, 0, 0, 255, 0, 0, 255, 255];
+    let mut out = vec![0xaau8; 12];
+    texdec::decode_into("RGB565", &mut src, &mut out, 2, 1, false, "", &[]).unwrap();
+    assert_eq!(out[..8], expected);
+    // bytes past the image are left alone
+    assert_eq!(out[8..], [0xaa; 4]);
+    let err = texdec::decode_into("RGB565", &mut src, &mut out[..7], 2, 1, false, "", &[]).err().unwrap();
+    assert_eq!((err.kind, err.expected, err.actual), (ImageErrorKind::OutputTooSmall, 8, 7));
+
+    // the buffer keeps its allocation between decodes of the same or a smaller size
+    let mut buf = texdec::DecodeBuffer::new();
+    assert!(buf.is_empty());
+    buf.decode("RGB565", &mut src, 2, 1, false, "", &[]).unwrap();
+    assert_eq!(buf.pixels(), &expected[..]);
+    let ptr = buf.ptr();
+    buf.decode("RGB565", &mut [0x1f, 0x00, 0x00, 0xf8], 2, 1, false, "", &[]).unwrap();
+    assert_eq!(buf.pixels(), &[0, 0, 255, 255, 255, 0, 0, 255][..]);
+    buf.decode("RGB565", &mut src, 1, 1, false, "", &[]).unwrap();
+    assert_eq!((buf.ptr(), buf.len(), buf.pixels()), (ptr, 4, &expected[..4]));
+    assert!(buf.decode("RGB565", &mut src, 4, 4, false, "", &[]).is_err());
+}
+
+#[wasm_bindgen_test]
+fn test_encode_qoi_tga() {
+    let data = [10u8, 20, 30, 255].repeat(4);
+    let qoi = qoienc::encode_qoi(2, 2, &data, false);
+    assert_eq!(qoi[..14], [b'q', b'o', b'i', b'f', 0, 0, 0, 2, 0, 0, 0, 2, 4, 0]);
+    assert_eq!(qoi[14..], [0xfe, 10, 20, 30, 0xc0 | 2, 0, 0, 0, 0, 0, 0, 0, 1]);
+
+    let tga = tgaenc::encode_tga_rle(2, 2, &data, true);
+    assert_eq!((tga[2], tga[16], tga[17]), (10, 32, 8));
+    assert_eq!(tga[18..], [0x81, 30, 20, 10, 255, 0x81, 30, 20, 10, 255]);
+}
+
+#[wasm_bindgen_test]
+fn test_encode_webp_header() {
+    let data: Vec<u8> = (0..16 * 16 * 4).map(|i| (i % 7) as u8).collect();
+    let webp = webpenc::encode_webp(16, 16, &data, false);
+    assert_eq!(webp[0..4], *b"RIFF");
+    assert_eq!(u32::from_le_bytes([webp[4], webp[5], webp[6], webp[7]]) as usize, webp.len() - 8);
+    assert_eq!(webp[8..16], *b"WEBPVP8L");
+    assert_eq!(webp[20], 0x2f);
+}
+
+#[wasm_bindgen_test]
+fn test_webp_roundtrip() {
+    fn roundtrip(width: usize, height: usize, rgba: &[u8]) -> Box<[u8]> {
+        let webp = webpenc::encode_webp(width as u32, height as u32, rgba, false);
+        let mut decoder = image_webp::WebPDecoder::new(std::io::Cursor::new(&webp[..])).unwrap();
+        assert_eq!(decoder.dimensions(), (width as u32, height as u32));
+        let has_alpha = rgba.chunks(4).any(|p| p[3] != 0xff);
+        assert_eq!(decoder.has_alpha(), has_alpha);
+        let mut decoded = vec![0u8; decoder.output_buffer_size().unwrap()];
+        decoder.read_image(&mut decoded).unwrap();
+        let decoded: Vec<u8> = if has_alpha {
+            decoded
+        } else {
+            decoded.chunks(3).flat_map(|p| [p[0], p[1], p[2], 0xff]).collect()
+        };
+        assert_eq!(decoded, rgba, "{}x{}", width, height);
+        webp
+    }
+    // the first transform in the bitstream: 5 = subtract green, 7 = colour indexing
+    let first_transform = |webp: &[u8]| webp[25] & 7;
+
+    let mut seed = 34u32;
+    let mut next = move || {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        seed >> 16
+    };
+
+    // smooth gradients with and without alpha, at odd sizes
+    for &(width, height) in &[(1, 1), (3, 5), (67, 45), (129, 3)] {
+        let gradient: Vec<u8> = (0..width * height)
+            .flat_map(|i| {
+                let (x, y) = (i % width, i / width);
+                [(x * 255 / width) as u8, (y * 255 / height) as u8, ((x + y) * 3) as u8, 0xff]
+            })
+            .collect();
+        roundtrip(width, height, &gradient);
+        let translucent: Vec<u8> = gradient.chunks(4).enumerate()
+            .flat_map(|(i, p)| [p[0], p[1], p[2], (i * 7 % 256) as u8])
+            .collect();
+        roundtrip(width, height, &translucent);
+    }
+
+    // noise with too many colours for a palette
+    let noise: Vec<u8> = (0..33 * 17 * 4).map(|_| next() as u8).collect();
+    assert_eq!(first_transform(&roundtrip(33, 17, &noise)), 5);
+
+    // 300 colours scattered at random: no palette, but the colour cache gets plenty of hits
+    let colors: Vec<[u8; 4]> = (0..300).map(|_| [next() as u8, next() as u8, next() as u8, next() as u8 | 0x80]).collect();
+    let scattered: Vec<u8> = (0..61 * 47).flat_map(|_| colors[next() as usize % colors.len()]).collect();
+    assert_eq!(first_transform(&roundtrip(61, 47, &scattered)), 5);
+
+    // palettes of every bundling size (8, 4, 2 and 1 indices per pixel), with partial bundles at the row ends
+    for &(count, width, height) in &[(2, 13, 7), (4, 11, 9), (16, 23, 5), (200, 41, 19), (256, 71, 43)] {
+        let palette: Vec<[u8; 4]> = (0..count).map(|i| [next() as u8, i as u8, next() as u8, if i % 3 == 0 { 0x40 } else { 0xff }]).collect();
+        let indexed: Vec<u8> = (0..width * height)
+            .flat_map(|i| palette[if i < count { i } else { next() as usize % count }])
+            .collect();
+        assert_eq!(first_transform(&roundtrip(width, height, &indexed)), 7, "{} colours", count);
+    }
+}
+
+#[wasm_bindgen_test]
+fn test_animation_sheet_gif() {
+    // 2x2 sheet of 1x1 tiles, bottom row first: the top-left tile is the first frame
+    let sheet = [3u8, 3, 3, 255, 4, 4, 4, 255, 1, 1, 1, 255, 2, 2, 2, 255];
+    let mut anim = animenc::AnimationEncoder::new(1, 1);
+    anim.add_sheet(&sheet, 2, 2, 2, 2, 3, 100);
+    assert_eq!(anim.frame_count(), 3);
+
+    let gif = anim.encode_gif(false);
+    assert_eq!(gif[0..6], *b"GIF89a");
+    assert_eq!(gif[gif.len() - 1], 0x3b);
+    // the first frame's local palette holds its only colour
+    let first = gif.iter().position(|&b| b == 0x2c).unwrap();
+    assert_eq!(gif[first + 10..first + 13], [1u8, 1, 1]);
+}
+
+#[wasm_bindgen_test]
+fn test_animation_frames_roundtrip() {
+    use std::io::Cursor;
+    let (width, height) = (9usize, 7usize);
+    let delays = [50u32, 120, 33];
+    // a moving gradient; the second frame has a transparent hole, the third is opaque
+    let frames: Vec<Vec<u8>> = (0..3).map(|f| {
+        (0..width * height).flat_map(|i| {
+            let (x, y) = (i % width, i / width);
+            let alpha = if f == 1 && (2..5).contains(&x) && (2..4).contains(&y) { 0 } else { 0xff };
+            [(x * 28 + f * 9) as u8, (y * 36) as u8, (f * 80 + x) as u8, alpha]
+        }).collect()
+    }).collect();
+    let mut anim = animenc::AnimationEncoder::new(width as u32, height as u32);
+    anim.loop_count = 3;
+    for (frame, &delay) in frames.iter().zip(&delays) {
+        anim.add_frame(frame, delay);
+    }
+    let flipped = |frame: &[u8]| -> Vec<u8> { frame.chunks(width * 4).rev().flatten().copied().collect() };
+
+    // GIF: transparent pixels lose their colour, delays are rounded to centiseconds
+    let gif = anim.encode("gif", false);
+    let mut options = gif::DecodeOptions::new();
+    options.set_color_output(gif::ColorOutput::RGBA);
+    let mut decoder = options.read_info(Cursor::new(&gif[..])).unwrap();
+    assert_eq!((decoder.width() as usize, decoder.height() as usize), (width, height));
+    assert_eq!(decoder.repeat(), gif::Repeat::Finite(3));
+    for (expected, &delay) in frames.iter().zip(&delays) {
+        let frame = decoder.next_frame_info().unwrap().unwrap();
+        assert_eq!(frame.delay as u32, (delay + 5) / 10);
+        assert_eq!((frame.width as usize, frame.height as usize), (width, height));
+        let mut buf = vec![0u8; decoder.buffer_size()];
+        decoder.read_into_buffer(&mut buf).unwrap();
+        for (got, want) in buf.chunks(4).zip(expected.chunks(4)) {
+            if want[3] == 0 {
+                assert_eq!(got[3], 0);
+            } else {
+                assert_eq!(got, want);
+            }
+        }
+    }
+    assert!(decoder.next_frame_info().unwrap().is_none());
+
+    // APNG, written top row first
+    let apng = anim.encode("apng", true);
+    let mut reader = png::Decoder::new(Cursor::new(&apng[..])).read_info().unwrap();
+    let control = reader.info().animation_control.unwrap();
+    assert_eq!((control.num_frames, control.num_plays), (3, 3));
+    let mut buf = vec![0u8; reader.output_buffer_size()];
+    for (expected, &delay) in frames.iter().zip(&delays) {
+        let info = reader.next_frame(&mut buf).unwrap();
+        assert_eq!((info.width as usize, info.height as usize, info.color_type), (width, height, png::ColorType::Rgba));
+        let fc = reader.info().frame_control.unwrap();
+        assert_eq!((fc.delay_num as u32, fc.delay_den), (delay, 1000));
+        assert_eq!(buf, flipped(expected));
+    }
+
+    // animated WebP
+    let webp = anim.encode("webp", false);
+    let mut decoder = image_webp::WebPDecoder::new(Cursor::new(&webp[..])).unwrap();
+    assert!(decoder.is_animated() && decoder.has_alpha());
+    assert_eq!((decoder.dimensions(), decoder.num_frames()), ((width as u32, height as u32), 3));
+    assert_eq!(decoder.loop_count(), image_webp::LoopCount::Times(std::num::NonZeroU16::new(3).unwrap()));
+    let mut buf = vec![0u8; decoder.output_buffer_size().unwrap()];
+    for (expected, &delay) in frames.iter().zip(&delays) {
+        assert_eq!(decoder.read_frame(&mut buf).unwrap(), delay);
+        assert_eq!(&buf, expected);
+    }
+}
+
+#[wasm_bindgen_test]
+fn test_cubemap_cross_and_equirect() {
+    // 1x1 faces, each filled with its index
+    let faces: Vec<u8> = (0..6u8).flat_map(|i| [i, i, i, 255]).collect();
+    let cross = cubemap::cubemap_to_cross(&faces, 1, false, true);
+    assert_eq!(cross.len(), 4 * 3 * 4);
+    // bottom row first: the middle row holds -X, +Z, +X, -Z and the top row has +Y over +Z
+    assert_eq!(cross[16..32], [1u8, 1, 1, 255, 4, 4, 4, 255, 0, 0, 0, 255, 5, 5, 5, 255]);
+    assert_eq!(cross[32 + 4..32 + 8], [2u8, 2, 2, 255]);
+    assert_eq!(cross[32..36], [0u8, 0, 0, 0]);
+
+    let pano = cubemap::cubemap_to_equirect(&faces, 1, 8, 4, true);
+    let at = |x: usize, y: usize| pano[(y * 8 + x) * 4];
+    // the centre looks down +Z, the edges down -Z, and the top and bottom rows see +Y and -Y
+    assert_eq!((at(3, 1), at(4, 2), at(0, 1), at(6, 1), at(1, 1)), (4, 4, 5, 0, 1));
+    assert_eq!((at(2, 3), at(2, 0)), (2, 3));
+}
+
+#[wasm_bindgen_test]
+fn test_duplicate_finder() {
+    // a smooth pattern, the same pattern at double size, and its mirror image
+    let pattern = |w: usize, h: usize, mirror: bool| -> Vec<u8> {
+        (0..w * h).flat_map(|i| {
+            let x = (i % w) as f32 / w as f32;
+            let y = (i / w) as f32 / h as f32;
+            let x = if mirror { 1.0 - x } else { x };
+            let v = (128.0 + 100.0 * (x * 5.0).sin() * (y * 3.0 + 1.0).cos()) as u8;
+            [v, v, v, 255]
+        }).collect()
+    };
+    let small = pattern(16, 16, false);
+    let large = pattern(32, 32, false);
+    let mirrored = pattern(16, 16, true);
+    assert_eq!(hash::content_hash(&small, 16, 16), hash::content_hash(&small.clone(), 16, 16));
+    assert_ne!(hash::content_hash(&small, 16, 16), hash::content_hash(&mirrored, 16, 16));
+    let (a, b) = (hash::perceptual_hash(&small, 16, 16, "phash"), hash::perceptual_hash(&large, 32, 32, "phash"));
+    assert!(hash::hash_distance(&a, &b) <= 4);
+
+    let mut finder = hash::DuplicateFinder::new();
+    finder.add(&small, 16, 16);
+    finder.add(&mirrored, 16, 16);
+    finder.add(&large, 32, 32);
+    finder.add(&small, 16, 16);
+    assert_eq!(*finder.groups(0), [0, 1, 2, 0]);
+    assert_eq!(*finder.groups(8), [0, 1, 0, 0]);
+}
+
+#[wasm_bindgen_test]
+fn test_duplicate_groups_match_pairwise() {
+    // 20 patterns, each also saved with a little noise, resized, and exactly once more
+    let mut seed = 12345u32;
+    let mut images = Vec::new();
+    for k in 0..80 {
+        let (fx, fy) = ((k % 20) as f32 * 0.7 + 1.0, (k % 20 % 7) as f32 * 0.9 + 0.5);
+        let size = if k / 20 == 2 { 24 } else { 16 };
+        let noise = k / 20 == 1;
+        let pixels: Vec<u8> = (0..size * size).flat_map(|i| {
+            let (x, y) = ((i % size) as f32 / size as f32, (i / size) as f32 / size as f32);
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let jitter = if noise { (seed >> 16) as f32 % 9.0 - 4.0 } else { 0.0 };
+            let v = (128.0 + 90.0 * (x * fx * 3.0).sin() * (y * fy * 2.0 + 0.3).cos() + jitter) as u8;
+            [v, 255 - v, v / 2, 255]
+        }).collect();
+        images.push((pixels, size));
+    }
+
+    let mut finder = hash::DuplicateFinder::new();
+    for (pixels, size) in images.iter() {
+        finder.add(pixels, *size, *size);
+    }
+    let hashes: Vec<(String, String, String)> = images.iter().map(|(p, s)| (hash::content_hash(p, *s, *s),
+        hash::perceptual_hash(p, *s, *s, "dhash"), hash::perceptual_hash(p, *s, *s, "phash"))).collect();
+    for &max_distance in [0u32, 4, 10, 24].iter() {
+        // label every image with the smallest index it's connected to, comparing every pair
+        let mut expected: Vec<usize> = (0..images.len()).collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for i in 0..images.len() {
+                for j in (i + 1)..images.len() {
+                    let (a, b) = (&hashes[i], &hashes[j]);
+                    let similar = a.0 == b.0 || (max_distance > 0 && hash::hash_distance(&a.1, &b.1) <= max_distance
+                        && hash::hash_distance(&a.2, &b.2) <= max_distance);
+                    if similar && expected[i] != expected[j] {
+                        let label = expected[i].min(expected[j]);
+                        expected[i] = label;
+                        expected[j] = label;
+                        changed = true;
+                    }
+                }
+            }
+        }
+        assert_eq!(*finder.groups(max_distance), expected[..], "max distance {}", max_distance);
+    }
+}
+
+#[wasm_bindgen_test]
+fn test_compare_images() {
+    let a: Vec<u8> = (0..32 * 32 * 4).map(|i| if i % 4 == 3 { 255 } else { (i / 4 % 251) as u8 }).collect();
+    let same = diff::compare_images(&a, &a, 32, 32, 0).unwrap();
+    assert_eq!((same.psnr, same.max_error, same.changed_pixels, same.region_count()), (f64::INFINITY, 0, 0, 0));
+    assert!((same.ssim - 1.0).abs() < 1e-9);
+
+    // change a 3x2 block and one far away pixel
+    let mut b = a.clone();
+    for &(x, y) in &[(4, 5), (5, 5), (6, 5), (4, 6), (5, 6), (6, 6), (30, 28)] {
+        b[(y * 32 + x) * 4 + 1] ^= 0x40;
+    }
+    let d = diff::compare_images(&a, &b, 32, 32, 0).unwrap();
+    assert_eq!((d.max_error, d.changed_pixels), (0x40, 7));
+    assert_eq!(*d.max_channel_error(), [0, 0x40, 0, 0]);
+    assert_eq!(*d.regions(), [4, 5, 3, 2, 30, 28, 1, 1]);
+    assert!(d.psnr.is_finite() && d.ssim < 1.0);
+    assert_eq!(d.heatmap()[(5 * 32 + 4) * 4..(5 * 32 + 4) * 4 + 4], [255, 255 - 0x40, 0, 255]);
+
+    use encoders::error::ImageErrorKind;
+    let err = diff::compare_images(&a, &b[..b.len() - 1], 32, 32, 0).err().unwrap();
+    assert_eq!((err.kind, err.expected, err.actual), (ImageErrorKind::DataTooShort, 32 * 32 * 4, 32 * 32 * 4 - 1));
+    assert!(diff::compare_images(&a, &b, 64, 32, 0).is_err());
+    assert_eq!(diff::compare_images(&[], &[], usize::MAX, 2, 0).err().unwrap().kind, ImageErrorKind::InvalidDimensions);
+}
+
+#[wasm_bindgen_test]
+fn test_strict_and_lenient_sizes() {
+    use encoders::error::ImageErrorKind;
+    // 8x8 DXT1 needs four 8-byte blocks
+    let mut data = vec![0u8; 24];
+    let err = texdec::decode("DXT1", &mut data, 8, 8, false, "", &[]).unwrap_err();
+    assert_eq!((err.kind, err.expected, err.actual, err.format()), (ImageErrorKind::DataTooShort, 32, 24, "DXT1".to_string()));
+    let err = texdec::decode("NotAFormat", &mut data, 8, 8, false, "", &[]).unwrap_err();
+    assert_eq!(err.kind, ImageErrorKind::UnknownFormat);
+
+    let image = texdec::decode_lenient("DXT1", &mut data, 8, 8, false, "", &[]).unwrap();
+    assert!(image.partial);
+    assert_eq!(image.pixels().len(), 8 * 8 * 4);
+    let image = texdec::decode_lenient("DXT1", &mut vec![0u8; 32], 8, 8, false, "", &[]).unwrap();
+    assert!(!image.partial);
+
+    assert_eq!(pngenc::encode_png(2, 2, &[0u8; 15], false).unwrap_err().kind, ImageErrorKind::DataTooShort);
+    assert_eq!(pngenc::encode_png(0, 2, &[], false).unwrap_err().kind, ImageErrorKind::InvalidDimensions);
+    assert!(pngenc::encode_png(2, 2, &[0u8; 16], true).is_ok());
+}
+
+#[wasm_bindgen_test]
+fn test_encode_image_png_options() {
+    let data: Vec<u8> = (0..16 * 16 * 4).map(|i| (i * 7 % 251) as u8).collect();
+    let fast = imgenc::encode_image("png", 16, 16, &data, false).unwrap();
+    let best = imgenc::encode_image_with_options("png", 16, 16, &data, false, "best", "paeth").unwrap();
+    assert_ne!(fast, best);
+    for png in [&fast, &best] {
+        let mut reader = png::Decoder::new(std::io::Cursor::new(&png[..])).read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut buf).unwrap();
+        assert_eq!(buf, data);
+    }
+    // other formats ignore the PNG options
+    assert_eq!(imgenc::encode_image_with_options("qoi", 16, 16, &data, false, "best", "paeth").unwrap(),
+               imgenc::encode_image("qoi", 16, 16, &data, false).unwrap());
+}
+
+#[wasm_bindgen_test]
+fn test_basis_fixtures() {
+    // files and reference decodes from tests/fixtures/make_basis_fixtures.py
+    let etc1s = basis::BasisFile::new(include_bytes!("fixtures/etc1s.basis")).unwrap();
+    let etc1s_ktx2 = basis::BasisFile::new(include_bytes!("fixtures/etc1s.ktx2")).unwrap();
+    let uastc = basis::BasisFile::new(include_bytes!("fixtures/uastc.basis")).unwrap();
+    let uastc_ktx2 = basis::BasisFile::new(include_bytes!("fixtures/uastc.ktx2")).unwrap();
+    let uastc_raw_ktx2 = basis::BasisFile::new(include_bytes!("fixtures/uastc_raw.ktx2")).unwrap();
+    for &(file, format, images, levels) in [(&etc1s, "ETC1S", 1, 2), (&etc1s_ktx2, "ETC1S", 2, 3), (&uastc, "UASTC", 1, 2),
+                                            (&uastc_ktx2, "UASTC", 2, 4), (&uastc_raw_ktx2, "UASTC", 2, 4)].iter() {
+        assert_eq!((file.format(), file.image_count(), file.level_count(0)), (format.to_string(), images, levels));
+    }
+    assert!(etc1s.has_alpha(0, 0));
+
+    let references: [(&basis::BasisFile, usize, usize, &[u8]); 14] = [
+        (&etc1s, 0, 0, include_bytes!("fixtures/etc1s_0_0.png")),
+        (&etc1s, 0, 1, include_bytes!("fixtures/etc1s_0_1.png")),
+        (&etc1s_ktx2, 0, 0, include_bytes!("fixtures/etc1s_ktx2_0_0.png")),
+        (&etc1s_ktx2, 1, 0, include_bytes!("fixtures/etc1s_ktx2_1_0.png")),
+        (&etc1s_ktx2, 0, 1, include_bytes!("fixtures/etc1s_ktx2_0_1.png")),
+        (&etc1s_ktx2, 1, 2, include_bytes!("fixtures/etc1s_ktx2_1_2.png")),
+        (&uastc, 0, 0, include_bytes!("fixtures/uastc_0_0.png")),
+        (&uastc, 0, 1, include_bytes!("fixtures/uastc_0_1.png")),
+        (&uastc_ktx2, 0, 0, include_bytes!("fixtures/uastc_ktx2_0_0.png")),
+        (&uastc_ktx2, 1, 0, include_bytes!("fixtures/uastc_ktx2_1_0.png")),
+        (&uastc_ktx2, 0, 1, include_bytes!("fixtures/uastc_ktx2_0_1.png")),
+        (&uastc_ktx2, 1, 2, include_bytes!("fixtures/uastc_ktx2_1_2.png")),
+        (&uastc_ktx2, 0, 3, include_bytes!("fixtures/uastc_ktx2_0_3.png")),
+        (&uastc_raw_ktx2, 1, 3, include_bytes!("fixtures/uastc_ktx2_1_3.png")),
+    ];
+    for &(file, image, level, png) in references.iter() {
+        let expected = fixture_png(png);
+        assert_eq!(expected.len(), file.width(image, level) * file.height(image, level) * 4);
+        assert_eq!(&file.transcode(image, level, "rgba").unwrap()[..], &expected[..], "image {} level {}", image, level);
+    }
+    for image in 0..2 {
+        for level in 0..4 {
+            assert_eq!(uastc_ktx2.transcode(image, level, "rgba").unwrap(), uastc_raw_ktx2.transcode(image, level, "rgba").unwrap());
+        }
+    }
+
+    assert_eq!(&etc1s.transcode(0, 0, "etc1").unwrap()[..], &include_bytes!("fixtures/etc1s_0_0.etc1")[..]);
+    // 30x18 is 8x5 blocks
+    assert_eq!(uastc_ktx2.transcode(0, 0, "bc7").unwrap().len(), 40 * 16);
+    assert_eq!(uastc_ktx2.transcode(0, 0, "astc").unwrap().len(), 40 * 16);
+    assert!(uastc.transcode(0, 0, "etc1").is_err());
+    assert!(basis::BasisFile::new(&include_bytes!("fixtures/etc1s.basis")[..60]).is_err());
+    let mut corrupt = include_bytes!("fixtures/uastc.ktx2").to_vec();
+    let len = corrupt.len();
+    corrupt.truncate(len - 10);
+    assert!(basis::BasisFile::new(&corrupt).is_err());
+}
+
+#[wasm_bindgen_test]
+fn test_godot_textures() {
+    // Godot 4 .ctex: a 2x2 RGBA8 image with one extra mipmap
+    let mut ctex = b"GST2".to_vec();
+    for v in [1u32, 2, 2, 0, 0, 0, 0, 0].iter() {
+        ctex.extend_from_slice(&v.to_le_bytes());
+    }
+    ctex.extend_from_slice(&[0, 0, 0, 0, 2, 0, 2, 0, 1, 0, 0, 0, 5, 0, 0, 0]);
+    let pixels: Vec<u8> = (0..16).collect();
+    ctex.extend_from_slice(&pixels);
+    ctex.extend_from_slice(&[9, 8, 7, 6]);
+    let texture = godot::GodotTexture::new(&ctex).unwrap();
+    assert_eq!((texture.version, texture.kind(), texture.layer_count(), texture.mipmap_count(0)), (4, "Texture2D".to_string(), 1, 2));
+    assert_eq!((texture.data_format(0), texture.format(0)), ("image".to_string(), "RGBA32".to_string()));
+    assert_eq!(*texture.decode(0, 0).unwrap().pixels(), pixels[..]);
+    let mip = texture.decode(0, 1).unwrap();
+    assert_eq!((mip.width, mip.height, &*mip.pixels()), (1, 1, &[9u8, 8, 7, 6][..]));
+    assert!(godot::GodotTexture::new(&ctex[..60]).is_err());
+
+    // Godot 3 .stex holding a lossless (PNG) image
+    let png = pngenc::encode_png(2, 2, &pixels, false).unwrap();
+    let mut stex = b"GDST".to_vec();
+    stex.extend_from_slice(&[2, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0]);
+    stex.extend_from_slice(&((1u32 << 20) | 5).to_le_bytes());
+    stex.extend_from_slice(&1u32.to_le_bytes());
+    stex.extend_from_slice(&(png.len() as u32 + 4).to_le_bytes());
+    stex.extend_from_slice(b"PNG ");
+    stex.extend_from_slice(&png);
+    let texture = godot::GodotTexture::new(&stex).unwrap();
+    assert_eq!((texture.version, texture.data_format(0)), (3, "png".to_string()));
+    assert_eq!(*texture.raw_data(0, 0).unwrap(), *png);
+    assert_eq!(*texture.decode(0, 0).unwrap().pixels(), pixels[..]);
+}
+
+#[wasm_bindgen_test]
+fn test_unreal_platform_data() {
+    // a 2x2 PF_B8G8R8A8 texture: mip 0 inline, mip 1 in the .ubulk at offset 4
+    let mut data = vec![0xaa; 3];
+    for v in [2u32, 2, 1, 12].iter() {
+        data.extend_from_slice(&v.to_le_bytes());
+    }
+    data.extend_from_slice(b"PF_B8G8R8A8\0");
+    data.extend_from_slice(&[0, 0, 0, 0, 2, 0, 0, 0]);
+    let bgra: Vec<u8> = (0..16).collect();
+    for &(flags, size, offset, dim) in [(0x40u32, 16u32, 0u64, 2u32), (0x10100, 4, 4, 1)].iter() {
+        data.extend_from_slice(&[1, 0, 0, 0]);
+        for v in [flags, size, size].iter() {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        data.extend_from_slice(&offset.to_le_bytes());
+        if flags == 0x40 {
+            data.extend_from_slice(&bgra);
+        }
+        for v in [dim, dim, 1].iter() {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+    let texture = unreal::UnrealTexture::new(&data, 3, false, 1000).unwrap();
+    assert_eq!((texture.width, texture.height, texture.num_slices, texture.mip_count()), (2, 2, 1, 2));
+    assert_eq!((texture.pixel_format(), texture.format()), ("PF_B8G8R8A8".to_string(), "BGRA32".to_string()));
+    assert_eq!((texture.mip_location(0), texture.mip_location(1)), ("inline".to_string(), "ubulk".to_string()));
+    assert_eq!(texture.decode_mip(0, &[], &[], &[]).unwrap()[..4], [2, 1, 0, 3]);
+    assert_eq!(*texture.decode_mip(1, &[], &[0, 0, 0, 0, 30, 20, 10, 255], &[]).unwrap(), [10, 20, 30, 255]);
+    assert_eq!(texture.mip_data(1, &[], &[0; 6], &[]).unwrap_err().kind, error::ImageErrorKind::DataTooShort);
+}
+
+#[wasm_bindgen_test]
+fn test_unreal_zlib_bulk_data() {
+    use std::io::Write;
+
+    // a 2x2 PF_B8G8R8A8 mip in the .ubulk, compressed as two 8-byte zlib chunks
+    let bgra: Vec<u8> = (0..16).collect();
+    let mut payload = Vec::new();
+    for v in [0x9E2A83C1u64, 8, 0, 16].iter() {
+        payload.extend_from_slice(&v.to_le_bytes());
+    }
+    let chunks: Vec<Vec<u8>> = bgra.chunks(8).map(|chunk| {
+        let mut zlib = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        zlib.write_all(chunk).unwrap();
+        zlib.finish().unwrap()
+    }).collect();
+    for chunk in chunks.iter() {
+        payload.extend_from_slice(&(chunk.len() as u64).to_le_bytes());
+        payload.extend_from_slice(&8u64.to_le_bytes());
+    }
+    payload.extend(chunks.concat());
+
+    let mut data = Vec::new();
+    for v in [2u32, 2, 1, 12].iter() {
+        data.extend_from_slice(&v.to_le_bytes());
+    }
+    data.extend_from_slice(b"PF_B8G8R8A8\0");
+    for v in [0u32, 1, 1, 0x10102, 16, payload.len() as u32].iter() {
+        data.extend_from_slice(&v.to_le_bytes());
+    }
+    data.extend_from_slice(&3u64.to_le_bytes());
+    for v in [2u32, 2, 1].iter() {
+        data.extend_from_slice(&v.to_le_bytes());
+    }
+    let texture = unreal::UnrealTexture::new(&data, 0, false, 0).unwrap();
+    let mut ubulk = vec![0xff; 3];
+    ubulk.extend_from_slice(&payload);
+    assert_eq!(*texture.mip_data(0, &[], &ubulk, &[]).unwrap(), bgra[..]);
+    assert_eq!(texture.decode_mip(0, &[], &ubulk, &[]).unwrap()[..8], [2, 1, 0, 3, 6, 5, 4, 7]);
+    // a corrupt chunk is an error rather than garbage pixels
+    let last = ubulk.len() - 3;
+    ubulk[last] ^= 0xff;
+    assert_eq!(texture.mip_data(0, &[], &ubulk, &[]).unwrap_err().kind, error::ImageErrorKind::InvalidData);
+}
+
+#[wasm_bindgen_test]
+fn test_hdr_reconstruction() {
+    let rgbm = hdr::decode_rgbm(&[255, 51, 0, 128], 5.0, 1.0);
+    assert!((rgbm[0] - 5.0 * 128.0 / 255.0).abs() < 1e-4 && (rgbm[1] - 128.0 / 255.0).abs() < 1e-4);
+    assert_eq!((rgbm[2], rgbm[3]), (0.0, 1.0));
+    let dldr = hdr::decode_hdr(&[255, 128, 0, 7], "dldr", false).unwrap();
+    assert!((dldr[0] - 2.0).abs() < 1e-4);
+    assert!(hdr::decode_hdr(&[0; 4], "nope", true).is_err());
+    // LogLuv encoded white: X'/Z' = 0.238, Y/Z' = 0.781, log luminance 127.35
+    let white = hdr::decode_logluv(&[61, 199, 127, 90]);
+    assert!(white[..3].iter().all(|&c| (c - 1.0).abs() < 0.05), "{:?}", white);
+
+    assert_eq!(*hdr::tonemap(&[0.5, 2.0, 0.0, 1.0], 0.0, "clamp"), [188, 255, 0, 255]);
+    let file = hdr::encode_hdr(8, 2, &vec![1.0; 8 * 2 * 4], false).unwrap();
+    assert!(file.starts_with(b"#?RADIANCE\n"));
+    let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 2 +X 8\n".len();
+    // 1.0 is 0.5 * 2^1: mantissa 128, exponent 129
+    assert_eq!(file[header..header + 4], [2, 2, 0, 8]);
+    assert_eq!(file[header + 4..header + 6], [8, 128]);
+    assert_eq!(file.len(), header + 2 * (4 + 4 * 9));
+}
+
+#[wasm_bindgen_test]
+fn test_merge_split_alpha() {
+    let mut colour = vec![10u8; 4 * 4 * 4];
+    let alpha: Vec<u8> = (0..16).flat_map(|i| [i as u8 * 16, 0, 0, 255]).collect();
+    color::merge_alpha(&mut colour, 4, 4, &alpha, 4, 4, 0).unwrap();
+    assert_eq!(colour[5 * 4..5 * 4 + 4], [10, 10, 10, 80]);
+
+    // a half-size alpha texture is resampled to the colour texture's size
+    let small = [[0u8, 0, 0, 255], [255, 0, 0, 255], [0, 0, 0, 255], [255, 0, 0, 255]].concat();
+    color::merge_alpha(&mut colour, 4, 4, &small, 2, 2, 0).unwrap();
+    assert_eq!((colour[3], colour[3 * 4 + 3]), (0, 255));
+    assert!(color::merge_alpha(&mut colour, 4, 4, &small, 4, 4, 0).is_err());
+}
+
+#[wasm_bindgen_test]
+fn test_extra_formats() {
+    let mut data = vec![0x00, 0x80, 0xff, 0x7f];
+    assert_eq!(*texdec::decode("R16_SIGNED", &mut data, 2, 1, false, "", &[]).unwrap(), [0, 0, 0, 255, 255, 0, 0, 255]);
+    assert_eq!(*texdec::decode("RG16_SIGNED", &mut vec![0, 0x81], 1, 1, false, "", &[]).unwrap(), [128, 0, 0, 255]);
+    assert_eq!(*texdec::decode("RGBA64_SIGNED", &mut vec![0; 8], 1, 1, false, "", &[]).unwrap(), [128; 4]);
+
+    let floats: Vec<u8> = [1f32, 0.5].iter().flat_map(|f| f.to_le_bytes()).collect();
+    assert_eq!(texdec::decode("RG32F", &mut floats.clone(), 1, 1, false, "", &[]).unwrap(),
+               texdec::decode("RGFloat", &mut floats.clone(), 1, 1, false, "", &[]).unwrap());
+    assert_eq!(*texdec::decode("BGRA32Old", &mut vec![1, 2, 3, 4], 1, 1, false, "", &[]).unwrap(), [3, 2, 1, 4]);
+
+    // R = 1.0, G = 0.5, B = 0
+    let packed = (15u32 << 6) | (14 << 6) << 11;
+    let pixels = texdec::decode("B10G11R11_UFloat", &mut packed.to_le_bytes().to_vec(), 1, 1, false, "", &[]).unwrap();
+    assert_eq!(*pixels, [255, 127, 0, 255]);
+
+    // 16-bit formats are big-endian on Xbox 360
+    let mut le = vec![0x12, 0x34, 0x56, 0x78];
+    let mut be = vec![0x34, 0x12, 0x78, 0x56];
+    assert_eq!(texdec::decode("RGBA4444", &mut le, 2, 1, false, "", &[]).unwrap(),
+               texdec::decode("RGBA4444", &mut be, 2, 1, true, "", &[]).unwrap());
+
+    assert_eq!(*texdec::decode_p8(&[1, 5], &[0, 0, 0, 255, 1, 2, 3, 4], 2, 1).unwrap(), [1, 2, 3, 4, 0, 0, 0, 0]);
+
+    let mut explicit = vec![0u8; 16];
+    explicit[0] = 0xf0;
+    let pixels = texdec::decode("ATC_RGBA_EXPLICIT", &mut explicit, 4, 4, false, "", &[]).unwrap();
+    assert_eq!((pixels[3], pixels[7]), (0, 255));
+    let mut interpolated = vec![0u8; 16];
+    interpolated[..3].copy_from_slice(&[255, 0, 1]);
+    let pixels = texdec::decode("ATC_RGBA_INTERPOLATED", &mut interpolated, 4, 4, false, "", &[]).unwrap();
+    assert_eq!((pixels[3], pixels[7]), (0, 255));
+}
+
+/// Decodes a reference image from tests/fixtures: RGBA, or one byte per pixel for the `_alpha.png` ones.
+fn fixture_png(png: &[u8]) -> Vec<u8> {
+    let mut reader = png::Decoder::new(png).read_info().unwrap();
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).unwrap();
+    pixels.truncate(info.buffer_size());
+    pixels
+}
+
+#[wasm_bindgen_test]
+fn test_extra_format_fixtures() {
+    // reference decodes from tests/fixtures/make_fixtures.py
+    let fixtures: [(&str, &[u8], &[u8], usize, usize); 7] = [
+        ("PVRTC2_4BPP", include_bytes!("fixtures/pvrtc2_4bpp.bin"), include_bytes!("fixtures/pvrtc2_4bpp.png"), 16, 16),
+        ("PVRTC2_4BPP", include_bytes!("fixtures/pvrtc2_4bpp_wide.bin"), include_bytes!("fixtures/pvrtc2_4bpp_wide.png"), 32, 8),
+        ("PVRTC2_2BPP", include_bytes!("fixtures/pvrtc2_2bpp.bin"), include_bytes!("fixtures/pvrtc2_2bpp.png"), 32, 16),
+        ("R16_SIGNED", include_bytes!("fixtures/r16_signed.bin"), include_bytes!("fixtures/r16_signed.png"), 8, 8),
+        ("RG16_SIGNED", include_bytes!("fixtures/rg16_signed.bin"), include_bytes!("fixtures/rg16_signed.png"), 8, 8),
+        ("RGBA64_SIGNED", include_bytes!("fixtures/rgba64_signed.bin"), include_bytes!("fixtures/rgba64_signed.png"), 8, 8),
+        ("B10G11R11_UFloat", include_bytes!("fixtures/b10g11r11.bin"), include_bytes!("fixtures/b10g11r11.png"), 8, 8),
+    ];
+    for &(format, data, reference, width, height) in fixtures.iter() {
+        let pixels = texdec::decode(format, &mut data.to_vec(), width, height, false, "", &[]).unwrap();
+        assert!(*pixels == *fixture_png(reference), "{} {}x{}", format, width, height);
+    }
+
+    // the alpha halves are checked against the reference, the colour halves against plain ATC
+    let atc: [(&str, &[u8], &[u8]); 2] = [
+        ("ATC_RGBA_EXPLICIT", include_bytes!("fixtures/atc_explicit.bin"), include_bytes!("fixtures/atc_explicit_alpha.png")),
+        ("ATC_RGBA_INTERPOLATED", include_bytes!("fixtures/atc_interpolated.bin"), include_bytes!("fixtures/atc_interpolated_alpha.png")),
+    ];
+    for &(format, data, reference) in atc.iter() {
+        let pixels = texdec::decode(format, &mut data.to_vec(), 16, 16, false, "", &[]).unwrap();
+        let mut colour: Vec<u8> = data.chunks(16).flat_map(|block| block[8..].to_vec()).collect();
+        let rgb = texdec::decode("ATC_RGB4", &mut colour, 16, 16, false, "", &[]).unwrap();
+        let alpha = fixture_png(reference);
+        for i in 0..16 * 16 {
+            assert_eq!(pixels[i * 4..i * 4 + 3], rgb[i * 4..i * 4 + 3], "{} pixel {}", format, i);
+            assert_eq!(pixels[i * 4 + 3], alpha[i], "{} pixel {}", format, i);
+        }
+    }
+
+    let p8 = texdec::decode_p8(include_bytes!("fixtures/p8.bin"), include_bytes!("fixtures/p8_palette.bin"), 8, 8).unwrap();
+    assert_eq!(*p8, *fixture_png(include_bytes!("fixtures/p8.png")));
+}
+
+#[wasm_bindgen_test]
+fn test_pvrtc2() {
+    use encoders::error::ImageErrorKind;
+    fn block(modulation: u32, color: u32) -> Vec<u8> {
+        [modulation.to_le_bytes(), color.to_le_bytes()].concat()
+    }
+    // opaque, colour B red and colour A blue
+    let red_blue = 1 << 31 | 31 << 26 | 15 << 1;
+    let decode = |modulation: u32| {
+        let mut data = block(modulation, red_blue).repeat(4);
+        texdec::decode("PVRTC2_4BPP", &mut data, 8, 8, false, "", &[]).unwrap()
+    };
+    assert_eq!(decode(0)[..4], [0, 0, 255, 255]);
+    assert_eq!(decode(0xffffffff)[..4], [255, 0, 0, 255]);
+    assert_eq!(decode(0x55555555)[..4], [95, 0, 159, 255]);
+
+    // hard transition blocks aren't supported, so they're rejected instead of decoded approximately
+    for (format, width) in [("PVRTC2_4BPP", 8), ("PVRTC2_2BPP", 16)] {
+        let mut data = block(0, 1 << 31 | 31 << 5 | 1 << 15);
+        data.extend(block(0, red_blue).repeat(3));
+        let err = texdec::decode(format, &mut data, width, 8, false, "", &[]).unwrap_err();
+        assert_eq!((err.kind, err.format()), (ImageErrorKind::InvalidData, format.to_string()));
+    }
+
+    // translucent punch-through
+    let mut data = block(0xaaaaaaaa, 1).repeat(4);
+    assert_eq!(texdec::decode("PVRTC2_4BPP", &mut data, 8, 8, false, "", &[]).unwrap()[3], 0);
+
+    let mut data = block(0xffffffff, red_blue).repeat(4);
+    let pixels = texdec::decode("PVRTC2_2BPP", &mut data, 16, 8, false, "", &[]).unwrap();
+    assert_eq!(pixels[..4], [255, 0, 0, 255]);
+}
+
+#[wasm_bindgen_test]
+fn test_guess_texture_format() {
+    // a smooth 16x16 RGBA32 gradient
+    let mut data = Vec::new();
+    for y in 0..16u8 {
+        for x in 0..16u8 {
+            data.extend_from_slice(&[x * 16, y * 16, 128, 255]);
+        }
+    }
+    let guesses = detect::guess_texture_format(&data, 0, 0, 4);
+    assert_eq!(guesses.count(), 4);
+    let best = &guesses.guesses()[0];
+    assert_eq!((best.format, best.width, best.height, best.mip_count), ("RGBA32", 16, 16, 1));
+    assert!(guesses.guesses().windows(2).all(|w| w[0].score >= w[1].score));
+    assert_eq!(guesses.contact_sheet().len(), guesses.sheet_width * guesses.sheet_height * 4);
+    assert_eq!((guesses.sheet_width, guesses.sheet_height), (2 * guesses.cell_size, 2 * guesses.cell_size));
+
+    // with the width known, the height is solved for whatever the data fills
+    let guesses = detect::guess_texture_format(&data, 16, 0, 100);
+    assert!(guesses.guesses().iter().all(|g| g.width == 16));
+    assert!(guesses.guesses().iter().any(|g| g.format == "RGB565" && g.height == 32 && g.mip_count == 1));
+
+    // mipmap chains are matched too
+    let mut with_mips = data.clone();
+    with_mips.extend(vec![0u8; 8 * 8 * 4 + 4 * 4 * 4 + 2 * 2 * 4 + 4]);
+    let guesses = detect::guess_texture_format(&with_mips, 16, 16, 100);
+    assert!(guesses.guesses().iter().any(|g| g.format == "RGBA32" && g.mip_count == 5));
+}
+
+#[wasm_bindgen_test]
+fn test_guess_texture_format_etc_mips() {
+    let has = |guesses: &detect::FormatGuesses, format: &str, width: usize, height: usize, mips: usize| {
+        guesses.guesses().iter().any(|g| g.format == format && (g.width, g.height, g.mip_count) == (width, height, mips))
+    };
+
+    // a 256x256 ETC1 texture without mipmaps is 64x64 blocks of 8 bytes
+    let guesses = detect::guess_texture_format(&vec![0u8; 32768], 0, 0, 10000);
+    assert!(has(&guesses, "ETC_RGB4", 256, 256, 1));
+    assert!(!has(&guesses, "ETC_RGB4", 256, 128, 1));
+
+    // the full chain down to 1x1, where the last three mips are one block each
+    let chain = 32768 + 8192 + 2048 + 512 + 128 + 32 + 8 * 3;
+    let guesses = detect::guess_texture_format(&vec![0u8; chain], 256, 256, 10000);
+    assert!(has(&guesses, "ETC_RGB4", 256, 256, 9));
+
+    // ETC2_RGBA8 blocks are 16 bytes
+    let chain = 4096 + 1024 + 256 + 64 + 16 * 3;
+    let guesses = detect::guess_texture_format(&vec![0u8; chain], 64, 64, 10000);
+    assert!(has(&guesses, "ETC2_RGBA8", 64, 64, 7));
+    let guesses = detect::guess_texture_format(&vec![0u8; 4096], 64, 0, 10000);
+    assert!(has(&guesses, "ETC2_RGBA8", 64, 64, 1));
+}
+
+#[wasm_bindgen_test]
+fn test_texture_array_slices() {
+    // three 2x2 RGBA32 slices, each with a 1x1 mipmap after it
+    let mut data = Vec::new();
+    for slice in 0..3u8 {
+        for p in 0..4u8 {
+            data.extend_from_slice(&[slice, p, 0, 255]);
+        }
+        data.extend_from_slice(&[slice, 100, 0, 255]);
+    }
+    let array = texarray::TextureArray::new("RGBA32", data.clone().into(), 2, 2, 3, 2, false, "").unwrap();
+    assert_eq!(array.decode_slice(1, 0).unwrap().pixels()[..8], [1, 0, 0, 255, 1, 1, 0, 255]);
+    assert_eq!(*array.decode_slice(2, 1).unwrap().pixels(), [2, 100, 0, 255]);
+    assert!(array.decode_slice(3, 0).is_err());
+    assert!(texarray::TextureArray::new("RGBA32", data[..50].into(), 2, 2, 3, 2, false, "").is_err());
+
+    let atlas = array.atlas(0, 2, false).unwrap();
+    assert_eq!((atlas.width, atlas.height), (4, 4));
+    // slice 1 is to the right of slice 0, and slice 2 below it
+    assert_eq!(atlas.pixels()[8..12], [1, 0, 0, 255]);
+    assert_eq!(atlas.pixels()[32..36], [2, 0, 0, 255]);
+    assert_eq!(atlas.pixels()[40..44], [0, 0, 0, 0]);
+
+    let dds = array.to_dds(false).unwrap();
+    assert_eq!(&dds[..4], b"DDS ");
+    // the DX10 header's array size, then slice 0's two mipmaps before slice 1
+    assert_eq!(dds[128 + 12], 3);
+    assert_eq!(dds.len(), 148 + 3 * 20);
+    assert_eq!(dds[148 + 16..148 + 20], [0, 100, 0, 255]);
+    let ktx = array.to_ktx().unwrap();
+    assert_eq!(ktx[1..4], *b"KTX");
+    assert_eq!(ktx.len(), 64 + 4 + 3 * 16 + 4 + 3 * 4);
+
+    // a 2x2x2 volume: both depth slices of mipmap 0, then the single 1x1x1 slice of mipmap 1
+    let mut volume_data = Vec::new();
+    for i in 0..9u8 {
+        volume_data.extend_from_slice(&[i, 0, 0, 255]);
+    }
+    let volume = texarray::TextureArray::new("RGBA32", volume_data.into(), 2, 2, 2, 2, true, "").unwrap();
+    assert_eq!(volume.mip_depth(1), 1);
+    assert_eq!(volume.decode_slice(1, 0).unwrap().pixels()[0], 4);
+    assert_eq!(volume.decode_slice(0, 1).unwrap().pixels()[0], 8);
+}
+
+#[wasm_bindgen_test]
+fn test_lzma_decompress() {
+    // ".lzma" with an unknown size and an end marker, dictionary 64 KiB
+    let alone: [u8; 79] = [
+        0x5d, 0x00, 0x00, 0x01, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x21, 0x90,
+        0x44, 0x50, 0xa6, 0x66, 0x25, 0xad, 0x6d, 0x8f, 0x20, 0xf0, 0x37, 0x41, 0x78, 0x6e, 0xac, 0x6b,
+        0xaa, 0x2c, 0x04, 0xcf, 0xc5, 0xc1, 0x64, 0x9c, 0x2f, 0x40, 0x4b, 0xc5, 0xdf, 0x3e, 0x90, 0xc9,
+        0x33, 0x6b, 0x62, 0x7f, 0xf5, 0xa4, 0x47, 0x1b, 0x61, 0x4d, 0x43, 0x29, 0x60, 0xc6, 0xc5, 0x0f,
+        0x25, 0xc9, 0x86, 0x04, 0x8d, 0x58, 0xbe, 0x91, 0x12, 0x43, 0xff, 0xf3, 0x68, 0x18, 0xe0,
+    ];
+    let mut expected = b"CAB-".to_vec();
+    expected.extend(b"0123456789abcdef".iter().cycle().take(128));
+    expected.extend_from_slice(b"CAB-");
+    expected.extend(0..32u8);
+    assert_eq!(compress::lzma_alone_decompress_raw(&alone).unwrap(), expected);
+
+    // the same stream as a UnityFS block: properties without the size
+    let mut block = alone[..5].to_vec();
+    block.extend_from_slice(&alone[13..]);
+    assert_eq!(compress::lzma_decompress_raw(&block, expected.len()).unwrap(), expected);
+    assert!(compress::lzma_decompress_raw(&block, expected.len() + 1).is_err());
+    assert!(compress::lzma_decompress_raw(&block[..40], expected.len()).is_err());
+
+    let mut stream = compress::LzmaStream::new(block.into(), expected.len()).ok().unwrap();
+    let mut streamed = Vec::new();
+    while !stream.finished() {
+        streamed.extend_from_slice(&stream.read(50).ok().unwrap());
+    }
+    assert_eq!(streamed, expected);
+    assert_eq!(stream.total_out(), expected.len());
+}
+
+#[wasm_bindgen_test]
+fn test_unified_decompress() {
+    use std::io::Write;
+    use compress::{decompress_with, Codec};
+    use error::CompressionErrorKind;
+
+    let text = b"m_Name m_Texture m_Name m_Texture m_Name m_Texture".to_vec();
+    let mut deflate = flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+    deflate.write_all(&text).unwrap();
+    let mut zlib = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    zlib.write_all(&text).unwrap();
+    let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    gzip.write_all(&text).unwrap();
+    for (codec, data) in [(Codec::Deflate, deflate.finish().unwrap()), (Codec::Zlib, zlib.finish().unwrap()),
+                          (Codec::GZip, gzip.finish().unwrap()), (Codec::Lz4, compress::lz4_compress(&mut text.clone()).to_vec()),
+                          (Codec::None, text.clone())] {
+        assert_eq!(decompress_with(codec, &data, text.len()).unwrap(), text, "{:?}", codec);
+        let short = decompress_with(codec, &data, text.len() - 1).unwrap_err();
+        if codec == Codec::None {
+            assert_eq!((short.kind, short.expected, short.actual), (CompressionErrorKind::SizeMismatch, text.len() - 1, text.len()));
+        } else {
+            // decoding stops at the expected size, so there's no real size to report
+            assert_eq!((short.kind, short.expected, short.actual), (CompressionErrorKind::OutputTooLarge, text.len() - 1, 0), "{:?}", codec);
+            assert_eq!(short.to_string(), format!("{} data decompresses to more than the expected {} bytes", codec.name(), text.len() - 1));
+        }
+        assert_eq!(decompress_with(codec, &data, text.len() + 1).unwrap_err().kind, CompressionErrorKind::SizeMismatch);
+    }
+
+    let zstd = [
+        0x28, 0xb5, 0x2f, 0xfd, 0x24, 0xc8, 0x6d, 0x00, 0x00, 0x30, 0x67, 0x6f, 0x64, 0x6f, 0x74, 0x67,
+        0x01, 0x00, 0x3f, 0x50, 0xa5, 0x08, 0x9b, 0x9d, 0x09, 0xc7,
+    ];
+    assert_eq!(decompress_with(Codec::Zstd, &zstd, 200).unwrap(), b"godot".repeat(40));
+    assert_eq!(decompress_with(Codec::Zstd, &zstd[..20], 200).unwrap_err().kind, CompressionErrorKind::InvalidData);
+    // one uncompressed meta-block of 5 bytes, then an empty last one
+    let brotli = [0x40, 0x00, 0x10, b'h', b'e', b'l', b'l', b'o', 0x03];
+    assert_eq!(decompress_with(Codec::Brotli, &brotli, 5).unwrap(), b"hello");
+
+    let lzma = compress::decompress("lzma", &[0x5d, 0, 0, 1, 0, 0, 0, 0, 0, 0], 0).unwrap();
+    assert!(lzma.is_empty());
+    assert_eq!(Codec::from_name("GZIP"), Some(Codec::GZip));
+    assert_eq!(compress::decompress("lzo", &[], 0).unwrap_err().kind, CompressionErrorKind::UnknownCodec);
+}
+
+#[wasm_bindgen_test]
+fn test_lz4hc_and_lzma_roundtrip() {
+    use compress::{decompress_with, Codec};
+    use lzma_rs::decompress::{Options, UnpackedSize};
+
+    // checks against decoders that don't share code with ours
+    fn lz4_reference(data: &[u8], size: usize) -> Vec<u8> {
+        lz4_flex::block::decompress(data, size).unwrap()
+    }
+    fn lzma_reference(data: &[u8], size: usize) -> Vec<u8> {
+        let options = Options { unpacked_size: UnpackedSize::UseProvided(Some(size as u64)), ..Default::default() };
+        let mut out = Vec::new();
+        lzma_rs::lzma_decompress_with_options(&mut &data[..], &mut out, &options).unwrap();
+        out
+    }
+
+    // text-like data with repeats at various distances, plus a stretch of noise
+    let mut data = Vec::new();
+    let mut seed = 1u32;
+    for i in 0..3000 {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        let word: &[u8] = [b"m_Texture".as_ref(), b"m_Name", b"Sprite", b"\x00\x00\x80\x3f"][(seed >> 16) as usize % 4];
+        data.extend_from_slice(word);
+        data.push((i % 251) as u8);
+    }
+    data.extend((0..2000).map(|_| {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        (seed >> 16) as u8
+    }));
+
+    let fast = compress::lz4_compress(&mut data.clone()).len();
+    for level in 1..=12 {
+        let compressed = compress::lz4hc_compress(&data, level);
+        assert_eq!(decompress_with(Codec::Lz4Hc, &compressed, data.len()).unwrap(), data, "level {}", level);
+        assert_eq!(lz4_reference(&compressed, data.len()), data, "level {}", level);
+        if level >= 9 {
+            assert!(compressed.len() < fast);
+        }
+    }
+    for level in 0..=9 {
+        let compressed = compress::lzma_compress(&data, level);
+        // lc = 3, lp = 0, pb = 2, with the dictionary cut down to the data size (rounded up to 32 KiB);
+        // level 0 has the SDK's 16 KiB dictionary
+        let dict: u32 = if level == 0 { 1 << 14 } else { 1 << 15 };
+        assert_eq!(compressed[0], 0x5d);
+        assert_eq!(compressed[1..5], dict.to_le_bytes());
+        assert_eq!(decompress_with(Codec::Lzma, &compressed, data.len()).unwrap(), data, "level {}", level);
+        assert_eq!(lzma_reference(&compressed, data.len()), data, "level {}", level);
+        assert!(compressed.len() < data.len() / 2);
+        let alone = compress::lzma_alone_compress(&data, level);
+        assert_eq!(compress::lzma_alone_decompress_raw(&alone).unwrap(), data);
+        let mut out = Vec::new();
+        lzma_rs::lzma_decompress(&mut &alone[..], &mut out).unwrap();
+        assert_eq!(out, data, "level {}", level);
+    }
+    for n in [0, 1, 12, 13, 40] {
+        let small: Vec<u8> = (0..n).map(|i| (i % 3) as u8).collect();
+        let lz4 = compress::lz4hc_compress(&small, 12);
+        assert_eq!(decompress_with(Codec::Lz4Hc, &lz4, n).unwrap(), small);
+        assert_eq!(lz4_reference(&lz4, n), small);
+        let lzma = compress::lzma_compress(&small, 9);
+        assert_eq!(decompress_with(Codec::Lzma, &lzma, n).unwrap(), small);
+        assert_eq!(lzma_reference(&lzma, n), small);
+    }
+}
+
+#[wasm_bindgen_test]
+fn test_simd_matches_scalar() {
+    type Decoder = fn(&mut [u8], usize, usize) -> Result<Box<[u8]>, error::ImageError>;
+    // A single pixel never fills a vector, so decoding pixel by pixel always takes the scalar loop.
+    fn per_pixel(decode: Decoder, data: &[u8], bpp: usize) -> Vec<u8> {
+        data.chunks(bpp).flat_map(|px| decode(&mut px.to_vec(), 1, 1).unwrap().into_vec()).collect()
+    }
+
+    let mut seed = 7u32;
+    let mut next = || {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        seed
+    };
+    // 37 pixels: several whole vectors plus a scalar tail for every vector width
+    let (width, height) = (37, 1);
+    let words: Vec<u8> = (0..width * 2).map(|_| next() as u8).collect();
+
+    // halfs: 0, 1, 0.5, -2, 65504, +inf, NaN, a subnormal, then random bit patterns
+    let special_halfs = [0x0000u16, 0x3c00, 0x3800, 0xc000, 0x7bff, 0x7c00, 0x7e00, 0x0001, 0x03ff, 0x8001];
+    let halfs: Vec<u8> = (0..width * 4)
+        .map(|i| special_halfs.get(i).copied().unwrap_or(next() as u16))
+        .flat_map(|h| h.to_le_bytes())
+        .collect();
+
+    let special_floats = [0.0f32, 1.0, 254.9, 255.0, 255.5, 300.0, -0.5, -1e9, 1e9, f32::INFINITY, f32::NEG_INFINITY, f32::NAN, 1e-40];
+    let floats: Vec<u8> = (0..width * 4)
+        .map(|i| special_floats.get(i).copied().unwrap_or((next() % 70000) as f32 / 200.0 - 50.0))
+        .flat_map(|f| f.to_le_bytes())
+        .collect();
+
+    let cases: [(Decoder, &[u8], usize); 4] = [
+        (texdec::decode_rgb565, &words, 2),
+        (texdec::decode_argb4444, &words, 2),
+        (texdec::decode_rgbahalf, &halfs, 8),
+        (texdec::decode_rgbafloat, &floats, 16),
+    ];
+    for (decode, data, bpp) in cases {
+        let whole = decode(&mut data.to_vec(), width, height).unwrap();
+        assert_eq!(whole[..], per_pixel(decode, data, bpp)[..]);
+    }
+}
+
+#[wasm_bindgen_test]
+fn test_astc_strips_match_whole_image() {
+    // constant-colour (void-extent) blocks with a different colour each, so a block decoded into the
+    // wrong place shows up
+    for (width, height, bw, bh) in [(16, 16, 6, 6), (20, 13, 8, 8), (12, 10, 4, 4)] {
+        let blocks = ((width + bw - 1) / bw) * ((height + bh - 1) / bh);
+        let mut data = Vec::new();
+        for i in 0..blocks as u16 {
+            data.extend_from_slice(&[0xfc, 0xfd, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+            for c in [i * 4000, 65535 - i * 3000, i * 1000, 65535] {
+                data.extend_from_slice(&c.to_le_bytes());
+            }
+        }
+        let mut whole = vec![0u32; width * height];
+        texture2ddecoder::decode_astc(&data, width, height, bw, bh, &mut whole).unwrap();
+        let whole: Vec<u8> = whole.iter().flat_map(|p| p.to_le_bytes()).collect();
+        assert_eq!(*texdec::decode_astc(&mut data, width, height, bw, bh).unwrap(), whole[..]);
+    }
+}
@@ -1 +1,20 @@
 export const isDebug = ['localhost', '127.0.0.1'].includes(window.location.hostname);
+
+export function getImageFormat() {
+  return document.getElementById('image-format')?.value ?? 'png';
+}
+
+// The PNG compression level and filter strategy for `encode_image_with_options`; other formats ignore them
+export function getPngOptions() {
+  return {
+    compression: document.getElementById('png-compression')?.value ?? 'fast',
+    filter: document.getElementById('png-filter')?.value ?? 'sub',
+  };
+}
+
+// The `decodeRaw` options chosen in the sidebar for exported images
+export function getExportOptions() {
+  return {
+    bleedAlpha: document.getElementById('bleed-alpha')?.checked ?? false,
+  };
+}
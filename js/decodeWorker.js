@@ -0,0 +1,25 @@
+// Decodes textures with the threaded build of the encoders (see encoders/build.sh). Rayon blocks while
+// it waits for its threads, which browsers only allow off the main thread, so the decoding happens in here.
+import init, {decode, decode_lenient, initThreadPool} from "./encoders-threads";
+
+const ready = init().then(() => initThreadPool(navigator.hardwareConcurrency));
+
+self.onmessage = async ({data: {id, format, data, width, height, isXbox, platform, platformBlob, lenient}}) => {
+  try {
+    await ready;
+    let pixels;
+    let partial = false;
+    if (lenient) {
+      const image = decode_lenient(format, data, width, height, isXbox, platform, platformBlob);
+      pixels = image.data;
+      partial = image.partial;
+      image.free();
+    } else {
+      pixels = decode(format, data, width, height, isXbox, platform, platformBlob);
+    }
+    // the pixels were copied out of the shared wasm memory, so they can be handed over without another copy
+    self.postMessage({id, pixels, partial}, [pixels.buffer]);
+  } catch (e) {
+    self.postMessage({id, error: e.message ?? String(e)});
+  }
+};
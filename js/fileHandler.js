@@ -111,6 +111,8 @@ export default class FileHandler {
     if (this._checkMagicBasic(4, [0x47, 0x44, 0x53, 0x54])) return fileTypes.GodotStreamTexture;
     // CompressedTexture: "GST2"
     if (this._checkMagicBasic(4, [0x47, 0x53, 0x54, 0x32])) return fileTypes.GodotCompressedTexture;
+    // CompressedTextureLayered: "GSTL"
+    if (this._checkMagicBasic(4, [0x47, 0x53, 0x54, 0x4c])) return fileTypes.GodotCompressedTexture;
     // Scene: "GDSC"
     if (this._checkMagicBasic(4, [0x47, 0x44, 0x53, 0x43])) return fileTypes.GodotScene;
     // [FSB5]
@@ -1,5 +1,4 @@
-import {ZSTDDecoder} from 'zstddec';
-import {lz4_decompress} from "../encoders";
+import {decompress} from "../encoders";
 
 const CompressionMode = [
   'LZ',
@@ -9,18 +8,21 @@ const CompressionMode = [
   'Brotli'
 ];
 
+// Godot's "Deflate" mode writes zlib streams
+const Codecs = {
+  LZ: 'LZ4',
+  Deflate: 'Zlib',
+  Zstd: 'Zstd',
+  GZip: 'GZip',
+  Brotli: 'Brotli'
+};
+
 export class DataDecompressor {
   constructor() {}
 
   async decompress(reader) {
     // we should be at the part we need already
     this.mode = CompressionMode[reader.readInt32()];
-    switch (this.mode) {
-      case 'Zstd':
-        this.decoder = new ZSTDDecoder();
-        await this.decoder.init();
-        break;
-    }
     this.blockSize = reader.readInt32();
     if (this.blockSize === 0) {
       throw new Error('Block size cannot be 0');
@@ -37,7 +39,7 @@ export class DataDecompressor {
     let offset = 0;
     for (const size of this.blockSizes) {
       let comp = reader.read(size);
-      const buf = this.decompressBlock(comp);
+      const buf = this.decompressBlock(comp, Math.min(this.blockSize, this.readTotal - offset));
       this.data.set(buf, offset);
       offset += buf.length;
     }
@@ -45,18 +47,7 @@ export class DataDecompressor {
     return this.data;
   }
 
-  decompressBlock(data) {
-    switch (this.mode) {
-      case 'LZ':
-        return lz4_decompress(data, this.blockSize);
-      case 'Deflate':
-        return pako.inflate(data);
-      case 'Zstd':
-        return this.decoder.decode(data, this.blockSize);
-      case 'GZip':
-        return pako.inflate(data);
-      case 'Brotli':
-        break;
-    }
+  decompressBlock(data, size) {
+    return decompress(Codecs[this.mode] ?? this.mode, data, size);
   }
 }
\ No newline at end of file
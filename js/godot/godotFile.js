@@ -2,7 +2,7 @@ import {BinaryReader} from "../binaryReader";
 import FileHandler, {fileTypes} from "../fileHandler";
 import {PckFile} from "./pckFile";
 import {Resource} from "./resource";
-import {StreamTexture} from "./types/texture";
+import {CompressedTexture, StreamTexture} from "./types/texture";
 import {NtExecutable} from "pe-library";
 import {ELFParser} from "@wokwi/elfist";
 
@@ -23,6 +23,7 @@ export class GodotFile {
         this.parser = new StreamTexture(reader);
         break;
       case fileTypes.GodotCompressedTexture:
+        this.parser = new CompressedTexture(reader);
         break;
       case fileTypes.GodotScene:
         break;
@@ -73,6 +73,7 @@ export class Resource extends ResourceType {
       case 'GDST':
         return StreamTexture;
       case 'GST2':
+      case 'GSTL':
         return CompressedTexture;
       default:
         return null;
@@ -1,202 +1,71 @@
-import {decode, encode_png, get_mipmap_byte_size, get_mipmap_offset_and_size} from "../../encoders";
-import {arraysEqual} from "../../utils";
+import {GodotTexture, encode_png} from "../../encoders";
 import {ResourceType} from "../type";
 import {ImagePreview} from "../../preview/image";
 
-const
-  DATA_FORMAT_IMAGE = 0,
-  DATA_FORMAT_PNG = 1,
-  DATA_FORMAT_WEBP = 2,
-  DATA_FORMAT_BASIS_UNIVERSAL = 3;
-
-const
-  FORMAT_MASK_IMAGE_FORMAT = (1 << 20) - 1,
-  FORMAT_BIT_LOSSLESS = 1 << 20,
-  FORMAT_BIT_LOSSY = 1 << 21,
-  FORMAT_BIT_STREAM = 1 << 22,
-  FORMAT_BIT_HAS_MIPMAPS = 1 << 23,
-  FORMAT_BIT_DETECT_3D = 1 << 24,
-  FORMAT_BIT_DETECT_SRGB = 1 << 25,  // legacy
-  FORMAT_BIT_DETECT_NORMAL = 1 << 26,
-  FORMAT_BIT_DETECT_ROUGHNESS = 1 << 27;  // CompressedTexture only
-
-
-const formatNames = [  // Names changed to match size-per-pixel, not size-per-channel
-  "L8", // luminance
-  "LA16", // luminance-alpha
-  "R8",
-  "RG16",
-  "RGB24",
-  "RGBA32",
-  "RGBA4444",
-  "RGBA5551",
-  "RFloat", // float
-  "RGFloat",
-  "RGBFloat",
-  "RGBAFloat",
-  "RHalf",
-  "RGHalf",
-  "RGBHalf",
-  "RGBAHalf",
-  "RGB9e5Float",
-  "DXT1",
-  "DXT3",
-  "DXT5",
-  "BC4",
-  "BC5",
-  "BC6H",
-  "BPTC_RGBF",  // unsupported
-  "BPTC_RGBFU",  // unsupported
-  "ETC_RGB4",
-  "ETC2_R11",  // unsupported
-  "ETC2_R11S",  // signed, NOT srgb. unsupported
-  "ETC2_RG11",  // unsupported
-  "ETC2_RG11S",  // unsupported
-  "ETC2_RGB",
-  "ETC2_RGBA8",
-  "ETC2_RGBA1",
-  "ETC2_RA_AS_RG",  // unsupported
-  "FORMAT_DXT5_RA_AS_RG",  // unsupported
-  "ASTC_RGB_4x4",
-  "ASTC_HDR_4x4",
-  "ASTC_RGB_8x8",
-  "ASTC_HDR_8x8",
-];
-
-export class StreamTexture extends ResourceType {  // older v3 format (last commit dc32083681a770e9d7e332c5beed30b52c793752)
+class GodotTextureBase extends ResourceType {  // .stex (v3), .ctex, .ctexarray, .ccube and .ccubearray (v4)
   constructor(reader) {
     super();
-    if (reader.readChars(4) !== 'GDST') {
-      throw new Error('Invalid stream texture');
+    this.texture = new GodotTexture(reader.read(reader.size - reader.tell()));
+    this.version = this.texture.version;
+    this.width = this.texture.width;
+    this.height = this.texture.height;
+    this.kind = this.texture.kind;
+
+    // one preview entry per mipmap of every layer
+    this.entries = [];
+    for (let layer = 0; layer < this.texture.layer_count; layer++) {
+      for (let level = 0; level < this.texture.mipmap_count(layer); level++) {
+        this.entries.push({layer, level});
+      }
     }
-
-    this.width = reader.readInt16();
-    this.customWidth = reader.readInt16();
-    this.height = reader.readInt16();
-    this.customHeight = reader.readInt16();
-
-    this.flags = reader.readUInt32();
-    this.dataFormat = reader.readUInt32();
-
-    if ((this.dataFormat & FORMAT_BIT_LOSSLESS) || (this.dataFormat & FORMAT_BIT_LOSSY)) {
-      this.mipMaps = reader.readInt32();
-      let size = reader.readInt32();
-      this.images = reader.readArrayT(() => {
-        let format = reader.readChars(4);
-        return reader.read(size)
-      }, this.mipMaps);
-    } else {
-      this.mipMaps = 1;
-      let format = formatNames[this.dataFormat & FORMAT_MASK_IMAGE_FORMAT];
-      this.images = [encode_png(
-        this.width,
-        this.height,
-        decode(
-          format,
-          reader.read(reader.readInt32()),
-          this.width,
-          this.height,
-          false
-        ),
-        false
-      )];
-    }
-
+    this.images = this.entries.map(() => null);
     this._lastViewed = 0;
   }
 
-  createDataUrl(imageNum) {
-    let image = this.images[imageNum];
-    return URL.createObjectURL(new Blob([image], {
-      type:  image[0] === 0x57 ? 'image/webp' : (image[0] === 0x89 ? 'image/png' : 'image/jpeg')}));
-  }
-
-  async createPreview() {
-    return await new ImagePreview(this.mipMaps, m => {
-      this._lastViewed = m;
-      return this.createDataUrl(m);
-    }).create();
+  get mipmapCount() {
+    return this.texture.mipmap_count(0);
   }
 
-  exportFile(res) {
-    return this.images[this._lastViewed];
+  get layerCount() {
+    return this.texture.layer_count;
   }
-}
 
-export class CompressedTexture extends ResourceType {  // newer format
-  constructor(reader) {
-    super();
-    if (reader.readChars(4) !== 'GST2') {
-      throw new Error('Invalid compressed texture');
+  getImage(imageNum) {
+    if (!this.images[imageNum]) {
+      const {layer, level} = this.entries[imageNum];
+      const dataFormat = this.texture.data_format(layer);
+      if (dataFormat === 'png' || dataFormat === 'webp') {
+        this.images[imageNum] = this.texture.raw_data(layer, level);
+      } else {
+        const image = this.texture.decode(layer, level);
+        this.images[imageNum] = encode_png(image.width, image.height, image.pixels, false);
+      }
     }
-
-    this.version = reader.readInt32();
-
-    this.width = reader.readInt32();
-    this.height = reader.readInt32();
-
-    this.flags = reader.readUInt32();
-    this.mipmapLimit = reader.readInt32();
-
-    reader.read(12);  // reserved
-
-    this.dataFormat = reader.readInt32();  // of DATA_FORMAT
-    this.imageWidth = reader.readInt16();
-    this.imageHeight = reader.readInt16();
-    this.mipmapCount = reader.readInt32() + 1;
-    this.imageFormat = formatNames[reader.readInt32()];
-
-    this.images = [];
-    switch (this.dataFormat) {
-      case DATA_FORMAT_PNG:
-      case DATA_FORMAT_WEBP:
-        for (let i = 0; i < this.mipmapCount; i++) {
-          this.images.push(reader.read(reader.readUInt32()));
-        }
-        break;
-      case DATA_FORMAT_IMAGE:
-        for (let i = 0; i < this.mipmapCount; i++) {
-          const r1 = get_mipmap_offset_and_size(i, this.imageFormat, this.imageWidth, this.imageHeight);
-          const r2 = get_mipmap_offset_and_size(i + 1, this.imageFormat, this.imageWidth, this.imageHeight);
-          const mipSize = r2[0] - r1[0];
-          const w = r1[1];
-          const h = r1[2];
-          const data = reader.read(mipSize);
-          this.images.push(encode_png(
-            w,
-            h,
-            decode(
-              this.imageFormat,
-              data,
-              w,
-              h,
-              false
-            ),
-            false
-          ));
-        }
-        break;
-      case DATA_FORMAT_BASIS_UNIVERSAL:
-        throw new Error('Basis Universal not supported');
-    }
-
-    this._lastViewed = 0;
+    return this.images[imageNum];
   }
 
   createDataUrl(imageNum) {
-    let image = this.images[imageNum];
+    let image = this.getImage(imageNum);
     return URL.createObjectURL(new Blob([image], {
-      type:  image[0] === 0x57 ? 'image/webp' : (image[0] === 0x89 ? 'image/png' : 'image/jpeg')}));
+      type:  image[0] === 0x52 ? 'image/webp' : (image[0] === 0x89 ? 'image/png' : 'image/jpeg')}));
   }
 
   async createPreview() {
-    return await new ImagePreview(this.mipmapCount, m => {
+    return await new ImagePreview(this.entries.length, m => {
       this._lastViewed = m;
       return this.createDataUrl(m);
     }).create();
   }
 
   exportFile(res) {
-    return this.images[this._lastViewed];
+    return this.getImage(this._lastViewed);
   }
 }
+
+export class StreamTexture extends GodotTextureBase {  // older v3 format (last commit dc32083681a770e9d7e332c5beed30b52c793752)
+  get mipMaps() {
+    return this.mipmapCount;
+  }
+}
+
+export class CompressedTexture extends GodotTextureBase {}  // newer format, including layered textures
@@ -9,18 +9,29 @@ function configureDebug() {
   }
 }
 
-async function onFile(f) {
+async function onFiles(files) {
   const h = new FileHandler('#tree');
-  const res = await h.loadFile(f);
+  const res = await h.loadFile(files[0]);
   if (!res) return;
   const tree = await h.getTree();
+  // more files are added to the same tree, if it supports it
+  for (const f of [...files].slice(1)) {
+    const other = new FileHandler('#tree');
+    if (!await other.loadFile(f)) continue;
+    if (typeof tree.addFile !== 'function' || !await tree.addFile(other.data, other.name)) {
+      console.warn(`Cannot open ${other.name} together with ${h.name}`);
+    }
+  }
   document.getElementById('export-zip').onclick = () => tree.exportZip();
+  const findDuplicates = document.getElementById('find-duplicates');
+  findDuplicates.style.display = typeof tree.showDuplicateTextures === 'function' ? '' : 'none';
+  findDuplicates.onclick = () => tree.showDuplicateTextures();
 }
 
 // MAIN
 function main() {
   const input = document.getElementById('file-input');
-  input.addEventListener('change', async e => onFile(e.target.files[0]));
+  input.addEventListener('change', async e => e.target.files.length > 0 && onFiles(e.target.files));
   const fileOverlay = document.getElementById('file-overlay');
   // document.body.addEventListener('dragover', () => fileOverlay.style.display = 'block');
   // document.body.addEventListener('dragleave', () => fileOverlay.style.display = 'none');
@@ -29,7 +40,7 @@ function main() {
     console.log(e.dataTransfer);
     const files = e.dataTransfer.files;
     if (files.length > 0) {
-      onFile(files[0]);
+      onFiles(files);
     }
     fileOverlay.style.display = 'none'
   }, true);
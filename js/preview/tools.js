@@ -0,0 +1,141 @@
+import {saveBlob} from "../utils";
+
+/**
+ * Renders an object's extra actions (from its `getTools()`) as collapsible forms under the preview.
+ *
+ * Each tool is `{label, fields, run}`. Fields are `{name, label, type, value, options}` where `type` is
+ * 'number', 'text', 'checkbox' or 'select'. A select's `options` are `[value, label]` pairs, or an async
+ * function returning them that is called when the tool is first opened.
+ * `run(values)` resolves to a file to save (`{name, data, type}`), an element to show under the form,
+ * or nothing.
+ */
+export function createToolPanel(tools) {
+  const panel = document.createElement('div');
+  panel.classList.add('preview-tools');
+  for (const tool of tools) {
+    panel.appendChild(createTool(tool));
+  }
+  return panel;
+}
+
+function createField(field) {
+  const label = document.createElement('label');
+  label.classList.add('preview-tool-field');
+  label.textContent = field.label + ' ';
+  let input;
+  let load = async () => {};
+  if (field.type === 'select') {
+    input = document.createElement('select');
+    const setOptions = options => {
+      input.innerHTML = '';
+      for (const [value, text] of options) {
+        const option = document.createElement('option');
+        option.value = value;
+        option.textContent = text;
+        input.appendChild(option);
+      }
+      input.value = field.value ?? options[0]?.[0];
+    };
+    if (typeof field.options === 'function') {
+      load = async () => setOptions(await field.options());
+    } else {
+      setOptions(field.options);
+    }
+  } else {
+    input = document.createElement('input');
+    input.type = field.type;
+    if (field.type === 'checkbox') {
+      input.checked = !!field.value;
+    } else {
+      input.value = field.value ?? '';
+    }
+    if (field.min !== undefined) input.min = field.min;
+    if (field.max !== undefined) input.max = field.max;
+    if (field.step !== undefined) input.step = field.step;
+  }
+  label.appendChild(input);
+  return {label, load, read: () => {
+    switch (field.type) {
+      case 'checkbox':
+        return input.checked;
+      case 'number':
+        return Number(input.value);
+      default:
+        return input.value;
+    }
+  }};
+}
+
+function createTool(tool) {
+  const details = document.createElement('details');
+  details.classList.add('preview-tool');
+  const summary = document.createElement('summary');
+  summary.textContent = tool.label;
+  details.appendChild(summary);
+
+  const fields = (tool.fields ?? []).map(f => ({name: f.name, ...createField(f)}));
+  for (const f of fields) {
+    details.appendChild(f.label);
+  }
+  let loaded = false;
+  details.addEventListener('toggle', async () => {
+    if (details.open && !loaded) {
+      loaded = true;
+      await Promise.all(fields.map(f => f.load()));
+    }
+  });
+
+  const button = document.createElement('button');
+  button.classList.add('button', 'preview-button');
+  button.textContent = tool.action ?? 'Run';
+  details.appendChild(button);
+
+  const output = document.createElement('div');
+  output.classList.add('preview-tool-output');
+  details.appendChild(output);
+
+  button.addEventListener('click', async () => {
+    const values = {};
+    for (const f of fields) {
+      values[f.name] = f.read();
+    }
+    button.disabled = true;
+    output.textContent = 'Working...';
+    try {
+      const result = await tool.run(values);
+      output.textContent = '';
+      if (result instanceof Node) {
+        output.appendChild(result);
+      } else if (result?.data !== undefined) {
+        saveBlob(result.name, [result.data], result.type);
+      }
+    } catch (e) {
+      console.error(e);
+      output.textContent = `Error: ${e.message ?? e}`;
+    } finally {
+      button.disabled = false;
+    }
+  });
+  return details;
+}
+
+/**
+ * Shows the result of an analysis tool: a table of `[label, value]` rows and, optionally, an image.
+ */
+export function createReport(rows, image = null) {
+  const container = document.createElement('div');
+  const table = document.createElement('table');
+  for (const [label, value] of rows) {
+    const row = table.insertRow();
+    row.insertCell().textContent = label;
+    row.insertCell().textContent = value;
+  }
+  container.appendChild(table);
+  if (image != null) {
+    const img = document.createElement('img');
+    img.src = URL.createObjectURL(new Blob([image], {type: 'image/png'}));
+    img.onload = () => URL.revokeObjectURL(img.src);
+    container.appendChild(img);
+  }
+  return container;
+}
@@ -0,0 +1,53 @@
+import {DecodeBuffer, decode_lenient, simdSupported} from "./encoders";
+
+// Textures are decoded on a worker with a thread pool when the page is cross-origin isolated (SharedArrayBuffer
+// needs it), and on the main thread with the single-threaded build otherwise. The threaded build also uses SIMD.
+const threaded = globalThis.crossOriginIsolated === true && simdSupported;
+
+// One wasm-side output buffer shared by every main-thread decode, so decoding doesn't allocate in wasm memory each time
+const decodeBuffer = new DecodeBuffer();
+
+let worker = null;
+let nextId = 0;
+const pending = new Map();
+
+function getWorker() {
+  if (worker == null) {
+    worker = new Worker(new URL('./decodeWorker.js', import.meta.url), {type: 'module'});
+    worker.onmessage = ({data: {id, pixels, partial, error}}) => {
+      const {resolve, reject} = pending.get(id);
+      pending.delete(id);
+      if (error !== undefined) {
+        reject(new Error(error));
+      } else {
+        resolve({pixels, partial});
+      }
+    };
+  }
+  return worker;
+}
+
+/**
+ * Decodes a texture to RGBA, taking the same arguments as `decode` in the encoders crate.
+ * With `lenient`, truncated data is padded with zeros instead of rejected.
+ * Resolves to `{pixels, partial}`, where `partial` is set if the data had to be padded.
+ */
+export async function decodeTexture(format, data, width, height, isXbox, platform, platformBlob, lenient = false) {
+  if (threaded) {
+    return new Promise((resolve, reject) => {
+      const id = nextId++;
+      pending.set(id, {resolve, reject});
+      getWorker().postMessage({id, format, data, width, height, isXbox, platform, platformBlob, lenient});
+    });
+  }
+  if (lenient) {
+    const image = decode_lenient(format, data, width, height, isXbox, platform, platformBlob);
+    const result = {pixels: image.data, partial: image.partial};
+    image.free();
+    return result;
+  }
+  decodeBuffer.decode(format, data, width, height, isXbox, platform, platformBlob);
+  // The view is only valid until the next wasm call, and the pixels are cached and reused across awaits, so this is
+  // the one copy a decode makes: decodeRaw hands the cached array out as is unless an option modifies it.
+  return {pixels: decodeBuffer.view().slice(), partial: false};
+}
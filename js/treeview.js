@@ -148,6 +148,7 @@ export class AssetTree {
 
     document.body.dispatchEvent(new CustomEvent('destroy-preview'));
     preview.innerHTML = '<h2 class="no-preview">Select an object to preview</h2>';
+    document.getElementById('preview-tools').innerHTML = '';
 
     this.tree.jstree("destroy").empty();
     this.tree.jstree({
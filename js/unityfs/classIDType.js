@@ -22,6 +22,7 @@ import {SkinnedMeshRenderer} from "./classes/skinnedMeshRenderer";
 import {AssetBundle, PreloadData} from "./classes/assetBundle";
 import {Material} from "./classes/material";
 import {Sprite} from "./classes/sprite";
+import {SpriteAtlas} from "./classes/spriteAtlas";
 import {AnimatorOverrideController} from "./classes/animatorOverrideController";
 import {CanvasRenderer} from "./classes/canvasRenderer";
 import {Font} from "./classes/font";
@@ -33,6 +34,7 @@ import {RuntimeAnimatorController} from "./classes/runtimeAnimatorController";
 import {AnimatorController} from "./classes/animatorController";
 import {ParticleSystemRenderer} from "./classes/particleSystemRenderer";
 import {VideoClip} from "./classes/videoClip";
+import {CubemapArray, Texture2DArray, Texture3D} from "./classes/textureArray";
 
 const ClassIDType = {
     0: ['UnityObject', UnityObject],
@@ -114,7 +116,7 @@ const ClassIDType = {
     114: ['MonoBehaviour', MonoBehaviour],
     115: ['MonoScript', MonoScript],
     116: 'MonoManager',
-    117: 'Texture3D',
+    117: ['Texture3D', Texture3D],
     118: 'NewAnimationTrack',
     119: 'Projector',
     120: 'LineRenderer',
@@ -174,8 +176,8 @@ const ClassIDType = {
     184: 'SubstanceArchive',
     185: 'ProceduralMaterial',
     186: 'ProceduralTexture',
-    187: 'Texture2DArray',
-    188: 'CubemapArray',
+    187: ['Texture2DArray', Texture2DArray],
+    188: ['CubemapArray', CubemapArray],
     191: 'OffMeshLink',
     192: 'OcclusionArea',
     193: 'Tree',
@@ -347,7 +349,7 @@ const ClassIDType = {
     646504946: 'RendererFake',
     662584278: 'AssemblyDefinitionReferenceAsset',
     668709126: 'BuiltAssetBundleInfoSet',
-    687078895: 'SpriteAtlas',
+    687078895: ['SpriteAtlas', SpriteAtlas],
     747330370: 'RayTracingShaderImporter',
     825902497: 'RayTracingShader',
     850595691: 'LightingSettings',
@@ -1,7 +1,99 @@
 import {Texture2D} from "./texture2d";
+import {
+  cross_dimensions,
+  cubemap_to_cross,
+  cubemap_to_equirect,
+  cubemap_to_equirect_f32,
+  decode_hdr,
+  encode_hdr,
+  encode_image_with_options,
+  image_extension
+} from "../../encoders";
+import {getExportOptions, getImageFormat, getPngOptions} from "../../config";
+import JSZip from "jszip";
 
 export class Cubemap extends Texture2D {
   constructor(reader) {
     super(reader);
   }
-}
\ No newline at end of file
+
+  async decodeFaces() {
+    const faceBytes = this.width * this.height * 4;
+    const faces = new Uint8Array(faceBytes * 6);
+    for (let i = 0; i < 6; i++) {
+      faces.set(await this.decodeRaw(i), faceBytes * i);
+    }
+    return faces;
+  }
+
+  async decodeFacesHDR(encoding, linear = true) {
+    const faceValues = this.width * this.height * 4;
+    const faces = new Float32Array(faceValues * 6);
+    for (let i = 0; i < 6; i++) {
+      faces.set(decode_hdr(await this.decodeRaw(i), encoding, linear), faceValues * i);
+    }
+    return faces;
+  }
+
+  async createPanorama(width = this.width * 4, format = 'png') {
+    const height = Math.floor(width / 2);
+    const raw = cubemap_to_equirect(await this.decodeFaces(), this.width, width, height, true);
+    const {compression, filter} = getPngOptions();
+    return encode_image_with_options(format, width, height, raw, true, compression, filter);
+  }
+
+  // A Radiance .hdr panorama of an RGBM, dLDR or LogLuv encoded cubemap, such as a baked reflection probe
+  async createHDRPanorama(width = this.width * 4, encoding = 'rgbm', linear = true) {
+    const height = Math.floor(width / 2);
+    const raw = cubemap_to_equirect_f32(await this.decodeFacesHDR(encoding, linear), this.width, width, height, true);
+    return encode_hdr(width, height, raw, true);
+  }
+
+  async createCross(vertical = false, format = 'png') {
+    const [width, height] = cross_dimensions(this.width, vertical);
+    const raw = cubemap_to_cross(await this.decodeFaces(), this.width, vertical, true);
+    const {compression, filter} = getPngOptions();
+    return encode_image_with_options(format, width, height, raw, true, compression, filter);
+  }
+
+  getTools(name, context) {
+    const tools = super.getTools(name, context);
+    if (this.imageCount !== 6) {
+      return tools;
+    }
+    return tools.concat([{
+      label: 'Panorama',
+      action: 'Export',
+      fields: [
+        {name: 'width', label: 'Width', type: 'number', value: this.width * 4, min: 2, step: 2},
+        {name: 'encoding', label: 'HDR encoding', type: 'select',
+          options: [['none', 'None (image)'], ['rgbm', 'RGBM (.hdr)'], ['dldr', 'dLDR (.hdr)'], ['logluv', 'LogLuv (.hdr)']]},
+        {name: 'linear', label: 'Linear colour space', type: 'checkbox', value: true},
+      ],
+      run: async ({width, encoding, linear}) => {
+        if (encoding === 'none') {
+          const format = getImageFormat();
+          return {name: `${name}_panorama${image_extension(format)}`, data: await this.createPanorama(width, format)};
+        }
+        return {name: `${name}_panorama.hdr`, data: await this.createHDRPanorama(width, encoding, linear)};
+      },
+    }]);
+  }
+
+  async getExport() {
+    if (this.imageCount !== 6) {
+      return await super.getExport();
+    }
+    const format = getImageFormat();
+    const extension = image_extension(format);
+    const names = ['+X', '-X', '+Y', '-Y', '+Z', '-Z'];
+    const options = getExportOptions();
+    let zip = new JSZip();
+    for (let i = 0; i < 6; i++) {
+      zip.file(`${names[i]}${extension}`, await this.createImage(i, format, options));
+    }
+    zip.file(`cross${extension}`, await this.createCross(false, format));
+    zip.file(`panorama${extension}`, await this.createPanorama(this.width * 4, format));
+    return await zip.generateAsync({type: 'uint8array'});
+  }
+}
@@ -2,6 +2,9 @@ import {NamedObject} from "./namedObject";
 import {PPtr} from "./pptr";
 import {BoneWeights4, SubMesh, VertexData} from "./mesh";
 import {KVPair} from "../basicTypes";
+import {bleed_alpha, encode_image_with_options, encode_png, extract_sprite, image_extension, SpriteInfo} from "../../encoders";
+import {ImagePreview} from "../../preview/image";
+import {getExportOptions, getImageFormat, getPngOptions} from "../../config";
 
 export class SecondarySpriteTexture {
   static exposedAttributes = [
@@ -43,6 +46,7 @@ export class SpriteSettings {
 
   constructor(reader) {
     let raw = reader.readUInt32();
+    this.raw = raw;
     this.packed = (raw & 1) === 1;
     this.packingMode = SpritePackingMode[(raw >> 1) & 1];
     this.packingRotation = SpritePackingRotation[(raw >> 2) & 0xf];
@@ -84,6 +88,8 @@ export class SpriteRenderData {
   ]
 
   constructor(reader) {
+    this._version = reader.version;
+    this._endian = reader.endian;
     this.texture = new PPtr(reader);
     this.alphaTexture = null;
     if (reader.versionGTE(5, 2)) {
@@ -146,6 +152,34 @@ export class SpriteRenderData {
       this.downscaleMultiplier = reader.readFloat32();
     }
   }
+
+  getPositions() {
+    if (this.vertexData == null) {
+      return new Float32Array((this.vertices ?? []).flatMap(v => [v.pos.x, v.pos.y]));
+    }
+    const channel = this.vertexData.channels[0];
+    const stream = this.vertexData.streams[channel.stream];
+    const view = new DataView(this.vertexData.data.buffer, this.vertexData.data.byteOffset);
+    const positions = new Float32Array(this.vertexData.vertexCount * 2);
+    for (let i = 0; i < this.vertexData.vertexCount; i++) {
+      const offset = stream.offset + channel.offset + stream.stride * i;
+      positions[i * 2] = view.getFloat32(offset, this._endian === 'little');
+      positions[i * 2 + 1] = view.getFloat32(offset + 4, this._endian === 'little');
+    }
+    return positions;
+  }
+
+  getIndices() {
+    if (this.vertexData == null) {
+      return new Uint16Array(this.indexBuffer);
+    }
+    const view = new DataView(this.indexBuffer.buffer, this.indexBuffer.byteOffset);
+    const indices = new Uint16Array(this.indexBuffer.length / 2);
+    for (let i = 0; i < indices.length; i++) {
+      indices[i] = view.getUint16(i * 2, this._endian === 'little');
+    }
+    return indices;
+  }
 }
 
 export class Rectf {
@@ -162,6 +196,8 @@ export class Rectf {
 }
 
 export class Sprite extends NamedObject {
+  exportExtension = '.png';
+
   static exposedAttributes = [
     'name',
     'rect',
@@ -223,4 +259,78 @@ export class Sprite extends NamedObject {
       }
     }
   }
+
+  // The texture and placement to cut the sprite from: its entry in the SpriteAtlas it was packed into,
+  // like AssetStudio, or else its own render data
+  getTextureData() {
+    const atlasData = this.spriteAtlas?.object?.getRenderData(this.renderDataKey);
+    return atlasData?.texture.object != null ? atlasData : this.renderData;
+  }
+
+  async extract() {
+    const rd = this.getTextureData();
+    const texture = rd.texture.object;
+    if (texture == null) {
+      throw new Error('Sprite texture could not be resolved');
+    }
+    // Android split-alpha: ETC1 colour with the alpha in a separate texture
+    const alphaTexture = rd.alphaTexture?.object
+      ?? rd.secondaryTextures?.find(t => t.name === '_AlphaTex')?.texture.object;
+    const raw = await texture.decodeRaw(0, {alphaTexture});
+
+    const info = new SpriteInfo();
+    info.rect_x = rd.textureRect.x;
+    info.rect_y = rd.textureRect.y;
+    info.rect_width = rd.textureRect.width;
+    info.rect_height = rd.textureRect.height;
+    info.offset_x = rd.textureRectOffset.x;
+    info.offset_y = rd.textureRectOffset.y;
+    info.sprite_width = this.rect.width;
+    info.sprite_height = this.rect.height;
+    if (this.pivot != null) {
+      info.pivot_x = this.pivot.x;
+      info.pivot_y = this.pivot.y;
+    }
+    info.pixels_to_units = this.pixelsToUnits;
+    if (this.border != null) {
+      info.border_left = this.border.x;
+      info.border_bottom = this.border.y;
+      info.border_right = this.border.z;
+      info.border_top = this.border.w;
+    }
+    info.settings_raw = rd.settings.raw;
+
+    // the mesh is only stored in the sprite's own render data
+    const mesh = this.renderData;
+    return extract_sprite(raw, texture.width, texture.height, info, mesh.getPositions(), mesh.getIndices());
+  }
+
+  async createPNG() {
+    const sprite = await this.extract();
+    return encode_png(sprite.width, sprite.height, sprite.data, true);
+  }
+
+  async createImage(format, options = {}) {
+    const sprite = await this.extract();
+    const data = sprite.data;
+    if (options.bleedAlpha) {
+      bleed_alpha(data, sprite.width, sprite.height, 0);
+    }
+    const {compression, filter} = getPngOptions();
+    return encode_image_with_options(format, sprite.width, sprite.height, data, true, compression, filter);
+  }
+
+  async createDataUrl() {
+    return URL.createObjectURL(new Blob([await this.createPNG()], {type: 'image/png'}));
+  }
+
+  async createPreview() {
+    return new ImagePreview(1, async _ => this.createDataUrl()).create();
+  }
+
+  async getExport() {
+    const format = getImageFormat();
+    this.exportExtension = image_extension(format);
+    return await this.createImage(format, getExportOptions());
+  }
 }
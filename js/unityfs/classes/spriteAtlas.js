@@ -0,0 +1,75 @@
+import {NamedObject} from "./namedObject";
+import {PPtr} from "./pptr";
+import {KVPair} from "../basicTypes";
+import {Rectf, SecondarySpriteTexture, SpriteSettings} from "./sprite";
+
+// Where a packed sprite ended up in the atlas. The fields are named like SpriteRenderData's so a sprite
+// can be cut out of either the same way.
+export class SpriteAtlasData {
+  static exposedAttributes = [
+    'texture',
+    'alphaTexture',
+    'textureRect',
+    'textureRectOffset',
+    'atlasRectOffset',
+    'uvTransform',
+    'downscaleMultiplier',
+    'settings',
+    'secondaryTextures'
+  ];
+
+  constructor(reader) {
+    this.texture = new PPtr(reader);
+    this.alphaTexture = new PPtr(reader);
+    this.textureRect = new Rectf(reader);
+    this.textureRectOffset = reader.readVector2();
+    this.atlasRectOffset = null;
+    if (reader.versionGTE(2017, 2)) {
+      this.atlasRectOffset = reader.readVector2();
+    }
+    this.uvTransform = reader.readVector4();
+    this.downscaleMultiplier = reader.readFloat32();
+    this.settings = new SpriteSettings(reader);
+    this.secondaryTextures = [];
+    if (reader.versionGTE(2020, 2)) {
+      let numSecondaryTextures = reader.readInt32();
+      for (let i = 0; i < numSecondaryTextures; i++) {
+        this.secondaryTextures.push(new SecondarySpriteTexture(reader));
+      }
+      reader.align(4);
+    }
+  }
+}
+
+export class SpriteAtlas extends NamedObject {
+  static exposedAttributes = [
+    'name',
+    'packedSprites',
+    'packedSpriteNamesToIndex',
+    'renderDataMap',
+    'tag',
+    'isVariant'
+  ];
+
+  constructor(reader) {
+    super(reader);
+    this.packedSprites = reader.readArrayT(() => new PPtr(reader), reader.readInt32());
+    this.packedSpriteNamesToIndex = reader.readArrayT(() => reader.readAlignedString(), reader.readInt32());
+    // keyed like Sprite.renderDataKey: the GUID's bytes in hex and the local ID
+    this.renderDataMap = [];
+    let numRenderData = reader.readInt32();
+    for (let i = 0; i < numRenderData; i++) {
+      let key = [...reader.read(16)].map(x => x.toString(16).padStart(2, '0')).join('');
+      let id = Number(reader.readInt64());
+      this.renderDataMap.push(new KVPair(new KVPair(key, id), new SpriteAtlasData(reader)));
+    }
+    this.tag = reader.readAlignedString();
+    this.isVariant = reader.readBool();
+    reader.align(4);
+  }
+
+  // The atlas entry for a sprite's renderDataKey, or undefined if the sprite isn't in this atlas
+  getRenderData(renderDataKey) {
+    return this.renderDataMap.find(({key}) => key.key === renderDataKey.key && key.value === renderDataKey.value)?.value;
+  }
+}
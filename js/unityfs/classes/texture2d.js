@@ -6,8 +6,33 @@ import {
 import {BinaryReader} from "../../binaryReader";
 import {requestExternalData} from "../utils";
 import JSZip from "jszip";
-import {decode, encode_png} from "../../encoders";
+import {
+  AnimationEncoder,
+  apply_color_options,
+  compare_images,
+  composite_checkerboard,
+  decode_hdr,
+  decode_thumbnail,
+  encode_channel_png,
+  encode_hdr,
+  encode_image_with_options,
+  encode_png,
+  guess_texture_format,
+  image_extension,
+  merge_alpha,
+  swizzle_channels,
+  tonemap,
+  unpack_normal_map
+} from "../../encoders";
 import {ImagePreview} from "../../preview/image";
+import {createReport} from "../../preview/tools";
+import {getExportOptions, getImageFormat, getPngOptions} from "../../config";
+import {decodeTexture} from "../../textureDecoder";
+
+// The last few plain decodes as {texture, imageNum, lenient, data}, so previews and tools don't decode the same
+// image again. Only a handful are kept, so decoding every texture (duplicate search, zip export) doesn't hold on to them all.
+const rawCache = [];
+const RAW_CACHE_SIZE = 4;
 
 export class StreamingInfo {
   static exposedAttributes = [
@@ -84,6 +109,7 @@ export const TextureFormat = {
   34: 'ETC_RGB4',
   35: 'ATC_RGB4',
   36: 'ATC_RGBA8',
+  37: 'BGRA32Old',
   41: 'EAC_R',
   42: 'EAC_R_SIGNED',
   43: 'EAC_RG',
@@ -117,7 +143,107 @@ export const TextureFormat = {
   71: 'ASTC_HDR_12x12',
   72: 'RG32',
   73: 'RGB48',
-  74: 'RGBA64'
+  74: 'RGBA64',
+  75: 'R8_SIGNED',
+  76: 'RG16_SIGNED',
+  77: 'RGB24_SIGNED',
+  78: 'RGBA32_SIGNED',
+  79: 'R16_SIGNED',
+  80: 'RG32_SIGNED',
+  81: 'RGB48_SIGNED',
+  82: 'RGBA64_SIGNED'
+}
+
+// Unity's GraphicsFormat, used by texture arrays and 3D textures, mapped to the decoder's format names
+export const GraphicsFormat = {
+  1: 'R8',
+  2: 'RG16',
+  3: 'RGB24',
+  4: 'RGBA32',
+  5: 'R8',
+  6: 'RG16',
+  7: 'RGB24',
+  8: 'RGBA32',
+  9: 'R8_SIGNED',
+  10: 'RG16_SIGNED',
+  11: 'RGB24_SIGNED',
+  12: 'RGBA32_SIGNED',
+  21: 'R16',
+  22: 'RG32',
+  23: 'RGB48',
+  24: 'RGBA64',
+  25: 'R16_SIGNED',
+  26: 'RG32_SIGNED',
+  27: 'RGB48_SIGNED',
+  28: 'RGBA64_SIGNED',
+  45: 'RHalf',
+  46: 'RGHalf',
+  47: 'RGBHalf',
+  48: 'RGBAHalf',
+  49: 'RFloat',
+  50: 'RGFloat',
+  51: 'RGBFloat',
+  52: 'RGBAFloat',
+  61: 'BGRA32',
+  62: 'BGRA32',
+  66: 'RGBA4444',
+  67: 'BGRA4444',
+  68: 'RGB565',
+  69: 'BGR565',
+  70: 'RGBA5551',
+  71: 'BGRA5551',
+  73: 'RGB9e5Float',
+  74: 'B10G11R11_UFloat',
+  75: 'RGBA1010102',
+  96: 'DXT1',
+  97: 'DXT1',
+  98: 'DXT3',
+  99: 'DXT3',
+  100: 'DXT5',
+  101: 'DXT5',
+  102: 'BC4',
+  104: 'BC5',
+  106: 'BC6H',
+  107: 'BC6H',
+  108: 'BC7',
+  109: 'BC7',
+  110: 'PVRTC_RGB2',
+  111: 'PVRTC_RGB2',
+  112: 'PVRTC_RGB4',
+  113: 'PVRTC_RGB4',
+  114: 'PVRTC_RGBA2',
+  115: 'PVRTC_RGBA2',
+  116: 'PVRTC_RGBA4',
+  117: 'PVRTC_RGBA4',
+  118: 'ETC_RGB4',
+  119: 'ETC2_RGB',
+  120: 'ETC2_RGB',
+  121: 'ETC2_RGBA1',
+  122: 'ETC2_RGBA1',
+  123: 'ETC2_RGBA8',
+  124: 'ETC2_RGBA8',
+  125: 'EAC_R',
+  126: 'EAC_R_SIGNED',
+  127: 'EAC_RG',
+  128: 'EAC_RG_SIGNED',
+  129: 'ASTC_RGBA_4x4',
+  130: 'ASTC_RGBA_4x4',
+  131: 'ASTC_RGBA_5x5',
+  132: 'ASTC_RGBA_5x5',
+  133: 'ASTC_RGBA_6x6',
+  134: 'ASTC_RGBA_6x6',
+  135: 'ASTC_RGBA_8x8',
+  136: 'ASTC_RGBA_8x8',
+  137: 'ASTC_RGBA_10x10',
+  138: 'ASTC_RGBA_10x10',
+  139: 'ASTC_RGBA_12x12',
+  140: 'ASTC_RGBA_12x12',
+  145: 'ASTC_HDR_4x4',
+  146: 'ASTC_HDR_5x5',
+  147: 'ASTC_HDR_6x6',
+  148: 'ASTC_HDR_8x8',
+  149: 'ASTC_HDR_10x10',
+  150: 'ASTC_HDR_12x12'
 }
 
 export class Texture2D extends Texture {
@@ -142,8 +268,6 @@ export class Texture2D extends Texture {
     this._version = reader.version;
     this._platform = reader.platform;
 
-    this.cachedRaw = null;
-
     this.width = reader.readInt32();
     this.height = reader.readInt32();
     this.completeSize = reader.readInt32();
@@ -186,6 +310,8 @@ export class Texture2D extends Texture {
     if (reader.versionGTE(3, 5)) {
       this.colorSpace = reader.readInt32();
     }
+    // Switch textures are only swizzled when they have a platform blob
+    this.platformBlob = new Uint8Array(0);
     if (reader.versionGTE(2020, 2)) {
       this.platformBlob = reader.read(reader.readInt32());
       reader.align(4);
@@ -207,9 +333,11 @@ export class Texture2D extends Texture {
     if (this.streamData != null) {
       try {
         this.data = await requestExternalData(this.streamData);
+        this.dataMissing = false;
       } catch {
         console.error('Failed to load image data, creating empty image');
         this.data = new Uint8Array(this.streamData.size).fill(0);
+        this.dataMissing = true;
       }
     }
   }
@@ -226,27 +354,175 @@ export class Texture2D extends Texture {
     }
   }
 
-  async decodeRaw(imageNum) {
-    console.log(this.textureFormat);
-    if (this.cachedRaw == null) {
-      await this.loadData();
-      let data = this.data.slice(this.completeSize * imageNum, this.completeSize * (imageNum + 1));
-      if (this.textureFormat === "DXT1Crunched"
-        || this.textureFormat === "DXT5Crunched"
-        || this.textureFormat === "ETC_RGB4Crunched"
-        || this.textureFormat === "ETC_RGBA8Crunched"
-      ) {
-        // uncrunch
-        data = await this.unpackCrunch(data);
-      }
-      return decode(this.textureFormat, data, this.width, this.height, this._platform === 'XBox 360');
-    } else {
-      return this.cachedRaw;
+  async decodePlain(imageNum, lenient) {
+    const index = rawCache.findIndex(entry => entry.texture === this && entry.imageNum === imageNum && entry.lenient === lenient);
+    if (index !== -1) {
+      return rawCache[index].data;
+    }
+    await this.loadData();
+    let data = this.data.slice(this.completeSize * imageNum, this.completeSize * (imageNum + 1));
+    if (this.textureFormat === "DXT1Crunched"
+      || this.textureFormat === "DXT5Crunched"
+      || this.textureFormat === "ETC_RGB4Crunched"
+      || this.textureFormat === "ETC_RGBA8Crunched"
+    ) {
+      // uncrunch
+      data = await this.unpackCrunch(data);
     }
+    const {pixels: raw, partial} = await decodeTexture(
+      this.textureFormat,
+      data,
+      this.width,
+      this.height,
+      this._platform === 'XBox 360',
+      this._platform,
+      this.platformBlob,
+      lenient
+    );
+    if (lenient) {
+      // truncated data was padded instead of rejected; remember that the image is incomplete
+      this.partial = partial;
+    }
+    // don't cache the zero-filled stand-in for a missing .resS, the file may still be provided later
+    if (!this.dataMissing) {
+      rawCache.unshift({texture: this, imageNum, lenient, data: raw});
+      rawCache.length = Math.min(rawCache.length, RAW_CACHE_SIZE);
+    }
+    return raw;
   }
 
-  async createPNG(imageNum) {
-    return encode_png(this.width, this.height, await this.decodeRaw(imageNum), true);
+  // Without options this is the cached decode itself, so callers must not modify it
+  async decodeRaw(imageNum, options = {}) {
+    let raw = await this.decodePlain(imageNum, !!options.lenient);
+    // most options modify the pixels in place, so only then work on a copy of the cached decode
+    // (the HDR tonemap makes a new array, so the options after it don't need one)
+    const inPlace = options.normalMap || options.linearToSrgb || options.unpremultiply || options.bleedAlpha
+      || options.channels || options.checkerboard;
+    if (options.alphaTexture || (inPlace && !options.hdr)) {
+      raw = raw.slice();
+    }
+    if (options.alphaTexture) {
+      // Android split-alpha: take alpha from the red channel of a separate texture
+      const alpha = options.alphaTexture;
+      merge_alpha(raw, this.width, this.height, await alpha.decodeRaw(0), alpha.width, alpha.height, 0);
+    }
+    if (options.hdr) {
+      // RGBM/dLDR/LogLuv lightmaps and probes, tonemapped back to 8-bit sRGB
+      const {encoding, linear = true, exposure = 0, operator = 'aces'} = options.hdr;
+      raw = tonemap(decode_hdr(raw, encoding, linear), exposure, operator);
+    }
+    if (options.normalMap) {
+      unpack_normal_map(raw, 'auto', false);
+    }
+    if (options.linearToSrgb || options.unpremultiply || options.bleedAlpha) {
+      apply_color_options(
+        raw,
+        this.width,
+        this.height,
+        this.colorSpace ?? -1,
+        !!options.linearToSrgb,
+        !!options.unpremultiply,
+        !!options.bleedAlpha
+      );
+    }
+    if (options.channels) {
+      swizzle_channels(raw, options.channels);
+    }
+    if (options.checkerboard) {
+      composite_checkerboard(raw, this.width, this.height, 8);
+    }
+    return raw;
+  }
+
+  async createPNG(imageNum, options = {}) {
+    return encode_png(this.width, this.height, await this.decodeRaw(imageNum, options), true);
+  }
+
+  async createHDR(imageNum, encoding, linear = true) {
+    return encode_hdr(this.width, this.height, decode_hdr(await this.decodeRaw(imageNum), encoding, linear), true);
+  }
+
+  async createImage(imageNum, format, options = {}) {
+    const {compression, filter} = getPngOptions();
+    const raw = await this.decodeRaw(imageNum, options);
+    return encode_image_with_options(format, this.width, this.height, raw, true, compression, filter);
+  }
+
+  async createFlipbook(columns, rows, fps = 30, format = 'gif', count = 0) {
+    const raw = await this.decodeRaw(0);
+    const anim = new AnimationEncoder(Math.floor(this.width / columns), Math.floor(this.height / rows));
+    anim.add_sheet(raw, this.width, this.height, columns, rows, count, Math.round(1000 / fps));
+    return anim.encode(format, true);
+  }
+
+  async compareWith(other, threshold = 0, imageNum = 0) {
+    if (other.width !== this.width || other.height !== this.height) {
+      throw new Error(`Cannot compare a ${this.width}x${this.height} texture with a ${other.width}x${other.height} one`);
+    }
+    const diff = compare_images(await this.decodeRaw(imageNum), await other.decodeRaw(imageNum), this.width, this.height, threshold);
+    const result = {
+      psnr: diff.psnr,
+      ssim: diff.ssim,
+      maxError: diff.max_error,
+      maxChannelError: Array.from(diff.max_channel_error),
+      changedPixels: diff.changed_pixels,
+      regions: [],
+      heatmap: encode_png(this.width, this.height, diff.heatmap, true),
+    };
+    const regions = diff.regions;
+    for (let i = 0; i < regions.length; i += 4) {
+      result.regions.push({x: regions[i], y: regions[i + 1], width: regions[i + 2], height: regions[i + 3]});
+    }
+    diff.free();
+    return result;
+  }
+
+  async createChannelPNGs(imageNum, options = {}) {
+    const raw = await this.decodeRaw(imageNum, options);
+    const zip = new JSZip();
+    ['R', 'G', 'B', 'A'].forEach((name, channel) => {
+      zip.file(`${name}.png`, encode_channel_png(this.width, this.height, raw, channel, true));
+    });
+    return await zip.generateAsync({type: 'uint8array'});
+  }
+
+  async createThumbnail(maxDim = 128) {
+    if (this.textureFormat.endsWith('Crunched')) {
+      return this.createPNG(0);
+    }
+    await this.loadData();
+    const thumb = decode_thumbnail(
+      this.textureFormat,
+      this.data.slice(0, this.completeSize),
+      this.width,
+      this.height,
+      maxDim,
+      this._platform === 'XBox 360',
+      this._platform,
+      this.platformBlob
+    );
+    return encode_png(thumb.width, thumb.height, thumb.data, true);
+  }
+
+  // Ranks the formats and sizes that could explain an image's data, for textures whose format is missing or wrong.
+  // The width and height are used as hints unless 0.
+  async guessFormat(imageNum, widthHint, heightHint, maxResults) {
+    await this.loadData();
+    const data = this.data.subarray(this.completeSize * imageNum, this.completeSize * (imageNum + 1));
+    const guesses = guess_texture_format(data, widthHint, heightHint, maxResults);
+    const result = {
+      guesses: [...Array(guesses.count).keys()].map(i => ({
+        format: guesses.format(i),
+        width: guesses.width(i),
+        height: guesses.height(i),
+        mipCount: guesses.mip_count(i),
+        score: guesses.score(i),
+      })),
+      // the sheet keeps the data's row order, so flip it like other texture PNGs
+      sheet: guesses.count > 0 ? encode_png(guesses.sheet_width, guesses.sheet_height, guesses.contact_sheet, true) : null,
+    };
+    guesses.free();
+    return result;
   }
 
   async createDataUrl(imageNum) {
@@ -258,13 +534,138 @@ export class Texture2D extends Texture {
     return new ImagePreview(this.imageCount, async i => this.createDataUrl(i)).create();
   }
 
+  getTools(name, context) {
+    let candidates = [];
+    return [
+      {
+        label: 'Flipbook animation',
+        action: 'Export',
+        fields: [
+          {name: 'columns', label: 'Columns', type: 'number', value: 4, min: 1},
+          {name: 'rows', label: 'Rows', type: 'number', value: 4, min: 1},
+          {name: 'count', label: 'Frames (0 = all)', type: 'number', value: 0, min: 0},
+          {name: 'fps', label: 'FPS', type: 'number', value: 30, min: 1},
+          {name: 'format', label: 'Format', type: 'select', options: [['gif', 'GIF'], ['apng', 'APNG'], ['webp', 'WebP']]},
+        ],
+        run: async ({columns, rows, count, fps, format}) => ({
+          name: `${name}.${format === 'apng' ? 'png' : format}`,
+          data: await this.createFlipbook(columns, rows, fps, format, count),
+        }),
+      },
+      {
+        label: 'Compare with another texture',
+        action: 'Compare',
+        fields: [
+          {name: 'other', label: 'Texture', type: 'select', options: async () => {
+            candidates = context.otherTextures();
+            return candidates.map((info, i) => [i, `${info.name} @ ${info.pathID}`]);
+          }},
+          {name: 'threshold', label: 'Threshold', type: 'number', value: 0, min: 0, max: 255},
+          {name: 'imageNum', label: 'Image', type: 'number', value: 0, min: 0, max: this.imageCount - 1},
+        ],
+        run: async ({other, threshold, imageNum}) => {
+          if (candidates[other] === undefined) {
+            throw new Error('No other textures are loaded');
+          }
+          const diff = await this.compareWith(candidates[other].object, threshold, imageNum);
+          return createReport([
+            ['PSNR', Number.isFinite(diff.psnr) ? `${diff.psnr.toFixed(2)} dB` : 'identical'],
+            ['SSIM', diff.ssim.toFixed(4)],
+            ['Max error', `${diff.maxError} (RGBA ${diff.maxChannelError.join(', ')})`],
+            ['Changed pixels', diff.changedPixels],
+            ['Changed regions', diff.regions.length],
+          ], diff.heatmap);
+        },
+      },
+      {
+        label: 'View options',
+        action: 'Apply',
+        fields: [
+          {name: 'channels', label: 'Channels', type: 'select', options: [
+            ['', 'RGBA'], ['RRR1', 'Red'], ['GGG1', 'Green'], ['BBB1', 'Blue'], ['AAA1', 'Alpha'],
+            ['RGB1', 'RGB, no alpha'], ['BGRA', 'Swap red and blue'],
+          ]},
+          {name: 'normalMap', label: 'Unpack normal map', type: 'checkbox', value: false},
+          {name: 'linearToSrgb', label: 'Linear to sRGB', type: 'checkbox', value: false},
+          {name: 'unpremultiply', label: 'Unpremultiply alpha', type: 'checkbox', value: false},
+          {name: 'bleedAlpha', label: 'Bleed colour into transparent pixels', type: 'checkbox', value: false},
+          {name: 'checkerboard', label: 'Checkerboard background', type: 'checkbox', value: false},
+          {name: 'lenient', label: 'Pad truncated data', type: 'checkbox', value: false},
+          {name: 'imageNum', label: 'Image', type: 'number', value: 0, min: 0, max: this.imageCount - 1},
+          {name: 'output', label: 'Output', type: 'select', options: [['preview', 'Preview'], ['png', 'PNG'], ['channels', 'PNG per channel (zip)']]},
+        ],
+        run: async ({output, imageNum, ...options}) => {
+          if (output === 'channels') {
+            return {name: `${name}_channels.zip`, data: await this.createChannelPNGs(imageNum, options)};
+          }
+          const png = await this.createPNG(imageNum, options);
+          if (output === 'png') {
+            return {name: `${name}.png`, data: png, type: 'image/png'};
+          }
+          return createReport([
+            ['Size', `${this.width}x${this.height}`],
+            ['Format', this.textureFormat],
+            ['Incomplete data', (options.lenient && this.partial) ? 'yes, padded' : 'no'],
+          ], png);
+        },
+      },
+      {
+        label: 'Guess format',
+        action: 'Guess',
+        fields: [
+          {name: 'width', label: 'Width (0 = any)', type: 'number', value: this.width, min: 0},
+          {name: 'height', label: 'Height (0 = any)', type: 'number', value: this.height, min: 0},
+          {name: 'maxResults', label: 'Results', type: 'number', value: 8, min: 1},
+          {name: 'imageNum', label: 'Image', type: 'number', value: 0, min: 0, max: this.imageCount - 1},
+        ],
+        run: async ({width, height, maxResults, imageNum}) => {
+          const {guesses, sheet} = await this.guessFormat(imageNum, width, height, maxResults);
+          if (guesses.length === 0) {
+            throw new Error('No format and size fits the data exactly');
+          }
+          return createReport([
+            ['Tagged format', this.textureFormat ?? 'unknown'],
+            ...guesses.map((g, i) => [
+              `${i + 1}. ${g.format}`,
+              `${g.width}x${g.height}, ${g.mipCount} mip${g.mipCount === 1 ? '' : 's'}, score ${g.score.toFixed(3)}`,
+            ]),
+          ], sheet);
+        },
+      },
+      {
+        label: 'HDR export',
+        action: 'Export',
+        fields: [
+          {name: 'encoding', label: 'Encoding', type: 'select', options: [['rgbm', 'RGBM'], ['dldr', 'dLDR'], ['logluv', 'LogLuv']]},
+          {name: 'linear', label: 'Linear colour space', type: 'checkbox', value: true},
+          {name: 'output', label: 'Output', type: 'select', options: [['hdr', 'Radiance .hdr'], ['png', 'Tonemapped PNG']]},
+          {name: 'exposure', label: 'Exposure (stops)', type: 'number', value: 0, step: 0.5},
+          {name: 'operator', label: 'Tonemapping', type: 'select', options: [['aces', 'ACES'], ['reinhard', 'Reinhard'], ['clamp', 'Clamp']]},
+          {name: 'imageNum', label: 'Image', type: 'number', value: 0, min: 0, max: this.imageCount - 1},
+        ],
+        run: async ({encoding, linear, output, exposure, operator, imageNum}) => (output === 'hdr') ? {
+          name: `${name}.hdr`,
+          data: await this.createHDR(imageNum, encoding, linear),
+        } : {
+          name: `${name}.png`,
+          data: await this.createPNG(imageNum, {hdr: {encoding, linear, exposure, operator}}),
+          type: 'image/png',
+        },
+      },
+    ];
+  }
+
   async getExport() {
+    const format = getImageFormat();
+    const extension = image_extension(format);
+    const options = getExportOptions();
     if (this.imageCount === 1) {
-      return await this.createPNG(0);
+      this.exportExtension = extension;
+      return await this.createImage(0, format, options);
     } else {
       let zip = new JSZip();
       for (let i = 0; i < this.imageCount; i++) {
-        zip.file(`${i}.png`, await this.createPNG(i));
+        zip.file(`${i}${extension}`, await this.createImage(i, format, options));
       }
       return await zip.generateAsync({type: 'uint8array'});
     }
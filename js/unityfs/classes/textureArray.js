@@ -0,0 +1,152 @@
+import {Texture} from "./texture";
+import {GLTextureSettings, GraphicsFormat, StreamingInfo, TextureFormat} from "./texture2d";
+import {requestExternalData} from "../utils";
+import {encode_image_with_options, image_extension, TextureArray} from "../../encoders";
+import {ImagePreview} from "../../preview/image";
+import {getImageFormat, getPngOptions} from "../../config";
+import JSZip from "jszip";
+
+// Texture2DArray, CubemapArray and Texture3D: `depth` slices of the same size and format
+class TextureArrayBase extends Texture {
+  static exposedAttributes = [
+    'name',
+    'width',
+    'height',
+    'depth',
+    'textureFormat',
+    'mipCount',
+    'isReadable',
+    'dataSize',
+    'textureSettings',
+    'colorSpace',
+    'streamData'
+  ];
+  exportExtension = '.zip';
+
+  constructor(reader, isCubemapArray = false) {
+    super(reader);
+    this._platform = reader.platform;
+    this._array = null;
+
+    if (reader.version[0] >= 2019) {
+      this.colorSpace = reader.readInt32();
+      this.textureFormat = GraphicsFormat[reader.readInt32()];
+    }
+    this.width = reader.readInt32();
+    if (isCubemapArray) {
+      this.height = this.width;
+      this.cubemapCount = reader.readInt32();
+      this.depth = this.cubemapCount * 6;
+    } else {
+      this.height = reader.readInt32();
+      this.depth = reader.readInt32();
+    }
+    if (reader.version[0] < 2019) {
+      this.textureFormat = TextureFormat[reader.readInt32()];
+    }
+    this.mipCount = reader.readInt32();
+    this.dataSize = reader.readUInt32();
+    this.textureSettings = new GLTextureSettings(reader);
+    if (reader.version[0] < 2019) {
+      this.colorSpace = reader.readInt32();
+    }
+    if (reader.versionGTE(2020, 2)) {
+      this.usageMode = reader.readInt32();
+    }
+    this.isReadable = reader.readBool();
+    reader.align(4);
+    const imageDataSize = reader.readInt32();
+    this.data = reader.read(imageDataSize);
+    reader.align(4);
+    this.streamData = imageDataSize === 0 ? new StreamingInfo(reader) : null;
+  }
+
+  get isVolume() {
+    return false;
+  }
+
+  sliceName(slice) {
+    return `${slice}`;
+  }
+
+  async loadData() {
+    if (this.streamData != null && this.data.length === 0) {
+      try {
+        this.data = await requestExternalData(this.streamData);
+      } catch {
+        console.error('Failed to load image data, creating empty image');
+        this.data = new Uint8Array(this.streamData.size).fill(0);
+      }
+    }
+  }
+
+  async getTextureArray() {
+    if (this._array == null) {
+      await this.loadData();
+      this._array = new TextureArray(this.textureFormat, this.data, this.width, this.height, this.depth,
+        this.mipCount, this.isVolume, this._platform);
+      this._array.cubemap = this instanceof CubemapArray;
+    }
+    return this._array;
+  }
+
+  async createSlice(slice, format, mip = 0) {
+    const image = (await this.getTextureArray()).decode_slice(slice, mip);
+    const {compression, filter} = getPngOptions();
+    return encode_image_with_options(format, image.width, image.height, image.data, true, compression, filter);
+  }
+
+  async createAtlas(format, mip = 0, columns = 0) {
+    const atlas = (await this.getTextureArray()).atlas(mip, columns, true);
+    const {compression, filter} = getPngOptions();
+    return encode_image_with_options(format, atlas.width, atlas.height, atlas.data, true, compression, filter);
+  }
+
+  async createDDS() {
+    return (await this.getTextureArray()).to_dds(true);
+  }
+
+  async createKTX() {
+    return (await this.getTextureArray()).to_ktx();
+  }
+
+  async createDataUrl(slice) {
+    const pngData = (await this.getTextureArray()).encode_slice_png(slice, 0, true);
+    return URL.createObjectURL(new Blob([pngData], {type: 'image/png'}));
+  }
+
+  async createPreview() {
+    return new ImagePreview(this.depth, async i => this.createDataUrl(i)).create();
+  }
+
+  async getExport() {
+    const format = getImageFormat();
+    const extension = image_extension(format);
+    const zip = new JSZip();
+    for (let i = 0; i < this.depth; i++) {
+      zip.file(`${this.sliceName(i)}${extension}`, await this.createSlice(i, format));
+    }
+    zip.file(`atlas${extension}`, await this.createAtlas(format));
+    zip.file(`${this.name || 'texture'}.dds`, await this.createDDS());
+    return await zip.generateAsync({type: 'uint8array'});
+  }
+}
+
+export class Texture2DArray extends TextureArrayBase {}
+
+export class CubemapArray extends TextureArrayBase {
+  constructor(reader) {
+    super(reader, true);
+  }
+
+  sliceName(slice) {
+    const faces = ['+X', '-X', '+Y', '-Y', '+Z', '-Z'];
+    return `${Math.floor(slice / 6)}/${faces[slice % 6]}`;
+  }
+}
+
+export class Texture3D extends TextureArrayBase {
+  get isVolume() {
+    return true;
+  }
+}
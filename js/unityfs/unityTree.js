@@ -9,6 +9,9 @@ import {AssetTree} from "../treeview";
 import {saveBlob} from "../utils";
 import {BinaryReader} from "../binaryReader";
 import {WebFile} from "./webFile";
+import {Texture2D} from "./classes/texture2d";
+import {DuplicateFinder} from "../encoders";
+import {createReport, createToolPanel} from "../preview/tools";
 
 export default class UnityTree extends AssetTree {
   styleObject(object, isHidden = false) {
@@ -37,6 +40,33 @@ export default class UnityTree extends AssetTree {
         '&lt;not loaded&gt;',
         'icon-generic'
       );
+      if (obj.classID === 28 && !isFromPPtr) {
+        this.thumbnailQueue.push({node: rootNode + '-' + obj.pathID, info: obj});
+      }
+    }
+  }
+
+  // Replaces the icons of Texture2D nodes with thumbnails, one texture at a time so the tree stays responsive.
+  // Textures whose data lives in a file that isn't loaded are skipped rather than asking for it.
+  async loadThumbnails() {
+    const queue = this.thumbnailQueue;
+    while (queue.length > 0 && queue === this.thumbnailQueue) {
+      if (!document.getElementById('show-thumbnails').checked) {
+        return;
+      }
+      const {node, info} = queue.shift();
+      try {
+        const texture = info.object;
+        if (texture.streamData != null && this.findExternalFile(texture.streamData.path).data === undefined) {
+          continue;
+        }
+        const url = URL.createObjectURL(new Blob([await texture.createThumbnail(32)], {type: 'image/png'}));
+        this.thumbnailUrls.push(url);
+        this.tree.jstree(true).set_icon(node, url);
+      } catch (e) {
+        console.warn('Failed to create thumbnail:', e);
+      }
+      await new Promise(resolve => setTimeout(resolve, 0));
     }
   }
 
@@ -155,72 +185,75 @@ export default class UnityTree extends AssetTree {
     }
   }
 
+  // Looks a streaming path up in the loaded and uploaded files. Returns the file data (undefined if it
+  // isn't available) and the path to ask the user for.
+  findExternalFile(path) {
+    if (path.startsWith('archive:/')) {
+      path = path.substring('archive:/'.length, path.length);
+    }
+    let matches = this.treeFiles.filter(f => f.fileName === path);
+    if (matches.length > 0) {
+      return {data: matches[0].parser.reader.data, path};
+    }
+    path = path.substring(path.indexOf('/') + 1, path.length);
+    matches = this.treeFiles.filter(f => f.fileName === path);
+    if (matches.length > 0) {
+      return {data: matches[0].parser.reader.data, path};
+    }
+    return {data: this.providedExternals[path], path};
+  }
+
   async setupResolver() {
     const preview = document.getElementById('preview');
     document.body.addEventListener('bundle-resolve-request', data => {
       if (this.isExporting) {
         return false;  // another listener is set up
       }
-      let path = data.detail;
-      console.log('Received bundle resolution request for path', path);
-      if (path.startsWith('archive:/')) {
-        path = path.substring('archive:/'.length, path.length);
-      }
-      let matches = this.treeFiles.filter(f => f.fileName === path);
-      let match;
-      if (matches.length > 0) {
-        match = matches[0].parser.reader.data;
-      } else {
-        path = path.substring(path.indexOf('/') + 1, path.length);
-        matches = this.treeFiles.filter(f => f.fileName === path);
-        if (matches.length > 0) {
-          match = matches[0].parser.reader.data;
-        } else {
-          if (this.providedExternals[path] !== undefined) {
-            match = this.providedExternals[path];
-          } else if (path === '') {  // some textures have this -- not sure how to handle it
-            document.body.dispatchEvent(new CustomEvent('bundle-resolve-response', {detail: {status: false, data: null}}));
-          } else {
-            console.warn('no matches, left with path:', path);
-            const text = document.createElement('h2');
-            text.classList.add('no-preview');
-            text.innerText = `Requires file: ${path}`;
-            const br = document.createElement('br');
-            const input = document.createElement('input');
-            input.id = 'ext' + 'xxxxxxxx'.replaceAll('x', () => Math.floor(Math.random() * 256).toString(16));
-            input.classList.add('external-input');
-            input.type = 'file';
-            const label = document.createElement('label');
-            label.htmlFor = input.id;
-            label.classList.add('external-input-button');
-            label.innerText = 'Upload...';
-            input.addEventListener('change', () => {
-              let reader = new FileReader();
-              reader.onloadend = async b => {
-                this.providedExternals[path] = new Uint8Array(reader.result);
-                document.body.dispatchEvent(new CustomEvent('bundle-resolve-response', {
-                  detail: {
-                    status: true,
-                    data: this.providedExternals[path]
-                  }
-                }));
+      console.log('Received bundle resolution request for path', data.detail);
+      const {data: match, path} = this.findExternalFile(data.detail);
+      if (match === undefined) {
+        if (path === '') {  // some textures have this -- not sure how to handle it
+          document.body.dispatchEvent(new CustomEvent('bundle-resolve-response', {detail: {status: false, data: null}}));
+          return false;
+        }
+        console.warn('no matches, left with path:', path);
+        const text = document.createElement('h2');
+        text.classList.add('no-preview');
+        text.innerText = `Requires file: ${path}`;
+        const br = document.createElement('br');
+        const input = document.createElement('input');
+        input.id = 'ext' + 'xxxxxxxx'.replaceAll('x', () => Math.floor(Math.random() * 256).toString(16));
+        input.classList.add('external-input');
+        input.type = 'file';
+        const label = document.createElement('label');
+        label.htmlFor = input.id;
+        label.classList.add('external-input-button');
+        label.innerText = 'Upload...';
+        input.addEventListener('change', () => {
+          let reader = new FileReader();
+          reader.onloadend = async b => {
+            this.providedExternals[path] = new Uint8Array(reader.result);
+            document.body.dispatchEvent(new CustomEvent('bundle-resolve-response', {
+              detail: {
+                status: true,
+                data: this.providedExternals[path]
               }
-              reader.readAsArrayBuffer(input.files[0]);
-            });
-            function cancelListener() {
-              document.body.removeEventListener('destroy-preview', cancelListener);
-              document.body.dispatchEvent(new CustomEvent('bundle-resolve-response', {detail: {status: false, data: null}}));
-            }
-            document.body.addEventListener('destroy-preview', cancelListener);
-
-            text.appendChild(br);
-            text.appendChild(input);
-            text.appendChild(label);
-            preview.innerHTML = '';
-            preview.appendChild(text);
-            return true;
+            }));
           }
+          reader.readAsArrayBuffer(input.files[0]);
+        });
+        function cancelListener() {
+          document.body.removeEventListener('destroy-preview', cancelListener);
+          document.body.dispatchEvent(new CustomEvent('bundle-resolve-response', {detail: {status: false, data: null}}));
         }
+        document.body.addEventListener('destroy-preview', cancelListener);
+
+        text.appendChild(br);
+        text.appendChild(input);
+        text.appendChild(label);
+        preview.innerHTML = '';
+        preview.appendChild(text);
+        return true;
       }
       document.body.dispatchEvent(new CustomEvent('bundle-resolve-response', {detail: {status: true, data: match}}));
     });
@@ -304,9 +337,101 @@ data like pixels, vertices, and UV maps used by the asset.`
         await this.exportFile(folder, file.type, file.parser.parser);
       }
     }
+    for (const file of this.addedFiles.filter(f => f.parser instanceof AssetFile)) {
+      await this.exportFile(zip.folder(file.name), FileType.Assets, file.parser);
+    }
     return await zip.generateAsync({type: 'uint8array'});
   }
 
+  // Every loaded asset file: the opened one or those in its bundles, and the same for files added with `addFile`.
+  assetFiles() {
+    const opened = (this.parser instanceof AssetFile) ? [this.parser] : [];
+    return opened.concat(
+      this.addedFiles.filter(f => f.parser instanceof AssetFile).map(f => f.parser),
+      this.treeFiles.filter(f => f.type === FileType.Assets).map(f => f.parser.parser),
+    );
+  }
+
+  // The ObjectInfos of every Texture2D in the loaded asset files.
+  textureInfos() {
+    return this.assetFiles().flatMap(p => p.objects.objects.filter(o => o.classID === 28));
+  }
+
+  async findDuplicateTextures(maxDistance = 6) {
+    const finder = new DuplicateFinder();
+    const textures = [];
+    for (const info of this.textureInfos()) {
+      try {
+        const object = info.object;
+        finder.add(await object.decodeRaw(0), object.width, object.height);
+        textures.push(info);
+      } catch (e) {
+        console.error('Failed to hash texture:', e);
+      }
+    }
+    const groups = new Map();
+    finder.groups(maxDistance).forEach((group, i) => {
+      if (!groups.has(group)) groups.set(group, []);
+      groups.get(group).push({info: textures[i], texture: textures[i].object, contentHash: finder.content_hash(i)});
+    });
+    finder.free();
+    // only groups with more than one member are duplicates
+    return [...groups.values()].filter(g => g.length > 1);
+  }
+
+  showDuplicateTextures() {
+    document.body.dispatchEvent(new CustomEvent('destroy-preview'));
+    document.getElementById('preview').innerHTML = '<h2 class="no-preview">Duplicate textures</h2>';
+    const tools = document.getElementById('preview-tools');
+    tools.innerHTML = '';
+    const panel = createToolPanel([{
+      label: 'Find duplicate textures',
+      action: 'Find',
+      fields: [
+        {name: 'maxDistance', label: 'Max hash distance (0 = identical only)', type: 'number', value: 6, min: 0, max: 64},
+      ],
+      run: async ({maxDistance}) => {
+        const groups = await this.findDuplicateTextures(maxDistance);
+        const rows = [['Groups', groups.length]];
+        groups.forEach((group, i) => {
+          for (const {info, texture, contentHash} of group) {
+            rows.push([`Group ${i + 1}`, `${info.name} @ ${info.pathID} (${texture.width}x${texture.height}, ${contentHash})`]);
+          }
+        });
+        return createReport(rows);
+      },
+    }]);
+    panel.querySelector('details').open = true;
+    tools.appendChild(panel);
+  }
+
+  /**
+   * Opens another bundle or asset file in the tree next to the first, e.g. to find textures duplicated across them.
+   * @returns {Promise<boolean>} Whether the file was a bundle or asset file
+   */
+  async addFile(data, name) {
+    const file = new UnityFS(data);
+    file.parse();
+    const id = `added-${this.addedFiles.length}`;
+    const thumbnailsIdle = this.thumbnailQueue.length === 0;
+    switch (file.fileType) {
+      case FileType.Bundle:
+        await this.createTreeForObject(file.parser, '#', id, 'bundle', 'icon-bundle');
+        break;
+      case FileType.Assets:
+        await this.createTreeForObject(file.parser, '#', id, 'asset', 'icon-asset');
+        break;
+      default:
+        return false;
+    }
+    this.tree.jstree(true).rename_node('#-' + id, this.styleTextAs(name, file.fileType === FileType.Bundle ? 'bundle' : 'asset'));
+    this.addedFiles.push({name, parser: file.parser});
+    if (thumbnailsIdle) {
+      this.loadThumbnails();
+    }
+    return true;
+  }
+
   async exportZip() {
     const {subtitle} = this.onExportStart();
     const modal = document.getElementById('modal');
@@ -457,11 +582,20 @@ data like pixels, vertices, and UV maps used by the asset.`
         saveBlob(name + '.json', [JSON.stringify(await object.getInfo(), undefined, 2)]);
       };
       document.getElementById('download-object').onclick = async () => {
-        saveBlob(name + object.exportExtension, [await object.getAnyExport()]);
+        const exported = await object.getAnyExport();
+        saveBlob(name + object.exportExtension, [exported]);
       };
       document.getElementById('download-raw').onclick = async () => {
         saveBlob(name + '.dat', [object._raw]);
       };
+      const tools = document.getElementById('preview-tools');
+      tools.innerHTML = '';
+      if (typeof object.getTools === 'function') {
+        const info = data.node.data.data;
+        tools.appendChild(createToolPanel(object.getTools(name, {
+          otherTextures: () => this.textureInfos().filter(t => t !== info),
+        })));
+      }
     } else if (data.node.data.type === 'pptr') {
       let pptr = data.node.data.data;
       pptr.resolve();
@@ -497,6 +631,11 @@ data like pixels, vertices, and UV maps used by the asset.`
 
   async loadFile(data) {
     this.treeFiles = [];
+    this.addedFiles?.forEach(f => f.parser.destroy?.());
+    this.addedFiles = [];
+    this.thumbnailQueue = [];
+    this.thumbnailUrls?.forEach(url => URL.revokeObjectURL(url));
+    this.thumbnailUrls = [];
     this.treeObjects = {};
 
     this.objectBranches = {};
@@ -554,5 +693,7 @@ data like pixels, vertices, and UV maps used by the asset.`
     }
 
     this.postInit();
+    document.getElementById('show-thumbnails').onchange = () => this.loadThumbnails();
+    this.loadThumbnails();
   }
 }
@@ -5,8 +5,17 @@ import {Transform} from "./classes/transform";
 import {PPtr} from "./classes/pptr";
 import {GameObject} from "./classes/gameObject";
 import {UnityObject} from "./classes/object";
+import {lzma_decompress} from "../encoders";
 
 export const lzmaDecompress = function (data, rawSize) {
+  try {
+    return lzma_decompress(data, rawSize);
+  } catch (e) {
+    console.error('error in Wasm LZMA decompress:');
+    console.error(e);
+    console.error('Falling back to slower pure-JS decompressor');
+  }
+
   let dec = new LZMA.Decoder();
   let stream = new BasicStream(data);
   let props = stream.readByte();
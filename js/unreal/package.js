@@ -1,4 +1,6 @@
 import {UE4ObjectVersion, UE5ObjectVersion} from "./version";
+import {UnrealTexture, encode_png} from "../encoders";
+import {BinaryReader, SEEK_CUR} from "../binaryReader";
 
 export const PACKAGE_FILE_MAGIC = 0x9E2A83C1;
 const LATEST_SUPPORTED = -8;
@@ -42,6 +44,17 @@ const PackageFlags = {
 
 PackageFlags.PKG_TransientFlags = PackageFlags.PKG_NewlyCreated | PackageFlags.PKG_IsSaving | PackageFlags.PKG_ReloadingForCooker;
 
+const TEXTURE_CLASSES = [
+  'Texture2D', 'TextureCube', 'Texture2DArray', 'TextureCubeArray', 'VolumeTexture', 'LightMapTexture2D', 'ShadowMapTexture2D'
+];
+
+/**
+ * Whether exports of a class are textures that `PackageFile.readTexture` can read.
+ */
+export function isTextureClass(className) {
+  return TEXTURE_CLASSES.includes(className);
+}
+
 class EngineVersion {
   constructor(reader) {
     this.major = reader.readInt16();
@@ -217,10 +230,236 @@ export class PackageFile {
 
     this.reader = reader;
     this.loadNames();
+    // The import and export maps are only needed to browse objects, so a layout we can't read
+    // shouldn't stop the rest of the package from loading
+    try {
+      this.loadImports();
+      this.loadExports();
+    } catch (e) {
+      console.error(`Failed to read the package's objects: ${e.message ?? e}`);
+      this.imports = [];
+      this.exports = [];
+    }
     this.loadAssetRegistry();
     this.loadThumbnails();
   }
 
+  readFName(reader = this.reader) {
+    const index = reader.readInt32();
+    const number = reader.readInt32();
+    const name = this.names[index]?.name ?? `<name ${index}>`;
+    return number > 0 ? `${name}_${number - 1}` : name;
+  }
+
+  loadImports() {
+    this.reader.seek(this.importOffset);
+    const noEditor = this.packageFlags & PackageFlags.PKG_FilterEditorOnly;
+    this.imports = [];
+    for (let i = 0; i < this.importCount; i++) {
+      const imp = {};
+      imp.classPackage = this.readFName();
+      imp.className = this.readFName();
+      imp.outerIndex = this.reader.readInt32();
+      imp.objectName = this.readFName();
+      if (this.version >= UE4ObjectVersion.VER_UE4_NON_OUTER_PACKAGE_IMPORT && !noEditor) {
+        imp.packageName = this.readFName();
+      }
+      if (this.version >= UE5ObjectVersion.OPTIONAL_RESOURCES) {
+        imp.importOptional = !!this.reader.readInt32();
+      }
+      this.imports.push(imp);
+    }
+  }
+
+  /**
+   * The class name of an export, from the import or export its class index points to.
+   */
+  getClassName(exp) {
+    if (exp.classIndex < 0) {
+      return this.imports[-exp.classIndex - 1]?.objectName ?? `<import ${-exp.classIndex - 1}>`;
+    } else if (exp.classIndex > 0) {
+      return this.exports[exp.classIndex - 1]?.objectName ?? `<export ${exp.classIndex - 1}>`;
+    }
+    return 'Class';
+  }
+
+  loadExports() {
+    this.reader.seek(this.exportOffset);
+    const readSize = () => this.version >= UE4ObjectVersion.VER_UE4_64BIT_EXPORTMAP_SERIALSIZES ?
+      Number(this.reader.readInt64()) : this.reader.readInt32();
+    this.exports = [];
+    for (let i = 0; i < this.exportCount; i++) {
+      const exp = {};
+      exp.classIndex = this.reader.readInt32();
+      exp.superIndex = this.reader.readInt32();
+      if (this.version >= UE4ObjectVersion.VER_UE4_TemplateIndex_IN_COOKED_EXPORTS) {
+        exp.templateIndex = this.reader.readInt32();
+      }
+      exp.outerIndex = this.reader.readInt32();
+      exp.objectName = this.readFName();
+      exp.objectFlags = this.reader.readUInt32();
+      exp.serialSize = readSize();
+      exp.serialOffset = readSize();
+      exp.forcedExport = !!this.reader.readInt32();
+      exp.notForClient = !!this.reader.readInt32();
+      exp.notForServer = !!this.reader.readInt32();
+      if (this.version < UE5ObjectVersion.REMOVE_OBJECT_EXPORT_PACKAGE_GUID) {
+        exp.packageGUID = this.reader.readGUID();
+      }
+      if (this.version >= UE5ObjectVersion.TRACK_OBJECT_EXPORT_IS_INHERITED) {
+        exp.isInheritedInstance = !!this.reader.readInt32();
+      }
+      exp.packageFlags = this.reader.readUInt32();
+      if (this.version >= UE4ObjectVersion.VER_UE4_LOAD_FOR_EDITOR_GAME) {
+        exp.notAlwaysLoadedForEditorGame = !!this.reader.readInt32();
+      }
+      if (this.version >= UE4ObjectVersion.VER_UE4_COOKED_ASSETS_IN_EDITOR_SUPPORT) {
+        exp.isAsset = !!this.reader.readInt32();
+      }
+      if (this.version >= UE5ObjectVersion.OPTIONAL_RESOURCES) {
+        exp.generatePublicHash = !!this.reader.readInt32();
+      }
+      if (this.version >= UE4ObjectVersion.VER_UE4_PRELOAD_DEPENDENCIES_IN_COOKED_EXPORTS) {
+        exp.firstExportDependency = this.reader.readInt32();
+        this.reader.read(16);  // dependency counts
+      }
+      this.exports.push(exp);
+    }
+  }
+
+  /**
+   * Reads tagged properties up to the "None" that ends them.
+   * Enum, byte enum, name and bool values are read; other values are skipped, leaving their offset in the data.
+   * @returns {Array<{name: string, type: string, size: number, arrayIndex: number, offset: number, value: *}>}
+   */
+  readProperties(reader) {
+    const properties = [];
+    for (;;) {
+      const name = this.readFName(reader);
+      if (name === 'None') {
+        return properties;
+      }
+      const tag = {name, type: this.readFName(reader), size: reader.readInt32(), arrayIndex: reader.readInt32()};
+      switch (tag.type) {
+        case 'StructProperty':
+          tag.structName = this.readFName(reader);
+          if (this.version >= UE4ObjectVersion.VER_UE4_STRUCT_GUID_IN_PROPERTY_TAG) {
+            tag.structGUID = reader.readGUID();
+          }
+          break;
+        case 'BoolProperty':
+          tag.value = !!reader.readUInt8();
+          break;
+        case 'ByteProperty':
+        case 'EnumProperty':
+          tag.enumName = this.readFName(reader);
+          break;
+        case 'ArrayProperty':
+          if (this.version >= UE4ObjectVersion.VAR_UE4_ARRAY_PROPERTY_INNER_TAGS) {
+            tag.innerType = this.readFName(reader);
+          }
+          break;
+        case 'SetProperty':
+        case 'MapProperty':
+          if (this.version >= UE4ObjectVersion.VER_UE4_PROPERTY_TAG_SET_MAP_SUPPORT) {
+            tag.innerType = this.readFName(reader);
+            if (tag.type === 'MapProperty') {
+              tag.valueType = this.readFName(reader);
+            }
+          }
+          break;
+      }
+      if (this.version >= UE4ObjectVersion.VER_UE4_PROPERTY_GUID_IN_PROPERTY_TAG && reader.readUInt8()) {
+        tag.propertyGUID = reader.readGUID();
+      }
+      tag.offset = reader.tell();
+      const isName = tag.type === 'NameProperty' || tag.type === 'EnumProperty' ||
+        (tag.type === 'ByteProperty' && tag.enumName !== 'None');
+      if (isName && tag.size === 8) {
+        tag.value = this.readFName(reader);
+      }
+      reader.seek(tag.offset + tag.size);
+      properties.push(tag);
+    }
+  }
+
+  /**
+   * Finds a texture's platform data in export data whose properties can't be skipped, because they're unversioned.
+   * The platform data follows an FName of its pixel format and a skip offset, and repeats the format's name,
+   * so look for a "PF_" name from the name map in both places.
+   * @returns {{pixelFormat: string, offset: number}}
+   */
+  findPlatformData(data, skipOffsetSize, ue5) {
+    const reader = new BinaryReader(data, 'little');
+    const formats = new Map();
+    this.names.forEach((n, i) => n.name.startsWith('PF_') && formats.set(i, n.name));
+    for (let pos = 0; pos + 8 + skipOffsetSize <= data.length; pos++) {
+      reader.seek(pos);
+      const pixelFormat = formats.get(reader.readInt32());
+      if (pixelFormat === undefined || reader.readInt32() !== 0) {
+        continue;
+      }
+      const offset = pos + 8 + skipOffsetSize;
+      const nameAt = offset + (ue5 ? 16 : 0) + 12;
+      if (nameAt + 4 + pixelFormat.length <= data.length) {
+        reader.seek(nameAt);
+        if (reader.readInt32() === pixelFormat.length + 1 && reader.readChars(pixelFormat.length) === pixelFormat) {
+          return {pixelFormat, offset};
+        }
+      }
+    }
+    throw new Error('No texture platform data found');
+  }
+
+  /**
+   * Reads the cooked platform data of a texture export.
+   * @param exportIndex The index of the texture in `exports`
+   * @param uexp The .uexp file, if the export data isn't in the .uasset
+   * @param ubulk The .ubulk file, if any
+   * @param uptnl The .uptnl file, if any
+   * @returns {{texture: UnrealTexture, properties: Array, decodeMip: function(number): Uint8Array}}
+   */
+  readTexture(exportIndex, uexp = new Uint8Array(0), ubulk = new Uint8Array(0), uptnl = new Uint8Array(0)) {
+    const exp = this.exports[exportIndex];
+    const pkg = new Uint8Array(this.reader.data.length + uexp.length);
+    pkg.set(this.reader.data);
+    pkg.set(uexp, this.reader.data.length);
+    if (exp.serialOffset + exp.serialSize > pkg.length) {
+      throw new Error('The export data is past the end of the package; is the .uexp missing?');
+    }
+    const data = pkg.slice(exp.serialOffset, exp.serialOffset + exp.serialSize);
+
+    const ue5 = this.version >= UE5ObjectVersion.INITIAL_VERSION;
+    const skipOffsetSize = this.version >= UE4ObjectVersion.VER_UE4_ADDED_SEARCHABLE_NAMES ? 8 : 4;
+    let properties = [];
+    let offset;
+    if (this.packageFlags & PackageFlags.PKG_UnversionedProperties) {
+      offset = this.findPlatformData(data, skipOffsetSize, ue5).offset;
+    } else {
+      // UObject: properties, then an optional GUID; UTexture and UTexture2D: strip flags, then whether it's cooked
+      const reader = new BinaryReader(data, 'little');
+      properties = this.readProperties(reader);
+      if (reader.readInt32()) {
+        reader.readGUID();
+      }
+      reader.seek(4, SEEK_CUR);
+      if (!reader.readInt32()) {
+        throw new Error('Texture is not cooked');
+      }
+      if (this.readFName(reader) === 'None') {
+        throw new Error('No texture platform data found');
+      }
+      offset = reader.tell() + skipOffsetSize;
+    }
+    const texture = new UnrealTexture(data, offset, ue5, BigInt(this.bulkDataStartOffset));
+    return {
+      texture,
+      properties,
+      decodeMip: mip => encode_png(
+        texture.mip_width(mip), texture.mip_height(mip), texture.decode_mip(mip, pkg, ubulk, uptnl), false)
+    };
+  }
+
   loadNames() {
     this.reader.seek(this.nameOffset);
     this.names = [];
@@ -199,9 +199,12 @@ export class PakFile {
       return this.reader.read(entry.uncompressedSize);
     }
     let res = new BinaryWriter(entry.uncompressedSize);
+    let remaining = entry.uncompressedSize;
     for (let block of entry.compressionBlocks) {
       this.reader.seek(entry.offset + block.start);
-      res.write(decompress(this.reader.read(block.end - block.start), entry.compressionMethod));
+      const size = Math.min(entry.compressionBlockSize || remaining, remaining);
+      res.write(decompress(this.reader.read(block.end - block.start), entry.compressionMethod, size));
+      remaining -= size;
     }
     return res.getData();
   }
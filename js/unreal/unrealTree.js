@@ -1,14 +1,142 @@
 import {AssetTree} from "../treeview";
 import {GodotFile} from "../godot/godotFile";
 import {fileTypes} from "../fileHandler";
-import {PACKAGE_FILE_MAGIC, PackageFile} from "./package";
+import {PACKAGE_FILE_MAGIC, PackageFile, isTextureClass} from "./package";
 import {BinaryReader} from "../binaryReader";
 import {PAK_MAGIC} from "./pakfile";
 import {PckFile} from "../godot/pckFile";
+import {ImagePreview} from "../preview/image";
+import {createToolPanel} from "../preview/tools";
+import {saveBlob} from "../utils";
 
 export class UnrealTree extends AssetTree {
+  styleExport(name, className) {
+    return `<span class="tree-label label-objectname">${this.htmlEscape(name)}</span>
+            <span class="tree-label label-generic"> : </span>
+            <span class="tree-label label-objecttype">${this.htmlEscape(className)}</span>`;
+  }
+
+  async createTreeForPackage() {
+    await this.createNode('#', 'package', this.styleTextAs(this.parser.packageName, 'generic'), 'icon-asset', true);
+    await this.createNode('package', 'package-exports', this.styleTextAs('exports', 'generic'), 'icon-generic', true);
+    for (let i = 0; i < this.parser.exports.length; i++) {
+      const exp = this.parser.exports[i];
+      const className = this.parser.getClassName(exp);
+      await this.createNode('package-exports', `package-export-${i}`, this.styleExport(exp.objectName, className),
+        isTextureClass(className) ? 'icon-img' : 'icon-generic', false, {type: 'export', index: i, className});
+    }
+  }
+
+  /**
+   * Gets a file that goes with the package, like its .uexp or .ubulk, asking for it the first time it's needed.
+   * Resolves to null if another object is selected instead.
+   */
+  async requestFile(fileName) {
+    if (this.providedExternals[fileName] !== undefined) {
+      return this.providedExternals[fileName];
+    }
+    const preview = document.getElementById('preview');
+    return new Promise(resolve => {
+      const text = document.createElement('h2');
+      text.classList.add('no-preview');
+      text.innerText = `Requires file: ${fileName}`;
+      const input = document.createElement('input');
+      input.id = 'ext' + 'xxxxxxxx'.replaceAll('x', () => Math.floor(Math.random() * 256).toString(16));
+      input.classList.add('external-input');
+      input.type = 'file';
+      const label = document.createElement('label');
+      label.htmlFor = input.id;
+      label.classList.add('external-input-button');
+      label.innerText = 'Upload...';
+      const cancelListener = () => {
+        document.body.removeEventListener('destroy-preview', cancelListener);
+        resolve(null);
+      };
+      input.addEventListener('change', async () => {
+        document.body.removeEventListener('destroy-preview', cancelListener);
+        this.providedExternals[fileName] = new Uint8Array(await input.files[0].arrayBuffer());
+        resolve(this.providedExternals[fileName]);
+      });
+      document.body.addEventListener('destroy-preview', cancelListener);
+
+      text.appendChild(document.createElement('br'));
+      text.appendChild(input);
+      text.appendChild(label);
+      preview.innerHTML = '';
+      preview.appendChild(text);
+    });
+  }
+
+  async previewTexture(index) {
+    const preview = document.getElementById('preview');
+    const tools = document.getElementById('preview-tools');
+    const exp = this.parser.exports[index];
+    const baseName = this.parser.packageName.substring(this.parser.packageName.lastIndexOf('/') + 1);
+    const files = {};
+    if (exp.serialOffset + exp.serialSize > this.parser.reader.data.length) {
+      files.uexp = await this.requestFile(baseName + '.uexp');
+      if (files.uexp === null) return;
+    }
+    const {texture} = this.parser.readTexture(index, files.uexp);
+    const locations = [...Array(texture.mip_count).keys()].map(i => texture.mip_location(i));
+    for (const ext of ['ubulk', 'uptnl']) {
+      if (locations.includes(ext)) {
+        files[ext] = await this.requestFile(`${baseName}.${ext}`);
+        if (files[ext] === null) return;
+      }
+    }
+    const {decodeMip} = this.parser.readTexture(index, files.uexp, files.ubulk, files.uptnl);
+    const mips = locations.map((_, i) => i).filter(i => locations[i] !== 'none');
+    if (mips.length === 0) {
+      throw new Error('The texture has no mips');
+    }
+
+    preview.innerHTML = '';
+    preview.appendChild(await new ImagePreview(mips.length, async i =>
+      URL.createObjectURL(new Blob([decodeMip(mips[i])], {type: 'image/png'}))).create());
+    document.getElementById('download-object').onclick = () => {
+      saveBlob(exp.objectName + '.png', [decodeMip(mips[0])], 'image/png');
+    };
+    tools.appendChild(createToolPanel([{
+      label: 'Export mip',
+      action: 'Export',
+      fields: [
+        {name: 'mip', label: 'Mip', type: 'select',
+          options: mips.map(i => [i, `${i}: ${texture.mip_width(i)}x${texture.mip_height(i)}`])},
+      ],
+      run: ({mip}) => ({name: `${exp.objectName}_mip${mip}.png`, data: decodeMip(Number(mip)), type: 'image/png'}),
+    }]));
+  }
+
+  async onNodeSelect(evt, data) {
+    if (data.node.data.type !== 'export') return;
+    document.body.dispatchEvent(new CustomEvent('destroy-preview'));
+    const preview = document.getElementById('preview');
+    document.getElementById('preview-tools').innerHTML = '';
+    const exp = this.parser.exports[data.node.data.index];
+    document.getElementById('download-info').onclick = () => {
+      saveBlob(exp.objectName + '.json', [JSON.stringify(exp, undefined, 2)]);
+    };
+    if (!isTextureClass(data.node.data.className)) {
+      preview.innerHTML = '<h2 class="no-preview">No preview available</h2>';
+      return;
+    }
+    preview.innerHTML = '<h2 class="no-preview">Loading preview...</h2>';
+    try {
+      await this.previewTexture(data.node.data.index);
+    } catch (e) {
+      console.error(e);
+      preview.innerHTML = `<h2 class="no-preview">${this.htmlEscape(`Failed to read texture: ${e.message ?? e}`)}</h2>`;
+    }
+  }
+
+  async onNodeOpen(evt, data) {
+
+  }
+
   async loadFile(data) {
     this.treeFiles = [];
+    this.providedExternals = {};
 
     this.objectBranches = {};
 
@@ -32,6 +160,9 @@ export class UnrealTree extends AssetTree {
       [],
       {}
     );
+    if (this.parser instanceof PackageFile) {
+      await this.createTreeForPackage();
+    }
 
     // const fileType = this.parser.fileType;
     //
@@ -1,16 +1,17 @@
-import pako from 'pako';
+import {decompress as decompressData} from "../encoders";
 
-export function decompress(data, method) {
+export function decompress(data, method, size) {
   switch (method) {
-    case 'Zlib':
-    case 'GZip':
-      return pako.inflate(data);
     case 'Oodle':
       console.error('Oodle not supported');
       return data;
     case 'None':
       return data;
     default:
-      console.error(`Unknown/unsupported compression method ${method}!`);
+      try {
+        return decompressData(method, data, size);
+      } catch (e) {
+        console.error(`Failed to decompress ${method} data: ${e.message ?? e}`);
+      }
   }
 }
@@ -0,0 +1,32 @@
+// Adds the headers that make the page cross-origin isolated, which SharedArrayBuffer (and so the
+// threaded texture decoder) needs, for hosts that can't be configured to send them.
+// Loaded from the page, it registers itself as a service worker and reloads once it's in control.
+if (typeof window === 'undefined') {
+  self.addEventListener('install', () => self.skipWaiting());
+  self.addEventListener('activate', event => event.waitUntil(self.clients.claim()));
+  self.addEventListener('fetch', event => {
+    const request = event.request;
+    if (request.cache === 'only-if-cached' && request.mode !== 'same-origin') {
+      return;
+    }
+    event.respondWith(fetch(request).then(response => {
+      // opaque cross-origin responses can't be changed, and `credentialless` doesn't need them to be
+      if (response.status === 0) {
+        return response;
+      }
+      const headers = new Headers(response.headers);
+      headers.set('Cross-Origin-Opener-Policy', 'same-origin');
+      // unlike `require-corp`, this still loads third-party scripts and images, just without cookies
+      headers.set('Cross-Origin-Embedder-Policy', 'credentialless');
+      return new Response(response.body, {status: response.status, statusText: response.statusText, headers});
+    }));
+  });
+} else if (!window.crossOriginIsolated && window.isSecureContext && 'serviceWorker' in navigator) {
+  navigator.serviceWorker.register(document.currentScript.src).then(() => {
+    // browsers without `credentialless` stay unisolated even with the worker; only reload once for them
+    if (!navigator.serviceWorker.controller && !sessionStorage.getItem('coiReloaded')) {
+      sessionStorage.setItem('coiReloaded', '1');
+      navigator.serviceWorker.ready.then(() => window.location.reload());
+    }
+  }, e => console.error('Failed to register the cross-origin isolation worker', e));
+}
@@ -2,6 +2,13 @@ import {defineConfig} from "vite";
 import wasm from 'vite-plugin-wasm';
 import legacy from '@vitejs/plugin-legacy';
 
+// SharedArrayBuffer, which the threaded decoder in js/decodeWorker.js needs, is only available on
+// cross-origin isolated pages (public/coi-serviceworker.js adds the same headers in production)
+const isolationHeaders = {
+  'Cross-Origin-Opener-Policy': 'same-origin',
+  'Cross-Origin-Embedder-Policy': 'credentialless'
+};
+
 export default defineConfig({
   esbuild: {
     minifyIdentifiers: true,
@@ -9,5 +16,14 @@ export default defineConfig({
       'top-level-await': true
     }
   },
+  server: {
+    headers: isolationHeaders
+  },
+  preview: {
+    headers: isolationHeaders
+  },
+  worker: {
+    format: 'es'
+  },
   plugins: [wasm(), legacy()]
 });